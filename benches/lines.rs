@@ -0,0 +1,177 @@
+//! Reading an input's lines: `Lines::read`, one buffer with an offset table, against a
+//! `String` per line, as the search kept them before.
+//!
+//! Run with `cargo bench --bench lines`. For each input it prints the time per read, the
+//! allocations made, the most memory allocated at once, and what stays allocated while
+//! the lines are held for the search.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use rgrep::io_utils::Lines;
+
+/// Counts allocations and the bytes allocated, to measure allocator traffic. A `realloc`
+/// counts as freeing the old block before allocating the new one.
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn grew(bytes: usize) {
+    let live = LIVE.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(live, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        grew(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        grew(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// How the search read its lines before `Lines`: a `String` per line, with the byte
+/// offset at which it starts.
+fn read_line_strings<R: Read>(reader: R) -> io::Result<Vec<(usize, String)>> {
+    let mut buf = BufReader::new(reader);
+    let mut lines = Vec::new();
+    let mut offset = 0usize;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let n = buf.read_line(&mut line)?;
+        if n == 0 {
+            break;
+        }
+        let text = match line.strip_suffix('\n') {
+            Some(t) => t.strip_suffix('\r').unwrap_or(t),
+            None => &line,
+        };
+        lines.push((offset, text.to_string()));
+        offset += n;
+    }
+    Ok(lines)
+}
+
+/// `total` bytes of log-like lines of 30 to 160 bytes, always the same.
+fn input(total: usize) -> Vec<u8> {
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut out = Vec::with_capacity(total + 160);
+    let mut n = 0;
+    while out.len() < total {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let len = 30 + (seed % 130) as usize;
+        let start = out.len();
+        out.extend_from_slice(
+            format!("2026-10-16T12:00:{:02} INFO request {} ", n % 60, n).as_bytes(),
+        );
+        while out.len() - start < len {
+            out.push(b'a' + (out.len() % 26) as u8);
+        }
+        out.push(b'\n');
+        n += 1;
+    }
+    out
+}
+
+/// Time, allocations, and memory of reading each of `inputs` and going over its lines.
+struct Measured {
+    per_read: Duration,
+    allocs: usize,
+    peak: usize,
+    held: usize,
+}
+
+fn measure<T>(
+    inputs: &[Vec<u8>],
+    rounds: u32,
+    read: impl Fn(&[u8]) -> T,
+    lines: impl Fn(&T) -> usize,
+) -> Measured {
+    // Once to warm up, and to count allocations and memory
+    let (allocs, peak, held) = {
+        let (allocs, live) = (ALLOCS.load(Ordering::Relaxed), LIVE.load(Ordering::Relaxed));
+        PEAK.store(live, Ordering::Relaxed);
+        let read: Vec<T> = inputs.iter().map(|input| read(input)).collect();
+        let held = LIVE.load(Ordering::Relaxed) - live;
+        black_box(read.iter().map(&lines).sum::<usize>());
+        (
+            ALLOCS.load(Ordering::Relaxed) - allocs,
+            PEAK.load(Ordering::Relaxed) - live,
+            held,
+        )
+    };
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for input in inputs {
+            let read = read(black_box(input));
+            black_box(lines(&read));
+        }
+    }
+    Measured {
+        per_read: start.elapsed() / (rounds * inputs.len() as u32),
+        allocs: allocs / inputs.len(),
+        peak,
+        held,
+    }
+}
+
+fn report(name: &str, inputs: &[Vec<u8>], rounds: u32) {
+    let bytes: usize = inputs.iter().map(Vec::len).sum::<usize>() / inputs.len();
+    println!(
+        "{} ({} inputs of {} KiB):",
+        name,
+        inputs.len(),
+        bytes / 1024
+    );
+    let strings = measure(
+        inputs,
+        rounds,
+        |input| read_line_strings(input).unwrap(),
+        |lines| lines.iter().map(|(_, line)| line.len()).sum(),
+    );
+    let buffer = measure(
+        inputs,
+        rounds,
+        |input| Lines::read(input).unwrap(),
+        |lines| lines.iter().map(|(_, line)| line.len()).sum(),
+    );
+    for (how, m) in [("String per line", strings), ("Lines::read", buffer)] {
+        println!(
+            "  {:<16} {:>10.1?} per read, {:>7.0} MiB/s, {:>7} allocs per read, peak {:>6} KiB, held {:>6} KiB",
+            how,
+            m.per_read,
+            bytes as f64 / (1024.0 * 1024.0) / m.per_read.as_secs_f64(),
+            m.allocs,
+            m.peak / 1024,
+            m.held / 1024,
+        );
+    }
+}
+
+fn main() {
+    report("large log", &[input(32 << 20)], 10);
+    let files: Vec<Vec<u8>> = (0..2000).map(|_| input(8 << 10)).collect();
+    report("small files", &files, 20);
+}
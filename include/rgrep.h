@@ -0,0 +1,45 @@
+/* C interface of rgrep, built with the `ffi` feature (see src/ffi.rs). */
+#ifndef RGREP_H
+#define RGREP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RgrepConfig RgrepConfig;
+typedef struct RgrepResults RgrepResults;
+
+/* One selected line; its strings belong to the results it came from. */
+typedef struct RgrepMatch {
+    const char *path;      /* NULL for buffers */
+    size_t line_number;    /* 1-based */
+    size_t byte_offset;    /* start of the line in its input */
+    const char *line;      /* without its newline, NUL-terminated */
+    size_t line_len;
+    size_t match_start;    /* first match in the line; both 0 without one */
+    size_t match_end;
+} RgrepMatch;
+
+RgrepConfig *rgrep_config_new(void);
+void rgrep_config_free(RgrepConfig *cfg);
+/* One pattern per configuration; write alternatives as "foo|bar". */
+int rgrep_config_add_pattern(RgrepConfig *cfg, const char *pattern);
+void rgrep_config_set_options(RgrepConfig *cfg, int ignore_case, int invert, int word,
+                              int line, int fixed_strings, int recursive);
+
+RgrepResults *rgrep_search_path(const RgrepConfig *cfg, const char *path);
+RgrepResults *rgrep_search_buffer(const RgrepConfig *cfg, const uint8_t *data, size_t len);
+
+size_t rgrep_results_len(const RgrepResults *results);
+const RgrepMatch *rgrep_results_get(const RgrepResults *results, size_t index);
+const char *rgrep_results_error(const RgrepResults *results);
+void rgrep_results_free(RgrepResults *results);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RGREP_H */
@@ -0,0 +1,216 @@
+//! Aggregation of values extracted from matching lines.
+//!
+//! With `Config::duration_stats`, a regex with one capture group pulls a number out of
+//! every matching line (e.g. `took (\d+)ms`). The values are summarized per file and
+//! across the whole run as min/avg/p50/p95/max.
+//!
+//! With `Config::timings`, the per-file search times of a run are ranked to show which
+//! files dominate the runtime.
+//!
+//! With `Config::stats`, the counters of a whole run are printed as a summary block.
+
+use regex::Regex;
+use std::fmt;
+
+use crate::config::{FileTiming, Stats};
+use crate::output::table::{Align, Table};
+
+/// Extracts numeric values from lines using the first capture group of a regex.
+pub struct DurationExtractor {
+    re: Regex,
+}
+
+impl DurationExtractor {
+    /// Compile `pattern`; it must contain at least one capture group.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+        if re.captures_len() < 2 {
+            return Err(format!(
+                "duration pattern '{}' needs a capture group, e.g. 'took (\\d+)ms'",
+                pattern
+            ));
+        }
+        Ok(Self { re })
+    }
+
+    /// Value captured from `line`, if the pattern matches and the capture is numeric.
+    pub fn extract(&self, line: &str) -> Option<f64> {
+        self.re.captures(line)?.get(1)?.as_str().parse().ok()
+    }
+}
+
+/// Summary statistics over a set of extracted values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationSummary {
+    /// Number of extracted values.
+    pub count: usize,
+    /// Smallest value.
+    pub min: f64,
+    /// Arithmetic mean.
+    pub avg: f64,
+    /// Median (nearest-rank).
+    pub p50: f64,
+    /// 95th percentile (nearest-rank).
+    pub p95: f64,
+    /// Largest value.
+    pub max: f64,
+}
+
+/// Nearest-rank percentile of sorted `values` (`pct` in 0..=100).
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Summarize `values`; returns `None` when there are no values.
+pub fn summarize(values: &[f64]) -> Option<DurationSummary> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Some(DurationSummary {
+        count: sorted.len(),
+        min: sorted[0],
+        avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        p50: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        max: sorted[sorted.len() - 1],
+    })
+}
+
+/// Format a value without a fractional part when it is integral, else with two decimals.
+fn fmt_value(v: f64) -> String {
+    if v.fract() == 0.0 {
+        format!("{}", v as i64)
+    } else {
+        format!("{:.2}", v)
+    }
+}
+
+impl fmt::Display for DurationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count={} min={} avg={} p50={} p95={} max={}",
+            self.count,
+            fmt_value(self.min),
+            fmt_value(self.avg),
+            fmt_value(self.p50),
+            fmt_value(self.p95),
+            fmt_value(self.max)
+        )
+    }
+}
+
+impl DurationSummary {
+    /// Column headers matching `cells`.
+    pub const COLUMNS: [&'static str; 6] = ["COUNT", "MIN", "AVG", "P50", "P95", "MAX"];
+
+    /// The summary values formatted as table cells, in `COLUMNS` order.
+    pub fn cells(&self) -> Vec<String> {
+        let mut cells = vec![self.count.to_string()];
+        cells.extend([self.min, self.avg, self.p50, self.p95, self.max].map(fmt_value));
+        cells
+    }
+}
+
+/// Table cells for the summary of `values`: `0` and dashes when empty.
+pub fn summary_cells(values: &[f64]) -> Vec<String> {
+    match summarize(values) {
+        Some(summary) => summary.cells(),
+        None => std::iter::once("0".to_string())
+            .chain(std::iter::repeat_n("-".to_string(), 5))
+            .collect(),
+    }
+}
+
+/// Render the summary of `values` as `count=... max=...`, or `count=0` when empty.
+pub fn format_summary(values: &[f64]) -> String {
+    match summarize(values) {
+        Some(summary) => summary.to_string(),
+        None => "count=0".to_string(),
+    }
+}
+
+/// Render the `limit` slowest files as a table of time, size, and throughput.
+pub fn format_timings(timings: &[FileTiming], limit: usize) -> String {
+    let mut sorted: Vec<&FileTiming> = timings.iter().collect();
+    sorted.sort_by_key(|t| std::cmp::Reverse(t.elapsed));
+
+    let mut table = Table::new(&[
+        ("FILE", Align::Left),
+        ("TIME", Align::Right),
+        ("BYTES", Align::Right),
+        ("MB/S", Align::Right),
+    ]);
+    for timing in sorted.into_iter().take(limit) {
+        let secs = timing.elapsed.as_secs_f64();
+        let throughput = if secs > 0.0 {
+            format!("{:.2}", timing.bytes as f64 / 1_000_000.0 / secs)
+        } else {
+            "-".to_string()
+        };
+        table.push_row(vec![
+            timing.path.clone(),
+            format!("{:.2}ms", secs * 1000.0),
+            timing.bytes.to_string(),
+            throughput,
+        ]);
+    }
+    let mut out = String::new();
+    table.render(&mut out);
+    out
+}
+
+/// Render the counters of a run as the block `--stats` prints after the results,
+/// starting with a blank line: a STAT/VALUE table, or with `table` unset
+/// (`Config::table`) raw `name:value` lines named like the JSON `summary` fields. Lines
+/// skipped for `Config::match_timeout_ms` are only listed when there were any.
+pub fn format_stats(stats: &Stats, table: bool) -> String {
+    let mut rows = vec![
+        (
+            "files searched",
+            "files_searched",
+            stats.searches.to_string(),
+        ),
+        (
+            "files with matches",
+            "files_with_matches",
+            stats.searches_with_match.to_string(),
+        ),
+        (
+            "matched lines",
+            "matched_lines",
+            stats.matched_lines.to_string(),
+        ),
+        ("matches", "matches", stats.matches.to_string()),
+    ];
+    if stats.timeouts > 0 {
+        rows.push(("lines timed out", "timeouts", stats.timeouts.to_string()));
+    }
+    rows.push((
+        "bytes searched",
+        "bytes_searched",
+        stats.bytes_searched.to_string(),
+    ));
+    rows.push((
+        "seconds",
+        "seconds",
+        format!("{:.6}", stats.elapsed.as_secs_f64()),
+    ));
+
+    let mut out = String::from("\n");
+    if table {
+        let mut table = Table::new(&[("STAT", Align::Left), ("VALUE", Align::Right)]);
+        for (label, _, value) in rows {
+            table.push_row(vec![label.to_string(), value]);
+        }
+        table.render(&mut out);
+    } else {
+        for (_, name, value) in rows {
+            out.push_str(&format!("{}:{}\n", name, value));
+        }
+    }
+    out
+}
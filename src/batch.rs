@@ -0,0 +1,499 @@
+//! Batch queries (`--queries FILE`).
+//!
+//! Many independent queries are evaluated in a single pass over the inputs: every file is
+//! read once, and each of its lines is tested against all queries. Queries that are plain
+//! regexes are combined into one `RegexSet`, so a line is checked against all of them in
+//! a single scan; queries using `&` or Boolean grouping are evaluated one by one.
+//!
+//! Results are grouped per query: each query with matches gets a `query: NAME` heading
+//! followed by its lines (files in input order), and groups are separated by a blank
+//! line. With `Config::count`, the number of matching lines per query is printed
+//! instead.
+//!
+//! A line of the queries file is either a pattern or a job spec of `;`-separated
+//! `key=value` fields, which can send the query's results to a file of its own:
+//!
+//! ```text
+//! name=errors; pattern=ERROR; out=errors.jsonl
+//! name=slow; pattern=took \d{4,}ms; out=slow.txt; format=text
+//! ```
+//!
+//! - `pattern` (required): the pattern expression
+//! - `name`: label used in headings and counts instead of the pattern
+//! - `out`: file that receives the query's results instead of stdout
+//! - `format`: `text` or `json` (JSON Lines `match` events and a `summary`); defaults to
+//!   `json` for `.json`/`.jsonl` files and `text` otherwise
+//!
+//! A line is only read as a job spec when all of its fields use these keys, so patterns
+//! such as `user=alice` are still plain patterns.
+
+// Running queries needs the `files` feature; parsing them does not
+#[cfg(feature = "files")]
+use rayon::prelude::*;
+#[cfg(feature = "files")]
+use regex::{RegexSet, RegexSetBuilder};
+use std::path::Path;
+
+#[cfg(feature = "files")]
+use crate::color::ColorChoice;
+#[cfg(feature = "files")]
+use crate::config::{Config, ExitStatus, JsonSchema, RunResult, Stats};
+#[cfg(feature = "files")]
+use crate::file_list_cache::FileList;
+#[cfg(feature = "files")]
+use crate::fs_utils::{Root, display_path, input_roots};
+#[cfg(feature = "files")]
+use crate::io_utils::Lines;
+#[cfg(feature = "files")]
+use crate::output::join_heading_groups;
+#[cfg(feature = "files")]
+use crate::output::json;
+#[cfg(feature = "files")]
+use crate::output::table::{Align, Table};
+#[cfg(feature = "files")]
+use crate::search::{
+    Match, STDIN_LABEL, Searcher, emit_line, open_file, select_files, skipped_dir_warnings,
+    walk_issue_warnings,
+};
+
+/// Output format of a query written to its own file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryFormat {
+    /// Lines as printed to stdout, without colors.
+    Text,
+    /// JSON Lines `match` events followed by a `summary` event.
+    Json,
+}
+
+/// One query of a batch run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    /// Pattern expression, as given to `-r`.
+    pub pattern: String,
+    /// Label used instead of the pattern in headings and counts.
+    pub name: Option<String>,
+    /// File receiving this query's results instead of stdout.
+    pub out: Option<String>,
+    /// Format of `out`.
+    pub format: QueryFormat,
+}
+
+impl Query {
+    /// A query for `pattern` whose results go to stdout.
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            name: None,
+            out: None,
+            format: QueryFormat::Text,
+        }
+    }
+
+    /// Name shown for this query: its `name`, or else its pattern.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.pattern)
+    }
+}
+
+const SPEC_KEYS: [&str; 4] = ["name", "pattern", "out", "format"];
+
+/// Parse one job spec line, or return `None` when `line` is a plain pattern.
+fn parse_spec(line: &str) -> Option<Result<Query, String>> {
+    let mut fields = Vec::new();
+    for field in line.split(';').map(str::trim).filter(|f| !f.is_empty()) {
+        let (key, value) = field.split_once('=')?;
+        let key = key.trim();
+        if !SPEC_KEYS.contains(&key) {
+            return None;
+        }
+        fields.push((key, value.trim()));
+    }
+
+    let mut pattern = None;
+    let mut name = None;
+    let mut out: Option<String> = None;
+    let mut format = None;
+    for (key, value) in fields {
+        match key {
+            "pattern" => pattern = Some(value.to_string()),
+            "name" => name = Some(value.to_string()),
+            "out" => out = Some(value.to_string()),
+            _ => {
+                format = match value {
+                    "text" => Some(QueryFormat::Text),
+                    "json" => Some(QueryFormat::Json),
+                    _ => {
+                        return Some(Err(format!(
+                            "unknown format '{}' (expected 'text' or 'json')",
+                            value
+                        )));
+                    }
+                }
+            }
+        }
+    }
+    let Some(pattern) = pattern.filter(|p| !p.is_empty()) else {
+        return Some(Err("job spec without 'pattern='".into()));
+    };
+    let format = format.unwrap_or_else(|| {
+        let ext = out
+            .as_deref()
+            .and_then(|o| Path::new(o).extension())
+            .and_then(|e| e.to_str());
+        match ext {
+            Some("json" | "jsonl") => QueryFormat::Json,
+            _ => QueryFormat::Text,
+        }
+    });
+    Some(Ok(Query {
+        pattern,
+        name,
+        out,
+        format,
+    }))
+}
+
+/// Parse a queries file: one pattern or job spec per line; blank lines and lines starting
+/// with `#` are skipped.
+pub fn parse_queries(text: &str) -> Result<Vec<Query>, String> {
+    let mut queries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let query = match parse_spec(line) {
+            Some(spec) => spec.map_err(|e| format!("line {}: {}", i + 1, e))?,
+            None => Query::new(line),
+        };
+        queries.push(query);
+    }
+    Ok(queries)
+}
+
+/// Read and parse the queries file at `path`.
+pub fn read_queries(path: &str) -> Result<Vec<Query>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let queries = parse_queries(&text).map_err(|e| format!("{}: {}", path, e))?;
+    if queries.is_empty() {
+        return Err(format!("{}: no queries found", path));
+    }
+    Ok(queries)
+}
+
+/// All queries compiled once per run.
+#[cfg(feature = "files")]
+struct QuerySet {
+    /// Configuration each query is printed with.
+    configs: Vec<Config>,
+    searchers: Vec<Searcher>,
+    /// Plain-regex queries combined for a single scan per line.
+    combined: RegexSet,
+    /// Query index of each pattern in `combined`.
+    combined_queries: Vec<usize>,
+    /// Queries that must be evaluated on their own.
+    separate: Vec<usize>,
+}
+
+#[cfg(feature = "files")]
+impl QuerySet {
+    fn new(cfg: &Config) -> Result<Self, String> {
+        let mut configs = Vec::with_capacity(cfg.queries.len());
+        let mut searchers = Vec::with_capacity(cfg.queries.len());
+        for query in &cfg.queries {
+            let query_cfg = query_config(cfg, query);
+            let searcher = Searcher::new(&query_cfg)
+                .map_err(|e| format!("query '{}': {}", query.label(), e))?;
+            configs.push(query_cfg);
+            searchers.push(searcher);
+        }
+
+        let mut combined_patterns = Vec::new();
+        let mut combined_queries = Vec::new();
+        let mut separate = Vec::new();
+        for (i, searcher) in searchers.iter().enumerate() {
+            match searcher.matcher.single_regex() {
+                Some(re) => {
+                    combined_patterns.push(re.as_str().to_string());
+                    combined_queries.push(i);
+                }
+                None => separate.push(i),
+            }
+        }
+        // Same flags as the individual regexes (see `regex_utils::build_regex`)
+        let combined = RegexSetBuilder::new(&combined_patterns)
+            .multi_line(true)
+            .case_insensitive(cfg.case_insensitive)
+            .dot_matches_new_line(cfg.dotall)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            configs,
+            searchers,
+            combined,
+            combined_queries,
+            separate,
+        })
+    }
+
+    /// Set `hits[q]` to whether query `q` matches `line` (before any inversion).
+    fn matches(&self, line: &str, hits: &mut [bool]) {
+        hits.fill(false);
+        for i in self.combined.matches(line).iter() {
+            hits[self.combined_queries[i]] = true;
+        }
+        for &q in &self.separate {
+            hits[q] = self.searchers[q].matcher.is_match(line);
+        }
+    }
+}
+
+/// `cfg` for running and printing `query`; results written to a file are never colored.
+#[cfg(feature = "files")]
+fn query_config(cfg: &Config, query: &Query) -> Config {
+    let to_file = query.out.is_some();
+    Config {
+        patterns: vec![query.pattern.clone()],
+        queries: Vec::new(),
+        definition: None,
+        color: if to_file {
+            ColorChoice::Never
+        } else {
+            cfg.color
+        },
+        json: to_file && query.format == QueryFormat::Json,
+        json_schema: JsonSchema::Rgrep,
+        ..cfg.clone()
+    }
+}
+
+/// Output, number of matching lines, and number of printed matches of every query for
+/// one file.
+#[cfg(feature = "files")]
+struct FileHits {
+    outputs: Vec<String>,
+    counts: Vec<usize>,
+    matches: Vec<usize>,
+}
+
+#[cfg(feature = "files")]
+fn scan_file(
+    cfg: &Config,
+    set: &QuerySet,
+    name: &str,
+    roots: &[Root],
+    show_name: bool,
+) -> Result<FileHits, String> {
+    let pre = set
+        .searchers
+        .first()
+        .and_then(|searcher| searcher.pre.as_ref());
+    let reader = open_file(cfg, pre, name).map_err(|e| e.to_string())?;
+    let lines = Lines::read(reader).map_err(|e| e.to_string())?;
+    let n = set.searchers.len();
+    let shown = display_path(roots, name);
+    let label = if name == "-" { None } else { Some(&*shown) };
+    let prefix = show_name.then(|| label.unwrap_or(STDIN_LABEL));
+    let mut found = FileHits {
+        outputs: vec![String::new(); n],
+        counts: vec![0; n],
+        matches: vec![0; n],
+    };
+    let mut hits = vec![false; n];
+    // NEAR queries are decided over the whole file up front
+    let texts = lines.texts();
+    let window_hits: Vec<Option<Vec<bool>>> = set
+        .searchers
+        .iter()
+        .map(|s| {
+            s.matcher
+                .is_windowed()
+                .then(|| s.matcher.match_lines(&texts))
+        })
+        .collect();
+
+    for (idx, (byte_offset, line)) in lines.iter().enumerate() {
+        set.matches(line, &mut hits);
+        for (hit, window) in hits.iter_mut().zip(&window_hits) {
+            if let Some(window) = window {
+                *hit = window[idx];
+            }
+        }
+        let mut record: Option<Match> = None;
+        for (q, &hit) in hits.iter().enumerate() {
+            if hit == cfg.invert {
+                continue;
+            }
+            found.counts[q] += 1;
+            let searcher = &set.searchers[q];
+            let spans = if hit {
+                searcher.matcher.find_spans(line)
+            } else {
+                Vec::new()
+            };
+            // As for `Stats::matches`, a line selected without matched text counts once
+            found.matches[q] += spans.len().max(1);
+            // Queries with their own file are written even in quiet mode
+            let to_file = cfg.queries[q].out.is_some();
+            if cfg.count || (cfg.quiet && !to_file) {
+                continue;
+            }
+            let m = record.get_or_insert_with(|| Match {
+                line_number: idx + 1,
+                byte_offset,
+                line: line.to_string(),
+                spans: Vec::new(),
+                terms: Vec::new(),
+                context_hash: None,
+            });
+            m.spans = spans;
+            m.terms = if hit {
+                searcher.matcher.matched_terms(&m.line)
+            } else {
+                Vec::new()
+            };
+            let out = &mut found.outputs[q];
+            emit_line(&set.configs[q], searcher, out, label, prefix, m, true);
+        }
+    }
+    Ok(found)
+}
+
+/// Results of one query over all files.
+#[cfg(feature = "files")]
+#[derive(Default)]
+struct QueryResult {
+    output: String,
+    matched_lines: usize,
+    matches: usize,
+    files_with_matches: usize,
+}
+
+/// Write the results of `query` to its `out` file.
+#[cfg(feature = "files")]
+fn write_query_file(
+    cfg: &Config,
+    query: &Query,
+    path: &str,
+    result: &QueryResult,
+    files_searched: usize,
+) -> Result<(), String> {
+    let mut content = String::new();
+    if !cfg.count {
+        content.push_str(&result.output);
+    }
+    match query.format {
+        QueryFormat::Json => json::append_summary(
+            &mut content,
+            files_searched,
+            result.files_with_matches,
+            result.matched_lines,
+            result.matches,
+            // `--match-timeout` does not apply to queries
+            0,
+        ),
+        QueryFormat::Text if cfg.count => content = format!("{}\n", result.matched_lines),
+        QueryFormat::Text => {}
+    }
+    std::fs::write(path, content)
+        .map_err(|e| format!("query '{}': cannot write {}: {}", query.label(), path, e))
+}
+
+/// Run every query in `cfg.queries` over `inputs`, grouping the results per query.
+#[cfg(feature = "files")]
+pub(crate) fn run_queries(cfg: &Config, inputs: &[String]) -> Result<RunResult, String> {
+    let set = QuerySet::new(cfg)?;
+    let FileList {
+        expanded,
+        files,
+        skipped_dirs,
+        walk_issues,
+    } = select_files(cfg, inputs)?;
+    let roots = input_roots(inputs);
+    let show_names = cfg.with_filename.unwrap_or(expanded > 1);
+
+    let per_file: Vec<Result<FileHits, String>> = files
+        .par_iter()
+        .map(|(_, name)| scan_file(cfg, &set, name, &roots, show_names))
+        .collect();
+
+    let mut results: Vec<QueryResult> = cfg.queries.iter().map(|_| Default::default()).collect();
+    let mut errs = Vec::new();
+    for res in per_file {
+        match res {
+            Ok(found) => {
+                for (q, out) in found.outputs.into_iter().enumerate() {
+                    let result = &mut results[q];
+                    result.output.push_str(&out);
+                    result.matched_lines += found.counts[q];
+                    result.matches += found.matches[q];
+                    result.files_with_matches += usize::from(found.counts[q] > 0);
+                }
+            }
+            Err(e) => errs.push(e),
+        }
+    }
+    if !errs.is_empty() {
+        return Err(errs.join("\n"));
+    }
+
+    for (query, result) in cfg.queries.iter().zip(&results) {
+        if let Some(path) = &query.out {
+            write_query_file(cfg, query, path, result, files.len())?;
+        }
+    }
+
+    // Queries with their own file are not printed
+    let printed = || {
+        cfg.queries
+            .iter()
+            .zip(&results)
+            .filter(|(query, _)| query.out.is_none())
+    };
+    let output = if cfg.quiet {
+        String::new()
+    } else if cfg.count && cfg.table {
+        let mut table = Table::new(&[("QUERY", Align::Left), ("COUNT", Align::Right)]);
+        for (query, result) in printed() {
+            table.push_row(vec![
+                query.label().to_string(),
+                result.matched_lines.to_string(),
+            ]);
+        }
+        let mut out = String::new();
+        table.render(&mut out);
+        out
+    } else if cfg.count {
+        printed()
+            .map(|(query, result)| format!("{}:{}\n", query.label(), result.matched_lines))
+            .collect()
+    } else {
+        let groups = printed().map(|(query, result)| {
+            if result.output.is_empty() {
+                return String::new();
+            }
+            format!("query: {}\n{}", query.label(), result.output)
+        });
+        join_heading_groups(groups)
+    };
+
+    let status = if results.iter().any(|r| r.matched_lines > 0) {
+        ExitStatus::MatchFound
+    } else {
+        ExitStatus::NoMatch
+    };
+    Ok(RunResult {
+        output,
+        status,
+        warnings: skipped_dir_warnings(&skipped_dirs)
+            .into_iter()
+            .chain(walk_issue_warnings(cfg, &walk_issues))
+            .collect(),
+        timings: Vec::new(),
+        files: Vec::new(),
+        errors: Vec::new(),
+        skipped: walk_issues.skipped,
+        stats: Stats::default(),
+    })
+}
@@ -1,21 +1,86 @@
 //! Boolean pattern expression parser.
 //!
 //! This module provides parsing and evaluation of Boolean expressions with patterns,
-//! supporting '&' (AND), '|' (OR), and parentheses for grouping.
+//! supporting '!' (NOT), '&' (AND), '|' (OR), '~N~' (NEAR), and parentheses for grouping.
+//! '!' binds tightest, then '~N~', '&', and '|', so `a|b&!c` is `a|(b&(!c))`.
 //!
 //! Examples:
 //! - `pattern1&pattern2` - both patterns must match
 //! - `pattern1|pattern2` - either pattern must match  
 //! - `pattern1&(pattern2|pattern3)` - pattern1 AND (pattern2 OR pattern3)
+//! - `error&!(timeout|retry)` - error, but neither timeout nor retry
+//! - `lock~5~deadlock` - lock and deadlock on lines at most 5 lines apart
+//!
+//! NEAR looks beyond the current line, so it needs the lines around it: `matches` only
+//! sees one line and treats `a~N~b` like `a&b`, while `matches_lines` evaluates a whole
+//! input. There, a line matches `a~N~b` when it matches one side and the other side
+//! matches a line at most N lines away (possibly the same line); both lines are reported.
+//!
+//! Operator characters are searched for literally when escaped with a backslash
+//! (`a\&b`, `f\(x\)`), or when a whole term is double-quoted (`"a&b"|"f(x)"`): a quoted
+//! term is plain text, not a regex, with `\"` for a quote. Quotes around text without
+//! operator characters keep their old meaning and are part of the pattern, so
+//! `"level":"error"` still finds JSON.
+//!
+//! '!' is an operator only at the start of an operand; elsewhere it is part of the
+//! pattern, and `\!` starts a pattern with a literal '!'. Regex groups with flags or
+//! without capture, such as `(?:a|b)` or `(?i:x)`, are part of the pattern too, never
+//! Boolean grouping.
+//!
+//! A term followed by a count threshold such as `retry{>=3}` must occur that many times
+//! on the line (`>=`, `>`, `<=`, `<`, or `==` N), counting non-overlapping matches.
+//! Regex repetitions like `a{2,}` are unaffected.
+//!
+//! Parse errors carry the character offset of the offending position, and
+//! `ParseError::diagnostic` renders it with a caret under the expression.
 
+use crate::clock::Instant;
 use crate::config::Config;
 use regex::{Regex, RegexBuilder};
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum BooleanExpr {
     Pattern(String),
     And(Box<BooleanExpr>, Box<BooleanExpr>),
     Or(Box<BooleanExpr>, Box<BooleanExpr>),
+    Not(Box<BooleanExpr>),
+    Near(Box<BooleanExpr>, Box<BooleanExpr>, usize),
+    /// A pattern whose number of matches on the line must satisfy a threshold.
+    Count(String, CountOp, usize),
+}
+
+/// Comparison of a count threshold such as `{>=3}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountOp {
+    /// `>=`
+    AtLeast,
+    /// `>`
+    MoreThan,
+    /// `<=`
+    AtMost,
+    /// `<`
+    LessThan,
+    /// `==`
+    Exactly,
+}
+
+impl CountOp {
+    /// Whether `count` satisfies the threshold `n`.
+    pub fn holds(self, count: usize, n: usize) -> bool {
+        match self {
+            CountOp::AtLeast => count >= n,
+            CountOp::MoreThan => count > n,
+            CountOp::AtMost => count <= n,
+            CountOp::LessThan => count < n,
+            CountOp::Exactly => count == n,
+        }
+    }
+}
+
+/// Whether the number of matches of `regex` in `line` satisfies `op` `n`.
+fn count_holds(regex: &Regex, line: &str, op: CountOp, n: usize) -> bool {
+    op.holds(regex.find_iter(line).count(), n)
 }
 
 impl BooleanExpr {
@@ -35,6 +100,90 @@ impl BooleanExpr {
             BooleanExpr::Or(left, right) => {
                 left.matches(line, regexes) || right.matches(line, regexes)
             }
+            BooleanExpr::Not(inner) => !inner.matches(line, regexes),
+            BooleanExpr::Near(left, right, _) => {
+                left.matches(line, regexes) && right.matches(line, regexes)
+            }
+            BooleanExpr::Count(pattern, op, n) => regexes
+                .get(pattern)
+                .is_some_and(|regex| count_holds(regex, line, *op, *n)),
+        }
+    }
+
+    /// Like `matches`, but checks `deadline` after every term and gives up with `None` once
+    /// it has passed.
+    pub fn matches_before(
+        &self,
+        line: &str,
+        regexes: &std::collections::HashMap<String, Regex>,
+        deadline: Instant,
+    ) -> Option<bool> {
+        let hit = match self {
+            BooleanExpr::Pattern(_) | BooleanExpr::Count(..) => self.matches(line, regexes),
+            BooleanExpr::And(left, right) | BooleanExpr::Near(left, right, _) => {
+                left.matches_before(line, regexes, deadline)?
+                    && right.matches_before(line, regexes, deadline)?
+            }
+            BooleanExpr::Or(left, right) => {
+                left.matches_before(line, regexes, deadline)?
+                    || right.matches_before(line, regexes, deadline)?
+            }
+            BooleanExpr::Not(inner) => !inner.matches_before(line, regexes, deadline)?,
+        };
+        (Instant::now() <= deadline).then_some(hit)
+    }
+
+    /// Evaluate this Boolean expression against every line of an input, taking NEAR
+    /// distances into account
+    pub fn matches_lines(
+        &self,
+        lines: &[&str],
+        regexes: &std::collections::HashMap<String, Regex>,
+    ) -> Vec<bool> {
+        match self {
+            BooleanExpr::Pattern(_) | BooleanExpr::Count(..) => lines
+                .iter()
+                .map(|line| self.matches(line, regexes))
+                .collect(),
+            BooleanExpr::And(left, right) => {
+                let right = right.matches_lines(lines, regexes);
+                let mut hits = left.matches_lines(lines, regexes);
+                hits.iter_mut().zip(right).for_each(|(l, r)| *l &= r);
+                hits
+            }
+            BooleanExpr::Or(left, right) => {
+                let right = right.matches_lines(lines, regexes);
+                let mut hits = left.matches_lines(lines, regexes);
+                hits.iter_mut().zip(right).for_each(|(l, r)| *l |= r);
+                hits
+            }
+            BooleanExpr::Not(inner) => inner
+                .matches_lines(lines, regexes)
+                .into_iter()
+                .map(|hit| !hit)
+                .collect(),
+            BooleanExpr::Near(left, right, distance) => {
+                let left = left.matches_lines(lines, regexes);
+                let right = right.matches_lines(lines, regexes);
+                let near_left = within(&left, *distance);
+                let near_right = within(&right, *distance);
+                (0..lines.len())
+                    .map(|i| (left[i] && near_right[i]) || (right[i] && near_left[i]))
+                    .collect()
+            }
+        }
+    }
+
+    /// Whether this expression contains a NEAR operator, so that lines can only be
+    /// evaluated together with their neighbors (see `matches_lines`)
+    pub fn is_windowed(&self) -> bool {
+        match self {
+            BooleanExpr::Pattern(_) | BooleanExpr::Count(..) => false,
+            BooleanExpr::And(left, right) | BooleanExpr::Or(left, right) => {
+                left.is_windowed() || right.is_windowed()
+            }
+            BooleanExpr::Not(inner) => inner.is_windowed(),
+            BooleanExpr::Near(..) => true,
         }
     }
 
@@ -45,23 +194,109 @@ impl BooleanExpr {
         patterns
     }
 
+    /// Get the patterns whose matches are highlighted: those not negated by a `!`, which
+    /// only ever contribute to a match by being absent
+    pub fn get_highlighted_patterns(&self) -> std::collections::HashSet<String> {
+        self.highlighted_terms().into_iter().collect()
+    }
+
+    /// Like `get_highlighted_patterns`, in order of first appearance in the expression.
+    pub fn highlighted_terms(&self) -> Vec<String> {
+        let mut terms = Vec::new();
+        self.collect_highlighted_patterns(&mut terms, false);
+        terms
+    }
+
     fn collect_patterns(&self, patterns: &mut std::collections::HashSet<String>) {
         match self {
-            BooleanExpr::Pattern(pattern) => {
+            BooleanExpr::Pattern(pattern) | BooleanExpr::Count(pattern, ..) => {
                 patterns.insert(pattern.clone());
             }
-            BooleanExpr::And(left, right) | BooleanExpr::Or(left, right) => {
+            BooleanExpr::And(left, right)
+            | BooleanExpr::Or(left, right)
+            | BooleanExpr::Near(left, right, _) => {
                 left.collect_patterns(patterns);
                 right.collect_patterns(patterns);
             }
+            BooleanExpr::Not(inner) => inner.collect_patterns(patterns),
+        }
+    }
+
+    fn collect_highlighted_patterns(&self, patterns: &mut Vec<String>, negated: bool) {
+        match self {
+            BooleanExpr::Pattern(pattern) | BooleanExpr::Count(pattern, ..) => {
+                if !negated && !patterns.contains(pattern) {
+                    patterns.push(pattern.clone());
+                }
+            }
+            BooleanExpr::And(left, right)
+            | BooleanExpr::Or(left, right)
+            | BooleanExpr::Near(left, right, _) => {
+                left.collect_highlighted_patterns(patterns, negated);
+                right.collect_highlighted_patterns(patterns, negated);
+            }
+            BooleanExpr::Not(inner) => inner.collect_highlighted_patterns(patterns, !negated),
         }
     }
 }
 
+/// For each line, whether `hits` is set for a line at most `distance` lines away.
+fn within(hits: &[bool], distance: usize) -> Vec<bool> {
+    let mut prefix = vec![0usize; hits.len() + 1];
+    for (i, &hit) in hits.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + usize::from(hit);
+    }
+    (0..hits.len())
+        .map(|i| {
+            let lo = i.saturating_sub(distance);
+            let hi = (i + distance + 1).min(hits.len());
+            prefix[hi] > prefix[lo]
+        })
+        .collect()
+}
+
+/// Error from parsing a Boolean pattern expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// What went wrong, e.g. `Expected closing parenthesis`.
+    pub message: String,
+    /// Offset of the offending position in the expression, in characters.
+    pub offset: usize,
+}
+
+impl ParseError {
+    /// Render `input` (the parsed expression) with a caret under the offending position,
+    /// followed by the message:
+    ///
+    /// ```text
+    ///   error&(timeout|retry
+    ///                       ^ Expected closing parenthesis
+    /// ```
+    pub fn diagnostic(&self, input: &str) -> String {
+        // Keep tabs so that the caret lines up with the expression
+        let pad: String = input
+            .chars()
+            .take(self.offset)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("  {}\n  {}^ {}\n", input, pad, self.message)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
 /// Parse a Boolean pattern expression
-pub fn parse_boolean_expression(input: &str) -> Result<BooleanExpr, String> {
+pub fn parse_boolean_expression(input: &str) -> Result<BooleanExpr, ParseError> {
     let mut parser = BooleanParser::new(input);
-    parser.parse_or_expression()
+    let expr = parser.parse_or_expression()?;
+    match parser.current_char() {
+        None => Ok(expr),
+        Some(ch) => Err(parser.error(format!("Unexpected '{}'", ch))),
+    }
 }
 
 struct BooleanParser {
@@ -87,6 +322,19 @@ impl BooleanParser {
         ch
     }
 
+    /// An error with `message` at the current position.
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            offset: self.pos.min(self.input.len()),
+        }
+    }
+
+    /// Length of the regex group `(?...)` starting at the current position, if any.
+    fn regex_group_len(&self) -> Option<usize> {
+        regex_group_len(&self.input[self.pos..])
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(ch) = self.current_char() {
             if ch.is_whitespace() {
@@ -97,7 +345,7 @@ impl BooleanParser {
         }
     }
 
-    fn parse_or_expression(&mut self) -> Result<BooleanExpr, String> {
+    fn parse_or_expression(&mut self) -> Result<BooleanExpr, ParseError> {
         let mut left = self.parse_and_expression()?;
 
         while self.current_char() == Some('|') {
@@ -109,70 +357,254 @@ impl BooleanParser {
         Ok(left)
     }
 
-    fn parse_and_expression(&mut self) -> Result<BooleanExpr, String> {
-        let mut left = self.parse_primary_expression()?;
+    fn parse_and_expression(&mut self) -> Result<BooleanExpr, ParseError> {
+        let mut left = self.parse_near_expression()?;
 
         while self.current_char() == Some('&') {
             self.advance(); // consume '&'
-            let right = self.parse_primary_expression()?;
+            let right = self.parse_near_expression()?;
             left = BooleanExpr::And(Box::new(left), Box::new(right));
         }
 
         Ok(left)
     }
 
-    fn parse_primary_expression(&mut self) -> Result<BooleanExpr, String> {
+    fn parse_near_expression(&mut self) -> Result<BooleanExpr, ParseError> {
+        let mut left = self.parse_not_expression()?;
+
+        while let Some((distance, len)) = near_operator(&self.input[self.pos..]) {
+            self.pos += len; // consume '~N~'
+            let right = self.parse_not_expression()?;
+            left = BooleanExpr::Near(Box::new(left), Box::new(right), distance);
+        }
+
+        Ok(left)
+    }
+
+    fn parse_not_expression(&mut self) -> Result<BooleanExpr, ParseError> {
         self.skip_whitespace();
 
-        if self.current_char() == Some('(') {
+        if self.current_char() == Some('!') {
+            self.advance(); // consume '!'
+            let inner = self.parse_not_expression()?;
+            Ok(BooleanExpr::Not(Box::new(inner)))
+        } else {
+            self.parse_primary_expression()
+        }
+    }
+
+    fn parse_primary_expression(&mut self) -> Result<BooleanExpr, ParseError> {
+        let expr = self.parse_term()?;
+        let Some((op, n, len)) = count_suffix(&self.input[self.pos..]) else {
+            return Ok(expr);
+        };
+        let BooleanExpr::Pattern(pattern) = expr else {
+            return Err(self.error("Count threshold needs a single pattern"));
+        };
+        self.pos += len;
+        self.skip_whitespace();
+        Ok(BooleanExpr::Count(pattern, op, n))
+    }
+
+    /// A quoted term, a parenthesized group, or a pattern.
+    fn parse_term(&mut self) -> Result<BooleanExpr, ParseError> {
+        self.skip_whitespace();
+
+        if let Some((text, len)) = quoted_literal(&self.input[self.pos..]) {
+            self.pos += len;
+            self.skip_whitespace();
+            return Ok(BooleanExpr::Pattern(regex::escape(&text)));
+        }
+
+        if self.current_char() == Some('(') && self.regex_group_len().is_none() {
+            let start = self.pos;
             self.advance(); // consume '('
             let expr = self.parse_or_expression()?;
             self.skip_whitespace();
             if self.current_char() != Some(')') {
-                return Err("Expected closing parenthesis".to_string());
+                return Err(self.error("Expected closing parenthesis"));
             }
             self.advance(); // consume ')'
+            self.skip_whitespace();
+            // A group followed by more text is a regex group in a longer pattern,
+            // e.g. `(red|blue) car`
+            if self.current_char().is_some() && !self.at_term_end() {
+                self.pos = start;
+                return self.parse_pattern();
+            }
             Ok(expr)
         } else {
-            // Parse pattern until we hit an operator or end
-            // Patterns can contain spaces, so we don't stop at whitespace
-            let mut pattern = String::new();
-            let mut escaped = false;
-
-            while let Some(ch) = self.current_char() {
-                if escaped {
-                    pattern.push(ch);
-                    escaped = false;
-                    self.advance();
-                    continue;
-                }
+            self.parse_pattern()
+        }
+    }
 
-                if ch == '\\' {
-                    pattern.push(ch);
-                    escaped = true;
-                    self.advance();
-                    continue;
-                }
+    /// Whether the current position ends a term: an operator or closing parenthesis.
+    fn at_term_end(&self) -> bool {
+        matches!(self.current_char(), Some('&' | '|' | ')'))
+            || near_operator(&self.input[self.pos..]).is_some()
+    }
 
-                // Stop only at operators or closing parenthesis
-                if ch == '&' || ch == '|' || ch == ')' {
-                    break;
-                }
+    fn parse_pattern(&mut self) -> Result<BooleanExpr, ParseError> {
+        // Parse pattern until we hit an operator or end
+        // Patterns can contain spaces, so we don't stop at whitespace
+        let mut pattern = String::new();
+        let mut escaped = false;
+        // Parentheses opened within the pattern are regex groups, and operators inside
+        // them belong to the regex
+        let mut depth = 0usize;
 
+        while let Some(ch) = self.current_char() {
+            if escaped {
                 pattern.push(ch);
+                escaped = false;
                 self.advance();
+                continue;
+            }
+
+            if ch == '\\' {
+                pattern.push(ch);
+                escaped = true;
+                self.advance();
+                continue;
+            }
+
+            // Regex groups are copied as is, including any operators inside
+            if let Some(len) = self.regex_group_len() {
+                pattern.extend(&self.input[self.pos..self.pos + len]);
+                self.pos += len;
+                continue;
             }
 
-            // Trim trailing whitespace from pattern
-            let pattern = pattern.trim_end().to_string();
+            match ch {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                // Stop only at operators, closing parenthesis, or a count threshold
+                _ if depth == 0 && self.at_term_end() => break,
+                '{' if depth == 0 && count_suffix(&self.input[self.pos..]).is_some() => break,
+                _ => {}
+            }
+
+            pattern.push(ch);
+            self.advance();
+        }
+
+        // Trim trailing whitespace from pattern
+        let pattern = pattern.trim_end().to_string();
+
+        if pattern.is_empty() {
+            return Err(self.error("Expected pattern"));
+        }
+
+        Ok(BooleanExpr::Pattern(pattern))
+    }
+}
 
-            if pattern.is_empty() {
-                return Err("Expected pattern".to_string());
+/// The text and length of the quoted term `"..."` at the start of `chars`, if any.
+///
+/// Only double-quoted text containing operator characters and followed by nothing but
+/// whitespace up to the next operator, closing parenthesis, count threshold, or end counts. Inside, `\"`
+/// and `\\` stand for `"` and `\`; other backslashes are kept.
+pub(crate) fn quoted_literal(chars: &[char]) -> Option<(String, usize)> {
+    let rest = chars.strip_prefix(&['"'])?;
+    let mut text = String::new();
+    let mut escaped = false;
+    let mut len = None;
+    for (i, &ch) in rest.iter().enumerate() {
+        if escaped {
+            if ch != '"' && ch != '\\' {
+                text.push('\\');
             }
+            text.push(ch);
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            len = Some(i + 2);
+            break;
+        } else {
+            text.push(ch);
+        }
+    }
+    let len = len?;
+    if !text.contains(['&', '|', '(', ')', '!', '~']) {
+        return None;
+    }
+    match chars[len..].iter().position(|c| !c.is_whitespace()) {
+        None => Some((text, len)),
+        Some(i) => {
+            let next = &chars[len + i..];
+            let ends_term = matches!(next[0], '&' | '|' | ')')
+                || near_operator(next).is_some()
+                || count_suffix(next).is_some();
+            ends_term.then_some((text, len))
+        }
+    }
+}
+
+/// The comparison, N, and length of the count threshold `{>=N}` (or `>`, `<=`, `<`, `==`)
+/// at the start of `chars`, if any. Spaces are allowed around the comparison and N.
+pub(crate) fn count_suffix(chars: &[char]) -> Option<(CountOp, usize, usize)> {
+    let rest = chars.strip_prefix(&['{'])?;
+    let mut i = rest.iter().take_while(|c| c.is_whitespace()).count();
+    let (op, op_len) = match (rest.get(i), rest.get(i + 1)) {
+        (Some('>'), Some('=')) => (CountOp::AtLeast, 2),
+        (Some('<'), Some('=')) => (CountOp::AtMost, 2),
+        (Some('='), Some('=')) => (CountOp::Exactly, 2),
+        (Some('>'), _) => (CountOp::MoreThan, 1),
+        (Some('<'), _) => (CountOp::LessThan, 1),
+        _ => return None,
+    };
+    i += op_len;
+    i += rest[i..].iter().take_while(|c| c.is_whitespace()).count();
+    let digits = rest[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let n = rest[i..i + digits]
+        .iter()
+        .collect::<String>()
+        .parse()
+        .ok()?;
+    i += digits;
+    i += rest[i..].iter().take_while(|c| c.is_whitespace()).count();
+    (rest.get(i) == Some(&'}')).then_some((op, n, i + 2))
+}
 
-            Ok(BooleanExpr::Pattern(pattern))
+/// The distance N and length of the NEAR operator `~N~` at the start of `chars`, if any.
+pub(crate) fn near_operator(chars: &[char]) -> Option<(usize, usize)> {
+    let rest = chars.strip_prefix(&['~'])?;
+    let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 || rest.get(digits) != Some(&'~') {
+        return None;
+    }
+    let distance = rest[..digits].iter().collect::<String>().parse().ok()?;
+    Some((distance, digits + 2))
+}
+
+/// Length of the regex group `(?...)` (non-capturing, or setting flags) at the start of
+/// `chars`, up to and including its closing parenthesis; `None` if `chars` does not start
+/// such a group or it is not closed.
+pub(crate) fn regex_group_len(chars: &[char]) -> Option<usize> {
+    if !chars.starts_with(&['(', '?']) {
+        return None;
+    }
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (i, &ch) in chars.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
         }
     }
+    None
 }
 
 /// Build regex map for all patterns in a Boolean expression
@@ -202,6 +634,7 @@ pub fn build_pattern_regexes(
         if cfg.dotall {
             builder.dot_matches_new_line(true);
         }
+        builder.crlf(cfg.crlf);
 
         regexes.insert(pattern, builder.build()?);
     }
@@ -277,4 +710,115 @@ mod tests {
             _ => panic!("Expected AND expression"),
         }
     }
+
+    #[test]
+    fn test_not_expression_precedence() {
+        let expr = parse_boolean_expression("a|b&!c").unwrap();
+        let BooleanExpr::Or(_, right) = expr else {
+            panic!("Expected OR expression");
+        };
+        let BooleanExpr::And(_, not) = right.as_ref() else {
+            panic!("Expected AND expression on right");
+        };
+        match not.as_ref() {
+            BooleanExpr::Not(inner) => {
+                assert!(matches!(inner.as_ref(), BooleanExpr::Pattern(p) if p == "c"))
+            }
+            _ => panic!("Expected NOT expression"),
+        }
+    }
+
+    #[test]
+    fn test_not_expression_matches() {
+        let cfg = Config::default();
+        let expr = parse_boolean_expression("error & !(timeout|retry)").unwrap();
+        let regexes = build_pattern_regexes(&expr, &cfg).unwrap();
+        assert!(expr.matches("error disk", &regexes));
+        assert!(!expr.matches("error timeout", &regexes));
+        assert!(!expr.matches("retry", &regexes));
+
+        let highlighted = expr.get_highlighted_patterns();
+        assert_eq!(highlighted.len(), 1);
+        assert!(highlighted.contains("error"));
+
+        assert!(parse_boolean_expression("a&!").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_positions() {
+        let err = parse_boolean_expression("a&(b|c").unwrap_err();
+        assert_eq!(err.message, "Expected closing parenthesis");
+        assert_eq!(err.offset, 6);
+        assert_eq!(parse_boolean_expression("a|(&b)").unwrap_err().offset, 3);
+        assert_eq!(parse_boolean_expression("(a)b)&c").unwrap_err().offset, 4);
+
+        let err = parse_boolean_expression("\té&(x").unwrap_err();
+        assert_eq!(err.to_string(), "Expected closing parenthesis at offset 5");
+        assert_eq!(
+            err.diagnostic("\té&(x"),
+            "  \té&(x\n  \t    ^ Expected closing parenthesis\n"
+        );
+    }
+
+    #[test]
+    fn test_near_expression() {
+        let cfg = Config::default();
+        let expr = parse_boolean_expression("a~2~b&c").unwrap();
+        let BooleanExpr::And(near, _) = &expr else {
+            panic!("Expected AND expression");
+        };
+        assert!(matches!(near.as_ref(), BooleanExpr::Near(_, _, 2)));
+        assert!(expr.is_windowed());
+
+        let expr = parse_boolean_expression("a~2~b").unwrap();
+        let regexes = build_pattern_regexes(&expr, &cfg).unwrap();
+        let hits = expr.matches_lines(&["a", "x", "b", "x", "x", "a"], &regexes);
+        assert_eq!(hits, vec![true, false, true, false, false, false]);
+        assert!(!expr.matches("a", &regexes));
+        assert!(expr.matches("a b", &regexes));
+
+        // Without the closing '~' it is part of the pattern
+        let expr = parse_boolean_expression("a~2b|c").unwrap();
+        assert!(!expr.is_windowed());
+    }
+
+    #[test]
+    fn test_quoted_terms() {
+        let expr = parse_boolean_expression(r#""a&b" | "say \"(hi)\"" & c"#).unwrap();
+        let mut patterns: Vec<String> = expr.get_patterns().into_iter().collect();
+        patterns.sort();
+        assert_eq!(patterns, vec![r"a\&b", "c", r#"say "\(hi\)""#]);
+
+        // Quotes that do not enclose a whole term are part of the pattern
+        assert_eq!(
+            quoted_literal(&r#""a|b"x"#.chars().collect::<Vec<_>>()),
+            None
+        );
+        assert_eq!(quoted_literal(&r#""ab""#.chars().collect::<Vec<_>>()), None);
+    }
+
+    #[test]
+    fn test_count_thresholds() {
+        let cfg = Config::default();
+        let expr = parse_boolean_expression("retry{>=2}&!ok").unwrap();
+        let BooleanExpr::And(count, _) = &expr else {
+            panic!("Expected AND expression");
+        };
+        assert!(matches!(
+            count.as_ref(),
+            BooleanExpr::Count(p, CountOp::AtLeast, 2) if p == "retry"
+        ));
+        let regexes = build_pattern_regexes(&expr, &cfg).unwrap();
+        assert!(expr.matches("retry, retry", &regexes));
+        assert!(!expr.matches("retry", &regexes));
+        assert!(!expr.matches("retry retry ok", &regexes));
+
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(
+            count_suffix(&chars("{ <= 10 }x")),
+            Some((CountOp::AtMost, 10, 9))
+        );
+        assert_eq!(count_suffix(&chars("{2,3}")), None);
+        assert_eq!(count_suffix(&chars("{>=}")), None);
+    }
 }
@@ -5,8 +5,16 @@
 //! On error (e.g., no pattern provided), `parse()` returns a user-friendly message
 //! suitable for printing to stderr.
 
+use clap::parser::ValueSource;
 use clap::{Arg, ArgAction, ArgMatches, Command};
-use rgrep::{Config, Context};
+use rgrep::batch::read_queries;
+use rgrep::fs_utils::{parse_root, read_file_list};
+use rgrep::ignore::glob_regex;
+use rgrep::regex_utils::ident_pattern;
+use rgrep::{
+    BinaryFiles, ColorChoice, Compat, Config, Context, Engine, FileGlob, JsonSchema, LogSource,
+    Matching, MergeTies, Scheduling, Syntax, parse_utc_offset,
+};
 
 /// Build the clap Command describing rgrep's CLI.
 ///
@@ -15,13 +23,55 @@ use rgrep::{Config, Context};
 pub fn build_cli() -> Command {
     Command::new("rgrep")
         .about("A powerful, feature-rich Rust grep implementation")
+        // -h is taken by --no-filename (as in grep); help stays available as --help
+        .disable_help_flag(true)
+        .arg(
+            Arg::new("help")
+                .long("help")
+                .action(ArgAction::Help)
+                .help("Print help"),
+        )
         .arg(
             Arg::new("pattern")
                 .short('r')
                 .long("regexp")
                 .num_args(1)
                 .action(ArgAction::Set)
-                .help("Pattern expression to search for (use '|' for OR and '&' for AND; only a single -e is allowed)"),
+                .help("Pattern expression to search for (use '|' for OR, '&' for AND, '!' for NOT, and '~N~' for NEAR; only a single -e is allowed)"),
+        )
+        .arg(
+            Arg::new("ident")
+                .long("ident")
+                .value_name("NAME")
+                .num_args(1)
+                .conflicts_with_all(["pattern", "queries"])
+                .help("Search for the identifier NAME as a whole token, case-sensitively, also at the end of a qualified path such as x.NAME (instead of -r)"),
+        )
+        .arg(
+            Arg::new("qualified")
+                .long("qualified")
+                .action(ArgAction::SetTrue)
+                .requires("ident")
+                .help("With --ident, extend each match over the '::' or '.' qualified path ending in NAME, such as std::mem::take, for highlighting and -o (the same lines match)"),
+        )
+        .arg(
+            Arg::new("def")
+                .long("def")
+                .value_name("NAME")
+                .num_args(1)
+                .conflicts_with_all(["pattern", "ident", "queries"])
+                .help("Search for lines defining the symbol NAME (function, struct, const, ...) using per-language heuristics (instead of -r)"),
+        )
+        .arg(
+            Arg::new("queries")
+                .long("queries")
+                .value_name("FILE")
+                .num_args(1)
+                .conflicts_with_all([
+                    "pattern", "follow", "json", "where", "duration-stats", "vimgrep", "format",
+                    "before", "after", "context", "path-or-content", "preview",
+                ])
+                .help("Run every query in FILE (one per line; '#' comments) in a single pass and group results per query"),
         )
         .arg(
             Arg::new("word")
@@ -51,6 +101,28 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Suppress normal output; instead print a count of matching lines for each input file"),
         )
+        .arg(
+            Arg::new("count-matches")
+                .long("count-matches")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "files-only", "queries", "follow", "journald", "syslog", "vimgrep", "format",
+                    "write", "diff", "duration-stats",
+                ])
+                .help("Like -c, but count every match: a line with three matches counts 3"),
+        )
+        .arg(
+            Arg::new("files-with-matches")
+                .short('l')
+                .long("files-with-matches")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "count", "count-matches", "json", "vimgrep", "format", "only-matching",
+                    "duration-stats", "preview", "path-or-content", "stats", "files-only",
+                    "queries", "follow", "journald", "syslog", "write", "diff",
+                ])
+                .help("Print only the names of files with a matching line; each file is read up to its first match"),
+        )
         .arg(
             Arg::new("quiet")
                 .short('q')
@@ -76,9 +148,46 @@ pub fn build_cli() -> Command {
         .arg(
             Arg::new("context")
                 .short('C')
-                .value_name("NUM")
+                .value_name("NUM[,NUM]")
+                .action(ArgAction::Append)
+                .value_parser(parse_context)
+                .help("Print NUM lines of output context, or BEFORE,AFTER lines (e.g. -C 2,5); the last -C wins"),
+        )
+        .arg(
+            Arg::new("context-percent")
+                .long("context-percent")
+                .value_name("PERCENT")
+                .value_parser(clap::value_parser!(u8).range(1..=100))
+                .conflicts_with("follow")
+                .help("Print at least PERCENT% of each file's lines as context before and after matching lines"),
+        )
+        .arg(
+            Arg::new("preview")
+                .long("preview")
+                .value_name("N")
                 .num_args(1)
-                .help("Print NUM lines of output context"),
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with_all(["follow", "duration-stats"])
+                .help("Print at most N matching lines per file, then '… (+K more matches)'; counts and JSON summaries include every match"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .alias("colour")
+                .value_name("WHEN")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("auto")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .help("When to color output: auto (only when stdout is a terminal), always, or never"),
+        )
+        .arg(
+            Arg::new("colors")
+                .long("colors")
+                .value_name("SPEC")
+                .num_args(1)
+                .help("Highlight colors as GREP_COLORS-style SGR codes, e.g. 'ms=1;31:fn=35:ln=32:se=36' (applied after $GREP_COLORS)"),
         )
         .arg(
             Arg::new("recursive")
@@ -87,6 +196,183 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Read all files under each directory, recursively"),
         )
+        .arg(
+            Arg::new("root")
+                .long("root")
+                .value_name("LABEL=PATH")
+                .action(ArgAction::Append)
+                .value_parser(parse_root_arg)
+                .help("Search PATH and show results below it as LABEL/... (same as a LABEL=PATH input); repeatable"),
+        )
+        .arg(
+            Arg::new("skip-binary-dirs")
+                .long("skip-binary-dirs")
+                .action(ArgAction::SetTrue)
+                .requires("recursive")
+                .help("Skip the rest of a directory once more than 90% of the first files sampled in it are binary (e.g. .git/objects)"),
+        )
+        .arg(
+            Arg::new("binary-files")
+                .long("binary-files")
+                .value_name("TYPE")
+                .value_parser(["binary", "text", "without-match"])
+                .help("What to do with binary files: 'without-match' skips them (the default), 'binary' prints 'Binary file X matches' instead of their lines (the default with --compat=gnu), 'text' searches them like text"),
+        )
+        .arg(
+            Arg::new("text")
+                .short('a')
+                .long("text")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("binary-files")
+                .help("Search binary files like text (same as --binary-files=text)"),
+        )
+        .arg(
+            Arg::new("search-zip")
+                .short('z')
+                .long("search-zip")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["follow", "journald", "syslog", "write", "diff"])
+                .help("Search inside gzip, bzip2, xz, and zstd compressed files, decompressing them as they are read"),
+        )
+        .arg(
+            Arg::new("pre")
+                .long("pre")
+                .value_name("COMMAND")
+                .conflicts_with_all(["queries", "follow", "journald", "syslog", "write", "diff"])
+                .help("Search the output of COMMAND run on each file instead of the file ('{}' stands for its path, which is otherwise appended), e.g. 'pdftotext {} -'"),
+        )
+        .arg(
+            Arg::new("pre-glob")
+                .long("pre-glob")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .requires("pre")
+                .help("Only run --pre on files matching GLOB (may be repeated)"),
+        )
+        .arg(
+            Arg::new("no-ignore")
+                .long("no-ignore")
+                .action(ArgAction::SetTrue)
+                .help("In recursive searches, also search files ignored by .gitignore, .git/info/exclude, the global excludes file, .rgrepignore, and ~/.config/rgrep/ignore, and the .git directory"),
+        )
+        .arg(
+            Arg::new("no-global-ignore")
+                .long("no-global-ignore")
+                .action(ArgAction::SetTrue)
+                .help("In recursive searches, do not read git's global excludes file or .git/info/exclude; .gitignore files still apply"),
+        )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .action(ArgAction::SetTrue)
+                .help("In recursive searches, also search hidden files and directories (names starting with '.')"),
+        )
+        .arg(
+            Arg::new("follow-symlinks")
+                .short('L')
+                .long("follow-symlinks")
+                .action(ArgAction::SetTrue)
+                .help("In recursive searches, follow symlinks to files and directories; each directory is walked once, so symlink loops are cut"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .value_parser(parse_glob)
+                .help("Search only files matching GLOB (e.g. '*.rs'; with a '/', relative to the searched directory); repeatable, the last matching --include/--exclude wins"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .value_parser(parse_glob)
+                .help("Skip files matching GLOB (e.g. '*.min.js'); repeatable, the last matching --include/--exclude wins"),
+        )
+        .arg(
+            Arg::new("exclude-dir")
+                .long("exclude-dir")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .value_parser(parse_glob)
+                .help("In recursive searches, do not walk directories matching GLOB (e.g. 'target' or '.*'); repeatable"),
+        )
+        .arg(
+            Arg::new("type")
+                .short('t')
+                .long("type")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("Search only files of type NAME (e.g. 'rust', 'py'; see --type-list); repeatable"),
+        )
+        .arg(
+            Arg::new("type-not")
+                .short('T')
+                .long("type-not")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("Skip files of type NAME; repeatable"),
+        )
+        .arg(
+            Arg::new("type-add")
+                .long("type-add")
+                .value_name("NAME:GLOB")
+                .action(ArgAction::Append)
+                .value_parser(parse_type_def)
+                .help("Add GLOB to the file type NAME, defining NAME if it is not built in (e.g. 'proto:*.pbtxt'); repeatable"),
+        )
+        .arg(
+            Arg::new("type-list")
+                .long("type-list")
+                .action(ArgAction::SetTrue)
+                .help("List the known file types and their globs, and exit"),
+        )
+        .arg(
+            Arg::new("files-only")
+                .long("files")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "follow", "json", "queries", "count", "write", "diff", "doctor", "self-check",
+                    "explain", "journald", "syslog",
+                ])
+                .help("Print the files that would be searched, after all filtering, and exit without searching; no pattern is needed"),
+        )
+        .arg(
+            Arg::new("max-entries")
+                .long("max-entries")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Stop recursive walks after N files and directories, warning that the results are incomplete"),
+        )
+        .arg(
+            Arg::new("max-filesize")
+                .long("max-filesize")
+                .value_name("SIZE")
+                .value_parser(parse_size)
+                .help("In recursive searches, skip files larger than SIZE bytes (suffixes K, M, G, e.g. 50M)"),
+        )
+        .arg(
+            Arg::new("list-skipped")
+                .long("list-skipped")
+                .action(ArgAction::SetTrue)
+                .help("Warn about each file or directory left out of the search and why (binary, too large, unreadable, ignored, hidden, excluded), then count them per reason"),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Descend at most N levels below each directory searched with -R (1: only the files directly inside it)"),
+        )
+        .arg(
+            Arg::new("ignore-file")
+                .long("ignore-file")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .value_parser(parse_ignore_file)
+                .help("In recursive searches, also skip the paths matched by the gitignore-style patterns in PATH (relative to the searched directory); repeatable"),
+        )
         .arg(
             Arg::new("ignore-case")
                 .short('i')
@@ -100,12 +386,428 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Make '.' match newlines as well (regex dotall mode)"),
         )
+        .arg(
+            Arg::new("crlf")
+                .long("crlf")
+                .action(ArgAction::SetTrue)
+                .help("Treat \\r as part of line endings: '$' matches before \\r\\n or a lone \\r"),
+        )
+        .arg(
+            Arg::new("syntax")
+                .long("syntax")
+                .value_name("SYNTAX")
+                .value_parser(["literal", "basic", "extended"])
+                .default_value("extended")
+                .conflicts_with_all(["ident", "def"])
+                .help("Pattern syntax: 'literal' text, POSIX 'basic' regexes (like grep -G), or 'extended' (regex crate syntax with Boolean operators)"),
+        )
+        .arg(
+            Arg::new("fixed-strings")
+                .short('F')
+                .long("fixed-strings")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["syntax", "basic-regexp", "extended-regexp", "ident", "def"])
+                .help("Match the pattern as literal text (--syntax=literal)"),
+        )
+        .arg(
+            Arg::new("basic-regexp")
+                .short('G')
+                .long("basic-regexp")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["syntax", "extended-regexp", "ident", "def"])
+                .help("Read the pattern as a POSIX basic regex (--syntax=basic)"),
+        )
+        .arg(
+            Arg::new("extended-regexp")
+                .short('E')
+                .long("extended-regexp")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("syntax")
+                .help("Read the pattern in the default extended syntax (--syntax=extended)"),
+        )
+        .arg(
+            Arg::new("compat")
+                .long("compat")
+                .value_name("MODE")
+                .value_parser(["rgrep", "gnu"])
+                .help("With 'gnu', behave like GNU grep: basic regexes and no line numbers by default, '-'/'--' context separators, 'Binary file X matches', unreadable files reported without stopping the search (exit 2), and -q exiting at the first match"),
+        )
+        .arg(
+            Arg::new("engine")
+                .long("engine")
+                .value_name("ENGINE")
+                .value_parser(["default", "fancy"])
+                .default_value("default")
+                .conflicts_with_all(["def", "where", "filter-script", "multiline"])
+                .help("Regex engine: 'default' (fast, linear time) or 'fancy' (lookaround and backreferences; needs the 'fancy' feature)"),
+        )
+        .arg(
+            Arg::new("strategy")
+                .long("strategy")
+                .value_name("LIST")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_parser(["auto", "literal", "regex", "sequential", "parallel"])
+                .help("Force how lines are matched ('literal' or 'regex') and files scheduled ('sequential' or 'parallel'), e.g. 'regex,sequential'; 'auto' chooses both again"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["follow", "doctor", "self-check", "journald", "syslog", "write", "diff"])
+                .help("Print how the search would be executed and why, and exit without searching"),
+        )
+        .arg(
+            Arg::new("multiline")
+                .short('U')
+                .long("multiline")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["follow", "queries", "def", "where"])
+                .help("Let matches span lines by matching each input as a whole; every line of a match is printed"),
+        )
         .arg(
             Arg::new("follow")
                 .short('f')
                 .long("follow")
                 .action(ArgAction::SetTrue)
-                .help("Follow file(s) for new lines (like tail -f | grep). Only supported for a single file."),
+                .help("Follow a file, or standard input (-), for new lines (like tail -f | grep), surviving log rotation"),
+        )
+        .arg(
+            Arg::new("journald")
+                .long("journald")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["syslog", "metrics-file", "queries", "after", "before", "context", "multiline", "duration-stats", "recursive"])
+                .help("Search the systemd journal instead of files; FILE arguments are journalctl matches (e.g. _SYSTEMD_UNIT=sshd.service), and -f follows new entries"),
+        )
+        .arg(
+            Arg::new("syslog")
+                .long("syslog")
+                .value_name("ADDR")
+                .conflicts_with_all(["follow", "count", "queries", "after", "before", "context", "multiline", "duration-stats", "recursive"])
+                .help("Listen for syslog messages on a UDP address (HOST:PORT) or unix datagram socket (unix:PATH) and print matching ones as they arrive"),
+        )
+        .arg(
+            Arg::new("metrics-file")
+                .long("metrics-file")
+                .value_name("PATH")
+                .requires("follow")
+                .help("With -f, keep match counters in PATH in the Prometheus text format (e.g. for node_exporter)"),
+        )
+        .arg(
+            Arg::new("follow-from-start")
+                .long("follow-from-start")
+                .action(ArgAction::SetTrue)
+                .requires("follow")
+                .conflicts_with_all(["tail-lines", "journald"])
+                .help("With -f, search the lines already in the file before following it, instead of starting at its end"),
+        )
+        .arg(
+            Arg::new("tail-lines")
+                .long("tail-lines")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("follow")
+                .conflicts_with("journald")
+                .help("With -f, first print the last N matching lines already in the file, like tail -n N -f"),
+        )
+        .arg(
+            Arg::new("column")
+                .long("column")
+                .action(ArgAction::SetTrue)
+                .help("Show the 1-based column number of the first match on each matching line"),
+        )
+        .arg(
+            Arg::new("line-number")
+                .short('n')
+                .long("line-number")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-line-number")
+                .help("Prefix each output line with its line number (default for files)"),
+        )
+        .arg(
+            Arg::new("no-line-number")
+                .short('N')
+                .long("no-line-number")
+                .action(ArgAction::SetTrue)
+                .overrides_with("line-number")
+                .help("Do not print line numbers (default when reading stdin)"),
+        )
+        .arg(
+            Arg::new("with-filename")
+                .short('H')
+                .long("with-filename")
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-filename")
+                .help("Print the file name for each match (default when searching more than one file)"),
+        )
+        .arg(
+            Arg::new("no-filename")
+                .short('h')
+                .long("no-filename")
+                .action(ArgAction::SetTrue)
+                .overrides_with("with-filename")
+                .help("Never print file name prefixes"),
+        )
+        .arg(
+            Arg::new("heading")
+                .long("heading")
+                .action(ArgAction::SetTrue)
+                .help("Print the file name once above its matches instead of on every line"),
+        )
+        .arg(
+            Arg::new("vimgrep")
+                .long("vimgrep")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["json", "count", "duration-stats"])
+                .help("Print every match as file:line:column:text (for Vim quickfix lists); no context lines"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("TEMPLATE")
+                .num_args(1)
+                .conflicts_with_all(["json", "count", "duration-stats", "vimgrep", "follow"])
+                .help("Print each line using TEMPLATE with {path}, {line}, {column}, {byte_offset}, {match}, {text}, {captures[N]}"),
+        )
+        .arg(
+            Arg::new("replace")
+                .long("replace")
+                .value_name("TEMPLATE")
+                .num_args(1)
+                .conflicts_with_all(["json", "format", "multiline", "follow", "duration-stats"])
+                .help("Print matching lines with each match replaced by TEMPLATE ($1, ${name} for capture groups)"),
+        )
+        .arg(
+            Arg::new("only-matching")
+                .short('o')
+                .long("only-matching")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["invert", "after", "before", "context", "json", "format", "vimgrep", "replace", "multiline", "duration-stats"])
+                .help("Print only the matched parts of matching lines, each on its own line"),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .value_name("N|NAME")
+                .requires("only-matching")
+                .conflicts_with("def")
+                .help("With -o, print capture group N (or the group named NAME) of each match instead of the whole match"),
+        )
+        .arg(
+            Arg::new("max-columns")
+                .long("max-columns")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Replace printed lines longer than N bytes with '[... omitted: line too long (N bytes)]'"),
+        )
+        .arg(
+            Arg::new("max-columns-preview")
+                .long("max-columns-preview")
+                .action(ArgAction::SetTrue)
+                .requires("max-columns")
+                .help("With --max-columns, print the N bytes around the first match of a long line instead"),
+        )
+        .arg(
+            Arg::new("match-window")
+                .long("match-window")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("max-columns-preview")
+                .help("Print only N characters before and after each match, with '…' for the text left out; with --max-columns, only for lines longer than that"),
+        )
+        .arg(
+            Arg::new("write")
+                .long("write")
+                .action(ArgAction::SetTrue)
+                .requires("replace")
+                .conflicts_with_all(["invert", "count", "quiet", "queries", "journald", "syslog"])
+                .help("Rewrite matching files in place with the --replace template (atomically, via a temporary file)"),
+        )
+        .arg(
+            Arg::new("backup")
+                .long("backup")
+                .value_name("SUFFIX")
+                .requires("write")
+                .help("With --write, keep each original file as FILE+SUFFIX (e.g. --backup .bak)"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .requires("write")
+                .help("With --write, report what would change without writing any file"),
+        )
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .action(ArgAction::SetTrue)
+                .requires("replace")
+                .conflicts_with_all(["invert", "count", "quiet", "queries", "journald", "syslog", "backup", "dry-run"])
+                .help("Print a unified diff of what --replace would change in each file, without writing any file"),
+        )
+        .arg(
+            Arg::new("merge-ties")
+                .long("merge-ties")
+                .value_name("MODE")
+                .num_args(1)
+                .value_parser(["input-order", "path", "stable"])
+                .default_value("input-order")
+                .help("Tie-breaking for equal timestamps when merging multiple files chronologically"),
+        )
+        .arg(
+            Arg::new("assume-tz")
+                .long("assume-tz")
+                .value_name("OFFSET")
+                .num_args(1)
+                .help("UTC offset (e.g. +02:00, -0500, UTC) for timestamps without one when merging files"),
+        )
+        .arg(
+            Arg::new("duration-stats")
+                .long("duration-stats")
+                .value_name("REGEX")
+                .num_args(1)
+                .conflicts_with_all(["json", "count"])
+                .help("Summarize the number captured by REGEX's first group (e.g. 'took (\\d+)ms') as min/avg/p50/p95/max per file and overall"),
+        )
+        .arg(
+            Arg::new("expand-env")
+                .long("expand-env")
+                .action(ArgAction::SetTrue)
+                .help("Expand ${NAME} in the pattern from the environment ($${NAME} for a literal ${NAME}); unset variables are an error"),
+        )
+        .arg(
+            Arg::new("where")
+                .long("where")
+                .value_name("EXPR")
+                .num_args(1)
+                .conflicts_with("follow")
+                .help("Only keep matches whose capture groups satisfy EXPR, e.g. '$1 > 500' or '$method == \"POST\" && $ms >= 1000'"),
+        )
+        .arg(
+            Arg::new("filter-script")
+                .long("filter-script")
+                .value_name("FILE")
+                .conflicts_with_all(["multiline", "follow", "queries", "journald", "syslog", "write", "diff"])
+                .help("Only keep matching lines for which the Rhai script FILE returns true; it sees line, captures, path, and line_number (needs the 'script' feature)"),
+        )
+        .arg(
+            Arg::new("map")
+                .long("map")
+                .value_name("SPEC")
+                .action(ArgAction::Append)
+                .conflicts_with_all(["multiline", "replace"])
+                .help("Transform each line before matching while printing the original: strip-ansi, lowercase, trim, squash-whitespace, or s/REGEX/REPLACEMENT/[gi]; repeatable, applied in order"),
+        )
+        .arg(
+            Arg::new("squash-whitespace")
+                .long("squash-whitespace")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["multiline", "replace"])
+                .help("Match as if every run of whitespace were a single space; lines are printed unchanged"),
+        )
+        .arg(
+            Arg::new("path-or-content")
+                .long("path-or-content")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["invert", "follow"])
+                .help("Also report files whose path matches the pattern when no line does (shown as 'PATH (path match)')"),
+        )
+        .arg(
+            Arg::new("file-list-cache")
+                .long("file-list-cache")
+                .value_name("PATH")
+                .num_args(1)
+                .help("Save the expanded file list to PATH and reuse it on later runs while the searched directories are unchanged"),
+        )
+        .arg(
+            Arg::new("no-match-message")
+                .long("no-match-message")
+                .value_name("STRING")
+                .conflicts_with_all(["follow", "quiet"])
+                .help("Print STRING when nothing matched (a 'no_match' event with --json)"),
+        )
+        .arg(
+            Arg::new("match-timeout")
+                .long("match-timeout")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u64))
+                .conflicts_with("queries")
+                .help("Skip lines whose match takes longer than MS milliseconds, with a warning for each"),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .value_name("N")
+                .num_args(0..=1)
+                .default_missing_value("10")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with_all(["json", "follow"])
+                .help("After the search, list the N slowest files (default 10) with time and throughput on stderr"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["json", "follow", "quiet", "queries", "files-only"])
+                .help("After the results, print how many files were searched and matched, the matched lines and matches, the bytes searched, and the time taken"),
+        )
+        .arg(
+            Arg::new("no-table")
+                .long("no-table")
+                .action(ArgAction::SetTrue)
+                .help("Print multi-file counts, summaries, and --stats as raw 'name:value' lines instead of an aligned table"),
+        )
+        .arg(
+            Arg::new("doctor")
+                .long("doctor")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("follow")
+                .help("Check colors, the pattern, inputs, and caches, print diagnostics, and exit without searching"),
+        )
+        .arg(
+            Arg::new("self-check")
+                .long("self-check")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["follow", "doctor", "journald", "syslog", "write", "diff"])
+                .help("Run the search in parallel and single-threaded, and report any difference between the results instead of printing them"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .value_name("SCHEMA")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("rgrep")
+                .value_parser(["rgrep", "rg"])
+                .help("Print results as JSON Lines events (begin, match, context, end, summary); with --json=rg, in ripgrep's --json schema"),
+        )
+        .arg(
+            Arg::new("context-hash")
+                .long("context-hash")
+                .action(ArgAction::SetTrue)
+                .requires("json")
+                .help("With --json, add a 'context_hash' to match events: a fingerprint of the line and the 2 lines around it that survives line-number shifts"),
+        )
+        .arg(
+            Arg::new("fd")
+                .long("fd")
+                .value_name("N")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(u32))
+                .help("Also search the open file descriptor N (read as /dev/fd/N), e.g. a pipe passed by the calling shell"),
+        )
+        .arg(
+            Arg::new("files-from")
+                .long("files-from")
+                .value_name("FILE")
+                .conflicts_with("files-from0")
+                .help("Also search the files listed in FILE, one per line (- for stdin), e.g. from find or git ls-files"),
+        )
+        .arg(
+            Arg::new("files-from0")
+                .long("files-from0")
+                .value_name("FILE")
+                .help("Like --files-from, with the files separated by NUL bytes (find -print0, git ls-files -z)"),
         )
         .arg(
             Arg::new("files")
@@ -115,6 +817,97 @@ pub fn build_cli() -> Command {
         )
 }
 
+/// Check a `--root LABEL=PATH` value.
+fn parse_root_arg(s: &str) -> Result<String, String> {
+    match parse_root(s) {
+        Some(_) => Ok(s.to_string()),
+        None => {
+            Err("expected LABEL=PATH, with a label of letters, digits, '_', '-', and '.'".into())
+        }
+    }
+}
+
+/// Check an `--include` or `--exclude` GLOB.
+fn parse_glob(s: &str) -> Result<String, String> {
+    glob_regex(s).map(|_| s.to_string())
+}
+
+/// Parse a `--max-filesize` SIZE: bytes, or a number with a `K`, `M`, or `G` suffix
+/// (powers of 1024).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size '{}': expected e.g. 500K, 50M, or 2G", s);
+    let (digits, unit) = match s.char_indices().last() {
+        Some((at, c)) if c.is_ascii_alphabetic() => (&s[..at], c.to_ascii_uppercase()),
+        _ => (s, 'B'),
+    };
+    let shift = match unit {
+        'B' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        _ => return Err(invalid()),
+    };
+    let n: u64 = digits.parse().map_err(|_| invalid())?;
+    n.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Parse a `-C` value: `NUM` lines on both sides, or `BEFORE,AFTER`.
+fn parse_context(s: &str) -> Result<(usize, usize), String> {
+    let num = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid context '{}': expected NUM or BEFORE,AFTER", s))
+    };
+    match s.split_once(',') {
+        Some((before, after)) => Ok((num(before)?, num(after)?)),
+        None => num(s).map(|n| (n, n)),
+    }
+}
+
+/// Validate a `--type-add` definition `NAME:GLOB`.
+fn parse_type_def(s: &str) -> Result<(String, String), String> {
+    let (name, glob) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid --type-add '{}': expected NAME:GLOB", s))?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "invalid type name '{}': use letters, digits, '_', and '-'",
+            name
+        ));
+    }
+    glob_regex(glob)?;
+    Ok((name.to_string(), glob.to_string()))
+}
+
+/// `--include` and `--exclude` globs in command-line order.
+fn get_globs(matches: &ArgMatches) -> Vec<FileGlob> {
+    let mut globs: Vec<(usize, FileGlob)> = Vec::new();
+    for (id, make) in [
+        ("include", FileGlob::Include as fn(String) -> FileGlob),
+        ("exclude", FileGlob::Exclude),
+    ] {
+        if let (Some(values), Some(indices)) =
+            (matches.get_many::<String>(id), matches.indices_of(id))
+        {
+            globs.extend(indices.zip(values.map(|v| make(v.clone()))));
+        }
+    }
+    globs.sort_by_key(|(idx, _)| *idx);
+    globs.into_iter().map(|(_, glob)| glob).collect()
+}
+
+/// Check that an `--ignore-file` PATH can be read.
+fn parse_ignore_file(s: &str) -> Result<String, String> {
+    match std::fs::read_to_string(s) {
+        Ok(_) => Ok(s.to_string()),
+        Err(e) => Err(format!("cannot read ignore file: {}", e)),
+    }
+}
+
 /// Parse an optional numeric argument into usize; returns 0 when absent or invalid.
 fn to_usize(matches: &ArgMatches, name: &str) -> usize {
     matches
@@ -125,36 +918,234 @@ fn to_usize(matches: &ArgMatches, name: &str) -> usize {
 
 /// Parse a list of optional string arguments into a Vec<String>.
 fn get_inputs(matches: &ArgMatches) -> Vec<String> {
-    matches
-        .get_many::<String>("files")
-        .map(|vals| vals.map(|s| s.to_string()).collect())
-        .unwrap_or_else(|| Vec::new())
+    let files = matches.get_many::<String>("files").into_iter().flatten();
+    let fds = matches.get_many::<u32>("fd").into_iter().flatten();
+    // --root LABEL=PATH is the same as a LABEL=PATH input after the others
+    let roots = matches.get_many::<String>("root").into_iter().flatten();
+    files
+        .cloned()
+        .chain(fds.map(|fd| format!("/dev/fd/{}", fd)))
+        .chain(roots.cloned())
+        .collect()
 }
 
 /// Set flags from the parsed `ArgMatches`.
 fn set_flags(matches: &ArgMatches, cfg: &mut Config) {
+    if matches
+        .get_one::<String>("compat")
+        .is_some_and(|c| c == "gnu")
+    {
+        cfg.compat = Compat::Gnu;
+    }
     cfg.invert = matches.get_flag("invert");
-    cfg.count = matches.get_flag("count");
+    cfg.count_matches = matches.get_flag("count-matches");
+    cfg.count = matches.get_flag("count") || cfg.count_matches;
+    cfg.files_with_matches = matches.get_flag("files-with-matches");
     cfg.quiet = matches.get_flag("quiet");
     cfg.word = matches.get_flag("word");
     cfg.line = matches.get_flag("line");
 
     cfg.recursive = matches.get_flag("recursive");
+    cfg.skip_binary_dirs = matches.get_flag("skip-binary-dirs");
+    cfg.binary_files = if matches.get_flag("text") {
+        Some(BinaryFiles::Text)
+    } else {
+        match matches
+            .get_one::<String>("binary-files")
+            .map(|s| s.as_str())
+        {
+            Some("binary") => Some(BinaryFiles::Report),
+            Some("text") => Some(BinaryFiles::Text),
+            Some(_) => Some(BinaryFiles::Skip),
+            None => None,
+        }
+    };
+    cfg.search_zip = matches.get_flag("search-zip");
+    cfg.pre = matches.get_one::<String>("pre").cloned();
+    cfg.pre_globs = matches
+        .get_many::<String>("pre-glob")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    cfg.no_ignore = matches.get_flag("no-ignore");
+    cfg.no_global_ignore = matches.get_flag("no-global-ignore");
+    cfg.hidden = matches.get_flag("hidden");
+    cfg.follow_symlinks = matches.get_flag("follow-symlinks");
+    cfg.ignore_files = matches
+        .get_many::<String>("ignore-file")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    cfg.globs = get_globs(matches);
+    cfg.exclude_dirs = matches
+        .get_many::<String>("exclude-dir")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    cfg.max_entries = matches.get_one::<usize>("max-entries").copied();
+    cfg.max_depth = matches.get_one::<usize>("max-depth").copied();
+    cfg.max_filesize = matches.get_one::<u64>("max-filesize").copied();
+    cfg.list_skipped = matches.get_flag("list-skipped");
+    for (id, types) in [("type", &mut cfg.types), ("type-not", &mut cfg.types_not)] {
+        *types = matches
+            .get_many::<String>(id)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+    }
+    cfg.type_defs = matches
+        .get_many::<(String, String)>("type-add")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
     cfg.case_insensitive = matches.get_flag("ignore-case");
     cfg.dotall = matches.get_flag("dotall");
+    cfg.crlf = matches.get_flag("crlf");
+    cfg.multiline = matches.get_flag("multiline");
+    cfg.syntax = if matches.get_flag("fixed-strings") {
+        Syntax::Literal
+    } else if matches.get_flag("basic-regexp") {
+        Syntax::Basic
+    } else {
+        let chosen = matches.get_flag("extended-regexp")
+            || matches.value_source("syntax") == Some(ValueSource::CommandLine);
+        match matches.get_one::<String>("syntax").map(|s| s.as_str()) {
+            Some("literal") => Syntax::Literal,
+            Some("basic") => Syntax::Basic,
+            // Like grep, GNU-compatible runs read basic regexes unless told otherwise
+            _ if cfg.compat == Compat::Gnu && !chosen => Syntax::Basic,
+            _ => Syntax::Extended,
+        }
+    };
+    cfg.engine = match matches.get_one::<String>("engine").map(|s| s.as_str()) {
+        Some("fancy") => Engine::Fancy,
+        _ => Engine::Default,
+    };
+    for choice in matches.get_many::<String>("strategy").into_iter().flatten() {
+        match choice.as_str() {
+            "literal" => cfg.matching = Some(Matching::Literal),
+            "regex" => cfg.matching = Some(Matching::Regex),
+            "sequential" => cfg.scheduling = Some(Scheduling::Sequential),
+            "parallel" => cfg.scheduling = Some(Scheduling::Parallel),
+            _ => {
+                cfg.matching = None;
+                cfg.scheduling = None;
+            }
+        }
+    }
+    cfg.explain = matches.get_flag("explain");
     cfg.follow = matches.get_flag("follow");
+    cfg.log_source = if matches.get_flag("journald") {
+        Some(LogSource::Journald)
+    } else {
+        matches
+            .get_one::<String>("syslog")
+            .map(|addr| LogSource::Syslog(addr.clone()))
+    };
+    cfg.column = matches.get_flag("column");
+    let json_schema = matches.get_one::<String>("json");
+    cfg.json = json_schema.is_some();
+    cfg.json_schema = match json_schema.map(|s| s.as_str()) {
+        Some("rg") => JsonSchema::Ripgrep,
+        _ => JsonSchema::Rgrep,
+    };
+    cfg.context_hashes = matches.get_flag("context-hash");
+    cfg.expand_env = matches.get_flag("expand-env");
+    cfg.heading = matches.get_flag("heading");
+    cfg.vimgrep = matches.get_flag("vimgrep");
+    cfg.format = matches.get_one::<String>("format").cloned();
+    if matches.get_flag("with-filename") {
+        cfg.with_filename = Some(true);
+    } else if matches.get_flag("no-filename") {
+        cfg.with_filename = Some(false);
+    }
+}
+
+/// Set line numbering from the parsed `ArgMatches`; without -n/-N it is on unless only
+/// stdin is read or `--compat=gnu` is given.
+fn set_line_number(matches: &ArgMatches, cfg: &mut Config, inputs: &[String]) {
+    cfg.line_number = if matches.get_flag("line-number") {
+        true
+    } else if matches.get_flag("no-line-number") || cfg.compat == Compat::Gnu {
+        false
+    } else {
+        // Log entries have no line numbers worth showing
+        let stdin_only = cfg.log_source.is_some()
+            || match inputs {
+                [] => !cfg.recursive,
+                [only] => only == "-",
+                _ => false,
+            };
+        !stdin_only
+    };
 }
 
 /// Set context from the parsed `ArgMatches`.
 fn set_context(matches: &ArgMatches, cfg: &mut Config) {
-    let mut before = to_usize(&matches, "before");
-    let mut after = to_usize(&matches, "after");
-    let ctx = to_usize(&matches, "context");
-    if ctx > 0 {
-        before = ctx;
-        after = ctx;
+    let mut before = to_usize(matches, "before");
+    let mut after = to_usize(matches, "after");
+    let ctx = matches
+        .get_many::<(usize, usize)>("context")
+        .and_then(|mut c| c.next_back())
+        .copied();
+    if let Some(ctx) = ctx
+        && ctx != (0, 0)
+    {
+        (before, after) = ctx;
     }
-    cfg.context = Context { before, after };
+    let percent = matches
+        .get_one::<u8>("context-percent")
+        .map(|&p| usize::from(p));
+    cfg.context = Context {
+        before,
+        after,
+        percent,
+    };
+}
+
+/// Set the chronological merge tie-breaking rule from the parsed `ArgMatches`.
+fn set_merge_ties(matches: &ArgMatches, cfg: &mut Config) {
+    cfg.merge_ties = match matches.get_one::<String>("merge-ties").map(|s| s.as_str()) {
+        Some("path") => MergeTies::Path,
+        Some("stable") => MergeTies::Stable,
+        _ => MergeTies::InputOrder,
+    };
+}
+
+/// Set the assumed timezone offset from the parsed `ArgMatches`.
+fn set_assume_tz(matches: &ArgMatches, cfg: &mut Config) -> Result<(), String> {
+    if let Some(tz) = matches.get_one::<String>("assume-tz") {
+        let offset = parse_utc_offset(tz)
+            .ok_or_else(|| format!("rgrep: invalid --assume-tz offset '{}'", tz))?;
+        cfg.assume_tz = Some(offset);
+    }
+    Ok(())
+}
+
+/// Set when to color from `--color`, and highlight colors from `GREP_COLORS`, then from
+/// `--colors`.
+fn set_colors(matches: &ArgMatches, cfg: &mut Config) -> Result<(), String> {
+    cfg.color = match matches.get_one::<String>("color").map(|s| s.as_str()) {
+        Some("always") => ColorChoice::Always,
+        Some("never") => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    };
+    if let Ok(spec) = std::env::var("GREP_COLORS") {
+        cfg.colors
+            .apply_spec(&spec)
+            .map_err(|e| format!("rgrep: invalid GREP_COLORS: {}", e))?;
+    }
+    if let Some(spec) = matches.get_one::<String>("colors") {
+        cfg.colors
+            .apply_spec_strict(spec)
+            .map_err(|e| format!("rgrep: invalid --colors: {}", e))?;
+    }
+    Ok(())
 }
 
 /// Tries setting the pattern from the cmd args, returns true if a pattern was set else false.
@@ -162,27 +1153,91 @@ fn try_set_pattern(matches: &ArgMatches, cfg: &mut Config) -> bool {
     if let Some(pattern) = matches.get_one::<String>("pattern") {
         cfg.patterns = vec![pattern.to_string()];
     }
+    if let Some(name) = matches.get_one::<String>("ident") {
+        cfg.patterns = vec![ident_pattern(name, matches.get_flag("qualified"))];
+    }
+    if let Some(name) = matches.get_one::<String>("def") {
+        cfg.definition = Some(name.to_string());
+    }
 
-    !cfg.patterns.is_empty()
+    !cfg.patterns.is_empty() || cfg.definition.is_some()
 }
 
 /// Parse CLI arguments into a `Config` and input file list.
 ///
 /// Returns `Err(String)` with a human-readable message when validation fails
-/// (e.g., no `-e/--regexp` patterns provided).
+/// (e.g., no `-r/--regexp` pattern or `--queries` file provided).
 pub fn parse() -> Result<(Config, Vec<String>), String> {
     let matches = build_cli().get_matches();
 
     let mut cfg = Config::default();
+    cfg.doctor = matches.get_flag("doctor");
+    cfg.self_check = matches.get_flag("self-check");
+    cfg.type_list = matches.get_flag("type-list");
+    cfg.list_files = matches.get_flag("files-only");
+
+    if let Some(path) = matches.get_one::<String>("queries") {
+        cfg.queries = read_queries(path).map_err(|e| format!("rgrep: {}", e))?;
+    }
 
-    if !try_set_pattern(&matches, &mut cfg) {
+    if !try_set_pattern(&matches, &mut cfg)
+        && !cfg.doctor
+        && !cfg.type_list
+        && !cfg.list_files
+        && cfg.queries.is_empty()
+    {
         return Err("rgrep: no pattern provided; use -r PATTERN".into());
     }
 
     set_flags(&matches, &mut cfg);
     set_context(&matches, &mut cfg);
+    set_merge_ties(&matches, &mut cfg);
+    set_assume_tz(&matches, &mut cfg)?;
+    set_colors(&matches, &mut cfg)?;
+    cfg.duration_stats = matches.get_one::<String>("duration-stats").cloned();
+    cfg.where_expr = matches.get_one::<String>("where").cloned();
+    cfg.filter_script = matches.get_one::<String>("filter-script").cloned();
+    cfg.squash_whitespace = matches.get_flag("squash-whitespace");
+    cfg.maps = matches
+        .get_many::<String>("map")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    cfg.table = !matches.get_flag("no-table");
+    cfg.timings = matches.get_one::<usize>("timings").copied();
+    cfg.stats = matches.get_flag("stats");
+    cfg.match_timeout_ms = matches.get_one::<u64>("match-timeout").copied();
+    cfg.no_match_message = matches.get_one::<String>("no-match-message").cloned();
+    cfg.metrics_file = matches.get_one::<String>("metrics-file").cloned();
+    cfg.follow_from_start = matches.get_flag("follow-from-start");
+    cfg.tail_lines = matches.get_one::<usize>("tail-lines").copied();
+    cfg.replace = matches.get_one::<String>("replace").cloned();
+    cfg.only_matching = matches.get_flag("only-matching");
+    cfg.group = matches.get_one::<String>("group").cloned();
+    cfg.max_columns = matches.get_one::<usize>("max-columns").copied();
+    cfg.max_columns_preview = matches.get_flag("max-columns-preview");
+    cfg.match_window = matches.get_one::<usize>("match-window").copied();
+    cfg.write = matches.get_flag("write");
+    cfg.backup_suffix = matches.get_one::<String>("backup").cloned();
+    cfg.dry_run = matches.get_flag("dry-run");
+    cfg.diff = matches.get_flag("diff");
+    cfg.preview = matches.get_one::<usize>("preview").copied();
+    cfg.path_or_content = matches.get_flag("path-or-content");
+    cfg.file_list_cache = matches.get_one::<String>("file-list-cache").cloned();
 
-    let inputs: Vec<String> = get_inputs(&matches);
+    let mut inputs: Vec<String> = get_inputs(&matches);
+    let list = match matches.get_one::<String>("files-from0") {
+        Some(path) => Some((path, true)),
+        None => matches.get_one::<String>("files-from").map(|p| (p, false)),
+    };
+    if let Some((path, nul)) = list {
+        if path == "-" && inputs.iter().any(|i| i == "-") {
+            return Err("rgrep: stdin cannot be both the file list and an input".into());
+        }
+        inputs.extend(read_file_list(path, nul).map_err(|e| format!("rgrep: {}", e))?);
+    }
+    set_line_number(&matches, &mut cfg, &inputs);
 
     Ok((cfg, inputs))
 }
@@ -0,0 +1,40 @@
+//! The clock of the matching core.
+//!
+//! `wasm32-unknown-unknown` has no clock, and `std::time::Instant::now` panics there. On
+//! that target `Instant` is a stand-in whose elapsed times are zero and whose deadlines
+//! never pass, so searches report no timings and `Config::match_timeout_ms` has no
+//! effect; everywhere else it is `std::time::Instant`.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::Instant;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use no_clock::Instant;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod no_clock {
+    use std::ops::Add;
+    use std::time::Duration;
+
+    /// A point in time on a target without a clock: always now.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant;
+
+    impl Instant {
+        pub fn now() -> Self {
+            Instant
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, _: Duration) -> Instant {
+            Instant
+        }
+    }
+}
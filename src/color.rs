@@ -0,0 +1,214 @@
+//! Whether and how output is colored (`--color`, `--colors`, `GREP_COLORS`).
+//!
+//! `ColorChoice` decides whether escape codes are written at all: always, never, or
+//! automatically when stdout is a terminal.
+//!
+//! Colors are ANSI SGR parameter strings such as `1;31` (bold red) and are configured
+//! with a `GREP_COLORS`-style specification: colon-separated `capability=value` pairs,
+//! e.g. `ms=1;31:fn=35:ln=32:se=36`. Recognized capabilities:
+//!
+//! - `ms` / `mt`: matched text (`mt` is accepted as an alias, as in GNU grep)
+//! - `fn`: file names
+//! - `ln`: line and column numbers
+//! - `se`: separators (`:` after file names and numbers)
+//!
+//! In `GREP_COLORS`, capabilities of GNU grep that rgrep does not use (e.g. `mc`, `sl`,
+//! `cx`, `ne`) are accepted and ignored, so an existing setting keeps working; `--colors`
+//! rejects them, and entries that are not `capability=value` pairs, since they would
+//! have no effect. An empty value turns coloring of that element off.
+
+use std::io::IsTerminal;
+
+/// When to color output (`--color`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal, `NO_COLOR` is unset or empty, and `TERM` is not
+    /// `dumb`.
+    #[default]
+    Auto,
+    /// Always write escape codes, even into pipes and files.
+    Always,
+    /// Never write escape codes.
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output written to stdout, reading the environment.
+    pub fn should_color(self) -> bool {
+        self.resolve(std::io::stdout().is_terminal(), |name| {
+            std::env::var(name).ok()
+        })
+    }
+
+    /// Whether to color output given whether stdout `is_tty` and the environment
+    /// variables returned by `env`.
+    pub fn resolve<F>(self, is_tty: bool, env: F) -> bool
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                is_tty
+                    && env("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && env("TERM").as_deref() != Some("dumb")
+            }
+        }
+    }
+}
+
+/// SGR parameters for each colored element of the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// Matched text (`ms`).
+    pub matched: String,
+    /// File names in prefixes and headings (`fn`).
+    pub filename: String,
+    /// Line and column numbers (`ln`).
+    pub line_number: String,
+    /// Separators after file names and numbers (`se`).
+    pub separator: String,
+}
+
+impl Default for Colors {
+    /// Bold red matches; file names, numbers, and separators are not colored.
+    fn default() -> Self {
+        Self {
+            matched: "1;31".into(),
+            filename: String::new(),
+            line_number: String::new(),
+            separator: String::new(),
+        }
+    }
+}
+
+impl Colors {
+    /// Apply the `GREP_COLORS`-style `spec` on top of these colors, ignoring capabilities
+    /// rgrep does not use.
+    pub fn apply_spec(&mut self, spec: &str) -> Result<(), String> {
+        self.apply(spec, false)
+    }
+
+    /// Like `apply_spec`, but reject entries that are not `capability=value` pairs and
+    /// capabilities rgrep does not use (`--colors`).
+    pub fn apply_spec_strict(&mut self, spec: &str) -> Result<(), String> {
+        self.apply(spec, true)
+    }
+
+    fn apply(&mut self, spec: &str, strict: bool) -> Result<(), String> {
+        for entry in spec.split(':').filter(|e| !e.is_empty()) {
+            let Some((cap, value)) = entry.split_once('=') else {
+                if strict {
+                    return Err(format!(
+                        "'{}' is not a capability=value pair such as 'ms=1;31'",
+                        entry
+                    ));
+                }
+                // Boolean capabilities such as `ne` and `rv` do not affect rgrep
+                continue;
+            };
+            if !value.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                return Err(format!(
+                    "invalid color '{}' for '{}': expected SGR parameters such as '1;31'",
+                    value, cap
+                ));
+            }
+            let value = value.to_string();
+            match cap {
+                "ms" | "mt" => self.matched = value,
+                "fn" => self.filename = value,
+                "ln" => self.line_number = value,
+                "se" => self.separator = value,
+                _ if strict => {
+                    return Err(format!(
+                        "unknown capability '{}': expected ms, mt, fn, ln, or se",
+                        cap
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Default colors with `spec` applied.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut colors = Self::default();
+        colors.apply_spec(spec)?;
+        Ok(colors)
+    }
+}
+
+/// Append `text` to `out` wrapped in the escape codes for `sgr`; an empty `sgr` appends
+/// `text` unchanged.
+pub fn paint(out: &mut String, sgr: &str, text: &str) {
+    if sgr.is_empty() {
+        out.push_str(text);
+        return;
+    }
+    out.push_str("\x1b[");
+    out.push_str(sgr);
+    out.push('m');
+    out.push_str(text);
+    out.push_str("\x1b[0m");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_grep_colors_spec() {
+        let colors = Colors::parse("ms=01;32:fn=35:ln=32:se=36:mc=01;31:ne").unwrap();
+        assert_eq!(colors.matched, "01;32");
+        assert_eq!(colors.filename, "35");
+        assert_eq!(colors.line_number, "32");
+        assert_eq!(colors.separator, "36");
+
+        let colors = Colors::parse("mt=4:fn=").unwrap();
+        assert_eq!(colors.matched, "4");
+        assert_eq!(colors.filename, "");
+        assert!(Colors::parse("ms=red").is_err());
+    }
+
+    #[test]
+    fn test_strict_spec_rejects_what_grep_colors_ignores() {
+        let mut colors = Colors::default();
+        colors.apply_spec_strict("ms=4:fn=35").unwrap();
+        assert_eq!(
+            (colors.matched.as_str(), colors.filename.as_str()),
+            ("4", "35")
+        );
+        for spec in ["mc=01;31", "ne", "match:fg:blue"] {
+            assert!(
+                Colors::default().apply_spec_strict(spec).is_err(),
+                "{}",
+                spec
+            );
+            assert!(Colors::default().apply_spec(spec).is_ok(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_color_choice_resolution() {
+        let term = |name: &str| (name == "TERM").then(|| "xterm".to_string());
+        assert!(ColorChoice::Auto.resolve(true, term));
+        assert!(!ColorChoice::Auto.resolve(false, term));
+        assert!(ColorChoice::Always.resolve(false, term));
+        assert!(!ColorChoice::Never.resolve(true, term));
+
+        let no_color = |name: &str| (name == "NO_COLOR").then(|| "1".to_string());
+        assert!(!ColorChoice::Auto.resolve(true, no_color));
+        let dumb = |name: &str| (name == "TERM").then(|| "dumb".to_string());
+        assert!(!ColorChoice::Auto.resolve(true, dumb));
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut out = String::new();
+        paint(&mut out, "35", "a.log");
+        paint(&mut out, "", ":");
+        assert_eq!(out, "\x1b[35ma.log\x1b[0m:");
+    }
+}
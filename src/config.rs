@@ -1,3 +1,8 @@
+use crate::batch::Query;
+use crate::color::{ColorChoice, Colors};
+use crate::output::{json, rg_json};
+use crate::search::Match;
+
 /// Controls how many lines of context are shown before and after a match.
 #[derive(Debug, Clone, Default)]
 pub struct Context {
@@ -5,6 +10,164 @@ pub struct Context {
     pub before: usize,
     /// Number of trailing lines to include after each matching line.
     pub after: usize,
+    /// Context on each side as a percentage of the lines of each input
+    /// (`--context-percent`), where that is more than `before` or `after`. Not used when
+    /// following a file, whose length is not known.
+    pub percent: Option<usize>,
+}
+
+impl Context {
+    /// The lines of context for an input of `lines` lines.
+    pub fn for_lines(&self, lines: usize) -> Context {
+        let share = self.percent.map_or(0, |p| lines * p / 100);
+        Context {
+            before: self.before.max(share),
+            after: self.after.max(share),
+            percent: None,
+        }
+    }
+
+    /// Whether any context is shown.
+    pub fn is_enabled(&self) -> bool {
+        self.before > 0 || self.after > 0 || self.percent.is_some_and(|p| p > 0)
+    }
+}
+
+/// Tie-breaking rule for lines with equal timestamps when merging multiple files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeTies {
+    /// Order tied lines by the position of their file in the input list.
+    #[default]
+    InputOrder,
+    /// Order tied lines by file path (lexicographically).
+    Path,
+    /// Keep tied lines from the file that was emitted last together, switching
+    /// sources only when the timestamp advances.
+    Stable,
+}
+
+/// Regex engine used to match patterns (see `Config::engine`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// The `regex` crate: matching in linear time, without lookaround or backreferences.
+    #[default]
+    Default,
+    /// fancy-regex, which adds lookaround and backreferences by backtracking. Needs the
+    /// `fancy` feature.
+    Fancy,
+}
+
+/// How lines are matched (see `Config::matching` and the `strategy` module).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matching {
+    /// Substring search, for patterns that are plain text.
+    Literal,
+    /// The regex engine.
+    Regex,
+}
+
+/// How the files of a search are scheduled (see `Config::scheduling` and the `strategy`
+/// module).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheduling {
+    /// One file after another on the calling thread.
+    Sequential,
+    /// Files are searched concurrently on the rayon thread pool.
+    Parallel,
+}
+
+/// What to do with binary files, those containing a NUL byte (see
+/// `Config::binary_files`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFiles {
+    /// Leave them out of the search (`--binary-files=without-match`).
+    Skip,
+    /// Search them, but report a match as `Binary file X matches` instead of with the
+    /// matching lines (`--binary-files=binary`).
+    Report,
+    /// Search them like text files (`-a`, `--binary-files=text`).
+    Text,
+}
+
+/// How patterns are written (see `Config::syntax` and the `syntax` module).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// Plain text (`-F`).
+    Literal,
+    /// POSIX basic regular expressions, as in `grep -G`.
+    Basic,
+    /// The regex crate's syntax with rgrep's Boolean operators (`-E`).
+    #[default]
+    Extended,
+}
+
+/// How closely rgrep follows GNU grep (see `Config::compat`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compat {
+    /// rgrep's own output and error handling.
+    #[default]
+    Rgrep,
+    /// GNU grep's output details, error handling, and exit codes (`--compat=gnu`).
+    Gnu,
+}
+
+/// Schema of the JSON Lines events (see `Config::json_schema`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonSchema {
+    /// rgrep's own events (see `output::json`).
+    #[default]
+    Rgrep,
+    /// ripgrep's `--json` messages, for tools that already parse them (`--json=rg`, see
+    /// `output::rg_json`).
+    Ripgrep,
+}
+
+/// A glob narrowing the files searched (see `Config::globs`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileGlob {
+    /// Search the files matching the glob (`--include`).
+    Include(String),
+    /// Skip the files matching the glob (`--exclude`).
+    Exclude(String),
+}
+
+/// A stream of log entries searched instead of files (see the `journal` module).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogSource {
+    /// The systemd journal, read with `journalctl`; with `Config::follow`, new entries
+    /// are searched as they arrive.
+    Journald,
+    /// Syslog messages received on a UDP address (`HOST:PORT`) or a unix datagram socket
+    /// (`unix:PATH`).
+    Syslog(String),
+}
+
+/// Parse a UTC offset such as `Z`, `UTC`, `+02:00`, `+0200`, or `-05` into seconds east of UTC.
+pub fn parse_utc_offset(s: &str) -> Option<i32> {
+    if s == "Z" || s.eq_ignore_ascii_case("UTC") {
+        return Some(0);
+    }
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (h, m) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if h > 23 || m > 59 {
+        return None;
+    }
+    Some(sign * (h * 3600 + m * 60))
 }
 
 /// Configuration for a search run.
@@ -19,6 +182,14 @@ pub struct Config {
     pub invert: bool, // -v
     /// Print only the count of matching lines (like `-c`).
     pub count: bool, // -c
+    /// With `count`, count every match instead of matching lines: a line with three
+    /// matches counts 3. Lines selected without matched text (with `invert`, or by a negated
+    /// term) count once.
+    pub count_matches: bool, // --count-matches
+    /// Print only the names of files with a matching line instead of the lines, one per
+    /// line; each file is searched only up to its first match. Takes precedence over
+    /// `count` and `json`.
+    pub files_with_matches: bool, // -l
     /// Suppress normal output; only exit status matters (like `-q`).
     pub quiet: bool, // -q
     /// Match whole words only (like `-w`).
@@ -27,16 +198,252 @@ pub struct Config {
     pub line: bool, // -x
     /// Lines of context before/after matches (like `-A`, `-B`, `-C`).
     pub context: Context, // -A, -B, -C
-    /// Whether to colorize matches in output (enabled by default).
-    pub color: bool, // syntax highlighting
+    /// When to colorize output; by default only when stdout is a terminal.
+    pub color: ColorChoice, // --color
+    /// Colors for matches, file names, line numbers, and separators when output is
+    /// colored.
+    pub colors: Colors, // --colors / GREP_COLORS
     /// Recurse into directories (like `-r`).
     pub recursive: bool, // -r
     /// Case-insensitive matching (like `-i`).
     pub case_insensitive: bool, // -i
     /// Make `.` match newlines (regex DOTALL).
     pub dotall: bool, // --dotall
+    /// Treat `\r` as part of line endings when matching: `$` matches before a `\r\n`
+    /// or lone `\r`, and `.` does not match `\r`. `\r\n` endings are stripped from
+    /// lines either way; this covers a `\r` left at the end of an input or inside it.
+    pub crlf: bool, // --crlf
     /// Follow a growing single file for new lines (like `-f/--follow`).
     pub follow: bool, // -f/--follow
+    /// Include the 1-based column of the first match on each matching line.
+    pub column: bool, // --column
+    /// Tie-breaking rule used when chronologically merging multiple files.
+    pub merge_ties: MergeTies, // --merge-ties
+    /// Emit JSON Lines events (begin/match/context/end/summary) instead of text.
+    pub json: bool, // --json
+    /// Schema of the events written with `json`. ripgrep's schema has no events for
+    /// `path_or_content` hits or `no_match_message`, which are rejected with it.
+    pub json_schema: JsonSchema, // --json=rg
+    /// UTC offset in seconds assumed for timestamps without one when merging files
+    /// chronologically. `None` treats such timestamps as UTC.
+    pub assume_tz: Option<i32>, // --assume-tz
+    /// Regex whose first capture group holds a number (e.g. `took (\d+)ms`). When set,
+    /// matching lines are summarized as min/avg/p50/p95/max instead of being printed.
+    pub duration_stats: Option<String>, // --duration-stats
+    /// Prefix output lines with the filename: `Some(true)` always (like `-H`), `Some(false)`
+    /// never (like `-h`), `None` only when more than one file is searched.
+    pub with_filename: Option<bool>, // -H / -h
+    /// Predicate on the pattern's capture groups that matching lines must also satisfy,
+    /// e.g. `$1 > 500`.
+    pub where_expr: Option<String>, // --where
+    /// Path of a Rhai script run for every matching line, which is kept only when the
+    /// script returns `true` (see `script`; needs the `script` feature).
+    pub filter_script: Option<String>, // --filter-script FILE
+    /// Print only the matched parts of matching lines, one per output line.
+    pub only_matching: bool, // -o / --only-matching
+    /// Replace printed lines longer than this many bytes with a
+    /// `[... omitted: line too long (N bytes)]` marker (see `output::shorten_line`). JSON
+    /// and `--format` output is not shortened.
+    pub max_columns: Option<usize>, // --max-columns N
+    /// With `max_columns`, print a window of that many bytes around the first match of a
+    /// long line instead of the marker.
+    pub max_columns_preview: bool, // --max-columns-preview
+    /// Print only this many characters before and after each match of a line, with `…`
+    /// where text is left out, instead of the whole line; lines without a match keep
+    /// their first characters. With `max_columns`, only lines longer than that are cut,
+    /// and this takes the place of the marker.
+    pub match_window: Option<usize>, // --match-window N
+    /// Take matches from this capture group (a number or name) instead of the whole
+    /// pattern, e.g. `2` to extract the ID from `user=(\w+) id=(\d+)`.
+    pub group: Option<String>, // --group N|NAME
+    /// Transformations applied in order to every line before matching, while the original
+    /// line is printed, e.g. `strip-ansi` or `s/\s+/ /g` (see `line_map`).
+    pub maps: Vec<String>, // --map SPEC
+    /// Collapse runs of whitespace to a single space before matching (after `maps`); the
+    /// printed lines keep their whitespace.
+    pub squash_whitespace: bool, // --squash-whitespace
+    /// Render multi-file counts and duration summaries as aligned tables; `false` keeps the
+    /// raw `name:value` lines.
+    pub table: bool, // --no-table
+    /// Prefix output lines with their 1-based line number. The CLI turns this off by
+    /// default when reading stdin.
+    pub line_number: bool, // -n / -N
+    /// Print each file's name once as a heading above its lines instead of prefixing every
+    /// line; applies whenever filenames are shown.
+    pub heading: bool, // --heading
+    /// Record per-file search times and report the slowest N files.
+    pub timings: Option<usize>, // --timings
+    /// Print the counters of the run (`RunResult::stats`) after the results.
+    pub stats: bool, // --stats
+    /// Print `file:line:column:text` once per individual match, for editor quickfix lists.
+    pub vimgrep: bool, // --vimgrep
+    /// File that stores the expanded file list between runs; it is reused while the walked
+    /// directories are unchanged, skipping the directory walk.
+    pub file_list_cache: Option<String>, // --file-list-cache
+    /// Also report files whose path matches the pattern even if no line does; such
+    /// path-only hits are listed as `path (path match)`.
+    pub path_or_content: bool, // --path-or-content
+    /// Output template applied to every printed line, e.g. `{path}:{line}:{text}`
+    /// (see `output::template`).
+    pub format: Option<String>, // --format
+    /// Replace `${NAME}` in patterns with environment variables before compiling them
+    /// (see `env_expand`).
+    pub expand_env: bool, // --expand-env
+    /// Check the setup (colors, pattern, inputs, caches) and report problems instead of
+    /// searching (see `doctor`).
+    pub doctor: bool, // --doctor
+    /// Run the search both in parallel and on a single thread and report whether the
+    /// results differ, instead of printing them (see `self_check`).
+    pub self_check: bool, // --self-check
+    /// Independent queries searched in a single pass instead of `patterns`; results are
+    /// grouped per query or written to per-query files (see `batch`).
+    pub queries: Vec<Query>, // --queries
+    /// Print at most this many matching lines per file, followed by a note with the
+    /// number left out; counts still include every match.
+    pub preview: Option<usize>, // --preview
+    /// Instead of `patterns`, match lines that define the symbol with this name, e.g. a
+    /// function or struct (see `definition`).
+    pub definition: Option<String>, // --def
+    /// In recursive searches, skip the rest of a directory once most of the files sampled
+    /// in it are binary (see `fs_utils::walk_inputs`).
+    pub skip_binary_dirs: bool, // --skip-binary-dirs
+    /// What to do with binary files (see `fs_utils::binary_files`). `None` skips them, or
+    /// reports them with `Compat::Gnu`. Binary files that are searched are read with
+    /// invalid UTF-8 replaced by U+FFFD.
+    pub binary_files: Option<BinaryFiles>, // --binary-files / -a
+    /// Decompress compressed inputs as they are read, like zgrep, so rotated logs
+    /// (`app.log.1.gz`, `syslog.2.xz`) can be searched directly. Inputs are recognized by
+    /// their content (see `io_utils::decompressed`); others are searched as they are.
+    /// Applies to inputs opened by path and to stdin, not to the readers of
+    /// `run_on_reader`. Each format requires its feature: `gzip`, `bzip2`, `xz`, or
+    /// `zstd`.
+    pub search_zip: bool, // -z/--search-zip
+    /// Command run on each file the preprocessor applies to, whose output is searched
+    /// instead of the file: split at whitespace, with `{}` replaced by the file's path,
+    /// which is otherwise appended (see `preprocess`). Not used for stdin, `--queries`,
+    /// follow mode, or `--write`.
+    pub pre: Option<String>, // --pre COMMAND
+    /// Globs of the files `pre` applies to, matched like `--include` globs; every file
+    /// when empty.
+    pub pre_globs: Vec<String>, // --pre-glob GLOB
+    /// In recursive searches, also walk what git ignores (`.gitignore`, `info/exclude`,
+    /// the global excludes file, and `.git` itself) and what `.rgrepignore` files and the
+    /// user's rgrep ignore file exclude (see `ignore`).
+    pub no_ignore: bool, // --no-ignore
+    /// In recursive searches, leave out the ignore files shared by a whole repository or
+    /// user, git's global excludes file and `$GIT_DIR/info/exclude`; `.gitignore` files
+    /// still apply.
+    pub no_global_ignore: bool, // --no-global-ignore
+    /// In recursive searches, also walk hidden files and directories, those whose name
+    /// starts with `.`; inputs named explicitly are searched either way.
+    pub hidden: bool, // --hidden
+    /// In recursive searches, follow symlinks to directories and files instead of
+    /// skipping them. Every directory is entered at most once per input (by device and
+    /// inode), so symlink loops end the descent.
+    pub follow_symlinks: bool, // -L / --follow-symlinks
+    /// Extra ignore files in gitignore syntax for recursive searches, with patterns
+    /// relative to the walked directory; they apply even with `no_ignore` (see `ignore`).
+    pub ignore_files: Vec<String>, // --ignore-file PATH
+    /// `--include` and `--exclude` globs in command-line order, e.g. `*.rs` or `src/*.js`;
+    /// the last glob matching a file decides whether it is searched (see
+    /// `ignore::GlobFilter`). Applies to named files and to those found by walking.
+    pub globs: Vec<FileGlob>, // --include / --exclude GLOB
+    /// Globs of directories not walked in recursive searches, e.g. `target` or `.*`; a
+    /// glob without a `/` matches directory names at any depth (see `ignore::DirFilter`).
+    /// The walked directories themselves are never excluded.
+    pub exclude_dirs: Vec<String>, // --exclude-dir GLOB
+    /// Stop recursive walks after this many entries (files and directories) across all
+    /// inputs, with a warning that the results are incomplete, so that huge or runaway
+    /// trees cannot make a search unbounded. `None` walks everything.
+    pub max_entries: Option<usize>, // --max-entries N
+    /// Descend at most this many levels below each walked directory: `1` searches only
+    /// the files directly inside it. `None` walks the whole tree.
+    pub max_depth: Option<usize>, // --max-depth N
+    /// Skip files larger than this many bytes in recursive walks, e.g. multi-gigabyte
+    /// logs or minified bundles; files named explicitly are searched regardless. Skipped
+    /// files are reported in one warning, or one warning each with `list_skipped`.
+    pub max_filesize: Option<u64>, // --max-filesize SIZE
+    /// Record every file and directory left out of the search and why (binary, too large,
+    /// unreadable, ignored, hidden, or excluded) in `RunResult::skipped`, and warn about
+    /// each one and their number per reason.
+    pub list_skipped: bool, // --list-skipped
+    /// Search only files of these types, e.g. `rust` or `py`, as defined by
+    /// `fs_utils::file_types`; like `globs`, this applies to named and walked files.
+    /// Unknown type names fail the run.
+    pub types: Vec<String>, // -t/--type NAME
+    /// Skip files of these types.
+    pub types_not: Vec<String>, // -T/--type-not NAME
+    /// `(NAME, GLOB)` definitions adding GLOB to the file type NAME, which is created if
+    /// it is not built in.
+    pub type_defs: Vec<(String, String)>, // --type-add NAME:GLOB
+    /// Fingerprint every matching line together with the lines around it
+    /// (`Match::context_hash`), printed in JSON match events, so that a finding can be
+    /// recognized after unrelated edits shift its line number.
+    pub context_hashes: bool, // --context-hash
+    /// List the known file types and their globs instead of searching.
+    pub type_list: bool, // --type-list
+    /// Print the files that would be searched, after all filtering, instead of searching
+    /// them (see `search::list_files`). No pattern is needed.
+    pub list_files: bool, // --files
+    /// Match the pattern against each input as a whole, so that matches can span lines;
+    /// every line of a match is reported as matching (see `Matcher::multiline_spans`).
+    pub multiline: bool, // -U / --multiline
+    /// Time budget for matching a single line. A line whose match takes longer is skipped
+    /// and recorded in `FileResult::timeouts` instead of holding up the rest of the input.
+    pub match_timeout_ms: Option<u64>, // --match-timeout MS
+    /// Regex engine for the pattern. With `Engine::Fancy` the pattern is always a single
+    /// regex (no Boolean operators), and `--where`, `--def`, `-U`, `--crlf`, and
+    /// `{captures[N]}` are not supported.
+    pub engine: Engine, // --engine
+    /// How lines are matched, instead of choosing from the pattern's shape (see
+    /// `strategy`). Forcing `Matching::Literal` on a pattern that is not plain text is an
+    /// error.
+    pub matching: Option<Matching>, // --strategy literal|regex
+    /// How files are scheduled, instead of choosing from their number and size (see
+    /// `strategy`).
+    pub scheduling: Option<Scheduling>, // --strategy sequential|parallel
+    /// Print the chosen strategy and the reasons for it instead of searching (see
+    /// `strategy::explain`).
+    pub explain: bool, // --explain
+    /// Text printed by the CLI after a run that found no matches (a `no_match` event with
+    /// `json`), so that an empty result is explicit. Not printed in quiet mode.
+    pub no_match_message: Option<String>, // --no-match-message
+    /// In follow mode, keep match counters in this file in the Prometheus text format
+    /// (see `metrics`), rewritten at most once per second.
+    pub metrics_file: Option<String>, // --metrics-file
+    /// In follow mode, process the lines already in the file before following it, instead
+    /// of starting at its end.
+    pub follow_from_start: bool, // --follow-from-start
+    /// In follow mode, first send the last this many selected lines already in the file,
+    /// with their context, like `tail -n N -f`; takes precedence over
+    /// `follow_from_start`.
+    pub tail_lines: Option<usize>, // --tail-lines N
+    /// Syntax of `patterns`. Literal and basic patterns are a single regex, without
+    /// Boolean operators.
+    pub syntax: Syntax, // --syntax / -F / -G / -E
+    /// Print matching lines with each match replaced by this template, in which `$1` and
+    /// `${name}` refer to capture groups (see `Matcher::replace`).
+    pub replace: Option<String>, // --replace TEMPLATE
+    /// Rewrite matching files in place with `replace` instead of printing them (see
+    /// `rewrite`).
+    pub write: bool, // --write
+    /// With `write`, keep each original file under its path with this suffix appended.
+    pub backup_suffix: Option<String>, // --backup SUFFIX
+    /// With `write`, only report what would change, without touching any file.
+    pub dry_run: bool, // --dry-run
+    /// Print a unified diff of the changes `replace` would make to each file instead of
+    /// the matching lines; no file is written, even with `write`.
+    pub diff: bool, // --diff
+    /// Search log entries from the journal or a syslog listener instead of files. Needs
+    /// the `journald` feature.
+    pub log_source: Option<LogSource>, // --journald / --syslog ADDR
+    /// With `Compat::Gnu`, output and errors follow GNU grep: context lines use `-` after
+    /// the filename and line number, `--` separates groups of context, files are listed
+    /// in input order instead of merged, counts are plain `name:count` lines, binary files
+    /// are searched and reported as `Binary file X matches`, inputs that cannot be read
+    /// end up in `RunResult::errors` instead of failing the run, and quiet searches stop
+    /// at the first match. The CLI also defaults to basic regexes without line numbers.
+    pub compat: Compat, // --compat=gnu
 }
 
 impl Default for Config {
@@ -45,15 +452,90 @@ impl Default for Config {
             patterns: vec![],
             invert: false,
             count: false,
+            count_matches: false,
+            files_with_matches: false,
             quiet: false,
             word: false,
             line: false,
             context: Context::default(),
-            color: true,
+            color: ColorChoice::Auto,
+            colors: Colors::default(),
             recursive: false,
             case_insensitive: false,
             dotall: false,
+            crlf: false,
             follow: false,
+            column: false,
+            merge_ties: MergeTies::default(),
+            json: false,
+            json_schema: JsonSchema::Rgrep,
+            assume_tz: None,
+            duration_stats: None,
+            with_filename: None,
+            where_expr: None,
+            filter_script: None,
+            only_matching: false,
+            max_columns: None,
+            max_columns_preview: false,
+            match_window: None,
+            group: None,
+            maps: Vec::new(),
+            squash_whitespace: false,
+            table: true,
+            line_number: true,
+            heading: false,
+            timings: None,
+            stats: false,
+            vimgrep: false,
+            file_list_cache: None,
+            path_or_content: false,
+            format: None,
+            expand_env: false,
+            doctor: false,
+            self_check: false,
+            queries: Vec::new(),
+            preview: None,
+            definition: None,
+            skip_binary_dirs: false,
+            binary_files: None,
+            search_zip: false,
+            pre: None,
+            pre_globs: Vec::new(),
+            no_ignore: false,
+            no_global_ignore: false,
+            hidden: false,
+            follow_symlinks: false,
+            ignore_files: Vec::new(),
+            globs: Vec::new(),
+            exclude_dirs: Vec::new(),
+            max_entries: None,
+            max_depth: None,
+            max_filesize: None,
+            list_skipped: false,
+            types: Vec::new(),
+            types_not: Vec::new(),
+            type_defs: Vec::new(),
+            type_list: false,
+            list_files: false,
+            context_hashes: false,
+            multiline: false,
+            match_timeout_ms: None,
+            engine: Engine::default(),
+            matching: None,
+            scheduling: None,
+            explain: false,
+            no_match_message: None,
+            metrics_file: None,
+            follow_from_start: false,
+            tail_lines: None,
+            syntax: Syntax::default(),
+            replace: None,
+            write: false,
+            backup_suffix: None,
+            dry_run: false,
+            diff: false,
+            log_source: None,
+            compat: Compat::default(),
         }
     }
 }
@@ -68,9 +550,239 @@ pub enum ExitStatus {
 }
 
 /// Result of a search run.
+#[derive(Debug, Clone)]
 pub struct RunResult {
     /// Formatted output string (may be empty when `quiet` is set).
     pub output: String,
     /// Status indicating whether any match was found.
     pub status: ExitStatus,
+    /// Non-fatal diagnostics collected during the run (e.g., files that could not be
+    /// merged chronologically). The CLI prints these to stderr.
+    pub warnings: Vec<String>,
+    /// Per-file search times, collected when `Config::timings` is set.
+    pub timings: Vec<FileTiming>,
+    /// Matches of every searched input, in input order. Empty for `Config::queries` runs.
+    pub files: Vec<FileResult>,
+    /// `path: reason` for every input that could not be searched with `Compat::Gnu`
+    /// (otherwise such an input fails the run). The CLI prints these to stderr and exits
+    /// with status 2.
+    pub errors: Vec<String>,
+    /// Files and directories left out of the search: those left out by the walk, in walk
+    /// order, then binary files. Only collected with `Config::list_skipped`.
+    pub skipped: Vec<Skipped>,
+    /// Counters of the search: files searched and with a match, matched lines, matches,
+    /// bytes read, and elapsed time (`Config::stats`). Zero for `Config::queries` and
+    /// `Config::list_files` runs.
+    pub stats: Stats,
+}
+
+/// Counters of a search, for one file or a whole run (see `RunResult::stats`); ripgrep's
+/// JSON `stats` objects carry the same (see `output::rg_json`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Time spent searching; for a run, from its start to its end.
+    pub elapsed: std::time::Duration,
+    /// Files searched, and those with a matching line.
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub bytes_searched: u64,
+    /// Bytes of output produced for the searched files.
+    pub bytes_printed: u64,
+    pub matched_lines: usize,
+    /// Individual matches, several per line where a line matched more than once; a line
+    /// selected without matched text (with `Config::invert` or by a negation) counts
+    /// once. Lines left out of the output, in count mode or beyond `Config::preview`,
+    /// are counted too.
+    pub matches: usize,
+    /// Lines skipped because matching them exceeded `Config::match_timeout_ms`.
+    pub timeouts: usize,
+}
+
+impl Stats {
+    /// Add the counters of `other`; elapsed times add up as well (see `RunResult::merge`
+    /// for combining runs).
+    pub fn add(&mut self, other: &Stats) {
+        self.elapsed += other.elapsed;
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.bytes_printed += other.bytes_printed;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+        self.timeouts += other.timeouts;
+    }
+}
+
+/// A file or directory left out of a search (see `RunResult::skipped`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+/// Why an entry was left out of a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// A binary file (see `Config::binary_files`).
+    Binary,
+    /// A file larger than `Config::max_filesize`, with its size in bytes.
+    TooLarge(u64),
+    /// An entry that could not be read while walking, with the reason.
+    Unreadable(String),
+    /// Excluded by an ignore file (see `ignore`).
+    Ignored,
+    /// A hidden file or directory (see `Config::hidden`).
+    Hidden,
+    /// A directory matching `Config::exclude_dirs`.
+    Excluded,
+}
+
+impl SkipReason {
+    /// Every `label`, in the order summaries list them.
+    pub const LABELS: [&'static str; 6] = [
+        "binary",
+        "too large",
+        "unreadable",
+        "ignored",
+        "hidden",
+        "excluded",
+    ];
+
+    /// Short name of the reason, as counted in summaries, e.g. `too large`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::Binary => "binary",
+            SkipReason::TooLarge(_) => "too large",
+            SkipReason::Unreadable(_) => "unreadable",
+            SkipReason::Ignored => "ignored",
+            SkipReason::Hidden => "hidden",
+            SkipReason::Excluded => "excluded",
+        }
+    }
+
+    /// What the reason means for the entry, and how to search it anyway.
+    pub fn describe(&self) -> String {
+        match self {
+            SkipReason::Binary => "binary file (-a to search it)".into(),
+            SkipReason::TooLarge(size) => format!("{} bytes is over --max-filesize", size),
+            SkipReason::Unreadable(reason) => format!("cannot be read: {}", reason),
+            SkipReason::Ignored => "excluded by an ignore file (--no-ignore to search it)".into(),
+            SkipReason::Hidden => "hidden (--hidden to search it)".into(),
+            SkipReason::Excluded => "matches --exclude-dir".into(),
+        }
+    }
+}
+
+impl RunResult {
+    /// Combine the results of runs over separate inputs (e.g. shards searched on different
+    /// threads or machines) into one, keeping the order in which they are given.
+    ///
+    /// Outputs, warnings, timings, per-file results, errors, and skipped entries are
+    /// concatenated, stats are added up, and the status is `MatchFound` if any part found
+    /// a match. The elapsed time of the stats is the longest of the parts', as for parts
+    /// run at the same time. When the parts end in a JSON `summary` event
+    /// (`Config::json`, in either schema), these are replaced by a single summary of all
+    /// parts; in ripgrep's schema, its `elapsed_total` is likewise the longest, and the
+    /// `elapsed` of its `stats`, the time spent searching files, adds up.
+    pub fn merge<I: IntoIterator<Item = RunResult>>(results: I) -> RunResult {
+        let mut merged = RunResult {
+            output: String::new(),
+            status: ExitStatus::NoMatch,
+            warnings: Vec::new(),
+            timings: Vec::new(),
+            files: Vec::new(),
+            errors: Vec::new(),
+            skipped: Vec::new(),
+            stats: Stats::default(),
+        };
+        let mut summary: Option<json::SummaryCounts> = None;
+        let mut rg_summary: Option<(Stats, std::time::Duration)> = None;
+        for part in results {
+            if let Some((events, (searched, with_matches, lines, matches, timeouts))) =
+                json::split_summary(&part.output)
+            {
+                merged.output.push_str(events);
+                let total = summary.get_or_insert((0, 0, 0, 0, 0));
+                total.0 += searched;
+                total.1 += with_matches;
+                total.2 += lines;
+                total.3 += matches;
+                total.4 += timeouts;
+            } else if let Some((events, stats, elapsed_total)) =
+                rg_json::split_summary(&part.output)
+            {
+                merged.output.push_str(events);
+                let total = rg_summary.get_or_insert_default();
+                total.0.add(&stats);
+                total.1 = total.1.max(elapsed_total);
+            } else {
+                merged.output.push_str(&part.output);
+            }
+            if part.status == ExitStatus::MatchFound {
+                merged.status = ExitStatus::MatchFound;
+            }
+            merged.warnings.extend(part.warnings);
+            merged.timings.extend(part.timings);
+            merged.files.extend(part.files);
+            merged.errors.extend(part.errors);
+            merged.skipped.extend(part.skipped);
+            let elapsed = merged.stats.elapsed.max(part.stats.elapsed);
+            merged.stats.add(&part.stats);
+            merged.stats.elapsed = elapsed;
+        }
+        if let Some((searched, with_matches, lines, matches, timeouts)) = summary {
+            json::append_summary(
+                &mut merged.output,
+                searched,
+                with_matches,
+                lines,
+                matches,
+                timeouts,
+            );
+        }
+        if let Some((stats, elapsed_total)) = rg_summary {
+            rg_json::append_summary(&mut merged.output, &stats, elapsed_total);
+        }
+        merged
+    }
+}
+
+/// Matches found in one searched input (see `RunResult::files`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileResult {
+    /// Path of the input as shown in the output (below its root label for `LABEL=PATH`
+    /// inputs, see `fs_utils::display_path`); `None` for stdin.
+    pub path: Option<String>,
+    /// Label of the root the input was found in, for `LABEL=PATH` inputs.
+    pub root: Option<String>,
+    /// Number of matching lines, including any not listed in `matches`.
+    pub matched_lines: usize,
+    /// Matching lines that were reported, in input order. Context lines are not
+    /// included, and nothing is collected in count and quiet mode.
+    pub matches: Vec<Match>,
+    /// Lines skipped because matching them exceeded `Config::match_timeout_ms`.
+    pub timeouts: Vec<MatchTimeout>,
+    /// Why the input could not be searched, from `run_detailed`; no lines are listed
+    /// then. Other runs fail instead, or list such inputs in `RunResult::errors`.
+    pub error: Option<String>,
+}
+
+/// A line whose match was abandoned after exceeding `Config::match_timeout_ms`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchTimeout {
+    /// 1-based number of the skipped line.
+    pub line_number: usize,
+    /// Time spent on the line before matching was abandoned.
+    pub elapsed: std::time::Duration,
+}
+
+/// Time spent searching one file (see `Config::timings`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTiming {
+    /// Path of the searched file.
+    pub path: String,
+    /// Wall-clock time spent opening and searching the file.
+    pub elapsed: std::time::Duration,
+    /// Size of the file in bytes.
+    pub bytes: u64,
 }
@@ -0,0 +1,103 @@
+//! Heuristic symbol definitions (`--def NAME`).
+//!
+//! Instead of parsing source code, a definition is recognized by the shape of its line:
+//! a definition keyword of a common language followed by the name, optionally after
+//! modifiers such as `pub`, `export`, or `static`:
+//!
+//! - functions: `fn`, `def`, `func` (including Go methods), `fun`, `function`
+//! - types: `class`, `struct`, `enum`, `union`, `trait`, `interface`, `type`, `typedef`
+//! - modules: `mod`, `module`, `namespace`
+//! - constants and variables: `const`, `static`, `let`, `var`
+//! - macros: `macro_rules!`, `#define`
+//!
+//! C-style function definitions without a keyword (`int main(void) {`) are recognized
+//! when they start at the beginning of the line and do not end in `;` like a
+//! declaration. The name itself is matched as an identifier (see
+//! `regex_utils::ident_pattern`), case-sensitively.
+
+use regex::Captures;
+use std::ops::Range;
+
+use crate::regex_utils::ident_pattern;
+
+/// Modifiers that may precede a definition keyword.
+const MODIFIERS: &str = r"pub(?:\([^\)]*\))?|export|default|async|unsafe|extern|static|public|private|protected|internal|abstract|final|sealed|open|override|virtual|inline|const";
+
+/// Definition keywords, mapped to the kind reported for them by `kind_of`.
+const KEYWORDS: &str = r"fn|def|func|fun|function|class|struct|enum|union|trait|interface|typedef|type|mod|module|namespace|const|static|let|var|macro_rules!|#\s*define";
+
+/// Build the pattern matching lines that define `name`.
+///
+/// The keyword, if any, is captured in the group `kind` (see `kind_of`), and the name in
+/// `name` or `cname` (see `name_span`).
+pub fn definition_pattern(name: &str) -> String {
+    let name = ident_pattern(name, false);
+    format!(
+        r"(?:^\s*(?:(?:{m})\s+)*(?P<kind>{k})\s+(?:\([^\)]*\)\s*)?(?P<name>{n})|^[A-Za-z_][\w\s\*&:<>,]*[\s\*&](?P<cname>{n})\s*\([^;]*$)",
+        m = MODIFIERS,
+        k = KEYWORDS,
+        n = name
+    )
+}
+
+/// The kind of definition (e.g. `function` or `struct`) matched by a `definition_pattern`
+/// regex with captures `caps`.
+pub fn kind_of(caps: &Captures) -> &'static str {
+    let Some(keyword) = caps.name("kind") else {
+        // C-style definitions have no keyword
+        return "function";
+    };
+    match keyword.as_str() {
+        "fn" | "def" | "func" | "fun" | "function" => "function",
+        "class" => "class",
+        "struct" => "struct",
+        "enum" => "enum",
+        "union" => "union",
+        "trait" => "trait",
+        "interface" => "interface",
+        "typedef" | "type" => "type",
+        "mod" | "module" | "namespace" => "module",
+        "const" | "static" => "constant",
+        "let" | "var" => "variable",
+        _ => "macro",
+    }
+}
+
+/// The byte range of the defined name in a match of a `definition_pattern` regex.
+pub fn name_span(caps: &Captures) -> Option<Range<usize>> {
+    caps.name("name")
+        .or_else(|| caps.name("cname"))
+        .map(|m| m.range())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn kind(line: &str) -> Option<&'static str> {
+        let re = Regex::new(&format!("(?m){}", definition_pattern("parse"))).unwrap();
+        re.captures(line).map(|caps| kind_of(&caps))
+    }
+
+    #[test]
+    fn test_definitions_across_languages() {
+        assert_eq!(kind("pub(crate) fn parse(input: &str) {"), Some("function"));
+        assert_eq!(kind("    async def parse(self):"), Some("function"));
+        assert_eq!(kind("func (p *Parser) parse() error {"), Some("function"));
+        assert_eq!(kind("export default function parse() {"), Some("function"));
+        assert_eq!(kind("static int parse(const char *s) {"), Some("function"));
+        assert_eq!(kind("pub struct parse;"), Some("struct"));
+        assert_eq!(kind("export const parse = () => 1;"), Some("constant"));
+        assert_eq!(kind("#define parse(x) x"), Some("macro"));
+        assert_eq!(kind("macro_rules! parse {"), Some("macro"));
+    }
+
+    #[test]
+    fn test_uses_are_not_definitions() {
+        assert_eq!(kind("    let x = parse(input);"), None);
+        assert_eq!(kind("int parse(const char *s);"), None);
+        assert_eq!(kind("fn parser() {}"), None);
+        assert_eq!(kind("// see parse"), None);
+    }
+}
@@ -0,0 +1,280 @@
+//! Environment and setup diagnostics (`--doctor`).
+//!
+//! `diagnose` runs a series of independent checks against the configuration the user
+//! would search with and returns one `Check` per topic: color output, the pattern and
+//! related expressions, the inputs, and the file list cache. Each failed or suspicious
+//! check carries a hint describing how to fix it. Nothing is searched.
+
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use crate::color::ColorChoice;
+use crate::config::Config;
+use crate::file_list_cache::{self, CacheState};
+use crate::fs_utils::input_path;
+use crate::search::validate;
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Ok => "ok",
+            Severity::Warning => "warn",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// Result of one diagnostic check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// Short name of what was checked, e.g. `pattern`.
+    pub topic: &'static str,
+    pub severity: Severity,
+    /// What was found.
+    pub detail: String,
+    /// How to fix a warning or error.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn ok(topic: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            topic,
+            severity: Severity::Ok,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warning(topic: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            topic,
+            severity: Severity::Warning,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn error(topic: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            topic,
+            severity: Severity::Error,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Run all checks for searching `inputs` with `cfg`.
+pub fn diagnose(cfg: &Config, inputs: &[String]) -> Vec<Check> {
+    let mut checks = vec![check_color(cfg, std::io::stdout().is_terminal(), |name| {
+        std::env::var(name).ok()
+    })];
+    checks.push(check_pattern(cfg));
+    checks.extend(check_inputs(cfg, inputs));
+    if let Some(check) = check_file_list_cache(cfg, inputs) {
+        checks.push(check);
+    }
+    checks
+}
+
+/// Check whether colored output suits the terminal described by `is_tty` and the
+/// environment variables returned by `env`.
+pub fn check_color<F>(cfg: &Config, is_tty: bool, env: F) -> Check
+where
+    F: Fn(&str) -> Option<String>,
+{
+    const TOPIC: &str = "color";
+    let enabled = cfg.color.resolve(is_tty, &env);
+    match cfg.color {
+        ColorChoice::Never => Check::ok(TOPIC, "disabled by --color=never"),
+        ColorChoice::Always if !is_tty => Check::warning(
+            TOPIC,
+            "--color=always but stdout is not a terminal; escape codes will appear in the output",
+            "use --color=auto when piping output to other programs",
+        ),
+        ColorChoice::Always => Check::ok(TOPIC, "always enabled by --color=always"),
+        ColorChoice::Auto if enabled => Check::ok(
+            TOPIC,
+            format!(
+                "enabled: stdout is a terminal (TERM={})",
+                env("TERM").unwrap_or_default()
+            ),
+        ),
+        ColorChoice::Auto if !is_tty => Check::ok(TOPIC, "disabled: stdout is not a terminal"),
+        ColorChoice::Auto => Check::warning(
+            TOPIC,
+            "disabled although stdout is a terminal: NO_COLOR is set or TERM is 'dumb'",
+            "unset NO_COLOR, set TERM to your terminal type, or use --color=always",
+        ),
+    }
+}
+
+/// Check that the pattern and the expressions compiled with it are valid.
+pub fn check_pattern(cfg: &Config) -> Check {
+    const TOPIC: &str = "pattern";
+    if !cfg.queries.is_empty() {
+        for query in &cfg.queries {
+            let query_cfg = Config {
+                patterns: vec![query.pattern.clone()],
+                ..cfg.clone()
+            };
+            if let Err(err) = validate(&query_cfg) {
+                return Check::error(
+                    TOPIC,
+                    format!("query '{}': {}", query.label(), err),
+                    "correct or remove the query in the --queries file",
+                );
+            }
+        }
+        return Check::ok(TOPIC, format!("all {} queries compile", cfg.queries.len()));
+    }
+    if let Some(name) = &cfg.definition {
+        return match validate(cfg) {
+            Ok(()) => Check::ok(TOPIC, format!("definitions of '{}' compile", name)),
+            Err(err) => Check::error(TOPIC, err, "correct the --where or --format expression"),
+        };
+    }
+    if cfg.patterns.is_empty() {
+        return Check::ok(TOPIC, "no pattern given; skipped");
+    }
+    match validate(cfg) {
+        Ok(()) => Check::ok(TOPIC, format!("'{}' compiles", cfg.patterns.join(""))),
+        Err(err) => Check::error(
+            TOPIC,
+            err,
+            "correct the pattern or the --where, --format, or --duration-stats expression",
+        ),
+    }
+}
+
+/// Check that every input exists, can be read, and is searched the way it looks.
+pub fn check_inputs(cfg: &Config, inputs: &[String]) -> Vec<Check> {
+    const TOPIC: &str = "input";
+    if inputs.is_empty() {
+        let detail = if cfg.recursive {
+            "no inputs; the current directory will be searched"
+        } else {
+            "no inputs; standard input will be read"
+        };
+        return vec![Check::ok(TOPIC, detail)];
+    }
+    inputs
+        .iter()
+        .map(|input| {
+            if input == "-" {
+                return Check::ok(TOPIC, "'-' reads standard input");
+            }
+            let input_path = input_path(input);
+            let path = Path::new(&input_path);
+            match std::fs::metadata(path) {
+                Err(err) => Check::error(
+                    TOPIC,
+                    format!("'{}': {}", input, err),
+                    "check the path and its permissions",
+                ),
+                Ok(meta) if meta.is_dir() && !cfg.recursive => Check::warning(
+                    TOPIC,
+                    format!("'{}' is a directory", input),
+                    "add -R to search the files it contains",
+                ),
+                Ok(meta) if meta.is_dir() => match std::fs::read_dir(path) {
+                    Ok(_) => Check::ok(TOPIC, format!("'{}' is a searchable directory", input)),
+                    Err(err) => Check::error(
+                        TOPIC,
+                        format!("'{}': {}", input, err),
+                        "check the directory permissions",
+                    ),
+                },
+                Ok(_) => match std::fs::File::open(path) {
+                    Ok(_) => Check::ok(TOPIC, format!("'{}' is readable", input)),
+                    Err(err) => Check::error(
+                        TOPIC,
+                        format!("'{}': {}", input, err),
+                        "check the file permissions",
+                    ),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Check the health of `Config::file_list_cache` for these inputs, if one is set.
+pub fn check_file_list_cache(cfg: &Config, inputs: &[String]) -> Option<Check> {
+    const TOPIC: &str = "file list cache";
+    let cache = cfg.file_list_cache.as_deref()?;
+    let key = file_list_cache::cache_key(cfg, inputs);
+    let check = match file_list_cache::inspect(Path::new(cache), &key) {
+        CacheState::Fresh(n) => {
+            Check::ok(TOPIC, format!("'{}' is up to date ({} files)", cache, n))
+        }
+        CacheState::Missing => Check::ok(
+            TOPIC,
+            format!("'{}' does not exist yet; the next search creates it", cache),
+        ),
+        CacheState::Unreadable(err) => Check::error(
+            TOPIC,
+            format!("'{}': {}", cache, err),
+            "check the cache file's permissions",
+        ),
+        CacheState::Corrupt => Check::error(
+            TOPIC,
+            format!("'{}' is not an rgrep file list cache", cache),
+            "delete it or point --file-list-cache at a new path",
+        ),
+        CacheState::OtherInputs => Check::warning(
+            TOPIC,
+            format!(
+                "'{}' was built for other inputs or another directory",
+                cache
+            ),
+            "use a separate cache file for each set of inputs to avoid rebuilding it",
+        ),
+        CacheState::Stale(path) => Check::ok(
+            TOPIC,
+            format!(
+                "'{}' is stale ('{}' changed); the next search rebuilds it",
+                cache, path
+            ),
+        ),
+    };
+    Some(check)
+}
+
+/// Render `checks` as one line per check, hints indented below, and a final verdict.
+pub fn format_report(checks: &[Check]) -> String {
+    let mut out = String::new();
+    for check in checks {
+        let _ = writeln!(
+            out,
+            "{:<5}  {}: {}",
+            check.severity.label(),
+            check.topic,
+            check.detail
+        );
+        if let Some(hint) = &check.hint {
+            let _ = writeln!(out, "       hint: {}", hint);
+        }
+    }
+    let problems = checks.iter().filter(|c| c.severity != Severity::Ok).count();
+    if problems == 0 {
+        out.push_str("no problems found\n");
+    } else {
+        let _ = writeln!(out, "{} problem(s) found", problems);
+    }
+    out
+}
+
+/// Whether any check failed outright.
+pub fn has_errors(checks: &[Check]) -> bool {
+    checks.iter().any(|c| c.severity == Severity::Error)
+}
@@ -0,0 +1,112 @@
+//! Environment-variable interpolation in patterns (`--expand-env`).
+//!
+//! With `Config::expand_env`, every `${NAME}` in a pattern is replaced by the value of the
+//! environment variable `NAME` before the pattern is compiled. Only the braced form is
+//! recognized, since a bare `$` is the regex end-of-line anchor.
+//!
+//! - `$${NAME}` is an escape for the literal text `${NAME}`.
+//! - A reference to an unset variable, an empty name, or a missing `}` is an error.
+//! - Values are inserted verbatim, so regex metacharacters in them keep their meaning.
+
+use std::borrow::Cow;
+
+use crate::config::Config;
+
+/// Replace `${NAME}` references in `input` with values from the environment.
+pub fn expand_env(input: &str) -> Result<String, String> {
+    expand_with(input, |name| std::env::var(name).ok())
+}
+
+/// Replace `${NAME}` references using `lookup` to resolve names.
+pub fn expand_with<F>(input: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(escaped) = after.strip_prefix("${") {
+            // `$${` stands for a literal `${`
+            out.push_str("${");
+            rest = escaped;
+        } else if let Some(body) = after.strip_prefix('{') {
+            let end = body
+                .find('}')
+                .ok_or_else(|| format!("unterminated '${{' in pattern '{}'", input))?;
+            let name = &body[..end];
+            if name.is_empty() {
+                return Err(format!(
+                    "empty variable name '${{}}' in pattern '{}'",
+                    input
+                ));
+            }
+            let value = lookup(name)
+                .ok_or_else(|| format!("environment variable '{}' is not set", name))?;
+            out.push_str(&value);
+            rest = &body[end + 1..];
+        } else {
+            out.push('$');
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `cfg` with environment variables expanded in its patterns when `Config::expand_env` is
+/// set; otherwise `cfg` itself.
+pub(crate) fn resolve_patterns(cfg: &Config) -> Result<Cow<'_, Config>, String> {
+    if !cfg.expand_env {
+        return Ok(Cow::Borrowed(cfg));
+    }
+    let patterns = cfg
+        .patterns
+        .iter()
+        .map(|p| expand_env(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Cow::Owned(Config {
+        patterns,
+        ..cfg.clone()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "USER" => Some("alice".into()),
+            "DOT" => Some("a.b".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expands_braced_references() {
+        assert_eq!(expand_with("user=${USER}", lookup).unwrap(), "user=alice");
+        assert_eq!(
+            expand_with("${DOT}|${USER}$", lookup).unwrap(),
+            "a.b|alice$"
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_plain_dollars() {
+        assert_eq!(expand_with("$${USER}", lookup).unwrap(), "${USER}");
+        assert_eq!(expand_with("end$|$x", lookup).unwrap(), "end$|$x");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(
+            expand_with("${MISSING}", lookup)
+                .unwrap_err()
+                .contains("MISSING")
+        );
+        assert!(expand_with("${USER", lookup).is_err());
+        assert!(expand_with("${}", lookup).is_err());
+    }
+}
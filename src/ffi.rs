@@ -0,0 +1,341 @@
+//! C-compatible interface for embedding rgrep in C, C++, Python, and other tooling (the
+//! `ffi` feature).
+//!
+//! A search is set up with an `RgrepConfig` and its pattern, run over a path or a buffer, and its
+//! matches are read from the returned `RgrepResults` by index; both are freed with their
+//! `_free` function. `include/rgrep.h` declares the functions for C. Build the shared
+//! library with:
+//!
+//! ```text
+//! cargo rustc --release --no-default-features --features ffi --crate-type cdylib
+//! ```
+//!
+//! Strings passed in must be NUL-terminated UTF-8. Strings handed out are NUL-terminated
+//! and live as long as the `RgrepResults` they belong to. No function panics across the
+//! boundary: invalid arguments are reported through return values.
+
+use std::ffi::{CStr, c_char, c_int};
+use std::ptr;
+
+use crate::config::{Config, FileResult, Syntax};
+use crate::search::{Match, run_detailed, search_iter};
+
+/// Options of a search, created by `rgrep_config_new`.
+pub struct RgrepConfig {
+    cfg: Config,
+}
+
+/// One selected line of `RgrepResults`.
+#[repr(C)]
+pub struct RgrepMatch {
+    /// File the line is in; NULL for buffers.
+    pub path: *const c_char,
+    /// 1-based line number.
+    pub line_number: usize,
+    /// Byte offset of the start of the line in its input.
+    pub byte_offset: usize,
+    /// The line without its newline, NUL-terminated; `line_len` bytes long, as it may
+    /// contain NUL bytes itself.
+    pub line: *const c_char,
+    pub line_len: usize,
+    /// Byte range of the first match within the line; both 0 for lines selected without
+    /// one (inverted matches).
+    pub match_start: usize,
+    pub match_end: usize,
+}
+
+/// The outcome of a search: its selected lines, in input order, and what went wrong.
+pub struct RgrepResults {
+    matches: Vec<RgrepMatch>,
+    error: Option<Box<[u8]>>,
+    /// The strings the matches point into; boxed, so they stay put as more are added.
+    strings: Vec<Box<[u8]>>,
+}
+
+impl RgrepResults {
+    fn new() -> Self {
+        RgrepResults {
+            matches: Vec::new(),
+            error: None,
+            strings: Vec::new(),
+        }
+    }
+
+    fn failed(error: &str) -> Box<Self> {
+        let mut results = Self::new();
+        results.error = Some(nul_terminated(error));
+        Box::new(results)
+    }
+
+    /// Keep a NUL-terminated copy of `text`, returning where it is.
+    fn keep(&mut self, text: &str) -> *const c_char {
+        let kept = nul_terminated(text);
+        let at = kept.as_ptr().cast();
+        self.strings.push(kept);
+        at
+    }
+
+    fn push(&mut self, path: *const c_char, found: &Match) {
+        let line = self.keep(&found.line);
+        let span = found.spans.first().cloned().unwrap_or(0..0);
+        self.matches.push(RgrepMatch {
+            path,
+            line_number: found.line_number,
+            byte_offset: found.byte_offset,
+            line,
+            line_len: found.line.len(),
+            match_start: span.start,
+            match_end: span.end,
+        });
+    }
+}
+
+fn nul_terminated(text: &str) -> Box<[u8]> {
+    let mut bytes = Vec::with_capacity(text.len() + 1);
+    bytes.extend_from_slice(text.as_bytes());
+    bytes.push(0);
+    bytes.into_boxed_slice()
+}
+
+/// The UTF-8 string at `s`, or `None` for NULL and invalid UTF-8.
+///
+/// # Safety
+/// `s` must be NULL or point to a NUL-terminated string.
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    // SAFETY: checked for NULL above; NUL-terminated per the caller's contract
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+/// Create a search configuration with rgrep's defaults and no patterns; free it with
+/// `rgrep_config_free`.
+#[unsafe(no_mangle)]
+pub extern "C" fn rgrep_config_new() -> *mut RgrepConfig {
+    Box::into_raw(Box::new(RgrepConfig {
+        cfg: Config::default(),
+    }))
+}
+
+/// Free a configuration from `rgrep_config_new`; NULL is ignored.
+///
+/// # Safety
+/// `cfg` must be NULL or come from `rgrep_config_new`, and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_config_free(cfg: *mut RgrepConfig) {
+    if !cfg.is_null() {
+        // SAFETY: allocated by `rgrep_config_new` per the caller's contract
+        drop(unsafe { Box::from_raw(cfg) });
+    }
+}
+
+/// Set the pattern, an expression as on the command line: alternatives are written in it
+/// with `|` (e.g. `foo|bar`), and `&` requires several terms. A configuration has one
+/// pattern. Returns 0, or -1 if `cfg` is NULL, `pattern` is NULL or not UTF-8, or a
+/// pattern was already added.
+///
+/// # Safety
+/// `cfg` must be NULL or a live configuration, and `pattern` NULL or a NUL-terminated
+/// string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_config_add_pattern(
+    cfg: *mut RgrepConfig,
+    pattern: *const c_char,
+) -> c_int {
+    // SAFETY: per the caller's contract
+    match (unsafe { cfg.as_mut() }, unsafe { str_arg(pattern) }) {
+        // The core joins several patterns into one, so a second one is refused rather
+        // than read as the continuation of the first
+        (Some(cfg), Some(pattern)) if cfg.cfg.patterns.is_empty() => {
+            cfg.cfg.patterns.push(pattern.to_string());
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Set the yes/no options of the configuration, each 0 for off and anything else for
+/// on: case-insensitive matching (`-i`), inverted selection (`-v`), whole words (`-w`),
+/// whole lines (`-x`), patterns as plain text (`-F`), and searching directories
+/// recursively (`-r`). Does nothing if `cfg` is NULL.
+///
+/// # Safety
+/// `cfg` must be NULL or a live configuration.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_config_set_options(
+    cfg: *mut RgrepConfig,
+    ignore_case: c_int,
+    invert: c_int,
+    word: c_int,
+    line: c_int,
+    fixed_strings: c_int,
+    recursive: c_int,
+) {
+    // SAFETY: per the caller's contract
+    let Some(cfg) = (unsafe { cfg.as_mut() }) else {
+        return;
+    };
+    let cfg = &mut cfg.cfg;
+    cfg.case_insensitive = ignore_case != 0;
+    cfg.invert = invert != 0;
+    cfg.word = word != 0;
+    cfg.line = line != 0;
+    cfg.syntax = if fixed_strings != 0 {
+        Syntax::Literal
+    } else {
+        Syntax::Extended
+    };
+    cfg.recursive = recursive != 0;
+}
+
+/// Search the file or directory at `path`; free the results with `rgrep_results_free`.
+///
+/// Returns NULL only if `cfg` is NULL. A file that cannot be read does not stop the
+/// search, and is reported by `rgrep_results_error` along with the matches of the
+/// others.
+///
+/// # Safety
+/// `cfg` must be NULL or a live configuration, and `path` NULL or a NUL-terminated
+/// string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_search_path(
+    cfg: *const RgrepConfig,
+    path: *const c_char,
+) -> *mut RgrepResults {
+    // SAFETY: per the caller's contract
+    let Some(cfg) = (unsafe { cfg.as_ref() }) else {
+        return ptr::null_mut();
+    };
+    // SAFETY: per the caller's contract
+    let Some(path) = (unsafe { str_arg(path) }) else {
+        return Box::into_raw(RgrepResults::failed("path is NULL or not UTF-8"));
+    };
+    let files = match run_detailed(&cfg.cfg, &[path.to_string()]) {
+        Ok(files) => files,
+        Err(e) => return Box::into_raw(RgrepResults::failed(&e)),
+    };
+
+    let mut results = RgrepResults::new();
+    let mut errors = Vec::new();
+    for FileResult {
+        path,
+        matches,
+        error,
+        ..
+    } in &files
+    {
+        let path = path.as_deref().unwrap_or("-");
+        if let Some(error) = error {
+            errors.push(format!("{}: {}", path, error));
+            continue;
+        }
+        if matches.is_empty() {
+            continue;
+        }
+        let path = results.keep(path);
+        for found in matches {
+            results.push(path, found);
+        }
+    }
+    if !errors.is_empty() {
+        results.error = Some(nul_terminated(&errors.join("\n")));
+    }
+    Box::into_raw(Box::new(results))
+}
+
+/// Search the `len` bytes at `data`, which must be UTF-8 text; free the results with
+/// `rgrep_results_free`. Returns NULL only if `cfg` is NULL.
+///
+/// # Safety
+/// `cfg` must be NULL or a live configuration, and `data` must point to `len` readable
+/// bytes (it may be NULL when `len` is 0).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_search_buffer(
+    cfg: *const RgrepConfig,
+    data: *const u8,
+    len: usize,
+) -> *mut RgrepResults {
+    // SAFETY: per the caller's contract
+    let Some(cfg) = (unsafe { cfg.as_ref() }) else {
+        return ptr::null_mut();
+    };
+    let data: &[u8] = if len == 0 {
+        &[]
+    } else if data.is_null() {
+        return Box::into_raw(RgrepResults::failed("data is NULL"));
+    } else {
+        // SAFETY: `len` readable bytes per the caller's contract
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+    let found = match search_iter(&cfg.cfg, data) {
+        Ok(found) => found,
+        Err(e) => return Box::into_raw(RgrepResults::failed(&e)),
+    };
+
+    let mut results = RgrepResults::new();
+    for found in found {
+        match found {
+            Ok(found) => results.push(ptr::null(), &found),
+            Err(e) => {
+                results.error = Some(nul_terminated(&e.to_string()));
+                break;
+            }
+        }
+    }
+    Box::into_raw(Box::new(results))
+}
+
+/// Number of selected lines in `results`; 0 for NULL.
+///
+/// # Safety
+/// `results` must be NULL or live results.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_results_len(results: *const RgrepResults) -> usize {
+    // SAFETY: per the caller's contract
+    unsafe { results.as_ref() }.map_or(0, |r| r.matches.len())
+}
+
+/// The selected line at `index`, in input order, or NULL past the end. The match
+/// belongs to `results`.
+///
+/// # Safety
+/// `results` must be NULL or live results.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_results_get(
+    results: *const RgrepResults,
+    index: usize,
+) -> *const RgrepMatch {
+    // SAFETY: per the caller's contract
+    unsafe { results.as_ref() }
+        .and_then(|r| r.matches.get(index))
+        .map_or(ptr::null(), |m| m as *const RgrepMatch)
+}
+
+/// What went wrong, or NULL if nothing did: why the search could not run (an invalid
+/// pattern, a missing input), or one `path: reason` line per file that could not be
+/// read. The string belongs to `results`.
+///
+/// # Safety
+/// `results` must be NULL or live results.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_results_error(results: *const RgrepResults) -> *const c_char {
+    // SAFETY: per the caller's contract
+    unsafe { results.as_ref() }
+        .and_then(|r| r.error.as_ref())
+        .map_or(ptr::null(), |e| e.as_ptr().cast())
+}
+
+/// Free results from `rgrep_search_path` or `rgrep_search_buffer`, with every string and
+/// match in them; NULL is ignored.
+///
+/// # Safety
+/// `results` must be NULL or come from a search function, and neither it nor anything
+/// obtained from it may be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rgrep_results_free(results: *mut RgrepResults) {
+    if !results.is_null() {
+        // SAFETY: allocated by a search function per the caller's contract
+        drop(unsafe { Box::from_raw(results) });
+    }
+}
@@ -0,0 +1,273 @@
+//! Reusing the expanded file list between runs (`--file-list-cache`).
+//!
+//! The first run walks the inputs as usual and saves the resulting list of searchable
+//! files, together with the modification time of every directory it visited, of every
+//! file named directly, of every ignore file read (see `ignore`), and, when binary files
+//! are skipped, of every file found, searched or not. Later runs with the same inputs
+//! only re-check those timestamps: when none changed, the saved list is used and the
+//! walk is skipped.
+//!
+//! So the cache is invalidated by adding, removing, or renaming an entry (which changes
+//! its directory's modification time), by editing an ignore file or a file named
+//! directly, and, when binary files are skipped, by rewriting any file found, which may
+//! turn it from binary to text or back. Editing a file otherwise leaves the list as it
+//! is, as does changing a file left unvisited in a directory skipped by
+//! `Config::skip_binary_dirs`.
+//!
+//! The cache is a line-based text file with tab-separated fields:
+//!
+//! ```text
+//! rgrep file list v1
+//! key <cwd and inputs>
+//! expanded <paths before binary filtering>
+//! stamp <secs>.<nanos> <path>
+//! skipped <sampled> <binary> <path>
+//! file <index> <path>
+//! ```
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::config::{BinaryFiles, Compat, Config};
+use crate::fs_utils::{SkippedDir, WalkIssues, binary_files};
+
+const HEADER: &str = "rgrep file list v1";
+
+/// Files selected for a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileList {
+    /// Number of paths before binary files were dropped.
+    pub expanded: usize,
+    /// Position in the expanded list and path of every file to search.
+    pub files: Vec<(usize, String)>,
+    /// Directories left out by `Config::skip_binary_dirs`.
+    pub skipped_dirs: Vec<SkippedDir>,
+    /// Problems met by the walk; lists with problems are not cached, so that they are
+    /// reported on every run.
+    pub walk_issues: WalkIssues,
+}
+
+/// Identifies the walk a cache was built for: working directory, walk options, and inputs.
+pub(crate) fn cache_key(cfg: &Config, inputs: &[String]) -> String {
+    let cwd = std::env::current_dir()
+        .map(|d| d.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut key = format!("{}\x1f{}", cwd, cfg.recursive);
+    if cfg.skip_binary_dirs {
+        key.push_str("\x1fskip-binary-dirs");
+    }
+    if cfg.no_ignore {
+        key.push_str("\x1fno-ignore");
+    }
+    if cfg.no_global_ignore {
+        key.push_str("\x1fno-global-ignore");
+    }
+    // Lists walked without recording skipped entries cannot report them
+    if cfg.list_skipped {
+        key.push_str("\x1flist-skipped");
+    }
+    if cfg.hidden {
+        key.push_str("\x1fhidden");
+    }
+    if cfg.follow_symlinks {
+        key.push_str("\x1ffollow-symlinks");
+    }
+    for file in &cfg.ignore_files {
+        key.push_str("\x1fignore-file=");
+        key.push_str(file);
+    }
+    for glob in &cfg.globs {
+        let _ = write!(key, "\x1f{:?}", glob);
+    }
+    for dir in &cfg.exclude_dirs {
+        key.push_str("\x1fexclude-dir=");
+        key.push_str(dir);
+    }
+    if let Some(max) = cfg.max_entries {
+        let _ = write!(key, "\x1fmax-entries={}", max);
+    }
+    if let Some(depth) = cfg.max_depth {
+        let _ = write!(key, "\x1fmax-depth={}", depth);
+    }
+    if let Some(max) = cfg.max_filesize {
+        let _ = write!(key, "\x1fmax-filesize={}", max);
+    }
+    for name in &cfg.types {
+        let _ = write!(key, "\x1ftype={}", name);
+    }
+    for name in &cfg.types_not {
+        let _ = write!(key, "\x1ftype-not={}", name);
+    }
+    for (name, glob) in &cfg.type_defs {
+        let _ = write!(key, "\x1ftype-add={}:{}", name, glob);
+    }
+    // GNU-compatible runs name the files below `.` differently
+    if cfg.compat == Compat::Gnu {
+        key.push_str("\x1fgnu");
+    }
+    if binary_files(cfg) != BinaryFiles::Skip {
+        key.push_str("\x1fkeep-binary");
+    }
+    // Which files are binary depends on decompressing and on the preprocessor
+    if cfg.search_zip {
+        key.push_str("\x1fsearch-zip");
+    }
+    if let Some(pre) = &cfg.pre {
+        let _ = write!(key, "\x1fpre={}", pre);
+        for glob in &cfg.pre_globs {
+            let _ = write!(key, "\x1fpre-glob={}", glob);
+        }
+    }
+    for input in inputs {
+        key.push('\x1f');
+        key.push_str(input);
+    }
+    key.replace(['\t', '\n'], " ")
+}
+
+/// Modification time of `path` as `secs.nanos`, if it can be read.
+fn mtime(path: &str) -> Option<String> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{}.{:09}", since.as_secs(), since.subsec_nanos()))
+}
+
+/// State of a cache file, as reported by `inspect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CacheState {
+    /// The cache file does not exist yet.
+    Missing,
+    /// The cache file exists but could not be read.
+    Unreadable(String),
+    /// The cache file is not in the expected format.
+    Corrupt,
+    /// The cache was built for a different working directory or different inputs.
+    OtherInputs,
+    /// The given directory or file changed since the cache was written.
+    Stale(String),
+    /// The cache is usable and lists the given number of files.
+    Fresh(usize),
+}
+
+/// Load the cached list at `cache` if it was built for `key` and is still fresh.
+pub(crate) fn load(cache: &Path, key: &str) -> Option<FileList> {
+    read(cache, key).ok()
+}
+
+/// Report whether the cache at `cache` would be used for `key`, and why not.
+pub(crate) fn inspect(cache: &Path, key: &str) -> CacheState {
+    match read(cache, key) {
+        Ok(list) => CacheState::Fresh(list.files.len()),
+        Err(state) => state,
+    }
+}
+
+fn read(cache: &Path, key: &str) -> Result<FileList, CacheState> {
+    let text = match fs::read_to_string(cache) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(CacheState::Missing),
+        Err(err) => return Err(CacheState::Unreadable(err.to_string())),
+    };
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(CacheState::Corrupt);
+    }
+    let mut expanded = None;
+    let mut files = Vec::new();
+    let mut skipped_dirs = Vec::new();
+    let mut key_matches = false;
+    let mut stale = None;
+    for line in lines {
+        let (kind, rest) = line.split_once('\t').ok_or(CacheState::Corrupt)?;
+        match kind {
+            "key" => key_matches = rest == key,
+            "expanded" => expanded = rest.parse().ok(),
+            "stamp" => {
+                let (stamp, path) = rest.split_once('\t').ok_or(CacheState::Corrupt)?;
+                if stale.is_none() && mtime(path).as_deref() != Some(stamp) {
+                    stale = Some(path.to_string());
+                }
+            }
+            "skipped" => {
+                let mut fields = rest.splitn(3, '\t');
+                let mut count = || fields.next().and_then(|n| n.parse().ok());
+                let (Some(sampled), Some(binary)) = (count(), count()) else {
+                    return Err(CacheState::Corrupt);
+                };
+                let path = fields.next().ok_or(CacheState::Corrupt)?;
+                skipped_dirs.push(SkippedDir {
+                    path: path.to_string(),
+                    sampled,
+                    binary,
+                });
+            }
+            "file" => {
+                let (idx, path) = rest.split_once('\t').ok_or(CacheState::Corrupt)?;
+                let idx = idx.parse().map_err(|_| CacheState::Corrupt)?;
+                files.push((idx, path.to_string()));
+            }
+            _ => return Err(CacheState::Corrupt),
+        }
+    }
+    let expanded = expanded.ok_or(CacheState::Corrupt)?;
+    if !key_matches {
+        return Err(CacheState::OtherInputs);
+    }
+    if let Some(path) = stale {
+        return Err(CacheState::Stale(path));
+    }
+    Ok(FileList {
+        expanded,
+        files,
+        skipped_dirs,
+        walk_issues: WalkIssues::default(),
+    })
+}
+
+/// Save `list` for `key`, stamping `walked` (the directories walked, the ignore files
+/// read, and the files sniffed for binary content) and the files named in `inputs`.
+pub(crate) fn store(
+    cache: &Path,
+    key: &str,
+    inputs: &[String],
+    walked: &[String],
+    list: &FileList,
+) -> io::Result<()> {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", HEADER);
+    let _ = writeln!(out, "key\t{}", key);
+    let _ = writeln!(out, "expanded\t{}", list.expanded);
+    let named_files = inputs.iter().filter(|p| !Path::new(p).is_dir());
+    for path in walked.iter().chain(named_files) {
+        // Paths that cannot be represented in the line format, or stamped, are not cached
+        if path.contains(['\t', '\n']) {
+            return Ok(());
+        }
+        match mtime(path) {
+            Some(stamp) => {
+                let _ = writeln!(out, "stamp\t{}\t{}", stamp, path);
+            }
+            None => return Ok(()),
+        }
+    }
+    for dir in &list.skipped_dirs {
+        if dir.path.contains(['\t', '\n']) {
+            return Ok(());
+        }
+        let _ = writeln!(
+            out,
+            "skipped\t{}\t{}\t{}",
+            dir.sampled, dir.binary, dir.path
+        );
+    }
+    for (idx, path) in &list.files {
+        if path.contains(['\t', '\n']) {
+            return Ok(());
+        }
+        let _ = writeln!(out, "file\t{}\t{}", idx, path);
+    }
+    fs::write(cache, out)
+}
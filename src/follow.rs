@@ -2,18 +2,27 @@ use std::collections::VecDeque;
 use std::fs::{self, File};
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::thread;
 use std::time::Duration;
 
-use crate::config::Config;
-use crate::fs_utils::{expand_inputs, is_binary_path};
-use crate::regex_utils::{build_and_matchers, build_regex, highlight_segments};
+use crate::color::Colors;
+use crate::config::{BinaryFiles, Config};
+use crate::fs_utils::{binary_files, display_path, expand_inputs, input_roots, is_binary_path};
+use crate::io_utils::{error_reason, is_broken_pipe};
+use crate::matcher::Matcher;
+use crate::metrics::FollowMetrics;
+use crate::output::shorten_line;
+use crate::regex_utils::highlight_spans;
+use crate::search::STDIN_LABEL;
 
 #[derive(Debug)]
 struct FollowEngine {
     before_n: usize,
     after_n: usize,
-    before_buf: VecDeque<String>,
+    /// Leading context candidates, with their line numbers.
+    before_buf: VecDeque<(usize, String)>,
     after_remaining: usize,
 }
 
@@ -29,7 +38,7 @@ impl FollowEngine {
 
     // Process a line and return the lines that should be printed right now,
     // in the right order (before-context lines, the line itself if match, or after-context lines)
-    fn handle_line(&mut self, line: String, is_match: bool) -> Vec<String> {
+    fn handle_line(&mut self, line: (usize, String), is_match: bool) -> Vec<(usize, String)> {
         let mut out = Vec::new();
         if is_match {
             // emit before-context if any
@@ -62,173 +71,576 @@ impl FollowEngine {
 /// Follow a single regular file for newly appended lines and print matches as they appear.
 ///
 /// Behavior:
-/// - Exactly one regular file must be provided in `inputs` (not `-`).
-/// - Starts reading at end-of-file and processes only new data.
+/// - Exactly one regular file, or standard input (`-`), must be provided in `inputs`.
+/// - Starts reading at end-of-file and processes only new data; with
+///   `Config::follow_from_start`, the lines already in the file are processed first, and
+///   with `Config::tail_lines`, the last of those that are selected are sent first.
+/// - Survives log rotation: when the path names another file (a new inode, on Unix),
+///   the rest of the old file is read and the new one is followed from its start; a
+///   missing file is waited for, and a file truncated in place is read again from its
+///   start. Lines are processed once their newline has been written.
 /// - Respects `Config` options like patterns, case, and context. Context applies within the
 ///   current batch of appended lines (no cross-batch leakage).
-pub fn follow(cfg: &Config, inputs: &[String]) -> Result<(), String> {
+/// - Standard input is read as lines arrive, a pipe's lines being batched as they come
+///   in, until it ends (`Config::follow_from_start` and `Config::tail_lines` do not apply
+///   to it).
+/// - Runs until stdout's reader goes away (e.g. `rgrep -f ... | head`) or standard input
+///   ends, and says which of the two happened (see `FollowEnd`).
+/// - With `Config::metrics_file`, match counters are written to that file as they change
+///   (see `metrics`).
+pub fn follow(cfg: &Config, inputs: &[String]) -> Result<FollowEnd, String> {
+    // Decide on colors once instead of for every printed line
+    let colors = cfg.color.should_color().then_some(&cfg.colors);
+    follow_with_sink(cfg, inputs, &mut Printer { cfg, colors })
+}
+
+/// Why `follow` or `follow_with_sink` stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowEnd {
+    /// The lines are no longer wanted: stdout's reader went away, or the sink was closed
+    /// or failed with a `BrokenPipe` error.
+    Closed,
+    /// Standard input ended.
+    InputEnded,
+}
+
+/// A line selected by `follow`: a new matching line, or one around it.
+///
+/// `path` is the followed file as `follow` names it, `None` for standard input, and
+/// `line_number` counts from 1 in the file currently at that path: after log rotation
+/// or truncation, lines are numbered again from the start of the new contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowEvent {
+    /// A line selected by the patterns (or, with `Config::invert`, not matching them),
+    /// with the byte ranges of the matched text.
+    Match {
+        path: Option<String>,
+        line_number: usize,
+        line: String,
+        spans: Vec<Range<usize>>,
+    },
+    /// A line printed around a match (`Config::context`).
+    Context {
+        path: Option<String>,
+        line_number: usize,
+        line: String,
+    },
+}
+
+/// Receiver of the lines selected by `follow_with_sink`, called in file order from the
+/// following thread.
+pub trait FollowSink {
+    /// Take the next selected line. Returning an error ends following: a
+    /// `BrokenPipe` error as if the sink were closed, and any other error is returned by
+    /// `follow_with_sink`.
+    fn on_line(&mut self, event: FollowEvent) -> io::Result<()>;
+
+    /// Whether the lines are no longer wanted, checked while waiting for new ones; once
+    /// it returns `true`, `follow_with_sink` returns `Ok(FollowEnd::Closed)`.
+    ///
+    /// When following standard input, it is only checked between lines: reading blocks
+    /// until the next line or the end of input, so an idle pipe keeps following going
+    /// until it writes again or is closed.
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+/// Prints followed lines to stdout, highlighting matches with `colors` and shortening
+/// long lines as `Config::max_columns` and `Config::match_window` say.
+struct Printer<'a> {
+    cfg: &'a Config,
+    colors: Option<&'a Colors>,
+}
+
+impl FollowSink for Printer<'_> {
+    fn on_line(&mut self, event: FollowEvent) -> io::Result<()> {
+        // Write errors are returned instead of panicking like `println!`, so a closed pipe
+        // ends the follow loop
+        let mut stdout = io::stdout().lock();
+        let (line, spans) = match event {
+            FollowEvent::Match { line, spans, .. } => (line, spans),
+            FollowEvent::Context { line, .. } => (line, Vec::new()),
+        };
+        let (line, spans) = shorten_line(self.cfg, &line, &spans).unwrap_or((line, spans));
+        match self.colors {
+            Some(colors) => writeln!(
+                stdout,
+                "{}",
+                highlight_spans(&line, &spans, &colors.matched)
+            ),
+            None => writeln!(stdout, "{}", line),
+        }
+    }
+}
+
+/// Follow a file or standard input like `follow`, passing each selected line to `sink`
+/// (see `FollowSink`) instead of printing it, for embedders such as TUIs and services.
+///
+/// Blocks while following: run it on a thread of its own, and have `FollowSink::closed`
+/// return `true` to stop it. Returns how following ended once the sink is closed or
+/// standard input ends, and an error if following cannot start or the sink fails.
+pub fn follow_with_sink(
+    cfg: &Config,
+    inputs: &[String],
+    sink: &mut dyn FollowSink,
+) -> Result<FollowEnd, String> {
     validate_follow_inputs(cfg, inputs)?;
     let path = &expand_inputs(cfg, inputs)[0];
 
-    let re = build_regex(cfg).map_err(|e| e.to_string())?;
-    let and_matchers = build_and_matchers(cfg).map_err(|e| e.to_string())?;
-    let mut pos = get_initial_file_position(path)?;
+    let matcher = Matcher::new(cfg)?;
+    if matcher.is_windowed() {
+        return Err("NEAR (~N~) is not supported in follow mode".into());
+    }
+    if matcher.is_multiline() {
+        return Err("multiline mode (-U) is not supported in follow mode".into());
+    }
+    let shown = (path != "-").then(|| display_path(&input_roots(inputs), path));
+    let mut metrics = cfg.metrics_file.as_deref().map(|file| {
+        let pattern = match &cfg.definition {
+            Some(name) => format!("--def {}", name),
+            None => cfg.patterns.join(""),
+        };
+        FollowMetrics::new(file, shown.as_deref().unwrap_or(STDIN_LABEL), &pattern)
+    });
+    let Some(shown) = shown else {
+        return follow_stdin(cfg, &matcher, &mut metrics, sink);
+    };
+    let mut followed = match cfg.tail_lines {
+        Some(0) | None => open_waiting(path, !cfg.follow_from_start),
+        Some(n) => {
+            let mut followed = open_waiting(path, false);
+            let mut tail = Tail::new(n, cfg.context.after);
+            // Keeping lines never fails
+            let _ = read_new_lines(
+                cfg,
+                &mut followed,
+                &matcher,
+                &mut metrics,
+                &shown,
+                &mut tail,
+                false,
+            );
+            for event in tail.into_events() {
+                if let Err(e) = sink.on_line(event) {
+                    return sink_failed(&mut metrics, e);
+                }
+            }
+            followed
+        }
+    };
 
-    follow_file_changes(cfg, path, &re, &and_matchers, &mut pos)
+    let watched = Watched {
+        path,
+        shown: &shown,
+    };
+    follow_file_changes(cfg, watched, &matcher, &mut followed, &mut metrics, sink)
 }
 
 fn validate_follow_inputs(cfg: &Config, inputs: &[String]) -> Result<(), String> {
     if !cfg.follow {
         return Err("follow mode not enabled".into());
     }
+    if cfg.search_zip {
+        return Err("-z is not supported in follow mode".into());
+    }
 
     let files = expand_inputs(cfg, inputs);
-    if files.len() != 1 || files[0] == "-" {
-        return Err("follow mode supports exactly one regular file".into());
+    if files.len() != 1 {
+        return Err("follow mode supports exactly one regular file or standard input".into());
     }
 
     let path = &files[0];
-    if is_binary_path(path) {
+    if path != "-" && binary_files(cfg) != BinaryFiles::Text && is_binary_path(path, false) {
         return Err("cannot follow binary file".into());
     }
 
     Ok(())
 }
 
-fn get_initial_file_position(path: &str) -> Result<u64, String> {
-    let one_hundred_milli_seconds = Duration::from_millis(100);
+/// The file being followed, kept open so that lines still written to it after it was
+/// rotated away (renamed or deleted) are read before moving on to its replacement.
+struct Followed {
+    reader: BufReader<File>,
+    /// Identity of the open file (see `file_id`).
+    id: Option<(u64, u64)>,
+    /// Bytes read from it so far.
+    pos: u64,
+    /// The start of a line whose newline has not been written yet.
+    partial: String,
+    /// Lines read from it so far, to number the next one.
+    line_number: usize,
+}
+
+impl Followed {
+    /// Open the file at `path`, positioned at its end with `at_end`, or else at its
+    /// start.
+    fn open(path: &str, at_end: bool) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let meta = file.metadata()?;
+        let mut reader = BufReader::new(file);
+        let (pos, line_number) = if at_end {
+            // Skipped lines still count, so that new ones get their numbers in the file
+            let skipped = count_lines((&mut reader).take(meta.len()))?;
+            (meta.len(), skipped)
+        } else {
+            (0, 0)
+        };
+        Ok(Self {
+            reader,
+            id: file_id(&meta),
+            pos,
+            partial: String::new(),
+            line_number,
+        })
+    }
 
+    /// Read the file again from its start, after it was truncated.
+    fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(0))?;
+        self.pos = 0;
+        self.partial.clear();
+        self.line_number = 0;
+        Ok(())
+    }
+}
+
+/// Number of newlines in `reader`, up to its end.
+fn count_lines(mut reader: impl BufRead) -> io::Result<usize> {
+    let mut lines = 0;
     loop {
-        match File::open(path) {
-            Ok(file) => match file.metadata() {
-                Ok(md) => return Ok(md.len()),
-                Err(_) => {
-                    thread::sleep(one_hundred_milli_seconds);
-                    continue;
-                }
-            },
-            Err(_) => {
-                // transient error (e.g., file not yet created/rotated)
-                thread::sleep(one_hundred_milli_seconds);
-                continue;
-            }
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(lines);
         }
+        lines += memchr::memchr_iter(b'\n', buf).count();
+        let read = buf.len();
+        reader.consume(read);
     }
 }
 
-fn follow_file_changes(
-    cfg: &Config,
-    path: &str,
-    re: &regex::Regex,
-    and_matchers: &Option<Vec<regex::Regex>>,
-    pos: &mut u64,
-) -> Result<(), String> {
-    let before_n = cfg.context.before;
-    let after_n = cfg.context.after;
+/// Device and inode of a file, which tell a file put in place by log rotation from the
+/// one it replaced.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+/// Without inodes, only truncation is noticed.
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
 
+/// Open the file to follow (see `Followed::open`), waiting for it to be created if it is
+/// missing.
+fn open_waiting(path: &str, at_end: bool) -> Followed {
     loop {
-        let meta_len = match fs::metadata(path) {
-            Ok(meta) => meta.len(),
-            Err(_) => {
-                // e.g., file temporarily missing (rotation); wait and retry
-                thread::sleep(Duration::from_millis(200));
-                continue;
+        match Followed::open(path, at_end) {
+            Ok(followed) => return followed,
+            // transient error (e.g., file not yet created/rotated)
+            Err(_) => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// Keeps the lines selected among those already in the file (`Config::tail_lines`): the
+/// last `keep` matches, each with its context.
+struct Tail {
+    keep: usize,
+    /// Trailing context lines after each match (`Config::context`).
+    after: usize,
+    groups: VecDeque<Vec<FollowEvent>>,
+    /// Context lines since the last match, not yet known to trail it or lead the next.
+    pending: Vec<FollowEvent>,
+}
+
+impl Tail {
+    fn new(keep: usize, after: usize) -> Self {
+        Self {
+            keep,
+            after,
+            groups: VecDeque::with_capacity(keep.min(1024)),
+            pending: Vec::new(),
+        }
+    }
+
+    /// The kept lines, in file order.
+    fn into_events(mut self) -> Vec<FollowEvent> {
+        if let Some(group) = self.groups.back_mut() {
+            group.append(&mut self.pending);
+        }
+        self.groups.into_iter().flatten().collect()
+    }
+}
+
+impl FollowSink for Tail {
+    fn on_line(&mut self, event: FollowEvent) -> io::Result<()> {
+        if let FollowEvent::Context { .. } = event {
+            self.pending.push(event);
+            return Ok(());
+        }
+        // `FollowEngine` sends up to `after` lines trailing a match before any leading
+        // the next one
+        let mut group = match self.groups.back_mut() {
+            Some(previous) => {
+                let leading = self.pending.split_off(self.after.min(self.pending.len()));
+                previous.append(&mut self.pending);
+                leading
             }
+            None => std::mem::take(&mut self.pending),
         };
+        group.push(event);
+        if self.groups.len() == self.keep {
+            self.groups.pop_front();
+        }
+        self.groups.push_back(group);
+        Ok(())
+    }
+}
+
+/// How the file at the followed path relates to the open one.
+enum PathState {
+    /// Still the open file.
+    Same,
+    /// The open file, cut shorter than what was read of it (e.g. logrotate's
+    /// `copytruncate`).
+    Truncated,
+    /// Another file, put in place by log rotation.
+    Replaced,
+    /// No file at the moment, e.g. between rotating the old file away and creating the
+    /// new one.
+    Missing,
+}
+
+fn path_state(path: &str, followed: &Followed) -> PathState {
+    match fs::metadata(path) {
+        Ok(meta) if file_id(&meta) != followed.id => PathState::Replaced,
+        Ok(meta) if meta.len() < followed.pos => PathState::Truncated,
+        Ok(_) => PathState::Same,
+        Err(_) => PathState::Missing,
+    }
+}
+
+/// The followed path, and how events name it (see `FollowEvent`).
+#[derive(Clone, Copy)]
+struct Watched<'a> {
+    path: &'a str,
+    shown: &'a str,
+}
+
+fn follow_file_changes(
+    cfg: &Config,
+    watched: Watched,
+    matcher: &Matcher,
+    followed: &mut Followed,
+    metrics: &mut Option<FollowMetrics>,
+    out: &mut dyn FollowSink,
+) -> Result<FollowEnd, String> {
+    loop {
+        if out.closed() {
+            return stop_following(metrics, FollowEnd::Closed);
+        }
+        write_metrics_if_due(cfg, metrics)?;
 
-        if meta_len < *pos {
-            *pos = meta_len;
+        let shown = watched.shown;
+        if let Err(e) = read_new_lines(cfg, followed, matcher, metrics, shown, out, false) {
+            return sink_failed(metrics, e);
         }
 
-        if meta_len > *pos {
-            match process_new_file_content(cfg, path, re, and_matchers, pos, before_n, after_n) {
-                Ok(new_pos) => *pos = new_pos,
-                Err(_) => {
-                    thread::sleep(Duration::from_millis(100));
+        match path_state(watched.path, followed) {
+            PathState::Replaced => {
+                // Lines written to the old file up to now were read above, apart from an
+                // unfinished last line; nothing more will be read from it
+                if let Err(e) = read_new_lines(cfg, followed, matcher, metrics, shown, out, true) {
+                    return sink_failed(metrics, e);
+                }
+                if let Ok(new) = Followed::open(watched.path, false) {
+                    *followed = new;
+                    // The new file may already have lines
+                    continue;
+                }
+            }
+            PathState::Truncated => {
+                // Whatever was written since the truncation is new
+                if followed.rewind().is_ok() {
                     continue;
                 }
             }
+            // A missing file is waited for; the open one may still be written to
+            PathState::Same | PathState::Missing => {}
         }
 
         thread::sleep(Duration::from_millis(100));
     }
 }
 
-fn process_new_file_content(
+/// Follow standard input until it ends. The lines of a pipe that are read together, as
+/// far as they were buffered at once, form a batch like the lines appended to a file.
+/// `out.closed()` is checked before each read, which blocks until a line comes in; the
+/// metrics file is brought up to date before a read that may block.
+fn follow_stdin(
     cfg: &Config,
-    path: &str,
-    re: &regex::Regex,
-    and_matchers: &Option<Vec<regex::Regex>>,
-    pos: &u64,
-    before_n: usize,
-    after_n: usize,
-) -> Result<u64, std::io::Error> {
-    let mut f = File::open(path)?;
-
-    use std::io::Seek;
-    f.seek(std::io::SeekFrom::Start(*pos))?;
-
-    let mut reader = BufReader::new(f);
-    let mut buf = String::new();
-    let mut engine = FollowEngine::new(before_n, after_n);
-
+    matcher: &Matcher,
+    metrics: &mut Option<FollowMetrics>,
+    out: &mut dyn FollowSink,
+) -> Result<FollowEnd, String> {
+    // Own buffer, to tell whether more lines have already been read
+    let mut reader = BufReader::new(io::stdin());
+    let mut engine = FollowEngine::new(cfg.context.before, cfg.context.after);
+    let mut buf = Vec::new();
+    let mut line_number = 0;
     loop {
-        match reader.read_line(&mut buf) {
-            Ok(0) => break,
-            Ok(_) => {
-                let line = buf.trim_end_matches(['\n', '\r']).to_string();
-                process_line(cfg, &mut engine, re, and_matchers, line);
-                buf.clear();
-            }
-            Err(e) => return Err(e),
+        if out.closed() {
+            return stop_following(metrics, FollowEnd::Closed);
+        }
+        if reader.buffer().is_empty() {
+            // Nothing is buffered, so the read waits for the writer for as long as it
+            // takes: the counts so far must not wait with it
+            write_metrics(cfg, metrics, FollowMetrics::write_if_changed)?;
+        } else {
+            write_metrics_if_due(cfg, metrics)?;
+        }
+
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| format!("{}: {}", STDIN_LABEL, error_reason(&e)))?;
+        if read == 0 {
+            return stop_following(metrics, FollowEnd::InputEnded);
+        }
+        let line = String::from_utf8_lossy(&buf)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+        line_number += 1;
+        let line = (line_number, line);
+        if let Err(e) = process_line(cfg, &mut engine, matcher, metrics, None, line, out) {
+            return sink_failed(metrics, e);
+        }
+        if reader.buffer().is_empty() {
+            // The next line has not been written yet, so it starts a new batch
+            engine = FollowEngine::new(cfg.context.before, cfg.context.after);
         }
     }
+}
 
-    Ok(fs::metadata(path)?.len())
+fn write_metrics_if_due(cfg: &Config, metrics: &mut Option<FollowMetrics>) -> Result<(), String> {
+    write_metrics(cfg, metrics, FollowMetrics::write_if_due)
 }
 
-fn process_line(
+fn write_metrics(
     cfg: &Config,
-    engine: &mut FollowEngine,
-    re: &regex::Regex,
-    and_matchers: &Option<Vec<regex::Regex>>,
-    line: String,
-) {
-    let is_match = if let Some(ands) = and_matchers {
-        ands.iter().all(|r| r.is_match(&line))
-    } else {
-        re.is_match(&line)
-    };
-    let final_match = if cfg.invert { !is_match } else { is_match };
+    metrics: &mut Option<FollowMetrics>,
+    write: fn(&mut FollowMetrics) -> io::Result<()>,
+) -> Result<(), String> {
+    if let (Some(metrics), Some(file)) = (metrics.as_mut(), &cfg.metrics_file) {
+        write(metrics).map_err(|e| format!("{}: cannot write metrics file: {}", file, e))?;
+    }
+    Ok(())
+}
 
-    let outs = engine.handle_line(line.clone(), final_match);
+/// Nobody reads the output any more, or there is nothing more to read; keep the final
+/// counts.
+fn stop_following(
+    metrics: &mut Option<FollowMetrics>,
+    end: FollowEnd,
+) -> Result<FollowEnd, String> {
+    if let Some(metrics) = metrics {
+        let _ = metrics.write();
+    }
+    Ok(end)
+}
 
-    if final_match {
-        print_match_lines(cfg, re, outs);
-    } else {
-        print_context_lines(outs);
+/// `out` failed: a broken pipe ends following like a closed sink, anything else is an
+/// error.
+fn sink_failed(metrics: &mut Option<FollowMetrics>, e: io::Error) -> Result<FollowEnd, String> {
+    if is_broken_pipe(&e) {
+        return stop_following(metrics, FollowEnd::Closed);
     }
+    Err(e.to_string())
 }
 
-fn print_match_lines(cfg: &Config, re: &regex::Regex, lines: Vec<String>) {
-    if cfg.color && !cfg.line {
-        let last_idx = lines.len().saturating_sub(1);
-        for (i, l) in lines.into_iter().enumerate() {
-            if i == last_idx {
-                println!("{}", highlight_segments(&l, &re));
-            } else {
-                println!("{}", l);
-            }
-        }
-    } else {
-        for l in lines {
-            println!("{}", l);
+/// Process the lines appended to the followed file since the last call. A last line
+/// without its newline is kept for the next call, unless `last` says the file is done
+/// with. Errors are those of `out`.
+fn read_new_lines(
+    cfg: &Config,
+    followed: &mut Followed,
+    matcher: &Matcher,
+    metrics: &mut Option<FollowMetrics>,
+    shown: &str,
+    out: &mut dyn FollowSink,
+    last: bool,
+) -> io::Result<()> {
+    let mut engine = FollowEngine::new(cfg.context.before, cfg.context.after);
+    // Read errors end the batch; reading is retried on the next round
+    while let Ok(read) = followed.reader.read_line(&mut followed.partial) {
+        followed.pos += read as u64;
+        if read == 0 || !followed.partial.ends_with('\n') {
+            break;
         }
+        let line = followed.partial.trim_end_matches(['\n', '\r']).to_string();
+        followed.partial.clear();
+        followed.line_number += 1;
+        let line = (followed.line_number, line);
+        process_line(cfg, &mut engine, matcher, metrics, Some(shown), line, out)?;
+    }
+    if last && !followed.partial.is_empty() {
+        followed.line_number += 1;
+        let line = (followed.line_number, std::mem::take(&mut followed.partial));
+        process_line(cfg, &mut engine, matcher, metrics, Some(shown), line, out)?;
     }
+    Ok(())
 }
 
-fn print_context_lines(lines: Vec<String>) {
-    for l in lines {
-        println!("{}", l);
+/// Select `line`, numbered in the file named `path` (`None` for standard input), and
+/// send it to `out` with the context it releases.
+fn process_line(
+    cfg: &Config,
+    engine: &mut FollowEngine,
+    matcher: &Matcher,
+    metrics: &mut Option<FollowMetrics>,
+    path: Option<&str>,
+    (line_number, line): (usize, String),
+    out: &mut dyn FollowSink,
+) -> io::Result<()> {
+    let is_match = matcher.is_match(&line);
+    let final_match = if cfg.invert { !is_match } else { is_match };
+    if let Some(metrics) = metrics {
+        let terms = if final_match && is_match {
+            matcher.matched_terms(&line)
+        } else {
+            Vec::new()
+        };
+        metrics.record(final_match, terms);
     }
+
+    // A match comes last, after the leading context it releases
+    let lines = engine.handle_line((line_number, line), final_match);
+    let last = lines.len().saturating_sub(1);
+    for (i, (line_number, line)) in lines.into_iter().enumerate() {
+        let path = path.map(str::to_string);
+        let event = if final_match && i == last {
+            let spans = if is_match {
+                matcher.find_spans(&line)
+            } else {
+                Vec::new()
+            };
+            FollowEvent::Match {
+                path,
+                line_number,
+                line,
+                spans,
+            }
+        } else {
+            FollowEvent::Context {
+                path,
+                line_number,
+                line,
+            }
+        };
+        out.on_line(event)?;
+    }
+    Ok(())
 }
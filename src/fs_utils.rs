@@ -1,33 +1,135 @@
 //! Filesystem helpers for expanding inputs and detecting binary files.
 //!
 //! These utilities are used by the search and follow engines to determine what
-//! to read and how.
+//! to read and how. They also discover the git setup around a search root: the
+//! repository's git directory and the user's global excludes file
+//! (`core.excludesFile`), from which `ignore` decides what a recursive walk skips.
+//!
+//! Inputs may name a search root with a label, `LABEL=PATH` (`--root LABEL=PATH` on the
+//! command line). The walk reads `PATH`, while results found below it are shown as
+//! `LABEL/relative/path` (see `display_path`) instead of the full path.
 
-use std::fs::File;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Read;
-use std::path::Path;
-use walkdir::WalkDir;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::{DirEntry, WalkDir};
 
-use crate::config::Config;
+use crate::config::{BinaryFiles, Compat, Config, SkipReason, Skipped};
+use crate::ignore::{DirFilter, GlobFilter, IgnoreFilter, RuleChain, TypeFilter};
+use crate::io_utils::{SNIFF_LEN, error_reason, is_stream, open_input};
 
 /// Heuristically determine whether a path refers to a binary file.
 ///
-/// Reads up to 4 KiB from the file and returns true if a NUL byte is observed.
-/// The special path "-" is treated as stdin and considered non-binary.
-pub fn is_binary_path(path: &str) -> bool {
-    if path == "-" {
+/// Reads up to 4 KiB from the file and returns true if a NUL byte is observed; with
+/// `decompress` (`Config::search_zip`), from its decompressed content, so compressed text
+/// is not taken for binary. The special path "-" is treated as stdin and considered
+/// non-binary. Streams such as `/dev/fd/63` are not read either, since the bytes read
+/// could not be read again; they are sniffed when searched instead (see
+/// `io_utils::open_searchable`).
+pub fn is_binary_path(path: &str, decompress: bool) -> bool {
+    if path == "-" || is_stream(path) {
         return false;
     }
-    let Ok(mut f) = File::open(path) else {
+    let Ok(f) = open_input(Some(path), decompress) else {
         return false;
     };
-    let mut buf = [0u8; 4096];
-    match f.read(&mut buf) {
-        Ok(n) => buf[..n].iter().any(|&b| b == 0),
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    match f.take(SNIFF_LEN as u64).read_to_end(&mut buf) {
+        Ok(_) => buf.contains(&0),
         Err(_) => false,
     }
 }
 
+/// What to do with binary files: `Config::binary_files`, or by default skip them, except
+/// with `Compat::Gnu`, which reports them as GNU grep does.
+pub fn binary_files(cfg: &Config) -> BinaryFiles {
+    cfg.binary_files.unwrap_or(match cfg.compat {
+        Compat::Gnu => BinaryFiles::Report,
+        Compat::Rgrep => BinaryFiles::Skip,
+    })
+}
+
+/// A search root named by a label, from an input `LABEL=PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Root {
+    pub label: String,
+    pub path: String,
+}
+
+/// The root named by `input` when it has the form `LABEL=PATH`, with a label of ASCII
+/// letters, digits, `_`, `-`, and `.`. An input that exists as a path is never a root, so
+/// files whose names contain `=` are still searched.
+pub fn parse_root(input: &str) -> Option<Root> {
+    let (label, path) = input.split_once('=')?;
+    let valid_label = !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid_label || path.is_empty() || Path::new(input).exists() {
+        return None;
+    }
+    Some(Root {
+        label: label.to_string(),
+        path: path.to_string(),
+    })
+}
+
+/// The path `input` reads, without its root label if it has one.
+pub fn input_path(input: &str) -> String {
+    parse_root(input).map_or_else(|| input.to_string(), |root| root.path)
+}
+
+/// The labeled roots among `inputs`.
+pub fn input_roots(inputs: &[String]) -> Vec<Root> {
+    inputs.iter().filter_map(|i| parse_root(i)).collect()
+}
+
+/// How `path` is shown in results: below the label of the root it was found in
+/// (`api/app.log`, or just `api` for a root that is a file), otherwise unchanged.
+pub fn display_path<'a>(roots: &[Root], path: &'a str) -> Cow<'a, str> {
+    for root in roots {
+        if let Ok(rel) = Path::new(path).strip_prefix(&root.path) {
+            return if rel.as_os_str().is_empty() {
+                Cow::Owned(root.label.clone())
+            } else {
+                Cow::Owned(format!("{}/{}", root.label, rel.to_string_lossy()))
+            };
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+/// The label of the root `path` was found in, if any.
+pub fn root_label<'a>(roots: &'a [Root], path: &str) -> Option<&'a str> {
+    roots
+        .iter()
+        .find(|root| Path::new(path).starts_with(&root.path))
+        .map(|root| root.label.as_str())
+}
+
+/// Read a list of inputs from the file at `path` (`-` for stdin), one per line, or
+/// separated by NUL bytes with `nul`, as `find -print0` and `git ls-files -z` write
+/// them. Empty entries are skipped; a list without any entry is an error.
+pub fn read_file_list(path: &str, nul: bool) -> Result<Vec<String>, String> {
+    let name = if path == "-" { "standard input" } else { path };
+    let mut bytes = Vec::new();
+    open_input(Some(path), false)
+        .and_then(|mut reader| reader.read_to_end(&mut bytes))
+        .map_err(|e| format!("{}: {}", name, error_reason(&e)))?;
+    let separator = if nul { b'\0' } else { b'\n' };
+    let files: Vec<String> = bytes
+        .split(|b| *b == separator)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| String::from_utf8_lossy(entry).into_owned())
+        .collect();
+    if files.is_empty() {
+        return Err(format!("{}: no files listed", name));
+    }
+    Ok(files)
+}
+
 /// Expand input paths according to `cfg.recursive` and defaulting rules.
 ///
 /// Behavior:
@@ -36,38 +138,966 @@ pub fn is_binary_path(path: &str) -> bool {
 ///   and returns all files.
 /// - When `cfg.recursive` is true and any input is a directory, it is recursively expanded
 ///   to the files it contains; non-directories are passed through.
+/// - Root labels (`LABEL=PATH`) are dropped; the files below `PATH` are returned.
 pub fn expand_inputs(cfg: &Config, inputs: &[String]) -> Vec<String> {
-    let mut files: Vec<String> = Vec::new();
+    expand_inputs_with_dirs(cfg, inputs).0
+}
+
+/// Like `expand_inputs`, but also returns every directory visited during the walk.
+pub fn expand_inputs_with_dirs(cfg: &Config, inputs: &[String]) -> (Vec<String>, Vec<String>) {
+    let walk = walk_inputs(cfg, inputs);
+    (walk.files, walk.dirs)
+}
+
+/// Built-in file types of `Config::types` (`--type NAME`): each name with the globs of the
+/// files it covers. `Config::type_defs` adds to them (see `file_types`).
+pub const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cmake", &["CMakeLists.txt", "*.cmake"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.inl"],
+    ),
+    ("cs", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("csv", &["*.csv", "*.tsv"]),
+    ("dart", &["*.dart"]),
+    ("docker", &["Dockerfile", "Dockerfile.*", "*.dockerfile"]),
+    ("elixir", &["*.ex", "*.exs"]),
+    ("erlang", &["*.erl", "*.hrl"]),
+    ("go", &["*.go"]),
+    ("haskell", &["*.hs", "*.lhs"]),
+    ("html", &["*.html", "*.htm", "*.xhtml"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json", "*.jsonl"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("log", &["*.log"]),
+    ("lua", &["*.lua"]),
+    (
+        "make",
+        &["Makefile", "makefile", "GNUmakefile", "*.mk", "*.mak"],
+    ),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("ocaml", &["*.ml", "*.mli"]),
+    ("perl", &["*.pl", "*.pm", "*.t"]),
+    ("php", &["*.php"]),
+    ("proto", &["*.proto"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("r", &["*.R", "*.r", "*.Rmd"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile", "*.gemspec"]),
+    ("rust", &["*.rs"]),
+    ("scala", &["*.scala", "*.sc"]),
+    (
+        "sh",
+        &["*.sh", "*.bash", "*.zsh", ".bashrc", ".zshrc", ".profile"],
+    ),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("terraform", &["*.tf", "*.tfvars"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.mts", "*.cts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml", "*.xsd", "*.xsl", "*.svg"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+    ("zig", &["*.zig"]),
+];
+
+/// The file types known to `cfg` and their globs: `FILE_TYPES` with the definitions of
+/// `Config::type_defs` added, which extend a type of the same name.
+pub fn file_types(cfg: &Config) -> BTreeMap<String, Vec<String>> {
+    let mut types: BTreeMap<String, Vec<String>> = FILE_TYPES
+        .iter()
+        .map(|(name, globs)| {
+            (
+                name.to_string(),
+                globs.iter().map(|g| g.to_string()).collect(),
+            )
+        })
+        .collect();
+    for (name, glob) in &cfg.type_defs {
+        types.entry(name.clone()).or_default().push(glob.clone());
+    }
+    types
+}
+
+/// Files sampled below a directory before `Config::skip_binary_dirs` may skip the rest of
+/// it.
+pub const BINARY_DIR_SAMPLE: usize = 20;
+
+/// Everything found by expanding the inputs (see `walk_inputs`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Walk {
+    /// Files to search, as returned by `expand_inputs`.
+    pub files: Vec<String>,
+    /// Every directory visited during the walk.
+    pub dirs: Vec<String>,
+    /// Directories whose remaining entries were skipped with `Config::skip_binary_dirs`.
+    pub skipped_dirs: Vec<SkippedDir>,
+    /// The ignore files (`.gitignore` and others) that decided what was skipped.
+    pub ignore_files: Vec<String>,
+    /// Number of entries (files, directories, and others) the walk went through.
+    pub entries: usize,
+    /// What kept the walk from reading everything below the inputs.
+    pub issues: WalkIssues,
+}
+
+/// Problems met while walking, reported as a summary rather than one by one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkIssues {
+    /// Number of entries that could not be read (permission denied, path too long, ...).
+    pub errors: usize,
+    /// The first `WALK_ERROR_EXAMPLES` of these errors.
+    pub examples: Vec<String>,
+    /// Directories not entered because the walk had already been inside them (same
+    /// device and inode), as with symlink loops and bind mounts.
+    pub cycles: Vec<String>,
+    /// The walk stopped at `Config::max_entries` entries.
+    pub truncated: bool,
+    /// Files left out for being larger than `Config::max_filesize`, with their sizes.
+    pub too_large: Vec<(String, u64)>,
+    /// Every entry left out and why, with `Config::list_skipped`. The walk records
+    /// unreadable, ignored, hidden, excluded, and too large entries; binary files are
+    /// added when the files are selected for the search.
+    pub skipped: Vec<Skipped>,
+}
+
+impl WalkIssues {
+    pub fn is_empty(&self) -> bool {
+        self.errors == 0
+            && self.cycles.is_empty()
+            && !self.truncated
+            && self.too_large.is_empty()
+            && self.skipped.is_empty()
+    }
+}
+
+/// Number of walk errors quoted in `WalkIssues::examples`.
+pub const WALK_ERROR_EXAMPLES: usize = 3;
+
+/// A directory skipped by `Config::skip_binary_dirs` after sampling its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedDir {
+    pub path: String,
+    /// Number of files sampled below the directory (`BINARY_DIR_SAMPLE`).
+    pub sampled: usize,
+    /// How many of the sampled files are binary.
+    pub binary: usize,
+}
+
+/// Expand inputs like `expand_inputs`, also reporting the directories visited and those
+/// skipped.
+///
+/// Directories are walked without what the ignore files exclude (see `ignore`): what git
+/// ignores inside a repository and what `.rgrepignore` files, the user's rgrep ignore
+/// file, and `Config::ignore_files` exclude; with `Config::no_ignore`, only the latter.
+/// Files, named or found, are also filtered by the `--include`/`--exclude` globs of
+/// `Config::globs` and by the file types of `Config::types` and `Config::types_not`, and
+/// the directories matching `Config::exclude_dirs` are not walked. Hidden files and
+/// directories below the inputs are skipped unless `Config::hidden` is set.
+///
+/// The walk is iterative, so deep nesting does not grow the stack. Symlinks below the
+/// inputs are skipped unless `Config::follow_symlinks` is set. A directory whose device
+/// and inode were already walked below the same input is not entered again, and
+/// with `Config::max_entries` the walk stops after that many entries across all inputs.
+/// `Config::max_depth` limits how far below each input the walk descends, and files
+/// larger than `Config::max_filesize` are left out (see `WalkIssues::too_large`).
+/// Unreadable entries are skipped; they, cycles, and a stopped walk are summed up in
+/// `Walk::issues`.
+///
+/// With `Config::skip_binary_dirs`, the first `BINARY_DIR_SAMPLE` files below each
+/// directory are sampled during the walk; when more than 90% of them are binary (e.g. in
+/// `.git/objects` or an asset store), the rest of the directory is not walked. Inputs
+/// named explicitly are never skipped, only directories below them.
+///
+/// With more than one thread in the rayon pool, directories are read in parallel (see
+/// `walks_in_parallel`); the result is the same as walking one directory after another.
+pub fn walk_inputs(cfg: &Config, inputs: &[String]) -> Walk {
+    walk_inputs_with(cfg, inputs, None)
+}
+
+/// Called by `walk_inputs_with` for every file the walk returns, with its place in the
+/// walk order and its path.
+pub type OnFile<'a> = dyn Fn(WalkKey, &str) + Sync + 'a;
+
+/// Like `walk_inputs`, calling `on_file` for each file in `Walk::files` as soon as the
+/// walk finds it. When the walk is parallel (see `walks_in_parallel`), the calls are made
+/// from the rayon pool, in no particular order, while the walk goes on; sorting their
+/// `WalkKey`s gives the order of `Walk::files`.
+pub fn walk_inputs_with(cfg: &Config, inputs: &[String], on_file: Option<&OnFile>) -> Walk {
+    let mut walk = Walk::default();
+    let parallel = walks_in_parallel(cfg);
+    // Directories walked with the parallel walker, by their position in the inputs, and
+    // named files with theirs
+    let mut roots: Vec<(u32, PathBuf)> = Vec::new();
+    let mut named: Vec<(u32, String)> = Vec::new();
     if inputs.is_empty() {
         if cfg.recursive {
             // Walk current directory
-            for entry in WalkDir::new(".").into_iter().filter_map(|e| e.ok()) {
-                if entry.file_type().is_file() {
-                    files.push(entry.path().to_string_lossy().to_string());
-                }
+            if parallel {
+                roots.push((0, PathBuf::from(".")));
+            } else {
+                walk_into(cfg, Path::new("."), &mut walk);
             }
         } else {
-            files.push("-".to_string()); // stdin
+            walk.files.push("-".to_string()); // stdin
+        }
+    } else {
+        // Named files are filtered by --include/--exclude and --type too, but stdin never is
+        let globs = GlobFilter::new(&cfg.globs);
+        let types = TypeFilter::new(cfg).unwrap_or(None);
+        let admits = |inp: &str| {
+            let path = Path::new(inp);
+            inp == "-"
+                || (globs.as_ref().is_none_or(|g| g.admits(path))
+                    && types.as_ref().is_none_or(|t| t.admits(path)))
+        };
+        for (pos, inp) in inputs.iter().map(|i| input_path(i)).enumerate() {
+            let pos = pos as u32;
+            let p = Path::new(&inp);
+            if cfg.recursive && p.is_dir() {
+                if parallel {
+                    roots.push((pos, p.to_path_buf()));
+                } else {
+                    walk_into(cfg, p, &mut walk);
+                }
+            } else if admits(&inp) {
+                if parallel {
+                    named.push((pos, inp));
+                } else {
+                    walk.files.push(inp);
+                }
+            }
+        }
+    }
+
+    // GNU grep names the files below `.` without the `./`
+    let strip_dot = inputs.is_empty() && cfg.compat == Compat::Gnu;
+    if parallel {
+        walk_parallel(cfg, &roots, &named, strip_dot, on_file, &mut walk);
+        return walk;
+    }
+    if strip_dot {
+        for file in &mut walk.files {
+            if let Some(rest) = file.strip_prefix("./") {
+                *file = rest.to_string();
+            }
+        }
+    }
+    if let Some(on_file) = on_file {
+        for (pos, file) in walk.files.iter().enumerate() {
+            on_file(WalkKey(vec![pos as u32]), file);
         }
-        return files;
     }
+    walk
+}
+
+/// Whether `walk_inputs` reads directories in parallel. It does when the rayon pool has
+/// more than one thread, unless the walk depends on the order entries are met in:
+/// `Config::skip_binary_dirs` samples the first files of each directory,
+/// `Config::max_entries` stops after the first entries, and following symlinks
+/// (`Config::follow_symlinks`) reports a loop at the first entry that closes it.
+pub fn walks_in_parallel(cfg: &Config) -> bool {
+    cfg.recursive
+        && !cfg.skip_binary_dirs
+        && cfg.max_entries.is_none()
+        && !cfg.follow_symlinks
+        && rayon::current_num_threads() > 1
+}
 
-    if cfg.recursive {
-        for inp in inputs {
-            let p = Path::new(inp);
-            if p.is_dir() {
-                for entry in WalkDir::new(p).into_iter().filter_map(|e| e.ok()) {
-                    if entry.file_type().is_file() {
-                        files.push(entry.path().to_string_lossy().to_string());
+fn walk_into(cfg: &Config, root: &Path, walk: &mut Walk) {
+    let mut guard = cfg.skip_binary_dirs.then(|| BinaryDirGuard {
+        decompress: cfg.search_zip,
+        ..Default::default()
+    });
+    let mut ignore = IgnoreFilter::new(root, cfg);
+    let globs = GlobFilter::new(&cfg.globs);
+    // Unknown type names are rejected before walking (see `search::select_files`)
+    let types = TypeFilter::new(cfg).unwrap_or(None);
+    let excluded_dirs = DirFilter::new(&cfg.exclude_dirs);
+    let mut visited: HashSet<(u64, u64)> = HashSet::new();
+    let mut cycles: Vec<String> = Vec::new();
+    let mut pruned: Vec<Skipped> = Vec::new();
+    let mut walker = WalkDir::new(root).follow_links(cfg.follow_symlinks);
+    if let Some(depth) = cfg.max_depth {
+        walker = walker.max_depth(depth);
+    }
+    let entries = walker.into_iter().filter_entry(|e| {
+        let is_dir = e.file_type().is_dir();
+        if is_dir
+            && let Some(id) = dir_id(e)
+            && !visited.insert(id)
+        {
+            cycles.push(e.path().to_string_lossy().to_string());
+            return false;
+        }
+        if e.depth() == 0 {
+            // The walked directory itself is never ignored
+            return guard.as_mut().is_none_or(|g| g.admit(root, e));
+        }
+        // Excluded and hidden directories are pruned before anything inside them is read
+        let reason = if !cfg.hidden && is_hidden(e) {
+            Some(SkipReason::Hidden)
+        } else if is_dir
+            && excluded_dirs
+                .as_ref()
+                .is_some_and(|d| d.excludes(e.path().strip_prefix(root).unwrap_or(e.path())))
+        {
+            Some(SkipReason::Excluded)
+        } else if ignore
+            .as_mut()
+            .is_some_and(|i| i.is_ignored(e.path(), is_dir))
+        {
+            Some(SkipReason::Ignored)
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                if cfg.list_skipped {
+                    pruned.push(Skipped {
+                        path: e.path().to_string_lossy().to_string(),
+                        reason,
+                    });
+                }
+                false
+            }
+            None => guard.as_mut().is_none_or(|g| g.admit(root, e)),
+        }
+    });
+    for entry in entries {
+        if cfg.max_entries.is_some_and(|max| walk.entries >= max) {
+            walk.issues.truncated = true;
+            break;
+        }
+        walk.entries += 1;
+        let entry = match entry {
+            Ok(entry) => entry,
+            // A symlink back to a directory above it, found while following symlinks
+            Err(err) if err.loop_ancestor().is_some() => {
+                let path = err.path().unwrap_or(root);
+                walk.issues.cycles.push(path.to_string_lossy().to_string());
+                continue;
+            }
+            Err(err) => {
+                walk.issues.errors += 1;
+                if walk.issues.examples.len() < WALK_ERROR_EXAMPLES {
+                    walk.issues.examples.push(err.to_string());
+                }
+                if cfg.list_skipped
+                    && let Some(path) = err.path()
+                {
+                    let reason = err.io_error().map_or(err.to_string(), error_reason);
+                    walk.issues.skipped.push(Skipped {
+                        path: path.to_string_lossy().to_string(),
+                        reason: SkipReason::Unreadable(reason),
+                    });
+                }
+                continue;
+            }
+        };
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            if globs.as_ref().is_none_or(|g| g.admits(rel))
+                && types.as_ref().is_none_or(|t| t.admits(rel))
+            {
+                let path = entry.path().to_string_lossy().to_string();
+                // The size comes from the metadata, before the file is ever opened
+                let size = cfg
+                    .max_filesize
+                    .and_then(|_| entry.metadata().ok())
+                    .map(|meta| meta.len());
+                match (size, cfg.max_filesize) {
+                    (Some(size), Some(max)) if size > max => {
+                        if cfg.list_skipped {
+                            walk.issues.skipped.push(Skipped {
+                                path: path.clone(),
+                                reason: SkipReason::TooLarge(size),
+                            });
+                        }
+                        walk.issues.too_large.push((path, size));
                     }
+                    _ => walk.files.push(path),
+                }
+            }
+        } else if entry.file_type().is_dir() {
+            walk.dirs.push(entry.path().to_string_lossy().to_string());
+        }
+    }
+    walk.issues.cycles.extend(cycles);
+    walk.issues.skipped.extend(pruned);
+    if let Some(guard) = guard {
+        walk.skipped_dirs.extend(guard.skipped);
+    }
+    if let Some(ignore) = ignore {
+        walk.ignore_files.extend(ignore.files);
+    }
+}
+
+/// The place of a file in the order of a walk (see `walk_inputs_with`): the position of
+/// its input, then its position among the entries of each directory down to it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WalkKey(Vec<u32>);
+
+impl WalkKey {
+    fn child(&self, pos: usize) -> Self {
+        let mut key = self.0.clone();
+        key.push(pos as u32);
+        Self(key)
+    }
+}
+
+/// What the parallel walk met at one place in the walk order.
+enum Found {
+    File(String),
+    /// A file named in the inputs, which is not an entry of the walk.
+    Named(String),
+    Dir(String),
+    /// Another entry, e.g. a symlink that is not followed.
+    Other,
+    TooLarge(String, u64),
+    Error(String, Option<Skipped>),
+    /// A directory already walked.
+    Cycle(String),
+    /// An entry left out as hidden, excluded, or ignored, with `Config::list_skipped`.
+    Pruned(Skipped),
+}
+
+/// Walk the directories of `roots` and take the files of `named`, each with the position
+/// of its input, reading directories in parallel, and add what is found to `walk` in the
+/// order `walk_into` would. `strip_dot` drops the `./` of files found below `.`.
+fn walk_parallel(
+    cfg: &Config,
+    roots: &[(u32, PathBuf)],
+    named: &[(u32, String)],
+    strip_dot: bool,
+    on_file: Option<&OnFile>,
+    walk: &mut Walk,
+) {
+    let walkers: Vec<ParallelWalk> = roots
+        .iter()
+        .map(|(pos, root)| ParallelWalk::new(cfg, root, strip_dot, on_file, WalkKey(vec![*pos])))
+        .collect();
+    let mut found: Vec<(WalkKey, Found)> = Vec::new();
+    rayon::scope(|scope| {
+        for walker in &walkers {
+            walker.start(scope);
+        }
+        for (pos, file) in named {
+            let key = WalkKey(vec![*pos]);
+            if let Some(on_file) = on_file {
+                let key = key.clone();
+                scope.spawn(move |_| on_file(key, file));
+            }
+            found.push((key, Found::Named(file.clone())));
+        }
+    });
+    for walker in walkers {
+        found.extend(walker.found.into_inner().unwrap_or_default());
+        walk.ignore_files
+            .extend(walker.ignore_files.into_inner().unwrap_or_default());
+    }
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // As in `walk_into`, the cycles and pruned entries of each input follow the rest
+    let mut input = None;
+    let mut cycles = Vec::new();
+    let mut pruned = Vec::new();
+    for (key, found) in found {
+        if input != Some(key.0[0]) {
+            walk.issues.cycles.append(&mut cycles);
+            walk.issues.skipped.append(&mut pruned);
+            input = Some(key.0[0]);
+        }
+        if !matches!(found, Found::Named(_) | Found::Cycle(_) | Found::Pruned(_)) {
+            walk.entries += 1;
+        }
+        match found {
+            Found::File(path) | Found::Named(path) => walk.files.push(path),
+            Found::Dir(path) => walk.dirs.push(path),
+            Found::Other => {}
+            Found::TooLarge(path, size) => {
+                if cfg.list_skipped {
+                    walk.issues.skipped.push(Skipped {
+                        path: path.clone(),
+                        reason: SkipReason::TooLarge(size),
+                    });
+                }
+                walk.issues.too_large.push((path, size));
+            }
+            Found::Error(message, skipped) => {
+                walk.issues.errors += 1;
+                if walk.issues.examples.len() < WALK_ERROR_EXAMPLES {
+                    walk.issues.examples.push(message);
+                }
+                walk.issues.skipped.extend(skipped);
+            }
+            Found::Cycle(path) => cycles.push(path),
+            Found::Pruned(skipped) => pruned.push(skipped),
+        }
+    }
+    walk.issues.cycles.append(&mut cycles);
+    walk.issues.skipped.append(&mut pruned);
+}
+
+/// The walk of one directory by `walk_parallel`: one rayon task per directory below it,
+/// recording what it finds with its `WalkKey`.
+struct ParallelWalk<'a> {
+    cfg: &'a Config,
+    root: PathBuf,
+    key: WalkKey,
+    strip_dot: bool,
+    on_file: Option<&'a OnFile<'a>>,
+    ignore: Option<IgnoreFilter>,
+    globs: Option<GlobFilter>,
+    types: Option<TypeFilter>,
+    excluded_dirs: Option<DirFilter>,
+    visited: Mutex<HashSet<(u64, u64)>>,
+    found: Mutex<Vec<(WalkKey, Found)>>,
+    ignore_files: Mutex<Vec<String>>,
+}
+
+impl<'a> ParallelWalk<'a> {
+    fn new(
+        cfg: &'a Config,
+        root: &Path,
+        strip_dot: bool,
+        on_file: Option<&'a OnFile<'a>>,
+        key: WalkKey,
+    ) -> Self {
+        Self {
+            cfg,
+            root: root.to_path_buf(),
+            key,
+            strip_dot,
+            on_file,
+            ignore: IgnoreFilter::new(root, cfg),
+            globs: GlobFilter::new(&cfg.globs),
+            // Unknown type names are rejected before walking (see `search::select_files`)
+            types: TypeFilter::new(cfg).unwrap_or(None),
+            excluded_dirs: DirFilter::new(&cfg.exclude_dirs),
+            visited: Mutex::new(HashSet::new()),
+            found: Mutex::new(Vec::new()),
+            ignore_files: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn record(&self, key: WalkKey, found: Found) {
+        self.found.lock().unwrap().push((key, found));
+    }
+
+    /// Record the walked directory itself and start walking it.
+    fn start<'s>(&'s self, scope: &rayon::Scope<'s>) {
+        // The walked directory itself is never ignored, and is read even if it is a symlink
+        if let Some(id) = path_dir_id(&self.root, true) {
+            self.visited.lock().unwrap().insert(id);
+        }
+        let path = self.root.to_string_lossy().to_string();
+        self.record(self.key.clone(), Found::Dir(path));
+        if self.cfg.max_depth == Some(0) {
+            return;
+        }
+        let (chain, files) = match &self.ignore {
+            Some(ignore) => ignore.root_chain(),
+            None => Default::default(),
+        };
+        self.ignore_files.lock().unwrap().extend(files);
+        scope.spawn(move |scope| {
+            self.walk_dir(scope, self.root.clone(), self.key.clone(), 0, chain)
+        });
+    }
+
+    /// Walk the entries of `dir`, found at `key` and `depth` below the walked directory,
+    /// `chain` being the ignore rules for them.
+    fn walk_dir<'s>(
+        &'s self,
+        scope: &rayon::Scope<'s>,
+        dir: PathBuf,
+        key: WalkKey,
+        depth: usize,
+        chain: RuleChain,
+    ) {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => return self.record_error(key.child(0), &dir, &err),
+        };
+        let depth = depth + 1;
+        for (pos, entry) in entries.enumerate() {
+            // Position 0 is that of an error opening the directory
+            let key = key.child(pos + 1);
+            let (entry, file_type) = match entry.and_then(|e| Ok((e.file_type()?, e))) {
+                Ok((file_type, entry)) => (entry, file_type),
+                Err(err) => {
+                    self.record_error(key, &dir, &err);
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let is_dir = file_type.is_dir();
+            if is_dir
+                && let Some(id) = path_dir_id(&path, false)
+                && !self.visited.lock().unwrap().insert(id)
+            {
+                self.record(key, Found::Cycle(path.to_string_lossy().to_string()));
+                continue;
+            }
+            if let Some(reason) = self.prune_reason(&chain, &path, is_dir) {
+                if self.cfg.list_skipped {
+                    let path = path.to_string_lossy().to_string();
+                    self.record(key, Found::Pruned(Skipped { path, reason }));
+                }
+                continue;
+            }
+            if file_type.is_file() {
+                self.file_found(scope, key, &path, &entry);
+            } else if is_dir {
+                self.record(key.clone(), Found::Dir(path.to_string_lossy().to_string()));
+                if self.cfg.max_depth.is_none_or(|max| depth < max) {
+                    let chain = chain.clone();
+                    scope.spawn(move |scope| {
+                        let chain = match &self.ignore {
+                            Some(ignore) => {
+                                let (chain, files) = ignore.enter(&chain, &path);
+                                self.ignore_files.lock().unwrap().extend(files);
+                                chain
+                            }
+                            None => chain,
+                        };
+                        self.walk_dir(scope, path, key, depth, chain)
+                    });
                 }
             } else {
-                files.push(inp.clone());
+                self.record(key, Found::Other);
             }
         }
+    }
+
+    /// Why the entry at `path` is left out of the walk, as in `walk_into`.
+    fn prune_reason(&self, chain: &RuleChain, path: &Path, is_dir: bool) -> Option<SkipReason> {
+        let hidden = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        if !self.cfg.hidden && hidden {
+            Some(SkipReason::Hidden)
+        } else if is_dir
+            && self
+                .excluded_dirs
+                .as_ref()
+                .is_some_and(|d| d.excludes(path.strip_prefix(&self.root).unwrap_or(path)))
+        {
+            Some(SkipReason::Excluded)
+        } else if self
+            .ignore
+            .as_ref()
+            .is_some_and(|i| i.is_ignored_in(chain, path, is_dir))
+        {
+            Some(SkipReason::Ignored)
+        } else {
+            None
+        }
+    }
+
+    /// Record the file at `path` if the globs, file types, and size limit admit it, and
+    /// hand it to `on_file`.
+    fn file_found<'s>(
+        &'s self,
+        scope: &rayon::Scope<'s>,
+        key: WalkKey,
+        path: &Path,
+        entry: &std::fs::DirEntry,
+    ) {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        if !(self.globs.as_ref().is_none_or(|g| g.admits(rel))
+            && self.types.as_ref().is_none_or(|t| t.admits(rel)))
+        {
+            return;
+        }
+        let mut path = path.to_string_lossy().to_string();
+        if self.strip_dot
+            && let Some(rest) = path.strip_prefix("./")
+        {
+            path = rest.to_string();
+        }
+        let size = self
+            .cfg
+            .max_filesize
+            .and_then(|_| entry.metadata().ok())
+            .map(|meta| meta.len());
+        if let (Some(size), Some(max)) = (size, self.cfg.max_filesize)
+            && size > max
+        {
+            return self.record(key, Found::TooLarge(path, size));
+        }
+        if let Some(on_file) = self.on_file {
+            let key = key.clone();
+            let path = path.clone();
+            scope.spawn(move |_| on_file(key, &path));
+        }
+        self.record(key, Found::File(path));
+    }
+
+    fn record_error(&self, key: WalkKey, path: &Path, err: &std::io::Error) {
+        // Worded like the errors of `walkdir`, which `walk_into` reports
+        let message = format!("IO error for operation on {}: {}", path.display(), err);
+        let skipped = self.cfg.list_skipped.then(|| Skipped {
+            path: path.to_string_lossy().to_string(),
+            reason: SkipReason::Unreadable(error_reason(err)),
+        });
+        self.record(key, Found::Error(message, skipped));
+    }
+}
+
+/// Device and inode of the directory at `path`, following a symlink if `follow` is set.
+#[cfg(unix)]
+fn path_dir_id(path: &Path, follow: bool) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = if follow {
+        std::fs::metadata(path)
     } else {
-        files.extend(inputs.iter().cloned());
+        std::fs::symlink_metadata(path)
     }
+    .ok()?;
+    meta.is_dir().then(|| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn path_dir_id(_path: &Path, _follow: bool) -> Option<(u64, u64)> {
+    None
+}
+
+/// Whether `entry` is a dotfile or dot-directory.
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
 
-    files
+/// Device and inode of the directory at `entry`, which identify it however it is reached.
+#[cfg(unix)]
+fn dir_id(entry: &DirEntry) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = entry.metadata().ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(_entry: &DirEntry) -> Option<(u64, u64)> {
+    None
+}
+
+/// Sampling state of `Config::skip_binary_dirs` for one walk.
+#[derive(Default)]
+struct BinaryDirGuard {
+    /// Files sampled and binary files among them, per directory below the root.
+    samples: HashMap<PathBuf, (usize, usize)>,
+    skipped_paths: HashSet<PathBuf>,
+    skipped: Vec<SkippedDir>,
+    /// Whether files are sampled decompressed (`Config::search_zip`).
+    decompress: bool,
+}
+
+impl BinaryDirGuard {
+    /// Whether to walk `entry`, sampling it if it is a file.
+    fn admit(&mut self, root: &Path, entry: &DirEntry) -> bool {
+        let path = entry.path();
+        // The directories containing the entry, outermost first; the root never counts
+        let mut dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|d| *d != root && d.starts_with(root))
+            .collect();
+        dirs.reverse();
+        if dirs.iter().any(|d| self.skipped_paths.contains(*d)) {
+            return false;
+        }
+        if !entry.file_type().is_file() {
+            return true;
+        }
+
+        let binary = is_binary_path(&path.to_string_lossy(), self.decompress);
+        for dir in dirs {
+            let (sampled, binaries) = self.samples.entry(dir.to_path_buf()).or_default();
+            *sampled += 1;
+            *binaries += usize::from(binary);
+            if *sampled == BINARY_DIR_SAMPLE && *binaries * 10 > *sampled * 9 {
+                self.skipped.push(SkippedDir {
+                    path: dir.to_string_lossy().to_string(),
+                    sampled: *sampled,
+                    binary: *binaries,
+                });
+                self.skipped_paths.insert(dir.to_path_buf());
+                // Directories inside it are skipped along with it
+                break;
+            }
+        }
+        true
+    }
+}
+
+/// The git directory of the repository containing `start`, if any.
+///
+/// Walks up from `start` looking for `.git`: a directory, or a file with a
+/// `gitdir: PATH` line as used by worktrees and submodules.
+pub fn find_git_dir(start: &Path) -> Option<PathBuf> {
+    let start = start.canonicalize().ok()?;
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let text = std::fs::read_to_string(&dot_git).ok()?;
+            let target = text.lines().find_map(|l| l.strip_prefix("gitdir:"))?.trim();
+            return Some(dir.join(target));
+        }
+    }
+    None
+}
+
+/// The user's global excludes file: `core.excludesFile` from the git configuration,
+/// or git's default `$XDG_CONFIG_HOME/git/ignore` (`~/.config/git/ignore`).
+///
+/// `git_dir` is the repository's git directory (see `find_git_dir`), whose `config`
+/// takes precedence over the global ones. The file may not exist.
+pub fn global_excludes_file(git_dir: Option<&Path>) -> Option<PathBuf> {
+    global_excludes_file_with(git_dir, |name| std::env::var(name).ok())
+}
+
+/// Like `global_excludes_file`, with environment variables (`HOME`, `XDG_CONFIG_HOME`)
+/// read through `env`.
+pub fn global_excludes_file_with<F>(git_dir: Option<&Path>, env: F) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let home = env("HOME").filter(|h| !h.is_empty()).map(PathBuf::from);
+    let xdg_config = config_home(&env);
+
+    // Later files take precedence, as in git
+    let mut configs: Vec<PathBuf> = Vec::new();
+    configs.extend(xdg_config.as_ref().map(|x| x.join("git/config")));
+    configs.extend(home.as_ref().map(|h| h.join(".gitconfig")));
+    configs.extend(git_dir.map(|g| g.join("config")));
+    let configured = configs
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|text| git_config_value(&text, "core", "excludesfile"))
+        .next_back();
+
+    match configured {
+        Some(value) => Some(match (value.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(value),
+        }),
+        None => xdg_config.map(|x| x.join("git/ignore")),
+    }
+}
+
+/// The user's rgrep ignore file, `$XDG_CONFIG_HOME/rgrep/ignore` (`~/.config/rgrep/ignore`).
+/// The file may not exist.
+pub fn user_ignore_file() -> Option<PathBuf> {
+    config_home(&|name: &str| std::env::var(name).ok()).map(|c| c.join("rgrep/ignore"))
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config` when it is unset, with environment variables read
+/// through `env`.
+fn config_home<F>(env: &F) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    env("XDG_CONFIG_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|h| PathBuf::from(h).join(".config"))
+        })
+}
+
+/// The last value of `key` in `[section]` of the git config file `text`.
+///
+/// Section and key names are case-insensitive; subsections, includes, and escapes
+/// other than surrounding quotes are not supported.
+fn git_config_value(text: &str, section: &str, key: &str) -> Option<String> {
+    let mut in_section = false;
+    let mut value = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.split(']').next().unwrap_or("").trim();
+            in_section = name.eq_ignore_ascii_case(section);
+            continue;
+        }
+        let Some((k, v)) = line.split_once('=') else {
+            continue;
+        };
+        if in_section && k.trim().eq_ignore_ascii_case(key) {
+            let v = v.trim();
+            let v = v
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(v);
+            value = Some(v.to_string());
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_git_config_value() {
+        let text =
+            "[user]\n\texcludesfile = no\n[Core]\n  # comment\n\tExcludesFile = \"~/ignore a\"\n";
+        assert_eq!(
+            git_config_value(text, "core", "excludesfile").as_deref(),
+            Some("~/ignore a")
+        );
+        assert_eq!(git_config_value(text, "core", "editor"), None);
+    }
+
+    #[test]
+    fn test_global_excludes_file_precedence() {
+        let td = tempfile::tempdir().unwrap();
+        let home = td.path().join("home");
+        let repo = td.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("sub")).unwrap();
+        fs::create_dir_all(&home).unwrap();
+        let home_str = home.to_string_lossy().to_string();
+        let env = |name: &str| (name == "HOME").then(|| home_str.clone());
+
+        // Default location when nothing is configured
+        assert_eq!(
+            global_excludes_file_with(None, env),
+            Some(home.join(".config/git/ignore"))
+        );
+
+        fs::write(
+            home.join(".gitconfig"),
+            "[core]\nexcludesFile = ~/.gitignore_global\n",
+        )
+        .unwrap();
+        assert_eq!(
+            global_excludes_file_with(None, env),
+            Some(home.join(".gitignore_global"))
+        );
+
+        // The repository's config overrides the global one
+        let git_dir = find_git_dir(&repo.join("sub")).unwrap();
+        assert_eq!(git_dir, repo.canonicalize().unwrap().join(".git"));
+        fs::write(
+            git_dir.join("config"),
+            "[core]\n\texcludesfile = /etc/ignore\n",
+        )
+        .unwrap();
+        assert_eq!(
+            global_excludes_file_with(Some(&git_dir), env),
+            Some(PathBuf::from("/etc/ignore"))
+        );
+    }
 }
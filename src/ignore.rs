@@ -0,0 +1,584 @@
+//! Ignore rules for recursive searches.
+//!
+//! Inside a git repository, walking a directory skips what git would ignore: the `.git`
+//! directory and every path matched by the repository's ignore files. rgrep's own ignore
+//! files apply inside and outside repositories: a `.rgrepignore` in any directory, the
+//! user's ignore file (`fs_utils::user_ignore_file`), and the files given with
+//! `--ignore-file` (`Config::ignore_files`). From lowest to highest precedence, the
+//! files are:
+//!
+//! 1. the user's ignore file and the `--ignore-file` files, in that order, whose
+//!    patterns are relative to the walked directory;
+//! 2. git's global excludes file (`core.excludesFile`, see
+//!    `fs_utils::global_excludes_file`) and `$GIT_DIR/info/exclude`;
+//! 3. the `.gitignore` and then the `.rgrepignore` of each directory, from the
+//!    repository root (or, outside a repository, the walked directory) down to the
+//!    directory holding the path.
+//!
+//! Within a file the last matching pattern decides, and `!pattern` re-includes a path; as
+//! in git, nothing inside an ignored directory can be re-included, since the walk never
+//! enters it. With `Config::no_ignore` only the `--ignore-file` files apply, and with
+//! `Config::no_global_ignore` the files of step 2 are left out.
+//!
+//! Patterns follow gitignore(5): `*`, `?`, and `[...]` do not match `/`, `**` matches
+//! across directories, a trailing `/` matches only directories, and a pattern containing
+//! a `/` elsewhere is relative to the directory of its ignore file instead of matching
+//! at any depth.
+//!
+//! `GlobFilter` narrows the searched files with `--include` and `--exclude` globs in the
+//! same syntax (see `Config::globs`), `TypeFilter` with the globs of named file types
+//! (see `Config::types`), and `DirFilter` prunes the directories matching `--exclude-dir`
+//! globs (see `Config::exclude_dirs`).
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::{Config, FileGlob};
+use crate::fs_utils::{file_types, find_git_dir, global_excludes_file, user_ignore_file};
+
+/// Name of the per-directory rgrep ignore file.
+pub const RGREPIGNORE: &str = ".rgrepignore";
+
+/// The patterns of one ignore file.
+#[derive(Debug)]
+pub struct Gitignore {
+    /// Directory the patterns are relative to.
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Gitignore {
+    /// Parse the patterns in `text`, relative to the directory `base`. Invalid patterns
+    /// are skipped.
+    pub fn parse(base: &Path, text: &str) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            rules: text.lines().filter_map(parse_rule).collect(),
+        }
+    }
+
+    /// Read the ignore file at `path`; `None` when it does not exist or cannot be read.
+    pub fn from_file(base: &Path, path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        Some(Self::parse(base, &text))
+    }
+
+    /// `Some(true)` when the last pattern matching `path` ignores it, `Some(false)` when
+    /// it re-includes it (`!pattern`), and `None` when no pattern matches or `path` is not
+    /// below `base`.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let rel = path.strip_prefix(&self.base).ok()?;
+        let rel = rel.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && r.regex.is_match(&rel))
+            .map(|r| !r.negated)
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    if line.starts_with('#') {
+        return None;
+    }
+    // Trailing spaces are dropped unless escaped
+    let mut line = line.trim_end_matches('\r');
+    while line.ends_with(' ') && !line.ends_with("\\ ") {
+        line = &line[..line.len() - 1];
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+    if line.is_empty() {
+        return None;
+    }
+    let regex = Regex::new(&glob_to_regex(line, anchored)).ok()?;
+    Some(Rule {
+        regex,
+        negated,
+        dir_only,
+    })
+}
+
+/// A regex matching the paths (relative to the ignore file, `/`-separated) that the
+/// gitignore `glob` matches; an unanchored glob matches at any depth.
+fn glob_to_regex(glob: &str, anchored: bool) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let starts_part = i == 0 || chars[i - 1] == '/';
+                match chars.get(i + 2) {
+                    // `**` at the end matches everything below
+                    None if starts_part => {
+                        re.push_str(".*");
+                        i += 2;
+                    }
+                    // `**/` matches any number of directories, including none
+                    Some('/') if starts_part => {
+                        re.push_str("(?:.*/)?");
+                        i += 3;
+                    }
+                    _ => {
+                        re.push_str("[^/]*");
+                        i += 2;
+                    }
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match class_end(&chars, i) {
+                Some(end) => {
+                    re.push('[');
+                    let mut j = i + 1;
+                    if matches!(chars[j], '!' | '^') {
+                        re.push('^');
+                        j += 1;
+                    }
+                    for &c in &chars[j..end] {
+                        if matches!(c, '\\' | '[' | ']' | '&' | '~' | '^') {
+                            re.push('\\');
+                        }
+                        re.push(c);
+                    }
+                    re.push(']');
+                    i = end + 1;
+                    continue;
+                }
+                None => re.push_str(r"\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                re.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re.push('$');
+    re
+}
+
+/// Index of the `]` closing the character class opened at `start`; a `]` right after
+/// the opening (or its negation) is part of the class.
+fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut j = start + 1;
+    if matches!(chars.get(j), Some('!' | '^')) {
+        j += 1;
+    }
+    if chars.get(j) == Some(&']') {
+        j += 1;
+    }
+    (j..chars.len()).find(|&k| chars[k] == ']')
+}
+
+/// The `--include` and `--exclude` globs of a search (`Config::globs`).
+///
+/// A glob without a `/` matches file names at any depth, one with a `/` matches paths
+/// relative to the walked directory. The last glob matching a file decides whether it is
+/// searched; a file no glob matches is searched unless there is an `--include` glob.
+pub struct GlobFilter {
+    /// Each glob and whether it includes the files it matches.
+    rules: Vec<(Regex, bool)>,
+    has_include: bool,
+}
+
+impl GlobFilter {
+    /// The filter for `globs`; `None` when there are none. Invalid globs match nothing.
+    pub fn new(globs: &[FileGlob]) -> Option<Self> {
+        if globs.is_empty() {
+            return None;
+        }
+        let rules = globs
+            .iter()
+            .filter_map(|glob| {
+                let (pattern, include) = match glob {
+                    FileGlob::Include(p) => (p, true),
+                    FileGlob::Exclude(p) => (p, false),
+                };
+                glob_regex(pattern).ok().map(|re| (re, include))
+            })
+            .collect();
+        Some(Self {
+            rules,
+            has_include: globs.iter().any(|g| matches!(g, FileGlob::Include(_))),
+        })
+    }
+
+    /// Whether the file at `path`, relative to the walked directory, is searched.
+    pub fn admits(&self, path: &Path) -> bool {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|(re, _)| re.is_match(&path))
+            .map_or(!self.has_include, |(_, include)| *include)
+    }
+}
+
+/// The `--exclude-dir` globs of a search (`Config::exclude_dirs`).
+///
+/// A glob without a `/` matches directory names at any depth, one with a `/` matches
+/// paths relative to the walked directory. Excluded directories are pruned from the
+/// walk, so nothing below them is read.
+pub struct DirFilter {
+    globs: Vec<Regex>,
+}
+
+impl DirFilter {
+    /// The filter for `globs`; `None` when there are none. Invalid globs match nothing.
+    pub fn new(globs: &[String]) -> Option<Self> {
+        if globs.is_empty() {
+            return None;
+        }
+        let globs = globs.iter().filter_map(|g| glob_regex(g).ok()).collect();
+        Some(Self { globs })
+    }
+
+    /// Whether the directory at `path`, relative to the walked directory, is left out.
+    pub fn excludes(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.globs.iter().any(|re| re.is_match(&path))
+    }
+}
+
+/// The file types of a search (`Config::types` and `Config::types_not`).
+///
+/// A file is searched when it matches a glob of one of the selected types (or none are
+/// selected) and no glob of an excluded type.
+pub struct TypeFilter {
+    selected: Vec<Regex>,
+    excluded: Vec<Regex>,
+}
+
+impl TypeFilter {
+    /// The filter for the types of `cfg`; `None` when there are none, and an error naming
+    /// an unknown type.
+    pub fn new(cfg: &Config) -> Result<Option<Self>, String> {
+        if cfg.types.is_empty() && cfg.types_not.is_empty() {
+            return Ok(None);
+        }
+        let known = file_types(cfg);
+        let regexes = |names: &[String]| -> Result<Vec<Regex>, String> {
+            let mut regexes = Vec::new();
+            for name in names {
+                let globs = known
+                    .get(name)
+                    .ok_or_else(|| format!("unknown file type '{}' (see --type-list)", name))?;
+                regexes.extend(globs.iter().filter_map(|g| glob_regex(g).ok()));
+            }
+            Ok(regexes)
+        };
+        Ok(Some(Self {
+            selected: regexes(&cfg.types)?,
+            excluded: regexes(&cfg.types_not)?,
+        }))
+    }
+
+    /// Whether the file at `path` is searched.
+    pub fn admits(&self, path: &Path) -> bool {
+        let path = path.strip_prefix(".").unwrap_or(path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        (self.selected.is_empty() || self.selected.iter().any(|re| re.is_match(&path)))
+            && !self.excluded.iter().any(|re| re.is_match(&path))
+    }
+}
+
+/// The regex of an `--include` or `--exclude` glob; errors describe invalid globs.
+pub fn glob_regex(glob: &str) -> Result<Regex, String> {
+    let anchored = glob.contains('/');
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    Regex::new(&glob_to_regex(glob, anchored))
+        .map_err(|e| format!("invalid glob '{}': {}", glob, e))
+}
+
+/// Decides which entries of one directory walk are ignored (see the module docs).
+pub struct IgnoreFilter {
+    /// The walked directory as given, and its canonical path.
+    root: PathBuf,
+    canonical_root: PathBuf,
+    /// Working tree root of the repository, if the walk is inside one.
+    worktree: Option<PathBuf>,
+    /// Whether the `.gitignore` and `.rgrepignore` files of the walked directories apply.
+    per_directory: bool,
+    /// Ignore files that apply to the whole walk, in increasing precedence.
+    global_rules: Vec<Gitignore>,
+    /// The directories from the top of the walk down to the one holding the last path
+    /// checked, outermost first, with their ignore files in increasing precedence. The
+    /// walk is depth-first, so each directory is pushed once, and deep trees cost no more
+    /// per entry than the length of the chain.
+    dir_rules: Vec<(PathBuf, Vec<Gitignore>)>,
+    /// Every ignore file read.
+    pub files: Vec<String>,
+}
+
+impl IgnoreFilter {
+    /// A filter for walking `root` with the ignore settings of `cfg`; `None` when nothing
+    /// can be ignored (`Config::no_ignore` without `Config::ignore_files`).
+    pub fn new(root: &Path, cfg: &Config) -> Option<Self> {
+        if cfg.no_ignore && cfg.ignore_files.is_empty() {
+            return None;
+        }
+        let canonical_root = root.canonicalize().ok()?;
+        let worktree = if cfg.no_ignore {
+            None
+        } else {
+            canonical_root
+                .ancestors()
+                .find(|d| d.join(".git").exists())
+                .map(Path::to_path_buf)
+        };
+        let mut filter = Self {
+            root: root.to_path_buf(),
+            canonical_root: canonical_root.clone(),
+            worktree: worktree.clone(),
+            per_directory: !cfg.no_ignore,
+            global_rules: Vec::new(),
+            dir_rules: Vec::new(),
+            files: Vec::new(),
+        };
+
+        let user_file = user_ignore_file().filter(|_| !cfg.no_ignore);
+        let walk_files: Vec<PathBuf> = user_file
+            .into_iter()
+            .chain(cfg.ignore_files.iter().map(PathBuf::from))
+            .collect();
+        for path in walk_files {
+            filter.add_global(&canonical_root, &path);
+        }
+        if let Some(worktree) = worktree.as_ref().filter(|_| !cfg.no_global_ignore) {
+            let git_dir = find_git_dir(worktree);
+            let repo_files = [
+                global_excludes_file(git_dir.as_deref()),
+                git_dir.map(|g| g.join("info/exclude")),
+            ];
+            for path in repo_files.into_iter().flatten() {
+                filter.add_global(worktree, &path);
+            }
+        }
+        Some(filter)
+    }
+
+    /// Apply the ignore file at `path`, if it can be read, to the whole walk.
+    fn add_global(&mut self, base: &Path, path: &Path) {
+        if let Some(rules) = Gitignore::from_file(base, path) {
+            self.files.push(path.to_string_lossy().to_string());
+            self.global_rules.push(rules);
+        }
+    }
+
+    /// The ignore files of `dir`: its `.gitignore` (only in a repository) and its
+    /// `.rgrepignore`, with the paths of those read.
+    fn read_dir_rules(&self, dir: &Path) -> (Vec<Gitignore>, Vec<String>) {
+        let names = [".gitignore", RGREPIGNORE];
+        let names = if self.worktree.is_some() {
+            &names[..]
+        } else {
+            &names[1..]
+        };
+        let mut rules = Vec::new();
+        let mut files = Vec::new();
+        for name in names {
+            let file = dir.join(name);
+            if let Some(parsed) = Gitignore::from_file(dir, &file) {
+                files.push(file.to_string_lossy().to_string());
+                rules.push(parsed);
+            }
+        }
+        (rules, files)
+    }
+
+    /// `path`, below the walked directory as given, below its canonical path instead.
+    fn canonical(&self, path: &Path) -> PathBuf {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        self.canonical_root.join(rel)
+    }
+
+    /// What the ignore files of the whole walk decide for the canonical `path`.
+    fn global_match(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let mut ignored = None;
+        for rules in &self.global_rules {
+            ignored = rules.matched(path, is_dir).or(ignored);
+        }
+        ignored
+    }
+
+    /// Whether `path` is a repository's `.git` directory, which is always skipped.
+    fn is_git_dir(&self, path: &Path) -> bool {
+        self.worktree.is_some() && path.file_name().is_some_and(|n| n == ".git")
+    }
+
+    /// Whether the walk should skip `path`, an entry below the walked directory.
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if self.is_git_dir(path) {
+            return true;
+        }
+        let path = self.canonical(path);
+        let mut ignored = self.global_match(&path, is_dir);
+        if !self.per_directory {
+            return ignored.unwrap_or(false);
+        }
+        // Keep the directories whose ignore files apply: leave the ones the walk is done
+        // with, and enter those between the last one kept and the path
+        let Some(parent) = path.parent() else {
+            return ignored.unwrap_or(false);
+        };
+        while self
+            .dir_rules
+            .last()
+            .is_some_and(|(dir, _)| !parent.starts_with(dir))
+        {
+            self.dir_rules.pop();
+        }
+        let top = self.worktree.as_ref().unwrap_or(&self.canonical_root);
+        let innermost = self.dir_rules.last().map(|(dir, _)| dir.as_path());
+        let mut entered: Vec<PathBuf> = parent
+            .ancestors()
+            .take_while(|d| d.starts_with(top) && Some(*d) != innermost)
+            .map(Path::to_path_buf)
+            .collect();
+        entered.reverse();
+        for dir in entered {
+            let (rules, files) = self.read_dir_rules(&dir);
+            self.files.extend(files);
+            self.dir_rules.push((dir, rules));
+        }
+
+        for (_, rules) in &self.dir_rules {
+            for rules in rules {
+                ignored = rules.matched(&path, is_dir).or(ignored);
+            }
+        }
+        ignored.unwrap_or(false)
+    }
+
+    /// The rules for the entries of the walked directory, for walks that check entries
+    /// with `is_ignored_in` instead of `is_ignored`: the ignore files of the directories
+    /// from the top of the walk down to the walked one. Also returns the paths of the
+    /// ignore files read.
+    pub fn root_chain(&self) -> (RuleChain, Vec<String>) {
+        let mut chain = RuleChain::default();
+        let mut files = Vec::new();
+        if !self.per_directory {
+            return (chain, files);
+        }
+        let top = self.worktree.as_ref().unwrap_or(&self.canonical_root);
+        let mut dirs: Vec<&Path> = self
+            .canonical_root
+            .ancestors()
+            .take_while(|d| d.starts_with(top))
+            .collect();
+        dirs.reverse();
+        for dir in dirs {
+            let (rules, read) = self.read_dir_rules(dir);
+            files.extend(read);
+            chain.0.push(Arc::new(rules));
+        }
+        (chain, files)
+    }
+
+    /// The rules for the entries of `dir`, a directory below the walked one: `chain`, the
+    /// rules of the directory holding it, followed by its own ignore files. Also returns
+    /// the paths of the ignore files read.
+    pub fn enter(&self, chain: &RuleChain, dir: &Path) -> (RuleChain, Vec<String>) {
+        if !self.per_directory {
+            return (chain.clone(), Vec::new());
+        }
+        let (rules, files) = self.read_dir_rules(&self.canonical(dir));
+        let mut chain = chain.clone();
+        chain.0.push(Arc::new(rules));
+        (chain, files)
+    }
+
+    /// Like `is_ignored`, with `chain` the rules of the directory holding `path` (see
+    /// `root_chain` and `enter`). Nothing is read or remembered, so the entries of
+    /// different directories can be checked from different threads.
+    pub fn is_ignored_in(&self, chain: &RuleChain, path: &Path, is_dir: bool) -> bool {
+        if self.is_git_dir(path) {
+            return true;
+        }
+        let path = self.canonical(path);
+        let mut ignored = self.global_match(&path, is_dir);
+        for rules in chain.0.iter().flat_map(|rules| rules.iter()) {
+            ignored = rules.matched(&path, is_dir).or(ignored);
+        }
+        ignored.unwrap_or(false)
+    }
+}
+
+/// The per-directory ignore files that apply to the entries of one directory: those of
+/// each directory from the top of the walk down to it, outermost first. Subdirectories
+/// share the rules of the directories above them.
+#[derive(Debug, Clone, Default)]
+pub struct RuleChain(Vec<Arc<Vec<Gitignore>>>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(patterns: &str, path: &str, is_dir: bool) -> Option<bool> {
+        Gitignore::parse(Path::new("/repo"), patterns)
+            .matched(&Path::new("/repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn test_gitignore_patterns() {
+        assert_eq!(ignored("*.log", "a/b/x.log", false), Some(true));
+        assert_eq!(
+            ignored("*.log\n!keep.log", "a/keep.log", false),
+            Some(false)
+        );
+        assert_eq!(ignored("target/", "target", true), Some(true));
+        assert_eq!(ignored("target/", "target", false), None);
+        assert_eq!(ignored("/build", "build", true), Some(true));
+        assert_eq!(ignored("/build", "src/build", true), None);
+        assert_eq!(ignored("doc/*.md", "doc/a.md", false), Some(true));
+        assert_eq!(ignored("doc/*.md", "doc/sub/a.md", false), None);
+        assert_eq!(ignored("a/**/b", "a/x/y/b", false), Some(true));
+        assert_eq!(ignored("a/**/b", "a/b", false), Some(true));
+        assert_eq!(ignored("**/cache", "x/cache", true), Some(true));
+        assert_eq!(ignored("logs/**", "logs/2024/a", false), Some(true));
+        assert_eq!(ignored("x[0-9]?", "x1a", false), Some(true));
+        assert_eq!(ignored("x[!0-9]", "x1", false), None);
+        assert_eq!(ignored("\\#notes\n# comment", "#notes", false), Some(true));
+        assert_eq!(ignored("*.log", "/elsewhere/x.log", false), None);
+    }
+
+    #[test]
+    fn test_last_matching_glob_wins() {
+        let filter = GlobFilter::new(&[
+            FileGlob::Include("*.js".into()),
+            FileGlob::Exclude("*.min.js".into()),
+            FileGlob::Include("vendor/keep.min.js".into()),
+        ])
+        .unwrap();
+        assert!(filter.admits(Path::new("src/app.js")));
+        assert!(!filter.admits(Path::new("src/app.min.js")));
+        assert!(filter.admits(Path::new("./vendor/keep.min.js")));
+        assert!(!filter.admits(Path::new("src/keep.min.js")));
+        assert!(!filter.admits(Path::new("README.md")));
+
+        let exclude_only = GlobFilter::new(&[FileGlob::Exclude("*.lock".into())]).unwrap();
+        assert!(exclude_only.admits(Path::new("README.md")));
+        assert!(!exclude_only.admits(Path::new("a/Cargo.lock")));
+    }
+}
@@ -2,9 +2,23 @@
 //!
 //! These functions provide thin wrappers around standard I/O to read line-oriented
 //! input and to open either a named file or stdin via the conventional "-" path.
+//!
+//! Inputs that are streams rather than regular files, such as the `/dev/fd/63` of a
+//! process substitution (`rgrep -e x <(generate)`), a named pipe, or a device, can only be
+//! read once: `open_searchable` sniffs them for binary content while keeping the sniffed
+//! bytes for the search.
+//!
+//! With `Config::search_zip` (`-z`), inputs are opened through `decompressed`, which
+//! decompresses gzip, bzip2, xz, and zstd data, each with its cargo feature, and passes
+//! anything else through unchanged.
+
+use std::borrow::Borrow;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
 
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
+/// Number of bytes at the start of an input checked for NUL bytes to tell whether it is
+/// binary.
+pub const SNIFF_LEN: usize = 4096;
 
 /// Read all lines from a reader into a `Vec<String>` (without trailing newlines).
 pub fn read_to_lines<R: Read>(reader: R) -> io::Result<Vec<String>> {
@@ -12,12 +26,409 @@ pub fn read_to_lines<R: Read>(reader: R) -> io::Result<Vec<String>> {
     buf.lines().collect()
 }
 
+/// Read all lines from a reader together with the byte offset at which each line starts.
+///
+/// Line terminators (`\n` or `\r\n`) are stripped from the returned text but counted in
+/// the offsets, so offsets refer to positions in the original input. `Lines` holds the
+/// same without allocating every line separately.
+pub fn read_to_lines_with_offsets<R: Read>(reader: R) -> io::Result<Vec<(usize, String)>> {
+    let lines = Lines::read(reader)?;
+    Ok(lines
+        .iter()
+        .map(|(offset, text)| (offset, text.to_string()))
+        .collect())
+}
+
+/// Smallest step by which `Lines::read` grows its buffer.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// The lines of one input, kept in a single buffer with a table of where each line is,
+/// instead of one allocation per line.
+///
+/// Line terminators (`\n` or `\r\n`) are left out of the texts but counted in the
+/// offsets, so offsets refer to positions in the original input.
+#[derive(Debug, Default)]
+pub struct Lines {
+    /// The whole input.
+    text: String,
+    /// For every line, the range of its text in `text`; the start is also the line's
+    /// byte offset in the input.
+    spans: Vec<(usize, usize)>,
+}
+
+impl Lines {
+    /// Read the whole of `reader`, which must be UTF-8.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        // Grown by half its size at a time, rather than doubled as `resize` alone would,
+        // so that reading a large input takes little more memory than the input
+        let mut buf: Vec<u8> = Vec::new();
+        let mut len = 0;
+        loop {
+            if len == buf.len() {
+                buf.reserve_exact((len / 2).max(READ_CHUNK));
+                buf.resize(buf.capacity(), 0);
+            }
+            match reader.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        buf.truncate(len);
+        // Small inputs would otherwise hold a whole `READ_CHUNK` each while searched
+        // (see benches/lines.rs)
+        buf.shrink_to_fit();
+        let text = String::from_utf8(buf).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+        let mut spans = Vec::with_capacity(text.bytes().filter(|&b| b == b'\n').count() + 1);
+        let mut start = 0;
+        while start < text.len() {
+            let (end, next) = match text[start..].find('\n') {
+                Some(at) => (start + at, start + at + 1),
+                None => (text.len(), text.len()),
+            };
+            let end = if next > end && text[start..end].ends_with('\r') {
+                end - 1
+            } else {
+                end
+            };
+            spans.push((start, end));
+            start = next;
+        }
+        Ok(Self { text, spans })
+    }
+
+    /// Number of lines.
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Text of line `idx` (0-based), without its terminator.
+    pub fn text(&self, idx: usize) -> &str {
+        let (start, end) = self.spans[idx];
+        &self.text[start..end]
+    }
+
+    /// Byte offset in the input at which line `idx` (0-based) starts.
+    pub fn offset(&self, idx: usize) -> usize {
+        self.spans[idx].0
+    }
+
+    /// The byte offset and text of every line, in input order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> + '_ {
+        self.spans
+            .iter()
+            .map(|&(start, end)| (start, &self.text[start..end]))
+    }
+
+    /// The lines one at a time, as a `LineSource`.
+    pub fn cursor(&self) -> LinesCursor<&Self> {
+        LinesCursor {
+            lines: self,
+            next: 0,
+        }
+    }
+
+    /// Like `cursor`, but owning the lines.
+    pub fn into_cursor(self) -> LinesCursor<Self> {
+        LinesCursor {
+            lines: self,
+            next: 0,
+        }
+    }
+
+    /// The texts of all lines, for matching them together.
+    pub fn texts(&self) -> Vec<&str> {
+        self.iter().map(|(_, text)| text).collect()
+    }
+}
+
+/// A source of lines read one at a time, with their byte offsets: an input being read
+/// (`LineReader`) or one read whole (`Lines::cursor`).
+pub trait LineSource {
+    /// The byte offset and text (without its terminator) of the next line, or `None`
+    /// after the last one.
+    fn next_line(&mut self) -> io::Result<Option<(usize, &str)>>;
+}
+
+/// The lines of `Lines`, borrowed or owned, in input order (see `Lines::cursor`).
+pub struct LinesCursor<L> {
+    lines: L,
+    next: usize,
+}
+
+impl<L: Borrow<Lines>> LineSource for LinesCursor<L> {
+    fn next_line(&mut self) -> io::Result<Option<(usize, &str)>> {
+        let lines = self.lines.borrow();
+        if self.next == lines.len() {
+            return Ok(None);
+        }
+        self.next += 1;
+        let idx = self.next - 1;
+        Ok(Some((lines.offset(idx), lines.text(idx))))
+    }
+}
+
+/// Reads an input line by line, keeping only the current line in memory, so inputs of
+/// any size can be searched. Lines are split and stripped as in `Lines`.
+pub struct LineReader<R> {
+    reader: BufReader<R>,
+    /// The current line, with its terminator.
+    line: String,
+    /// Byte offset of the next line.
+    offset: usize,
+}
+
+impl<R: Read> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::with_capacity(READ_CHUNK, reader),
+            line: String::new(),
+            offset: 0,
+        }
+    }
+}
+
+impl<R: Read> LineSource for LineReader<R> {
+    fn next_line(&mut self) -> io::Result<Option<(usize, &str)>> {
+        self.line.clear();
+        let read = self.reader.read_line(&mut self.line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        self.offset += read;
+        let mut end = self.line.len();
+        if self.line.ends_with('\n') {
+            end -= 1;
+            if self.line[..end].ends_with('\r') {
+                end -= 1;
+            }
+        }
+        Ok(Some((offset, &self.line[..end])))
+    }
+}
+
+/// A reader that adds the number of bytes read through it to `count`.
+pub struct CountingReader<'a, R> {
+    inner: R,
+    count: &'a mut u64,
+}
+
+impl<'a, R: Read> CountingReader<'a, R> {
+    pub fn new(inner: R, count: &'a mut u64) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        *self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Whether `err` means the reader of our output went away, e.g. `rgrep ... | head -1`
+/// after `head` exited.
+pub fn is_broken_pipe(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::BrokenPipe
+}
+
+/// The message of `err` without the ` (os error N)` suffix, e.g. `No such file or
+/// directory`, as grep prints it.
+pub fn error_reason(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.find(" (os error ") {
+        Some(at) => message[..at].to_string(),
+        None => message,
+    }
+}
+
+/// Whether `path` names a stream that cannot be read twice or seeked, like a pipe,
+/// FIFO, socket, or device, rather than a regular file or a directory. Stdin (`-`) is
+/// not counted: it is never sniffed.
+pub fn is_stream(path: &str) -> bool {
+    path != "-" && fs::metadata(path).is_ok_and(|meta| !meta.is_file() && !meta.is_dir())
+}
+
+/// Read up to `SNIFF_LEN` bytes from `reader` and tell whether they contain a NUL byte.
+/// The returned reader yields the whole input, the sniffed bytes included, so nothing is
+/// lost on inputs that cannot be reopened.
+pub fn sniff_binary<'a, R: Read + 'a>(mut reader: R) -> io::Result<(bool, Box<dyn Read + 'a>)> {
+    let mut prefix = Vec::with_capacity(SNIFF_LEN);
+    // A pipe may return fewer bytes than requested before its end
+    (&mut reader)
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut prefix)?;
+    let binary = prefix.contains(&0);
+    Ok((binary, Box::new(Cursor::new(prefix).chain(reader))))
+}
+
+/// Open the input at `path` for searching, decompressing it with `decompress` (see
+/// `open_input`). Streams (see `is_stream`) are sniffed on the way: a binary stream is
+/// skipped like a binary file, by reading nothing from it.
+pub fn open_searchable(path: &str, decompress: bool) -> io::Result<Box<dyn Read>> {
+    let reader = open_input(Some(path), decompress)?;
+    if !is_stream(path) {
+        return Ok(reader);
+    }
+    match sniff_binary(reader)? {
+        (true, _) => Ok(Box::new(io::empty())),
+        (false, reader) => Ok(reader),
+    }
+}
+
+/// Read the whole input at `path` as text, with invalid UTF-8 replaced by U+FFFD, and
+/// tell whether it contains a NUL byte, i.e. is binary. With `decompress`, this is the
+/// decompressed text (see `open_input`).
+pub fn read_lossy(path: &str, decompress: bool) -> io::Result<(String, bool)> {
+    let mut bytes = Vec::new();
+    open_input(Some(path), decompress)?.read_to_end(&mut bytes)?;
+    let binary = bytes.contains(&0);
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    };
+    Ok((text, binary))
+}
+
 /// Open a file path for reading or return stdin when `path` is None or Some("-").
 ///
-/// The returned reader is boxed to allow dynamic dispatch across different sources.
-pub fn open_input(path: Option<&str>) -> io::Result<Box<dyn Read>> {
-    match path {
-        Some(p) if p != "-" => Ok(Box::new(File::open(p)?)),
-        _ => Ok(Box::new(io::stdin())),
+/// With `decompress` (`Config::search_zip`), compressed input is decompressed as it is
+/// read (see `decompressed`). The returned reader is boxed to allow dynamic dispatch
+/// across different sources.
+pub fn open_input(path: Option<&str>, decompress: bool) -> io::Result<Box<dyn Read>> {
+    let reader: Box<dyn Read> = match path {
+        Some(p) if p != "-" => Box::new(File::open(p)?),
+        _ => Box::new(io::stdin()),
+    };
+    if decompress {
+        decompressed(reader)
+    } else {
+        Ok(reader)
+    }
+}
+
+/// A compression format `decompressed` recognizes by the first bytes of its data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// The longest header checked by `detect`.
+    const MAGIC_LEN: usize = 6;
+
+    /// The format of data starting with `magic`, if it is compressed.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [b'B', b'Z', b'h', b'1'..=b'9', ..] => Some(Self::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Self::Xz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// `reader`, decompressed if it starts like data of a compression format rgrep is built
+/// with (the `gzip`, `bzip2`, `xz`, and `zstd` features), or else as it is. Inputs are
+/// told apart by their content rather than their name, so `app.log.1.gz` and a
+/// compressed stream on stdin are decompressed alike, and so are renamed files.
+/// Concatenated members or frames are read as one input, as `zcat` does.
+pub fn decompressed<'a, R: Read + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let mut magic = Vec::with_capacity(Compression::MAGIC_LEN);
+    (&mut reader)
+        .take(Compression::MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let format = Compression::detect(&magic);
+    let whole = Cursor::new(magic).chain(reader);
+    Ok(match format {
+        #[cfg(feature = "gzip")]
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(whole)),
+        #[cfg(feature = "bzip2")]
+        Some(Compression::Bzip2) => Box::new(bzip2::read::MultiBzDecoder::new(whole)),
+        #[cfg(feature = "xz")]
+        Some(Compression::Xz) => Box::new(liblzma::read::XzDecoder::new_multi_decoder(whole)),
+        #[cfg(feature = "zstd")]
+        Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::new(whole)?),
+        // Formats rgrep is built without are searched as they are, i.e. as binary
+        _ => Box::new(whole),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_index_one_buffer() {
+        let lines = Lines::read("a\r\n\nlast\r".as_bytes()).unwrap();
+        let all: Vec<(usize, &str)> = lines.iter().collect();
+        // `\r` is only stripped as part of `\r\n`
+        assert_eq!(all, vec![(0, "a"), (3, ""), (4, "last\r")]);
+        assert_eq!(lines.text(2), "last\r");
+        assert_eq!(lines.offset(1), 3);
+        assert!(Lines::read(&b""[..]).unwrap().is_empty());
+
+        let err = Lines::read(&b"ok\n\xff\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_line_reader_splits_like_lines() {
+        let input = "a\r\n\nmid\rdle\nlast\r";
+        let lines = Lines::read(input.as_bytes()).unwrap();
+        let mut cursor = lines.cursor();
+        let mut reader = LineReader::new(input.as_bytes());
+        while let Some((offset, text)) = cursor.next_line().unwrap() {
+            assert_eq!(reader.next_line().unwrap(), Some((offset, text)));
+        }
+        assert_eq!(reader.next_line().unwrap(), None);
+
+        let mut reader = LineReader::new(&b"ok\n\xff\n"[..]);
+        assert_eq!(reader.next_line().unwrap(), Some((0, "ok")));
+        let err = reader.next_line().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_compression_detected_by_magic_bytes() {
+        assert_eq!(
+            Compression::detect(b"\x1f\x8b\x08"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::detect(b"BZh91AY"), Some(Compression::Bzip2));
+        assert_eq!(Compression::detect(b"BZh0"), None);
+        assert_eq!(
+            Compression::detect(b"\xfd7zXZ\x00\x00"),
+            Some(Compression::Xz)
+        );
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"\x1f"), None);
+        assert_eq!(Compression::detect(b"plain text"), None);
+
+        let mut plain = String::new();
+        decompressed(&b"BZ"[..])
+            .unwrap()
+            .read_to_string(&mut plain)
+            .unwrap();
+        assert_eq!(plain, "BZ");
     }
 }
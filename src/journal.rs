@@ -0,0 +1,473 @@
+//! Log entries from systemd-journald and syslog (`--journald`, `--syslog ADDR`).
+//!
+//! Instead of files, entries are read from the journal, via `journalctl --output=export`,
+//! or received by a syslog listener on a UDP or unix datagram socket (RFC 5424 and the
+//! older RFC 3164 format). The message of each entry is matched like a line of a file and
+//! printed after a `IDENTIFIER[PID]:` prefix; with `--json`, match events carry the other
+//! fields of the entry in a `fields` object, using journald's field names:
+//!
+//! ```text
+//! sshd[812]:Failed password for root from 10.0.0.7
+//! {"type":"match","path":null,"line_number":1,"byte_offset":0,"text":"Failed password ...","submatches":[...],"fields":{"PRIORITY":"4","SYSLOG_IDENTIFIER":"sshd",...}}
+//! ```
+//!
+//! `line_number` counts the entries received. The journal is read up to its end, or with
+//! `Config::follow` until stdout's reader goes away; a syslog listener always runs until
+//! then.
+//!
+//! Needs the `journald` feature.
+
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::UdpSocket;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use crate::config::{Config, ExitStatus, JsonSchema, LogSource};
+use crate::output::json;
+use crate::search::{Match, Searcher, emit_line};
+
+/// Largest syslog message received in one datagram.
+const MAX_DATAGRAM: usize = 64 * 1024;
+
+/// A log entry: its message and its other fields, in the order they were read.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Entry {
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl Entry {
+    /// An entry from journal fields, taking the message from `MESSAGE`.
+    pub fn from_fields(mut fields: Vec<(String, String)>) -> Self {
+        let message = match fields.iter().position(|(name, _)| name == "MESSAGE") {
+            Some(i) => fields.remove(i).1,
+            None => String::new(),
+        };
+        Self { message, fields }
+    }
+
+    /// The value of the field `name`, if the entry has it.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `IDENTIFIER[PID]` the entry is printed after, like journalctl's short output.
+    pub fn label(&self) -> Option<String> {
+        let ident = self
+            .field("SYSLOG_IDENTIFIER")
+            .or_else(|| self.field("_COMM"))?;
+        match self.field("SYSLOG_PID").or_else(|| self.field("_PID")) {
+            Some(pid) => Some(format!("{}[{}]", ident, pid)),
+            None => Some(ident.to_string()),
+        }
+    }
+}
+
+/// Read the next entry of journalctl's export format from `reader`, or `None` at its end.
+///
+/// Entries are separated by an empty line. A field is either `NAME=value` on one line or,
+/// for values with newlines or binary data, `NAME` followed by the value's length as a
+/// little-endian u64, the value, and a newline.
+pub fn read_export_entry<R: BufRead>(reader: &mut R) -> io::Result<Option<Entry>> {
+    let mut fields = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok((!fields.is_empty()).then(|| Entry::from_fields(fields)));
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        if line.is_empty() {
+            if fields.is_empty() {
+                continue;
+            }
+            return Ok(Some(Entry::from_fields(fields)));
+        }
+        match line.iter().position(|&b| b == b'=') {
+            Some(eq) => fields.push((
+                String::from_utf8_lossy(&line[..eq]).into_owned(),
+                String::from_utf8_lossy(&line[eq + 1..]).into_owned(),
+            )),
+            None => {
+                let mut len = [0u8; 8];
+                reader.read_exact(&mut len)?;
+                let mut value = vec![0u8; u64::from_le_bytes(len) as usize];
+                reader.read_exact(&mut value)?;
+                let mut newline = [0u8; 1];
+                reader.read_exact(&mut newline)?;
+                fields.push((
+                    String::from_utf8_lossy(&line).into_owned(),
+                    String::from_utf8_lossy(&value).into_owned(),
+                ));
+            }
+        }
+    }
+}
+
+/// Parse a syslog message in the RFC 5424 or RFC 3164 format into an entry with
+/// journald's field names (`PRIORITY`, `SYSLOG_FACILITY`, `SYSLOG_TIMESTAMP`,
+/// `_HOSTNAME`, `SYSLOG_IDENTIFIER`, `SYSLOG_PID`, `SYSLOG_MSGID`). Text that does not
+/// follow either format becomes the message.
+pub fn parse_syslog(datagram: &str) -> Entry {
+    let mut rest = datagram.trim_end_matches(['\n', '\r', '\0']);
+    let mut fields = Vec::new();
+    let mut add = |name: &str, value: &str| {
+        if !value.is_empty() && value != "-" {
+            fields.push((name.to_string(), value.to_string()));
+        }
+    };
+
+    if let Some((pri, after)) = rest
+        .strip_prefix('<')
+        .and_then(|r| r.split_once('>'))
+        .and_then(|(pri, after)| Some((pri.parse::<u32>().ok()?, after)))
+    {
+        add("PRIORITY", &(pri % 8).to_string());
+        add("SYSLOG_FACILITY", &(pri / 8).to_string());
+        rest = after;
+    }
+
+    if let Some(after) = rest.strip_prefix("1 ") {
+        // RFC 5424: TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+        let mut parts = after.splitn(6, ' ');
+        let mut next = || parts.next().unwrap_or("");
+        add("SYSLOG_TIMESTAMP", next());
+        add("_HOSTNAME", next());
+        add("SYSLOG_IDENTIFIER", next());
+        add("SYSLOG_PID", next());
+        add("SYSLOG_MSGID", next());
+        rest = skip_structured_data(next());
+        // A UTF-8 byte order mark may precede the message
+        rest = rest.strip_prefix('\u{feff}').unwrap_or(rest);
+    } else {
+        // RFC 3164: "Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG"; messages sent to a local
+        // socket have no hostname
+        if let Some(stamp) = rest.get(..15).filter(|s| is_bsd_timestamp(s)) {
+            add("SYSLOG_TIMESTAMP", stamp);
+            rest = rest[15..].trim_start_matches(' ');
+            let first = rest.split(' ').next().unwrap_or("");
+            if !first.ends_with(':') && !first.contains('[') {
+                add("_HOSTNAME", first);
+                rest = rest[first.len()..].trim_start_matches(' ');
+            }
+        }
+        if let Some((tag, msg)) = rest.split_once(": ").filter(|(tag, _)| is_tag(tag)) {
+            match tag.strip_suffix(']').and_then(|t| t.split_once('[')) {
+                Some((ident, pid)) => {
+                    add("SYSLOG_IDENTIFIER", ident);
+                    add("SYSLOG_PID", pid);
+                }
+                None => add("SYSLOG_IDENTIFIER", tag),
+            }
+            rest = msg;
+        }
+    }
+
+    Entry {
+        message: rest.to_string(),
+        fields,
+    }
+}
+
+/// Whether `s` is an RFC 3164 timestamp such as `Oct 16 09:05:01` or `Oct  6 09:05:01`.
+fn is_bsd_timestamp(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 15
+        && b[..3].iter().all(u8::is_ascii_alphabetic)
+        && b[3] == b' '
+        && (b[4] == b' ' || b[4].is_ascii_digit())
+        && b[5].is_ascii_digit()
+        && b[6] == b' '
+        && b[9] == b':'
+        && b[12] == b':'
+}
+
+/// Whether `s` looks like an RFC 3164 tag (`sshd` or `sshd[812]`) rather than text.
+fn is_tag(s: &str) -> bool {
+    !s.is_empty() && s.len() <= 48 && !s.contains(' ')
+}
+
+/// The RFC 5424 message after the structured data (`-` or `[id k="v"]...`) at the start
+/// of `s`.
+fn skip_structured_data(s: &str) -> &str {
+    if let Some(rest) = s.strip_prefix('-') {
+        return rest.strip_prefix(' ').unwrap_or(rest);
+    }
+    let mut in_element = false;
+    let mut in_value = false;
+    let mut escaped = false;
+    for (i, ch) in s.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' if in_element => in_value = !in_value,
+            '[' if !in_element => in_element = true,
+            ']' if in_element && !in_value => in_element = false,
+            _ if !in_element => {
+                let rest = &s[i..];
+                return rest.strip_prefix(' ').unwrap_or(rest);
+            }
+            _ => {}
+        }
+    }
+    ""
+}
+
+/// Where entries are read from.
+enum Entries {
+    Journal {
+        child: Child,
+        stdout: BufReader<ChildStdout>,
+    },
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix {
+        socket: UnixDatagram,
+        path: String,
+    },
+}
+
+impl Entries {
+    fn open(cfg: &Config, source: &LogSource, matches: &[String]) -> Result<Self, String> {
+        match source {
+            LogSource::Journald => {
+                let mut cmd = Command::new("journalctl");
+                cmd.args(["--output=export", "--no-pager"]);
+                if cfg.follow {
+                    // Like following a file, start with the entries written from now on
+                    cmd.args(["--follow", "--lines=0"]);
+                }
+                let mut child = cmd
+                    .args(matches)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| format!("cannot run journalctl: {}", e))?;
+                let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+                Ok(Entries::Journal { child, stdout })
+            }
+            LogSource::Syslog(addr) => match addr.strip_prefix("unix:") {
+                #[cfg(unix)]
+                Some(path) => {
+                    // A socket left behind by an earlier listener that was killed
+                    if fs::symlink_metadata(path).is_ok_and(|md| md.file_type().is_socket()) {
+                        let _ = fs::remove_file(path);
+                    }
+                    UnixDatagram::bind(path)
+                        .map(|socket| Entries::Unix {
+                            socket,
+                            path: path.to_string(),
+                        })
+                        .map_err(|e| format!("{}: cannot listen: {}", path, e))
+                }
+                #[cfg(not(unix))]
+                Some(_) => Err("unix sockets are not supported on this platform".into()),
+                None => UdpSocket::bind(addr)
+                    .map(Entries::Udp)
+                    .map_err(|e| format!("{}: cannot listen: {}", addr, e)),
+            },
+        }
+    }
+
+    /// The next entry, or `None` when the journal has no more.
+    fn next_entry(&mut self) -> io::Result<Option<Entry>> {
+        let mut buf = [0u8; MAX_DATAGRAM];
+        let len = match self {
+            Entries::Journal { stdout, .. } => return read_export_entry(stdout),
+            Entries::Udp(socket) => socket.recv(&mut buf)?,
+            #[cfg(unix)]
+            Entries::Unix { socket, .. } => socket.recv(&mut buf)?,
+        };
+        Ok(Some(parse_syslog(&String::from_utf8_lossy(&buf[..len]))))
+    }
+
+    /// Check how journalctl ended once all entries were read.
+    fn finish(&mut self) -> Result<(), String> {
+        if let Entries::Journal { child, .. } = self {
+            let status = child.wait().map_err(|e| e.to_string())?;
+            if !status.success() {
+                return Err(format!("journalctl failed ({})", status));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Entries {
+    fn drop(&mut self) {
+        match self {
+            Entries::Journal { child, .. } => {
+                // Stops journalctl when the search ends early (e.g. -q or a closed pipe)
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            Entries::Udp(_) => {}
+            #[cfg(unix)]
+            Entries::Unix { path, .. } => {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Search the entries of `source` and write the matching ones to `out` as they are read.
+/// For the journal, `matches` are journalctl matches such as
+/// `_SYSTEMD_UNIT=sshd.service`.
+///
+/// Returns once the journal was read to its end (with `Config::quiet`, at the first
+/// match), or with the error of a failed write, e.g. when stdout's reader went away.
+/// With `Config::count`, the number of matching entries is written at the end.
+pub fn search_log_source<W: Write>(
+    cfg: &Config,
+    source: &LogSource,
+    matches: &[String],
+    out: &mut W,
+) -> Result<ExitStatus, String> {
+    if let LogSource::Syslog(_) = source
+        && !matches.is_empty()
+    {
+        return Err("--syslog takes no FILE arguments".into());
+    }
+    let searcher = Searcher::new(cfg)?;
+    let mut entries = Entries::open(cfg, source, matches)?;
+    let count = search_with(cfg, &searcher, || entries.next_entry(), out)?;
+    entries.finish()?;
+    Ok(status(count))
+}
+
+/// Search the entries returned by `next` until it returns `None`, writing the matching
+/// ones to `out`, and return the number of matching entries.
+pub fn search_entries<W: Write>(
+    cfg: &Config,
+    next: impl FnMut() -> io::Result<Option<Entry>>,
+    out: &mut W,
+) -> Result<usize, String> {
+    search_with(cfg, &Searcher::new(cfg)?, next, out)
+}
+
+fn search_with<W: Write>(
+    cfg: &Config,
+    searcher: &Searcher,
+    mut next: impl FnMut() -> io::Result<Option<Entry>>,
+    out: &mut W,
+) -> Result<usize, String> {
+    let matcher = &searcher.matcher;
+    if matcher.is_windowed() {
+        return Err("NEAR (~N~) is not supported for log entries".into());
+    }
+    if matcher.is_multiline() {
+        return Err("multiline mode (-U) is not supported for log entries".into());
+    }
+    let mut count = 0;
+    let mut received = 0;
+    let mut buf = String::new();
+    while let Some(entry) = next().map_err(|e| e.to_string())? {
+        received += 1;
+        let is_match = matcher.is_match(&entry.message);
+        if is_match == cfg.invert {
+            continue;
+        }
+        count += 1;
+        if cfg.quiet {
+            break;
+        }
+        if cfg.count {
+            continue;
+        }
+        let (spans, terms) = if is_match {
+            (
+                matcher.find_spans(&entry.message),
+                matcher.matched_terms(&entry.message),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let label = entry.label();
+        let m = Match {
+            line_number: received,
+            byte_offset: 0,
+            line: entry.message,
+            spans,
+            terms,
+            context_hash: None,
+        };
+        buf.clear();
+        // ripgrep's schema has no field for the entry's other fields
+        if cfg.json && cfg.json_schema == JsonSchema::Rgrep {
+            json::append_line_with_fields(&mut buf, None, &m, true, &entry.fields);
+        } else {
+            emit_line(cfg, searcher, &mut buf, None, label.as_deref(), &m, true);
+        }
+        out.write_all(buf.as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(|e| e.to_string())?;
+    }
+    if cfg.count {
+        writeln!(out, "{}", count).map_err(|e| e.to_string())?;
+    }
+    Ok(count)
+}
+
+fn status(count: usize) -> ExitStatus {
+    if count > 0 {
+        ExitStatus::MatchFound
+    } else {
+        ExitStatus::NoMatch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_syslog_formats() {
+        let entry = parse_syslog("<34>Oct 16 09:05:01 web1 sshd[812]: Failed password\n");
+        assert_eq!(entry.message, "Failed password");
+        assert_eq!(entry.field("PRIORITY"), Some("2"));
+        assert_eq!(entry.field("SYSLOG_FACILITY"), Some("4"));
+        assert_eq!(entry.field("_HOSTNAME"), Some("web1"));
+        assert_eq!(entry.label().as_deref(), Some("sshd[812]"));
+
+        // Sent to a local socket, without a hostname
+        let entry = parse_syslog("<13>Oct  6 09:05:02 cron: job done");
+        assert_eq!(entry.field("_HOSTNAME"), None);
+        assert_eq!(entry.label().as_deref(), Some("cron"));
+
+        let entry = parse_syslog(
+            r#"<165>1 2026-10-16T09:05:03Z host app - ID7 [ex@1 a="b\]" c="]"][x@2] denied"#,
+        );
+        assert_eq!(entry.message, "denied");
+        assert_eq!(entry.field("SYSLOG_PID"), None);
+        assert_eq!(entry.field("SYSLOG_MSGID"), Some("ID7"));
+        assert_eq!(entry.label().as_deref(), Some("app"));
+
+        assert_eq!(parse_syslog("no header: here").message, "no header: here");
+    }
+
+    #[test]
+    fn test_read_export_entries() {
+        let mut data = b"__CURSOR=s=1\nMESSAGE=first\n_PID=7\n_COMM=app\n\nMESSAGE\n".to_vec();
+        data.extend_from_slice(&11u64.to_le_bytes());
+        data.extend_from_slice(b"two\nlines\n!\nPRIORITY=3\n");
+        let mut reader = io::Cursor::new(data);
+
+        let first = read_export_entry(&mut reader).unwrap().unwrap();
+        assert_eq!(first.message, "first");
+        assert_eq!(first.label().as_deref(), Some("app[7]"));
+        let second = read_export_entry(&mut reader).unwrap().unwrap();
+        assert_eq!(second.message, "two\nlines\n!");
+        assert_eq!(second.fields, vec![("PRIORITY".into(), "3".into())]);
+        assert_eq!(read_export_entry(&mut reader).unwrap(), None);
+    }
+}
@@ -4,7 +4,27 @@
 //! also be embedded as a library. The public API lets you:
 //! - Configure search behavior via Config (patterns, context, case, etc.).
 //! - Run searches over readers or files (run_on_reader, run).
-//! - Follow a single growing file for new matches (follow).
+//! - Iterate lazily over the matching lines of a reader as structured values, with no
+//!   output formatted (search_iter).
+//! - Get the matches of every searched file, and why a file could not be searched, as
+//!   structured per-file results (run_detailed).
+//! - Write the output of a search to any writer as files are searched (run_to_writer).
+//! - Stream JSON Lines events to any writer as matches are found (run_json_lines).
+//! - Receive files, matches, context lines, and errors as structured events through a
+//!   Sink, without parsing output (run_with_sink).
+//! - List the files a search would read, after all filtering, without searching them
+//!   (list_files).
+//! - Combine results of searches run separately, e.g. over shards (RunResult::merge).
+//! - Follow a single growing file or standard input for new matches (follow), or receive
+//!   them as FollowEvent values through a FollowSink (follow_with_sink), optionally
+//!   exporting match counters for Prometheus (Config::metrics_file).
+//! - Rewrite matching files in place with a replacement template (rewrite).
+//! - Run searches and follow files as Streams of events from async code (stream::run_async,
+//!   stream::follow_async, with the `tokio` feature).
+//! - Embed searches in C, C++, or Python tooling through a C interface (ffi, with the
+//!   `ffi` feature).
+//! - Search the systemd journal or syslog messages as they arrive (journal, with the
+//!   `journald` feature).
 //!
 //! Quick example: search a string buffer
 //!
@@ -20,32 +40,91 @@
 //! Quick example: search files
 //!
 //! ```no_run
+//! # #[cfg(feature = "files")] {
 //! use rgrep::{Config, run};
 //! let mut cfg = Config::default();
 //! cfg.patterns = vec!["TODO".into()];
 //! let result = run(&cfg, &["./src".into()]).unwrap();
 //! println!("{}", result.output);
+//! # }
 //! ```
 //!
 //! See README for CLI usage examples.
+//!
+//! Searching files and directories needs the `files` feature (on by default). Embedders
+//! that only match provided data can build with `default-features = false`, which leaves
+//! `run_on_reader` and the matching core with `regex` and `memchr` as the only
+//! dependencies.
 
+pub mod aggregate;
+pub mod batch;
 pub mod boolean_parser;
+pub mod clock;
+pub mod color;
 pub mod config;
+pub mod definition;
+#[cfg(feature = "files")]
+pub mod doctor;
+pub mod env_expand;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "files")]
+mod file_list_cache;
+#[cfg(feature = "files")]
 pub mod follow;
+#[cfg(feature = "files")]
 pub mod fs_utils;
+#[cfg(feature = "files")]
+pub mod ignore;
 pub mod io_utils;
+#[cfg(feature = "journald")]
+pub mod journal;
+pub mod line_map;
+mod literal;
+pub mod matcher;
+#[cfg(feature = "files")]
+mod merge;
+#[cfg(feature = "files")]
+pub mod metrics;
 pub mod output;
+pub mod predicate;
+#[cfg(feature = "files")]
+mod preprocess;
 pub mod regex_utils;
+#[cfg(feature = "files")]
+pub mod rewrite;
+#[cfg(feature = "script")]
+pub mod script;
 pub mod search;
-
-pub use config::{Config, Context, ExitStatus, RunResult};
-pub use follow::follow;
-pub use search::{run, run_on_reader};
+#[cfg(feature = "files")]
+pub mod self_check;
+pub mod sink;
+pub mod strategy;
+#[cfg(feature = "tokio")]
+pub mod stream;
+pub mod syntax;
+
+pub use color::{ColorChoice, Colors};
+pub use config::{
+    BinaryFiles, Compat, Config, Context, Engine, ExitStatus, FileGlob, FileResult, FileTiming,
+    JsonSchema, LogSource, MatchTimeout, Matching, MergeTies, RunResult, Scheduling, SkipReason,
+    Skipped, Stats, Syntax, parse_utc_offset,
+};
+#[cfg(feature = "files")]
+pub use follow::{FollowEnd, FollowEvent, FollowSink, follow, follow_with_sink};
+pub use matcher::Matcher;
+pub use search::{Match, run_on_reader, search_iter};
+#[cfg(feature = "files")]
+pub use search::{
+    list_files, run, run_detailed, run_json_lines, run_to_writer, run_to_writer_until,
+    run_with_sink,
+};
+pub use sink::Sink;
 
 // -----------------------
 // Tests
 // -----------------------
-#[cfg(test)]
+#[cfg(all(test, feature = "files"))]
 mod tests {
     use super::*;
     use std::fs;
@@ -63,7 +142,7 @@ mod tests {
         let mut cfg = Config::default();
         cfg.patterns = vec!["hello".to_string()];
         cfg.case_insensitive = true;
-        cfg.color = false; // disable color to assert on raw content
+        cfg.color = ColorChoice::Never; // disable color to assert on raw content
         let data = "HeLLo world\nbye";
         let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
         assert_eq!(res.status, ExitStatus::MatchFound);
@@ -82,7 +161,7 @@ mod tests {
         let mut cfg = Config::default();
         cfg.patterns = vec!["matchme".to_string()];
         cfg.recursive = true;
-        cfg.color = false;
+        cfg.color = ColorChoice::Never;
 
         let inputs = vec![root.to_string_lossy().to_string()];
         let res = run(&cfg, &inputs).unwrap();
@@ -144,7 +223,8 @@ mod tests {
     fn multiple_files_count_shows_names() {
         let mut c = cfg(&["o"]);
         c.count = true;
-        c.color = false;
+        c.color = ColorChoice::Never;
+        c.table = false;
         let td = tempfile::tempdir().unwrap();
         let p1 = td.path().join("a.txt");
         let p2 = td.path().join("b.txt");
@@ -207,7 +287,7 @@ mod tests {
         let mut c = cfg(&["b"]);
         c.context.before = 1;
         c.context.after = 1;
-        c.color = false;
+        c.color = ColorChoice::Never;
         let data = "a\nb\nc\n";
         let res = run_on_reader(&c, data.as_bytes(), None).unwrap();
         let out = res.output;
@@ -217,7 +297,7 @@ mod tests {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "files"))]
 mod more_tests {
     use super::*;
     use std::fs;
@@ -250,7 +330,7 @@ mod more_tests {
     fn word_boundary_with_punctuation() {
         let mut c = cfg(&["he"]);
         c.word = true;
-        c.color = false;
+        c.color = ColorChoice::Never;
         let data = "he, she helo\n";
         let res = run_on_reader(&c, Cursor::new(data), None).unwrap();
         let lines: Vec<_> = res.output.lines().collect();
@@ -262,7 +342,7 @@ mod more_tests {
     #[test]
     fn color_highlighting_included_when_enabled() {
         let mut c = cfg(&["hello"]);
-        c.color = true; // default, but be explicit
+        c.color = ColorChoice::Always;
         let data = "say hello there\n";
         let res = run_on_reader(&c, Cursor::new(data), None).unwrap();
         // Expect ANSI escape sequences in output
@@ -304,7 +384,8 @@ mod more_tests {
     fn multi_file_count_includes_file_names() {
         let mut c = cfg(&["a"]);
         c.count = true;
-        c.color = false;
+        c.color = ColorChoice::Never;
+        c.table = false;
         let td = tempfile::tempdir().unwrap();
         let p1 = td.path().join("one.txt");
         let p2 = td.path().join("two.txt");
@@ -336,7 +417,7 @@ mod more_tests {
     fn after_context_beyond_end_is_safe() {
         let mut c = cfg(&["last"]);
         c.context.after = 3;
-        c.color = false;
+        c.color = ColorChoice::Never;
         let data = "first\nsecond\nlast\n";
         let res = run_on_reader(&c, Cursor::new(data), None).unwrap();
         let lines: Vec<_> = res.output.lines().collect();
@@ -0,0 +1,286 @@
+//! Line transformations applied before matching (`--map`).
+//!
+//! A map rewrites each line before the pattern sees it, so that e.g. log lines with
+//! embedded ANSI color codes or inconsistent casing match reliably, while the original
+//! line is still what gets printed. A map is either a built-in (`strip-ansi`,
+//! `lowercase`, `trim`, `squash-whitespace`) or a sed-style substitution `s/REGEX/REPLACEMENT/FLAGS`, whose
+//! replacement uses the `--replace` syntax (`$1`, `${name}`) and whose flags are `g`
+//! (every match instead of the first) and `i` (ignore case). Any character may stand in
+//! for `/`, and `\/` is a literal delimiter.
+//!
+//! `apply` keeps track of where each byte of the transformed line came from, so that
+//! spans found in it can be reported on the original line (`Mapped::original_span`).
+
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// Escape sequences of ANSI terminals: CSI (colors, cursor movement) and OSC (titles,
+/// hyperlinks) sequences.
+const ANSI_ESCAPE: &str = r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)";
+
+/// One transformation of `--map`.
+#[derive(Debug)]
+pub enum LineMap {
+    Lowercase,
+    /// Remove leading and trailing whitespace.
+    Trim,
+    /// Replace the first match of `regex` (every match when `global`) with `replacement`.
+    Substitute {
+        regex: Regex,
+        replacement: String,
+        global: bool,
+    },
+}
+
+impl LineMap {
+    /// Collapse every run of whitespace to a single space (`--squash-whitespace`), so
+    /// that column-aligned padding does not matter.
+    pub fn squash_whitespace() -> Self {
+        LineMap::Substitute {
+            regex: Regex::new(r"\s+").expect("valid regex"),
+            replacement: " ".to_string(),
+            global: true,
+        }
+    }
+
+    /// Parse a `--map` argument (see the module docs).
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "strip-ansi" => Ok(LineMap::Substitute {
+                regex: Regex::new(ANSI_ESCAPE).expect("valid regex"),
+                replacement: String::new(),
+                global: true,
+            }),
+            "lowercase" => Ok(LineMap::Lowercase),
+            "trim" => Ok(LineMap::Trim),
+            "squash-whitespace" => Ok(LineMap::squash_whitespace()),
+            _ => parse_substitution(spec).ok_or_else(|| {
+                format!(
+                    "invalid --map '{}': expected strip-ansi, lowercase, trim, squash-whitespace, or s/REGEX/REPLACEMENT/[gi]",
+                    spec
+                )
+            })?,
+        }
+    }
+}
+
+/// `Some` for anything shaped like `s/REGEX/REPLACEMENT/FLAGS`, with an error when the
+/// regex or the flags are invalid.
+fn parse_substitution(spec: &str) -> Option<Result<LineMap, String>> {
+    let rest = spec.strip_prefix('s')?;
+    let delim = rest.chars().next()?;
+    if delim.is_alphanumeric() || delim.is_whitespace() || delim == '\\' {
+        return None;
+    }
+    let parts = split_unescaped(&rest[delim.len_utf8()..], delim);
+    let [regex, replacement, flags] = <[String; 3]>::try_from(parts).ok()?;
+    Some((|| {
+        let mut global = false;
+        let mut ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                other => return Err(format!("invalid --map flag '{}' in '{}'", other, spec)),
+            }
+        }
+        let regex = RegexBuilder::new(&regex)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| format!("invalid regex in --map '{}': {}", spec, e))?;
+        Ok(LineMap::Substitute {
+            regex,
+            replacement,
+            global,
+        })
+    })())
+}
+
+/// Split `s` at every `delim` not preceded by a backslash; `\` before `delim` is dropped,
+/// other escapes are kept for the regex.
+fn split_unescaped(s: &str, delim: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().expect("never empty");
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delim => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delim => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+    parts
+}
+
+/// A transformed line, with the origin of each of its bytes in the original line.
+pub struct Mapped {
+    pub text: String,
+    /// `starts[i]` is where the original text of byte `i` of `text` begins; the last entry
+    /// is where text appended at the end would begin.
+    starts: Vec<usize>,
+    /// `ends[i]` is where the original text of byte `i` ends.
+    ends: Vec<usize>,
+}
+
+impl Mapped {
+    fn new() -> Self {
+        Self {
+            text: String::new(),
+            starts: Vec::new(),
+            ends: Vec::new(),
+        }
+    }
+
+    /// Append text copied unchanged from offset `at` of the original line.
+    fn copy(&mut self, s: &str, at: usize) {
+        self.text.push_str(s);
+        self.starts.extend(at..at + s.len());
+        self.ends.extend(at + 1..at + s.len() + 1);
+    }
+
+    /// Append text that replaced the original text at `replaced`.
+    fn insert(&mut self, s: &str, replaced: Range<usize>) {
+        self.text.push_str(s);
+        self.starts
+            .extend(std::iter::repeat_n(replaced.start, s.len()));
+        self.ends.extend(std::iter::repeat_n(replaced.end, s.len()));
+    }
+
+    fn finish(mut self, end: usize) -> Self {
+        self.starts.push(end);
+        self
+    }
+
+    /// The part of the original line that `span` of the transformed line came from.
+    /// Text inserted by a substitution maps to all of the text it replaced, and text
+    /// removed around the span is left out.
+    pub fn original_span(&self, span: Range<usize>) -> Range<usize> {
+        let start = self.starts[span.start];
+        if span.is_empty() {
+            start..start
+        } else {
+            start..self.ends[span.end - 1]
+        }
+    }
+}
+
+/// `line` after applying `maps` in order.
+pub fn apply(maps: &[LineMap], line: &str) -> Mapped {
+    let mut mapped = Mapped::new();
+    mapped.copy(line, 0);
+    let mut mapped = mapped.finish(line.len());
+    for map in maps {
+        let next = map_line(map, &mapped.text);
+        // Trace each byte back through the previous transformations
+        let starts = next.starts.iter().map(|&o| mapped.starts[o]).collect();
+        let ends = next
+            .ends
+            .iter()
+            .map(|&o| {
+                if o == 0 {
+                    mapped.starts[0]
+                } else {
+                    mapped.ends[o - 1]
+                }
+            })
+            .collect();
+        mapped = Mapped {
+            text: next.text,
+            starts,
+            ends,
+        };
+    }
+    mapped
+}
+
+fn map_line(map: &LineMap, line: &str) -> Mapped {
+    let mut out = Mapped::new();
+    let end = match map {
+        LineMap::Lowercase => {
+            for (at, c) in line.char_indices() {
+                if c.is_lowercase() || !c.is_alphabetic() {
+                    out.copy(&line[at..at + c.len_utf8()], at);
+                } else {
+                    out.insert(&c.to_lowercase().to_string(), at..at + c.len_utf8());
+                }
+            }
+            line.len()
+        }
+        LineMap::Trim => {
+            let trimmed = line.trim_start();
+            let start = line.len() - trimmed.len();
+            let kept = trimmed.trim_end();
+            out.copy(kept, start);
+            start + kept.len()
+        }
+        LineMap::Substitute {
+            regex,
+            replacement,
+            global,
+        } => {
+            let mut last = 0;
+            let limit = if *global { usize::MAX } else { 1 };
+            for caps in regex.captures_iter(line).take(limit) {
+                let whole = caps.get(0).expect("group 0 always matches");
+                out.copy(&line[last..whole.start()], last);
+                let mut expanded = String::new();
+                caps.expand(replacement, &mut expanded);
+                out.insert(&expanded, whole.range());
+                last = whole.end();
+            }
+            out.copy(&line[last..], last);
+            line.len()
+        }
+    };
+    out.finish(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_maps_spans_to_original() {
+        let maps = [LineMap::parse("strip-ansi").unwrap()];
+        let line = "\x1b[31mERROR\x1b[0m disk full";
+        let mapped = apply(&maps, line);
+        assert_eq!(mapped.text, "ERROR disk full");
+        assert_eq!(&line[mapped.original_span(0..5)], "ERROR");
+        assert_eq!(&line[mapped.original_span(6..10)], "disk");
+    }
+
+    #[test]
+    fn test_substitution_syntax() {
+        let maps = [
+            LineMap::parse(r"s/a\/b/X/g").unwrap(),
+            LineMap::parse("s/(\\d+)/<$1>/").unwrap(),
+        ];
+        assert_eq!(apply(&maps, "a/b a/b 1 2").text, "X X <1> 2");
+        assert!(LineMap::parse("s/x/y/q").unwrap_err().contains("flag 'q'"));
+        assert!(
+            LineMap::parse("s/(/y/")
+                .unwrap_err()
+                .contains("invalid regex")
+        );
+        assert!(
+            LineMap::parse("upper")
+                .unwrap_err()
+                .contains("expected strip-ansi")
+        );
+    }
+
+    #[test]
+    fn test_squash_whitespace_spans_cover_padding() {
+        let line = "DBG     Driver_\t\t  Transmit  message";
+        let mapped = apply(&[LineMap::squash_whitespace()], line);
+        assert_eq!(mapped.text, "DBG Driver_ Transmit message");
+        assert_eq!(&line[mapped.original_span(4..20)], "Driver_\t\t  Transmit");
+    }
+}
@@ -0,0 +1,107 @@
+//! Substring search for plain-text patterns (`Matching::Literal`), without the regex
+//! engine.
+//!
+//! Case-sensitive text is found with `memchr::memmem`. Ignoring case, the text must be
+//! ASCII: candidates are found with `memchr` on the two cases of its first byte and
+//! compared ignoring ASCII case. That agrees with the regex engine's Unicode case folding
+//! except for `k` and `s`, which also match the Kelvin sign (`K`) and the long s (`ſ`);
+//! for such text, lines that are not ASCII are left to the regex engine (see `handles`).
+
+use memchr::memmem::Finder;
+use std::ops::Range;
+
+/// A plain-text pattern, compiled for searching.
+pub(crate) struct Literal {
+    /// Finds the text as it is; unused when ignoring case.
+    finder: Finder<'static>,
+    /// The text in lowercase, when ignoring ASCII case.
+    folded: Option<Vec<u8>>,
+    /// Whether the text has letters whose case folds to non-ASCII characters.
+    folds_beyond_ascii: bool,
+}
+
+impl Literal {
+    /// Compile `text`; with `ignore_case`, `text` must be ASCII.
+    pub(crate) fn new(text: &str, ignore_case: bool) -> Self {
+        debug_assert!(!ignore_case || text.is_ascii());
+        let folded = ignore_case.then(|| text.to_ascii_lowercase().into_bytes());
+        Self {
+            finder: Finder::new(text.as_bytes()).into_owned(),
+            folds_beyond_ascii: folded
+                .as_ref()
+                .is_some_and(|t| t.iter().any(|&b| b == b'k' || b == b's')),
+            folded,
+        }
+    }
+
+    /// Whether `line` can be searched here with the same result as the regex engine.
+    pub(crate) fn handles(&self, line: &str) -> bool {
+        !self.folds_beyond_ascii || line.is_ascii()
+    }
+
+    /// Whether `line` contains the text.
+    pub(crate) fn is_match(&self, line: &str) -> bool {
+        self.find_at(line.as_bytes(), 0).is_some()
+    }
+
+    /// Byte ranges of the non-overlapping occurrences of the text in `line`, from the left.
+    pub(crate) fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        let line = line.as_bytes();
+        let mut spans = Vec::new();
+        let mut at = 0;
+        while let Some(span) = self.find_at(line, at) {
+            // The text is never empty, so this always moves on
+            at = span.end;
+            spans.push(span);
+        }
+        spans
+    }
+
+    /// The first occurrence of the text in `line` starting at or after `at`.
+    fn find_at(&self, line: &[u8], at: usize) -> Option<Range<usize>> {
+        let rest = line.get(at..)?;
+        let Some(folded) = &self.folded else {
+            let len = self.finder.needle().len();
+            return self.finder.find(rest).map(|i| at + i..at + i + len);
+        };
+        let first = folded[0];
+        let mut from = 0;
+        while let Some(i) = memchr::memchr2(first, first.to_ascii_uppercase(), &rest[from..]) {
+            let start = from + i;
+            let candidate = rest.get(start..start + folded.len())?;
+            if candidate.eq_ignore_ascii_case(folded) {
+                return Some(at + start..at + start + folded.len());
+            }
+            from = start + 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_non_overlapping_occurrences() {
+        let literal = Literal::new("aba", false);
+        assert_eq!(literal.find_iter("ababa xaba"), vec![0..3, 7..10]);
+        assert!(!literal.is_match("ABA"));
+
+        let literal = Literal::new("Error:", true);
+        assert_eq!(literal.find_iter("error: ERROR: eRRor"), vec![0..6, 7..13]);
+        assert!(literal.is_match("… fatal ERROR: disk"));
+        assert!(!literal.is_match("erro"));
+    }
+
+    #[test]
+    fn test_leaves_non_ascii_folds_to_the_regex_engine() {
+        let literal = Literal::new("disk", true);
+        assert!(literal.handles("DISK full"));
+        assert!(!literal.handles("DIS\u{212A} full"));
+        let literal = Literal::new("error", true);
+        assert!(literal.handles("ERROR \u{212A}"));
+        assert!(literal.handles("error"));
+        assert!(!Literal::new("disk", false).folds_beyond_ascii);
+    }
+}
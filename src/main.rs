@@ -1,8 +1,205 @@
-use rgrep::{ExitStatus, follow, run};
+use rgrep::aggregate::{format_stats, format_timings};
+use rgrep::doctor;
+use rgrep::fs_utils::file_types;
+use rgrep::io_utils::is_broken_pipe;
+#[cfg(feature = "journald")]
+use rgrep::journal;
+use rgrep::output::json;
+use rgrep::regex_utils::boolean_diagnostic;
+use rgrep::rewrite;
+use rgrep::self_check;
+use rgrep::strategy;
+use rgrep::{
+    Config, ExitStatus, FollowEnd, LogSource, follow, run_json_lines, run_to_writer_until,
+};
+use std::io::{self, IsTerminal, Write};
 use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 mod cli;
 
+/// Exit status when the reader of stdout went away, as for a process killed by SIGPIPE.
+const EXIT_BROKEN_PIPE: u8 = 128 + 13;
+
+/// Locked stdout that remembers whether its reader closed the pipe.
+struct Stdout {
+    inner: io::StdoutLock<'static>,
+    closed: bool,
+}
+
+impl Stdout {
+    fn new() -> Self {
+        Self {
+            inner: io::stdout().lock(),
+            closed: false,
+        }
+    }
+
+    fn track<T>(&mut self, res: io::Result<T>) -> io::Result<T> {
+        if let Err(err) = &res {
+            self.closed |= is_broken_pipe(err);
+        }
+        res
+    }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let res = self.inner.write(buf);
+        self.track(res)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let res = self.inner.flush();
+        self.track(res)
+    }
+}
+
+/// Set `closed` once the reader of stdout, a pipe, goes away, checking every 50 ms until
+/// `done` is set. Output merged by timestamp is only written at the end, so no write
+/// fails before then to tell.
+#[cfg(target_os = "linux")]
+fn watch_stdout(closed: &AtomicBool, done: &AtomicBool) {
+    use std::ffi::{c_int, c_short, c_ulong};
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+    unsafe extern "C" {
+        fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+    }
+    // Reported for the writing end of a pipe without readers, whatever the events asked
+    const POLLERR: c_short = 0x008;
+
+    while !done.load(Ordering::Relaxed) {
+        let mut stdout = PollFd {
+            fd: 1,
+            events: 0,
+            revents: 0,
+        };
+        // SAFETY: `stdout` is a single valid `pollfd` for the duration of the call
+        if unsafe { poll(&mut stdout, 1, 50) } > 0 && stdout.revents & POLLERR != 0 {
+            closed.store(true, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+/// Elsewhere, a closed pipe is only noticed when writing to it.
+#[cfg(not(target_os = "linux"))]
+fn watch_stdout(_closed: &AtomicBool, _done: &AtomicBool) {}
+
+/// Write `text` to stdout; `Err` holds the exit code when writing failed.
+fn print_all(text: &str) -> Result<(), ExitCode> {
+    let mut stdout = Stdout::new();
+    match stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        Ok(()) => Ok(()),
+        Err(_) if stdout.closed => Err(ExitCode::from(EXIT_BROKEN_PIPE)),
+        Err(err) => {
+            eprintln!("rgrep error: {}", err);
+            Err(ExitCode::from(2))
+        }
+    }
+}
+
+/// Print `err` after `label`, followed by a caret diagnostic when the pattern is a Boolean
+/// expression that failed to parse.
+fn report_error(cfg: &Config, label: &str, err: &str) {
+    eprintln!("{}: {}", label, err);
+    if let Some(diagnostic) = boolean_diagnostic(cfg) {
+        eprint!("{}", diagnostic);
+    }
+}
+
+/// The `--no-match-message` output after a run with `status`, if any.
+fn no_match_output(cfg: &Config, status: ExitStatus) -> Option<String> {
+    let message = cfg.no_match_message.as_deref()?;
+    if cfg.quiet || status != ExitStatus::NoMatch {
+        return None;
+    }
+    let mut out = String::new();
+    if cfg.json {
+        json::append_no_match(&mut out, message);
+    } else {
+        out.push_str(message);
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// Search the journal or syslog messages (`--journald`, `--syslog`), streaming matches
+/// to stdout.
+#[cfg(feature = "journald")]
+fn search_log_source(cfg: &Config, source: &LogSource, inputs: &[String]) -> ExitCode {
+    let mut stdout = Stdout::new();
+    match journal::search_log_source(cfg, source, inputs, &mut stdout) {
+        Ok(ExitStatus::MatchFound) => ExitCode::from(0),
+        Ok(ExitStatus::NoMatch) => ExitCode::from(1),
+        Err(_) if stdout.closed => ExitCode::from(EXIT_BROKEN_PIPE),
+        Err(err) => {
+            report_error(cfg, "rgrep error", &err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(not(feature = "journald"))]
+fn search_log_source(_cfg: &Config, _source: &LogSource, _inputs: &[String]) -> ExitCode {
+    eprintln!(
+        "rgrep error: --journald and --syslog require rgrep to be built with the `journald` feature"
+    );
+    ExitCode::from(2)
+}
+
+/// Rewrite files in place (`--write`), reporting the changes with `--dry-run` or as a
+/// unified diff with `--diff`, and every file that could not be rewritten.
+fn rewrite_files(cfg: &Config, inputs: &[String]) -> ExitCode {
+    let result = match rewrite::rewrite(cfg, inputs) {
+        Ok(result) => result,
+        Err(err) => {
+            report_error(cfg, "rgrep error", &err);
+            return ExitCode::from(2);
+        }
+    };
+    if cfg.diff {
+        let diffs: String = result
+            .files
+            .iter()
+            .filter_map(|f| f.diff.as_deref())
+            .collect();
+        if let Err(code) = print_all(&diffs) {
+            return code;
+        }
+    } else if cfg.dry_run {
+        let mut out = String::new();
+        for file in &result.files {
+            out.push_str(&format!(
+                "{}: would replace {} match(es) on {} line(s)\n",
+                file.path, file.replacements, file.lines
+            ));
+        }
+        if let Err(code) = print_all(&out) {
+            return code;
+        }
+    }
+    for err in &result.errors {
+        eprintln!("rgrep: cannot rewrite {}", err);
+    }
+    if !result.errors.is_empty() {
+        return ExitCode::from(2);
+    }
+    match result.status() {
+        ExitStatus::MatchFound => ExitCode::from(0),
+        ExitStatus::NoMatch => ExitCode::from(1),
+    }
+}
+
 fn main() -> ExitCode {
     let (cfg, inputs) = match cli::parse() {
         Ok(v) => v,
@@ -12,27 +209,143 @@ fn main() -> ExitCode {
         }
     };
 
-    if cfg.follow {
-        if let Err(err) = follow(&cfg, &inputs) {
-            eprintln!("rgrep follow error: {}", err);
-            return ExitCode::from(2);
+    if cfg.type_list {
+        let mut list = String::new();
+        for (name, globs) in file_types(&cfg) {
+            list.push_str(&format!("{}: {}\n", name, globs.join(", ")));
+        }
+        return match print_all(&list) {
+            Ok(()) => ExitCode::from(0),
+            Err(code) => code,
+        };
+    }
+
+    if cfg.doctor {
+        let checks = doctor::diagnose(&cfg, &inputs);
+        if let Err(code) = print_all(&doctor::format_report(&checks)) {
+            return code;
+        }
+        return ExitCode::from(if doctor::has_errors(&checks) { 2 } else { 0 });
+    }
+
+    if cfg.explain {
+        return match strategy::explain(&cfg, &inputs) {
+            Ok(report) => match print_all(&report) {
+                Ok(()) => ExitCode::from(0),
+                Err(code) => code,
+            },
+            Err(err) => {
+                report_error(&cfg, "rgrep error", &err);
+                ExitCode::from(2)
+            }
+        };
+    }
+
+    if cfg.self_check {
+        let check = match self_check::self_check(&cfg, &inputs) {
+            Ok(check) => check,
+            Err(err) => {
+                report_error(&cfg, "rgrep error", &err);
+                return ExitCode::from(2);
+            }
+        };
+        if let Err(code) = print_all(&self_check::format_report(&check)) {
+            return code;
         }
-        // follow never returns on success; but if it returns, treat as success
-        return ExitCode::from(0);
+        return ExitCode::from(if check.divergences.is_empty() { 0 } else { 2 });
+    }
+
+    if let Some(source) = &cfg.log_source {
+        return search_log_source(&cfg, source, &inputs);
+    }
+
+    if cfg.write || cfg.diff {
+        return rewrite_files(&cfg, &inputs);
+    }
+
+    if cfg.follow {
+        return match follow(&cfg, &inputs) {
+            Ok(FollowEnd::InputEnded) => ExitCode::from(0),
+            Ok(FollowEnd::Closed) => ExitCode::from(EXIT_BROKEN_PIPE),
+            Err(err) => {
+                report_error(&cfg, "rgrep follow error", &err);
+                ExitCode::from(2)
+            }
+        };
+    }
+
+    if cfg.json {
+        // Stream JSON events as they are found instead of buffering the whole run
+        // A closed pipe stops the search at the next event
+        let mut stdout = Stdout::new();
+        return match run_json_lines(&cfg, &inputs, &mut stdout) {
+            Ok(ExitStatus::MatchFound) => ExitCode::from(0),
+            Ok(ExitStatus::NoMatch) => match no_match_output(&cfg, ExitStatus::NoMatch) {
+                Some(out) => print_all(&out).err().unwrap_or(ExitCode::from(1)),
+                None => ExitCode::from(1),
+            },
+            Err(_) if stdout.closed => ExitCode::from(EXIT_BROKEN_PIPE),
+            Err(err) => {
+                report_error(&cfg, "rgrep error", &err);
+                ExitCode::from(2)
+            }
+        };
     }
 
-    match run(&cfg, &inputs) {
+    // Output is written as files are searched; a terminal sees every line at once, a
+    // pipe gets it in blocks. The search stops once the pipe's reader goes away, even
+    // while output is held back for the end.
+    let (ran, closed) = {
+        let mut stdout = Stdout::new();
+        let reader_gone = AtomicBool::new(false);
+        let ran = if io::stdout().is_terminal() {
+            run_to_writer_until(&cfg, &inputs, &mut stdout, &reader_gone)
+        } else {
+            let done = AtomicBool::new(false);
+            std::thread::scope(|scope| {
+                scope.spawn(|| watch_stdout(&reader_gone, &done));
+                let mut buffered = io::BufWriter::with_capacity(64 * 1024, &mut stdout);
+                let ran = run_to_writer_until(&cfg, &inputs, &mut buffered, &reader_gone);
+                done.store(true, Ordering::Relaxed);
+                ran
+            })
+        };
+        (ran, stdout.closed || reader_gone.load(Ordering::Relaxed))
+    };
+    match ran {
         Ok(result) => {
-            if !cfg.quiet {
-                print!("{}", result.output);
+            for warning in &result.warnings {
+                eprintln!("rgrep: warning: {}", warning);
+            }
+            for error in &result.errors {
+                eprintln!("rgrep: {}", error);
+            }
+            if let Some(out) = no_match_output(&cfg, result.status)
+                && let Err(code) = print_all(&out)
+            {
+                return code;
+            }
+            if cfg.stats
+                && let Err(code) = print_all(&format_stats(&result.stats, cfg.table))
+            {
+                return code;
+            }
+            if let Some(limit) = cfg.timings {
+                eprint!("{}", format_timings(&result.timings, limit));
+            }
+            // As with grep, -q succeeds on a match even when some input was unreadable
+            let quiet_hit = cfg.quiet && result.status == ExitStatus::MatchFound;
+            if !result.errors.is_empty() && !quiet_hit {
+                return ExitCode::from(2);
             }
             match result.status {
                 ExitStatus::MatchFound => ExitCode::from(0),
                 ExitStatus::NoMatch => ExitCode::from(1),
             }
         }
+        Err(_) if closed => ExitCode::from(EXIT_BROKEN_PIPE),
         Err(err) => {
-            eprintln!("rgrep error: {}", err);
+            report_error(&cfg, "rgrep error", &err);
             ExitCode::from(2)
         }
     }
@@ -0,0 +1,533 @@
+//! Line matching across the supported pattern modes.
+//!
+//! A `Matcher` is built once per search from the `Config`. It decides whether a line
+//! matches (a plain regex, `&`-separated AND terms, or a full Boolean expression) and
+//! reports where the matched text is, which output formats use for columns, spans,
+//! and highlighting (negated terms of a Boolean expression are never highlighted), and
+//! which terms of an expression matched. An optional `--where` predicate further filters
+//! lines by the pattern's capture groups, and `--group` reports one group of each match
+//! instead of the whole match.
+//! With `--def`, the pattern is replaced by one matching definitions of a symbol.
+//! With `--engine fancy`, the pattern is matched by fancy-regex instead, as a single regex.
+//! With `--map` or `--squash-whitespace`, every line is transformed before matching (see
+//! `line_map`), and spans are reported on the original line.
+//! With `--filter-script` (the `script` feature), a user script decides which matching
+//! lines to keep (see `keeps`).
+//! A pattern that is plain text is found by substring search instead of the regex
+//! engine, unless `--strategy regex` says otherwise (see `strategy`).
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::clock::Instant;
+use crate::config::{Config, Engine, Matching, Syntax};
+use crate::definition::{definition_pattern, kind_of, name_span};
+use crate::env_expand::resolve_patterns;
+use crate::line_map::{self, LineMap};
+use crate::literal::Literal;
+use crate::predicate::{Predicate, parse_predicate};
+#[cfg(feature = "fancy")]
+use crate::regex_utils::build_fancy_regex;
+use crate::regex_utils::{
+    BooleanMatcher, and_terms, build_and_matchers, build_regex, build_terms_regex,
+    captures_referenced, is_single_regex, merge_spans, parse_boolean_if_complex,
+};
+#[cfg(feature = "script")]
+use crate::script::FilterScript;
+use crate::strategy::{Choice, choose_matching};
+use crate::syntax::translate;
+
+enum MatchMode {
+    /// The highlight regex is the matcher itself.
+    Regex,
+    /// Lines containing this text match (`Matching::Literal`); the highlight regex
+    /// matches the same text, and decides lines the literal does not handle.
+    Literal(Box<Literal>),
+    /// Every term must match somewhere on the line.
+    And(Vec<(String, Regex)>),
+    /// A parsed Boolean expression with one regex per pattern, and the patterns that are
+    /// not negated with their regexes (for highlighting and attribution), in expression
+    /// order.
+    Boolean(BooleanMatcher, Vec<(String, Regex)>),
+    /// A fancy-regex pattern (`Engine::Fancy`), deciding matches and spans on its own.
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Regex),
+}
+
+/// Compiled matcher for a search configuration.
+pub struct Matcher {
+    highlight: Regex,
+    mode: MatchMode,
+    predicate: Option<Predicate>,
+    /// Whether the pattern is a `definition_pattern`.
+    definitions: bool,
+    /// Whether matches may span lines (`Config::multiline`; see `multiline_spans`).
+    multiline: bool,
+    /// The capture group whose text is the match (`Config::group`).
+    group: Option<Group>,
+    /// Transformations applied to each line before matching (`Config::maps` and
+    /// `Config::squash_whitespace`).
+    maps: Vec<LineMap>,
+    /// The `--filter-script` deciding which matching lines to keep.
+    #[cfg(feature = "script")]
+    script: Option<FilterScript>,
+    /// How lines are matched, and why.
+    matching: Choice<Matching>,
+}
+
+/// A capture group selected by number or by name.
+enum Group {
+    Index(usize),
+    Named(String),
+}
+
+impl Group {
+    /// Parse `spec` and check that `re` has such a group.
+    fn new(spec: &str, re: &Regex) -> Result<Self, String> {
+        match spec.parse::<usize>() {
+            Ok(i) if i >= re.captures_len() => Err(format!(
+                "--group {} but the pattern has {} capture group(s)",
+                i,
+                re.captures_len() - 1
+            )),
+            Ok(i) => Ok(Group::Index(i)),
+            Err(_) if !re.capture_names().any(|n| n == Some(spec)) => Err(format!(
+                "--group {} but the pattern has no group named '{}'",
+                spec, spec
+            )),
+            Err(_) => Ok(Group::Named(spec.to_string())),
+        }
+    }
+
+    fn get<'h>(&self, caps: &regex::Captures<'h>) -> Option<regex::Match<'h>> {
+        match self {
+            Group::Index(i) => caps.get(*i),
+            Group::Named(name) => caps.name(name),
+        }
+    }
+}
+
+impl Matcher {
+    /// Compile the patterns in `cfg`, choosing the matching mode from the expression shape.
+    pub fn new(cfg: &Config) -> Result<Self, String> {
+        if cfg.patterns.is_empty() && cfg.definition.is_none() {
+            return Err("no pattern provided".into());
+        }
+        let mut cfg = resolve_patterns(cfg)?;
+        if cfg.syntax != Syntax::Extended && cfg.definition.is_none() {
+            let pattern = translate(&cfg.patterns.join(""), cfg.syntax);
+            cfg.to_mut().patterns = vec![pattern];
+        }
+        if let Some(name) = &cfg.definition {
+            let pattern = definition_pattern(name);
+            cfg.to_mut().patterns = vec![pattern];
+        }
+        let cfg = &*cfg;
+
+        if cfg.multiline
+            && (cfg.where_expr.is_some() || cfg.filter_script.is_some() || cfg.definition.is_some())
+        {
+            return Err(
+                "multiline mode (-U) does not support --where, --filter-script, or --def".into(),
+            );
+        }
+        #[cfg(not(feature = "script"))]
+        if cfg.filter_script.is_some() {
+            return Err(
+                "--filter-script requires rgrep to be built with the `script` feature".into(),
+            );
+        }
+        if cfg.group.is_some() && cfg.definition.is_some() {
+            return Err("--group cannot be combined with --def".into());
+        }
+        let transforms = !cfg.maps.is_empty() || cfg.squash_whitespace;
+        if transforms && (cfg.multiline || cfg.replace.is_some()) {
+            return Err(
+                "--map and --squash-whitespace cannot be combined with -U or --replace".into(),
+            );
+        }
+        let mut maps = cfg
+            .maps
+            .iter()
+            .map(|spec| LineMap::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if cfg.squash_whitespace {
+            maps.push(LineMap::squash_whitespace());
+        }
+        let (matching, literal) = choose_matching(cfg)?;
+        if cfg.engine == Engine::Fancy {
+            return Self::fancy(cfg, matching).map(|m| Self { maps, ..m });
+        }
+
+        // Check for complex Boolean expressions first. When capture groups are referenced
+        // (`--where`, or `{captures[n]}` in `--format`), matches span lines (`-U`), or the
+        // pattern is not in extended syntax, the pattern is a plain regex: its parentheses
+        // are capture groups, not grouping.
+        let boolean_expr = if captures_referenced(cfg) || is_single_regex(cfg) {
+            None
+        } else {
+            parse_boolean_if_complex(cfg)?
+        };
+        // For Boolean expressions, we still need a regex combining the terms
+        let highlight = match &boolean_expr {
+            Some((expr, _)) => {
+                let mut terms: Vec<String> = expr.get_patterns().into_iter().collect();
+                terms.sort();
+                build_terms_regex(cfg, &terms)
+            }
+            None => build_regex(cfg),
+        }
+        .map_err(|e| e.to_string())?;
+
+        let mode = match boolean_expr {
+            Some((expr, regexes)) => {
+                let highlighted = expr
+                    .highlighted_terms()
+                    .into_iter()
+                    .filter_map(|p| regexes.get(&p).cloned().map(|r| (p, r)))
+                    .collect();
+                MatchMode::Boolean((expr, regexes), highlighted)
+            }
+            None => match build_and_matchers(cfg).map_err(|e| e.to_string())? {
+                Some(ands) => MatchMode::And(and_terms(cfg).into_iter().zip(ands).collect()),
+                None => literal.map_or(MatchMode::Regex, |text| {
+                    MatchMode::Literal(Box::new(Literal::new(&text, cfg.case_insensitive)))
+                }),
+            },
+        };
+
+        // Predicates read the capture groups of the combined pattern
+        let predicate = match &cfg.where_expr {
+            Some(expr) => {
+                let pred = parse_predicate(expr)?;
+                pred.validate(&highlight)?;
+                Some(pred)
+            }
+            None => None,
+        };
+        let group = match &cfg.group {
+            Some(spec) => Some(Group::new(spec, &highlight)?),
+            None => None,
+        };
+
+        Ok(Self {
+            highlight,
+            mode,
+            predicate,
+            definitions: cfg.definition.is_some(),
+            multiline: cfg.multiline,
+            group,
+            maps,
+            #[cfg(feature = "script")]
+            script: cfg
+                .filter_script
+                .as_deref()
+                .map(FilterScript::load)
+                .transpose()?,
+            matching,
+        })
+    }
+
+    /// A matcher for `Engine::Fancy`.
+    #[cfg(feature = "fancy")]
+    fn fancy(cfg: &Config, matching: Choice<Matching>) -> Result<Self, String> {
+        if captures_referenced(cfg) || cfg.definition.is_some() || cfg.multiline || cfg.crlf {
+            return Err(
+                "--engine fancy does not support --where, --filter-script, --def, -U, --crlf, --group, or {captures[N]}"
+                    .into(),
+            );
+        }
+        let regex = build_fancy_regex(cfg)?;
+        Ok(Self {
+            // Everything reading the highlight regex is rejected above
+            highlight: Regex::new(r"[^\s\S]").expect("valid regex"),
+            mode: MatchMode::Fancy(regex),
+            predicate: None,
+            definitions: false,
+            multiline: false,
+            group: None,
+            maps: Vec::new(),
+            #[cfg(feature = "script")]
+            script: None,
+            matching,
+        })
+    }
+
+    #[cfg(not(feature = "fancy"))]
+    fn fancy(_cfg: &Config, _matching: Choice<Matching>) -> Result<Self, String> {
+        Err("--engine fancy requires rgrep to be built with the `fancy` feature".into())
+    }
+
+    /// `line` after the `--map` transformations.
+    fn mapped<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.maps.is_empty() {
+            Cow::Borrowed(line)
+        } else {
+            Cow::Owned(line_map::apply(&self.maps, line).text)
+        }
+    }
+
+    /// Whether `line` matches (before any inversion).
+    pub fn is_match(&self, line: &str) -> bool {
+        let line = &*self.mapped(line);
+        let selected = match &self.mode {
+            MatchMode::Literal(literal) if literal.handles(line) => literal.is_match(line),
+            MatchMode::Regex | MatchMode::Literal(_) => self.highlight.is_match(line),
+            MatchMode::And(ands) => ands.iter().all(|(_, r)| r.is_match(line)),
+            MatchMode::Boolean((expr, regexes), _) => expr.matches(line, regexes),
+            // Lines exceeding the backtracking limit do not match
+            #[cfg(feature = "fancy")]
+            MatchMode::Fancy(regex) => regex.is_match(line).unwrap_or(false),
+        };
+        match &self.predicate {
+            Some(pred) => selected && pred.matches(&self.highlight, line),
+            None => selected,
+        }
+    }
+
+    /// Like `is_match`, but gives up with `None` once `deadline` has passed. The regex
+    /// engine cannot be interrupted within a search, so the deadline is checked after
+    /// every term of an expression.
+    pub fn is_match_before(&self, line: &str, deadline: Instant) -> Option<bool> {
+        let line = &*self.mapped(line);
+        let in_time = || Instant::now() <= deadline;
+        let selected = match &self.mode {
+            MatchMode::Literal(literal) if literal.handles(line) => literal.is_match(line),
+            MatchMode::Regex | MatchMode::Literal(_) => self.highlight.is_match(line),
+            MatchMode::And(ands) => {
+                let mut all = true;
+                for (_, regex) in ands {
+                    all = regex.is_match(line);
+                    if !in_time() {
+                        return None;
+                    }
+                    if !all {
+                        break;
+                    }
+                }
+                all
+            }
+            MatchMode::Boolean((expr, regexes), _) => {
+                expr.matches_before(line, regexes, deadline)?
+            }
+            // Exceeding the backtracking limit counts as running out of time
+            #[cfg(feature = "fancy")]
+            MatchMode::Fancy(regex) => regex.is_match(line).ok()?,
+        };
+        let hit = match &self.predicate {
+            Some(pred) => selected && pred.matches(&self.highlight, line),
+            None => selected,
+        };
+        in_time().then_some(hit)
+    }
+
+    /// Whether the `--filter-script` keeps the matching `line`, line `line_number` of
+    /// `path` (`None` for stdin). The script sees the line after the `--map`
+    /// transformations. Always true without a script.
+    #[cfg(feature = "script")]
+    pub fn keeps(&self, line: &str, path: Option<&str>, line_number: usize) -> bool {
+        match &self.script {
+            Some(script) => script.keeps(&self.highlight, &self.mapped(line), path, line_number),
+            None => true,
+        }
+    }
+
+    #[cfg(not(feature = "script"))]
+    pub fn keeps(&self, _line: &str, _path: Option<&str>, _line_number: usize) -> bool {
+        true
+    }
+
+    /// Whether lines must be evaluated together with their neighbors (`match_lines`)
+    /// because the expression uses NEAR (`a~N~b`).
+    pub fn is_windowed(&self) -> bool {
+        match &self.mode {
+            MatchMode::Boolean((expr, _), _) => expr.is_windowed(),
+            _ => false,
+        }
+    }
+
+    /// Whether each of the `lines` of one input matches (before any inversion). Unlike
+    /// `is_match`, this honors NEAR distances across lines.
+    pub fn match_lines(&self, lines: &[&str]) -> Vec<bool> {
+        match &self.mode {
+            MatchMode::Boolean((expr, regexes), _) if expr.is_windowed() => {
+                let mapped: Vec<Cow<str>> = lines.iter().map(|l| self.mapped(l)).collect();
+                let mapped: Vec<&str> = mapped.iter().map(|l| l.as_ref()).collect();
+                expr.matches_lines(&mapped, regexes)
+            }
+            _ => lines.iter().map(|line| self.is_match(line)).collect(),
+        }
+    }
+
+    /// Whether matches may span lines, so that an input must be matched as a whole with
+    /// `multiline_spans`.
+    pub fn is_multiline(&self) -> bool {
+        self.multiline
+    }
+
+    /// Match the pattern against `lines` joined with newlines, so that a match can span
+    /// several lines. For every line, returns the byte ranges of matched text within it,
+    /// or `None` when no match touches the line; every line a match spans matches.
+    pub fn multiline_spans(&self, lines: &[&str]) -> Vec<Option<Vec<Range<usize>>>> {
+        let text = lines.join("\n");
+        let mut starts = Vec::with_capacity(lines.len());
+        let mut pos = 0;
+        for line in lines {
+            starts.push(pos);
+            pos += line.len() + 1;
+        }
+        // The line containing byte `at` of `text`
+        let line_of = |at: usize| starts.partition_point(|&s| s <= at).saturating_sub(1);
+
+        let mut spans: Vec<Option<Vec<Range<usize>>>> = vec![None; lines.len()];
+        for m in self.highlight.find_iter(&text) {
+            let first = line_of(m.start());
+            let last = line_of(m.end().saturating_sub(1).max(m.start()));
+            for (idx, line_spans) in spans.iter_mut().enumerate().take(last + 1).skip(first) {
+                let (start, end) = (starts[idx], starts[idx] + lines[idx].len());
+                let line_spans = line_spans.get_or_insert_with(Vec::new);
+                let span = m.start().max(start) - start..m.end().min(end) - start;
+                if !span.is_empty() {
+                    line_spans.push(span);
+                }
+            }
+        }
+        spans
+    }
+
+    /// Byte ranges of matched text in `line`, sorted and with overlaps merged.
+    ///
+    /// Returns an empty list when nothing in the line matches. With `Config::group`, the
+    /// spans are those of the group in each match, where it took part in the match.
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        if self.maps.is_empty() {
+            return self.spans_in(line);
+        }
+        let mapped = line_map::apply(&self.maps, line);
+        let spans = self.spans_in(&mapped.text);
+        merge_spans(spans.into_iter().map(|s| mapped.original_span(s)).collect())
+    }
+
+    /// `find_spans` for a line after the `--map` transformations.
+    fn spans_in(&self, line: &str) -> Vec<Range<usize>> {
+        if let Some(group) = &self.group {
+            return self
+                .highlight
+                .captures_iter(line)
+                .filter_map(|caps| group.get(&caps).map(|m| m.range()))
+                .collect();
+        }
+        if self.definitions {
+            // Only the defined name, not the keyword or signature around it
+            return self
+                .highlight
+                .captures_iter(line)
+                .filter_map(|caps| name_span(&caps))
+                .collect();
+        }
+        let spans: Vec<Range<usize>> = match &self.mode {
+            MatchMode::Literal(literal) if literal.handles(line) => return literal.find_iter(line),
+            MatchMode::Regex | MatchMode::Literal(_) => {
+                return self.highlight.find_iter(line).map(|m| m.range()).collect();
+            }
+            #[cfg(feature = "fancy")]
+            MatchMode::Fancy(regex) => {
+                return regex
+                    .find_iter(line)
+                    .map_while(Result::ok)
+                    .map(|m| m.range())
+                    .collect();
+            }
+            MatchMode::And(terms) | MatchMode::Boolean(_, terms) => terms
+                .iter()
+                .flat_map(|(_, r)| r.find_iter(line).map(|m| m.range()))
+                .collect(),
+        };
+        merge_spans(spans)
+    }
+
+    /// `line` with every match replaced by `template`, and the byte ranges of the
+    /// replacements in the result. In `template`, `$1` and `${name}` refer to the capture
+    /// groups of the match and `$$` is a literal `$` (see `regex::Captures::expand`). For
+    /// `&` and Boolean expressions, matches of any term are replaced.
+    pub fn replace(&self, line: &str, template: &str) -> (String, Vec<Range<usize>>) {
+        let mut out = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut last = 0;
+        let mut replace_one = |whole: Range<usize>, expand: &dyn Fn(&mut String)| {
+            out.push_str(&line[last..whole.start]);
+            let start = out.len();
+            expand(&mut out);
+            spans.push(start..out.len());
+            last = whole.end;
+        };
+        match &self.mode {
+            #[cfg(feature = "fancy")]
+            MatchMode::Fancy(regex) => {
+                for caps in regex.captures_iter(line).map_while(Result::ok) {
+                    let whole = caps.get(0).map_or(0..0, |m| m.range());
+                    replace_one(whole, &|out| caps.expand(template, out));
+                }
+            }
+            _ => {
+                for caps in self.highlight.captures_iter(line) {
+                    let whole = caps.get(0).map_or(0..0, |m| m.range());
+                    replace_one(whole, &|out| caps.expand(template, out));
+                }
+            }
+        }
+        out.push_str(&line[last..]);
+        (out, spans)
+    }
+
+    /// The terms of an `&` or Boolean expression found in `line`, in expression order:
+    /// which branches of e.g. `error|panic` made the line match. Negated terms are never
+    /// included, and plain patterns have no terms.
+    pub fn matched_terms(&self, line: &str) -> Vec<String> {
+        let line = &*self.mapped(line);
+        match &self.mode {
+            MatchMode::Regex | MatchMode::Literal(_) => Vec::new(),
+            #[cfg(feature = "fancy")]
+            MatchMode::Fancy(_) => Vec::new(),
+            MatchMode::And(terms) | MatchMode::Boolean(_, terms) => terms
+                .iter()
+                .filter(|(_, r)| r.is_match(line))
+                .map(|(term, _)| term.clone())
+                .collect(),
+        }
+    }
+
+    /// The kind of symbol (e.g. `function` or `struct`) that `line` defines, when searching
+    /// for definitions (`--def`).
+    pub fn definition_kind(&self, line: &str) -> Option<&'static str> {
+        if !self.definitions {
+            return None;
+        }
+        self.highlight
+            .captures(&self.mapped(line))
+            .map(|caps| kind_of(&caps))
+    }
+
+    /// The regex deciding matches on its own, when there is no `&`, Boolean grouping, or
+    /// predicate or `--map`; such matchers can be evaluated together in a `RegexSet`.
+    pub fn single_regex(&self) -> Option<&Regex> {
+        match (&self.mode, &self.predicate) {
+            (MatchMode::Regex | MatchMode::Literal(_), None) if self.maps.is_empty() => {
+                Some(&self.highlight)
+            }
+            _ => None,
+        }
+    }
+
+    /// How lines are matched, and why (see `strategy`).
+    pub fn matching(&self) -> &Choice<Matching> {
+        &self.matching
+    }
+
+    /// The combined regex of all terms; its capture groups are the pattern's groups.
+    /// Highlighting uses `find_spans` instead, which reflects what actually matched.
+    pub fn highlight_regex(&self) -> &Regex {
+        &self.highlight
+    }
+}
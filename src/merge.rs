@@ -0,0 +1,253 @@
+//! Chronological merging of per-file search output.
+//!
+//! When several files are searched and their emitted lines carry timestamps, the
+//! per-file outputs are interleaved by time so multi-file log views read naturally.
+//! Merged lines that do not carry a filename prefix are tagged with a `[name]` source tag
+//! so their origin stays visible.
+//!
+//! Lines without a timestamp (stack traces, wrapped messages) stay attached to the
+//! nearest preceding timestamped line of the same file. Files without any timestamp
+//! are appended after the merged block and reported as a warning.
+//!
+//! Timestamps are compared in UTC: a trailing offset (`Z`, `+02:00`, `-0500`) is honored,
+//! and lines without one use `Config::assume_tz` (UTC when unset).
+
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config::{Config, MergeTies, parse_utc_offset};
+
+/// Formatted output of a single searched file, as handed to the merger.
+pub(crate) struct FileOutput {
+    /// Position of the file in the expanded input list.
+    pub idx: usize,
+    /// Path the file was opened from.
+    pub path: String,
+    /// Formatted output produced for the file.
+    pub output: String,
+}
+
+/// Parsed timestamp normalized to UTC as (seconds since the Unix epoch, nanoseconds).
+type Timestamp = (i64, u32);
+
+/// A formatted output line tagged with its timestamp and origin.
+struct MergeLine<'a> {
+    ts: Option<Timestamp>,
+    file_idx: usize,
+    path: &'a str,
+    line_idx: usize,
+    text: &'a str,
+}
+
+fn timestamp_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        // Regex for timestamps: YYYY-MM-DD[ T]HH:MM:SS(.fraction)?( ?offset)?
+        // The space in the separator class is escaped because (?x) ignores bare whitespace.
+        Regex::new(
+            r"(?x)
+            (?P<y>\d{4})-
+            (?P<m>\d{2})-
+            (?P<d>\d{2})
+            [\ T]
+            (?P<h>\d{2}):
+            (?P<min>\d{2}):
+            (?P<s>\d{2})
+            (?:[.,](?P<frac>\d{1,9}))?
+            (?:\ ?(?P<tz>Z|[+-]\d{2}:?\d{2})\b)?
+        ",
+        )
+        .expect("timestamp regex is valid")
+    })
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn parse_ts_from_formatted_line(line: &str, assume_tz: Option<i32>) -> Option<Timestamp> {
+    // Formatted lines look like "[<file>:][<lineno>:]<content>". The timestamp regex needs
+    // a full date and time, so searching the whole line finds the one in the content.
+    let caps = timestamp_regex().captures(line)?;
+    let y: i64 = caps.name("y")?.as_str().parse().ok()?;
+    let m: i64 = caps.name("m")?.as_str().parse().ok()?;
+    let d: i64 = caps.name("d")?.as_str().parse().ok()?;
+    let h: i64 = caps.name("h")?.as_str().parse().ok()?;
+    let min: i64 = caps.name("min")?.as_str().parse().ok()?;
+    let s: i64 = caps.name("s")?.as_str().parse().ok()?;
+    let frac_str = caps.name("frac").map(|m| m.as_str()).unwrap_or("");
+    let mut nanos: u32 = 0;
+    if !frac_str.is_empty() {
+        // Normalize to nanoseconds by right-padding with zeros up to 9 digits
+        let mut ns = String::from(frac_str);
+        while ns.len() < 9 {
+            ns.push('0');
+        }
+        // Truncate if more than 9
+        let ns = &ns[..9];
+        nanos = ns.parse().unwrap_or(0);
+    }
+    let offset = match caps.name("tz") {
+        Some(tz) => parse_utc_offset(tz.as_str())?,
+        None => assume_tz.unwrap_or(0),
+    };
+    let local = days_from_civil(y, m, d) * 86_400 + h * 3600 + min * 60 + s;
+    Some((local - i64::from(offset), nanos))
+}
+
+/// Short label identifying a file in merged output (its final path component).
+fn source_tag(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+// Helper: Collect all lines with optional parsed timestamps from per-file outputs.
+fn collect_all_lines<'a>(cfg: &Config, outputs: &'a [FileOutput]) -> Vec<MergeLine<'a>> {
+    let mut all_lines = Vec::new();
+    for file in outputs {
+        for (line_idx, line) in file.output.lines().enumerate() {
+            all_lines.push(MergeLine {
+                ts: parse_ts_from_formatted_line(line, cfg.assume_tz),
+                file_idx: file.idx,
+                path: &file.path,
+                line_idx,
+                text: line,
+            });
+        }
+    }
+    all_lines
+}
+
+/// Within each run of equal timestamps, move lines from the file that produced the
+/// previously emitted line to the front so a source is not interrupted needlessly.
+fn keep_sources_contiguous(lines: &mut [MergeLine<'_>]) {
+    let mut prev_file: Option<usize> = None;
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start + 1;
+        while end < lines.len() && lines[end].ts == lines[start].ts {
+            end += 1;
+        }
+        if let Some(prev) = prev_file {
+            lines[start..end].sort_by_key(|l| l.file_idx != prev);
+        }
+        prev_file = Some(lines[end - 1].file_idx);
+        start = end;
+    }
+}
+
+/// Give each untimestamped line the timestamp of the closest preceding timestamped line in
+/// the same file (or the first one, for leading lines) so it keeps its place on merge.
+///
+/// Returns the indexes of files that have no timestamp at all.
+fn inherit_timestamps(all_lines: &mut [MergeLine<'_>]) -> Vec<usize> {
+    let mut untimed_files = Vec::new();
+    let mut start = 0;
+    while start < all_lines.len() {
+        let file_idx = all_lines[start].file_idx;
+        let mut end = start;
+        while end < all_lines.len() && all_lines[end].file_idx == file_idx {
+            end += 1;
+        }
+        let file_lines = &mut all_lines[start..end];
+        match file_lines.iter().find_map(|l| l.ts) {
+            Some(first) => {
+                let mut current = first;
+                for line in file_lines.iter_mut() {
+                    match line.ts {
+                        Some(ts) => current = ts,
+                        None => line.ts = Some(current),
+                    }
+                }
+            }
+            None => untimed_files.push(file_idx),
+        }
+        start = end;
+    }
+    untimed_files
+}
+
+fn push_line(merged: &mut String, line: &MergeLine<'_>, tag_sources: bool) {
+    if tag_sources {
+        merged.push('[');
+        merged.push_str(&source_tag(line.path));
+        merged.push_str("] ");
+    }
+    merged.push_str(line.text);
+    merged.push('\n');
+}
+
+// Helper: Merge lines chronologically when at least one file has timestamps; otherwise return None.
+//
+// Files without any timestamp are appended after the merged lines, in input order.
+fn merge_chronologically(
+    cfg: &Config,
+    mut all_lines: Vec<MergeLine<'_>>,
+    tag_sources: bool,
+) -> Option<(String, Vec<String>)> {
+    let untimed_files = inherit_timestamps(&mut all_lines);
+    let (mut timed, untimed): (Vec<_>, Vec<_>) =
+        all_lines.into_iter().partition(|l| l.ts.is_some());
+    if timed.is_empty() {
+        return None;
+    }
+    match cfg.merge_ties {
+        MergeTies::InputOrder => timed.sort_by_key(|l| (l.ts, l.file_idx, l.line_idx)),
+        MergeTies::Path => timed.sort_by_key(|l| (l.ts, l.path, l.file_idx, l.line_idx)),
+        MergeTies::Stable => {
+            timed.sort_by_key(|l| (l.ts, l.file_idx, l.line_idx));
+            keep_sources_contiguous(&mut timed);
+        }
+    }
+    let mut merged = String::new();
+    for line in timed.iter().chain(untimed.iter()) {
+        push_line(&mut merged, line, tag_sources);
+    }
+    let warnings = untimed_files
+        .iter()
+        .filter_map(|idx| untimed.iter().find(|l| l.file_idx == *idx))
+        .map(|l| {
+            format!(
+                "{}: no timestamps found; lines appended after merged output",
+                l.path
+            )
+        })
+        .collect();
+    Some((merged, warnings))
+}
+
+// Helper: Concatenate outputs in input order.
+fn concat_outputs(outputs: Vec<FileOutput>) -> String {
+    let mut out = String::new();
+    for file in outputs {
+        out.push_str(&file.output);
+    }
+    out
+}
+
+/// Combine per-file outputs, interleaving them by timestamp when any file has them and
+/// falling back to plain concatenation in input order otherwise.
+///
+/// With `tag_sources`, merged lines are prefixed with a `[name]` tag of their file.
+/// Returns the combined output together with warnings about files that could not be merged.
+pub(crate) fn merge_outputs(
+    cfg: &Config,
+    outputs: Vec<FileOutput>,
+    tag_sources: bool,
+) -> (String, Vec<String>) {
+    let all_lines = collect_all_lines(cfg, &outputs);
+    match merge_chronologically(cfg, all_lines, tag_sources) {
+        Some(merged) => merged,
+        None => (concat_outputs(outputs), Vec::new()),
+    }
+}
@@ -0,0 +1,188 @@
+//! Prometheus textfile metrics for follow mode (`--metrics-file PATH`).
+//!
+//! While a file is followed, match counters are kept per followed file and pattern, and
+//! per term of `&` and Boolean expressions (see `Matcher::matched_terms`). They are
+//! written in the Prometheus text exposition format, e.g. for node_exporter's textfile
+//! collector, so that an "error line rate" can be graphed from
+//! `rate(rgrep_matched_lines_total[5m])`:
+//!
+//! ```text
+//! # HELP rgrep_lines_total Lines read from the followed file.
+//! # TYPE rgrep_lines_total counter
+//! rgrep_lines_total{path="app.log"} 120
+//! # HELP rgrep_matched_lines_total Lines selected by the pattern.
+//! # TYPE rgrep_matched_lines_total counter
+//! rgrep_matched_lines_total{path="app.log",pattern="error|panic"} 4
+//! # HELP rgrep_term_matched_lines_total Selected lines containing each term of the expression.
+//! # TYPE rgrep_term_matched_lines_total counter
+//! rgrep_term_matched_lines_total{path="app.log",term="error"} 3
+//! ```
+//!
+//! The file is replaced atomically (written next to it, then renamed), so a scraper never
+//! sees it half-written. Counters start at zero when following starts.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two writes of the metrics file.
+pub const WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Match counters of one followed file, written to a metrics file.
+#[derive(Debug)]
+pub struct FollowMetrics {
+    file: String,
+    path: String,
+    pattern: String,
+    lines: u64,
+    matched_lines: u64,
+    terms: BTreeMap<String, u64>,
+    /// When the file was last written; `None` before the first write.
+    written: Option<Instant>,
+    /// Whether the counters changed since the last write.
+    dirty: bool,
+}
+
+impl FollowMetrics {
+    /// Counters for following `path` with `pattern`, written to the metrics file `file`.
+    pub fn new(file: &str, path: &str, pattern: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            path: path.to_string(),
+            pattern: pattern.to_string(),
+            lines: 0,
+            matched_lines: 0,
+            terms: BTreeMap::new(),
+            written: None,
+            dirty: true,
+        }
+    }
+
+    /// Count a line read from the followed file; `terms` are the expression terms found
+    /// on a selected line.
+    pub fn record(&mut self, selected: bool, terms: Vec<String>) {
+        self.lines += 1;
+        if selected {
+            self.matched_lines += 1;
+            for term in terms {
+                *self.terms.entry(term).or_insert(0) += 1;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// The counters in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let path = label_value(&self.path);
+        let mut out = String::new();
+        out.push_str("# HELP rgrep_lines_total Lines read from the followed file.\n");
+        out.push_str("# TYPE rgrep_lines_total counter\n");
+        let _ = writeln!(out, "rgrep_lines_total{{path=\"{}\"}} {}", path, self.lines);
+        out.push_str("# HELP rgrep_matched_lines_total Lines selected by the pattern.\n");
+        out.push_str("# TYPE rgrep_matched_lines_total counter\n");
+        let _ = writeln!(
+            out,
+            "rgrep_matched_lines_total{{path=\"{}\",pattern=\"{}\"}} {}",
+            path,
+            label_value(&self.pattern),
+            self.matched_lines
+        );
+        if !self.terms.is_empty() {
+            out.push_str("# HELP rgrep_term_matched_lines_total Selected lines containing each term of the expression.\n");
+            out.push_str("# TYPE rgrep_term_matched_lines_total counter\n");
+            for (term, count) in &self.terms {
+                let _ = writeln!(
+                    out,
+                    "rgrep_term_matched_lines_total{{path=\"{}\",term=\"{}\"}} {}",
+                    path,
+                    label_value(term),
+                    count
+                );
+            }
+        }
+        out
+    }
+
+    /// Write the metrics file when the counters changed and `WRITE_INTERVAL` has passed
+    /// since the last write (or nothing was written yet).
+    pub fn write_if_due(&mut self) -> io::Result<()> {
+        let due = self.written.is_none_or(|at| at.elapsed() >= WRITE_INTERVAL);
+        if self.dirty && due {
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    /// Write the metrics file when the counters changed since the last write, however
+    /// recently that was.
+    pub fn write_if_changed(&mut self) -> io::Result<()> {
+        if self.dirty {
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the metrics file with the current counters.
+    pub fn write(&mut self) -> io::Result<()> {
+        let tmp = format!("{}.tmp", self.file);
+        fs::write(&tmp, self.render())?;
+        fs::rename(&tmp, &self.file)?;
+        self.written = Some(Instant::now());
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Escape `s` as a Prometheus label value (without the surrounding quotes).
+fn label_value(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counts_lines_and_terms() {
+        let mut metrics = FollowMetrics::new("unused.prom", "app.log", r#"error|"a\b""#);
+        metrics.record(true, vec!["error".to_string()]);
+        metrics.record(false, Vec::new());
+        metrics.record(true, vec!["error".to_string(), "panic".to_string()]);
+
+        let text = metrics.render();
+        assert!(text.contains("rgrep_lines_total{path=\"app.log\"} 3\n"));
+        assert!(
+            text.contains(
+                r#"rgrep_matched_lines_total{path="app.log",pattern="error|\"a\\b\""} 2"#
+            )
+        );
+        assert!(
+            text.contains("rgrep_term_matched_lines_total{path=\"app.log\",term=\"error\"} 2\n")
+        );
+        assert!(
+            text.contains("rgrep_term_matched_lines_total{path=\"app.log\",term=\"panic\"} 1\n")
+        );
+    }
+
+    #[test]
+    fn test_write_replaces_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("rgrep.prom");
+        let file = file.to_string_lossy();
+        let mut metrics = FollowMetrics::new(&file, "app.log", "error");
+
+        metrics.write_if_due().unwrap();
+        assert!(fs::read_to_string(&*file).unwrap().contains("} 0\n"));
+
+        metrics.record(true, Vec::new());
+        metrics.write().unwrap();
+        let text = fs::read_to_string(&*file).unwrap();
+        assert!(text.contains("rgrep_matched_lines_total{path=\"app.log\",pattern=\"error\"} 1\n"));
+        // Nothing is left behind next to the file
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
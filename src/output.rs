@@ -1,29 +1,336 @@
 //! Output formatting helpers.
 //!
-//! Currently we always prefix lines with a 1-based line number. Filename prefixes
-//! are intentionally omitted for simplicity, except in count mode for multi-file
-//! searches where aggregation occurs elsewhere.
+//! Lines are formatted as `[filename:][line:][column:]content`. Whether the filename and
+//! line number are shown is decided by the caller (see `Config::with_filename` and
+//! `Config::line_number`). In heading mode (`Config::heading`) the filename is instead
+//! printed once above the lines of each file, and file groups are separated by a blank
+//! line. With `Compat::Gnu`, context lines use `-` instead of `:` as separator, as in
+//! GNU grep.
+//!
+//! Structured (JSON Lines) output lives in the `json` submodule, and in `rg_json` for
+//! ripgrep's schema (`--json=rg`); aligned tables for multi-file summaries live in
+//! `table`, user-defined `--format` templates in `template`, and unified diffs of
+//! `--replace` changes (`--diff`) in `diff`.
+//!
+//! With `Config::max_columns` or `Config::match_window`, lines too long to read are
+//! shortened before they are formatted (see `shorten_line`).
 
 use std::fmt::Write as _;
+use std::ops::Range;
+
+use crate::color::{Colors, paint};
+use crate::config::Config;
+
+pub mod diff;
+pub mod json;
+pub mod rg_json;
+pub mod table;
+pub mod template;
 
 /// Append a single formatted line to the output buffer.
 ///
 /// Parameters:
 /// - `out`: destination buffer
-/// - `_filename`: optional filename (ignored in current formatting)
-/// - `idx`: zero-based line index; will be printed as one-based
+/// - `filename`: optional filename prefix
+/// - `idx`: zero-based line index, printed as one-based; `None` omits the line number
+/// - `column`: optional 1-based column of the first match (printed after the line number)
 /// - `line`: the line content without trailing newline
 /// - `_is_match`: whether the line is a primary match (currently unused here)
 /// - `_line_mode`: whether whole-line matching is active (unused here)
 pub fn append_formatted_line(
     out: &mut String,
-    _filename: Option<&str>,
-    idx: usize,
+    filename: Option<&str>,
+    idx: Option<usize>,
+    column: Option<usize>,
     line: &str,
     _is_match: bool,
     _line_mode: bool,
 ) {
-    // Always prefix with 1-based line number, not filename
-    let line_no = idx + 1;
-    let _ = writeln!(out, "{}:{}", line_no, line);
+    if let Some(name) = filename {
+        out.push_str(name);
+        out.push(':');
+    }
+    // Prefix with 1-based line number
+    if let Some(idx) = idx {
+        let _ = write!(out, "{}:", idx + 1);
+    }
+    if let Some(col) = column {
+        let _ = write!(out, "{}:", col);
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Like `append_formatted_line`, but with the filename, line and column numbers, and
+/// separators colored with `colors` when given. `line` is appended as is, so matches are
+/// highlighted by the caller.
+pub fn append_styled_line(
+    out: &mut String,
+    colors: Option<&Colors>,
+    filename: Option<&str>,
+    idx: Option<usize>,
+    column: Option<usize>,
+    line: &str,
+) {
+    let Some(colors) = colors else {
+        append_formatted_line(out, filename, idx, column, line, false, false);
+        return;
+    };
+    if let Some(name) = filename {
+        paint(out, &colors.filename, name);
+        paint(out, &colors.separator, ":");
+    }
+    if let Some(idx) = idx {
+        paint(out, &colors.line_number, &(idx + 1).to_string());
+        paint(out, &colors.separator, ":");
+    }
+    if let Some(col) = column {
+        paint(out, &colors.line_number, &col.to_string());
+        paint(out, &colors.separator, ":");
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Append a context line in GNU grep's format (`Compat::Gnu`): like `append_styled_line`,
+/// but with `-` instead of `:` after the filename and line number.
+pub fn append_context_line(
+    out: &mut String,
+    colors: Option<&Colors>,
+    filename: Option<&str>,
+    idx: Option<usize>,
+    line: &str,
+) {
+    let field = |out: &mut String, sgr: Option<&String>, text: &str| {
+        match sgr {
+            Some(sgr) => paint(out, sgr, text),
+            None => out.push_str(text),
+        }
+        match colors {
+            Some(colors) => paint(out, &colors.separator, "-"),
+            None => out.push('-'),
+        }
+    };
+    if let Some(name) = filename {
+        field(out, colors.map(|c| &c.filename), name);
+    }
+    if let Some(idx) = idx {
+        field(out, colors.map(|c| &c.line_number), &(idx + 1).to_string());
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Append the `--` line GNU grep prints between groups of lines that are not adjacent
+/// (`Compat::Gnu` with context).
+pub fn append_group_separator(out: &mut String, colors: Option<&Colors>) {
+    match colors {
+        Some(colors) => paint(out, &colors.separator, "--"),
+        None => out.push_str("--"),
+    }
+    out.push('\n');
+}
+
+/// Append the entry for a file reported only because its path matched.
+pub fn append_path_match(out: &mut String, filename: &str) {
+    out.push_str(filename);
+    out.push_str(" (path match)\n");
+}
+
+/// Append a heading line naming the file whose lines follow, colored with `colors` when
+/// given.
+pub fn append_heading(out: &mut String, filename: &str, colors: Option<&Colors>) {
+    match colors {
+        Some(colors) => paint(out, &colors.filename, filename),
+        None => out.push_str(filename),
+    }
+    out.push('\n');
+}
+
+/// Join per-file heading groups, separating non-empty groups with a blank line.
+pub fn join_heading_groups<I: IntoIterator<Item = String>>(groups: I) -> String {
+    let mut out = String::new();
+    for group in groups.into_iter().filter(|g| !g.is_empty()) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&group);
+    }
+    out
+}
+
+/// `line` shortened for printing as `cfg` says, with `spans` moved along, or `None` when
+/// it is printed whole: lines longer than `Config::max_columns` (any line without it)
+/// are cut down to `Config::match_window`, or else fitted to `max_columns`.
+pub fn shorten_line(
+    cfg: &Config,
+    line: &str,
+    spans: &[Range<usize>],
+) -> Option<(String, Vec<Range<usize>>)> {
+    if cfg.max_columns.is_some_and(|max| line.len() <= max) {
+        return None;
+    }
+    match (cfg.match_window, cfg.max_columns) {
+        (Some(chars), _) => match_windows(line, spans, chars),
+        (None, Some(max)) => fit_line(line, spans, max, cfg.max_columns_preview),
+        (None, None) => None,
+    }
+}
+
+/// Marks where `fit_line` cut a line short.
+const CUT: &str = "[...]";
+
+/// Marks where `match_windows` left text out.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// `line` shortened to at most `max` bytes (`Config::max_columns`), with `spans` moved
+/// along, or `None` when it fits. A long line is replaced by a
+/// `[... omitted: line too long (N bytes)]` marker; with `preview`, by the `max` bytes
+/// around its first span (or its start), with `[...]` where text was cut.
+fn fit_line(
+    line: &str,
+    spans: &[Range<usize>],
+    max: usize,
+    preview: bool,
+) -> Option<(String, Vec<Range<usize>>)> {
+    if line.len() <= max {
+        return None;
+    }
+    if !preview {
+        let marker = format!("[... omitted: line too long ({} bytes)]", line.len());
+        return Some((marker, Vec::new()));
+    }
+    // Center the first match in the window, as far as it fits
+    let first = spans.first().cloned().unwrap_or(0..0);
+    let slack = max.saturating_sub(first.len());
+    let mut start = first.start.saturating_sub(slack / 2).min(line.len() - max);
+    let mut end = start + max;
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut text = String::with_capacity(end - start + 2 * CUT.len());
+    if start > 0 {
+        text.push_str(CUT);
+    }
+    let shift = text.len();
+    text.push_str(&line[start..end]);
+    if end < line.len() {
+        text.push_str(CUT);
+    }
+    let spans = spans
+        .iter()
+        .filter(|s| s.start < end && s.end > start)
+        .map(|s| s.start.max(start) - start + shift..s.end.min(end) - start + shift)
+        .collect();
+    Some((text, spans))
+}
+
+/// `line` cut down to `chars` characters before and after each of its `spans`
+/// (`Config::match_window`), with `…` where text was left out, and `spans` moved along;
+/// `None` when nothing is left out. A line without spans keeps its first `2 * chars`
+/// characters.
+fn match_windows(
+    line: &str,
+    spans: &[Range<usize>],
+    chars: usize,
+) -> Option<(String, Vec<Range<usize>>)> {
+    // Byte offsets `n` characters before and after `at`
+    let back = |at: usize, n: usize| {
+        line[..at]
+            .char_indices()
+            .rev()
+            .take(n)
+            .last()
+            .map_or(at, |(i, _)| i)
+    };
+    let ahead = |at: usize, n: usize| {
+        line[at..]
+            .char_indices()
+            .nth(n)
+            .map_or(line.len(), |(i, _)| at + i)
+    };
+
+    // Windows around the spans, merged where they touch
+    let mut windows: Vec<Range<usize>> = Vec::new();
+    for span in spans {
+        let window = back(span.start, chars)..ahead(span.end, chars);
+        match windows.last_mut() {
+            Some(last) if window.start <= last.end => last.end = last.end.max(window.end),
+            _ => windows.push(window),
+        }
+    }
+    if windows.is_empty() {
+        windows.push(0..ahead(0, 2 * chars));
+    }
+    if windows.len() == 1 && windows[0] == (0..line.len()) {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut moved = Vec::with_capacity(spans.len());
+    let mut spans = spans.iter().peekable();
+    for window in &windows {
+        if window.start > 0 {
+            text.push_str(ELLIPSIS);
+        }
+        let shift = text.len();
+        text.push_str(&line[window.clone()]);
+        while let Some(span) = spans.next_if(|s| s.end <= window.end) {
+            moved.push(span.start - window.start + shift..span.end - window.start + shift);
+        }
+    }
+    if windows.last().is_some_and(|w| w.end < line.len()) {
+        text.push_str(ELLIPSIS);
+    }
+    Some((text, moved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_line_replaces_or_previews_long_lines() {
+        // The line's only match at `span`
+        let fit = |line: &str, span: Range<usize>, max: usize, preview: bool| {
+            fit_line(line, std::slice::from_ref(&span), max, preview)
+        };
+        assert_eq!(fit("short", 0..5, 5, false), None);
+        let (text, spans) = fit("0123456789", 4..6, 4, false).unwrap();
+        assert_eq!(text, "[... omitted: line too long (10 bytes)]");
+        assert!(spans.is_empty());
+
+        let (text, spans) = fit("aaaaaaaaaaMATCHbbbbbbbbbb", 10..15, 9, true).unwrap();
+        assert_eq!(text, "[...]aaMATCHbb[...]");
+        assert_eq!(&text[spans[0].clone()], "MATCH");
+        // Windows stay within the line and on character boundaries
+        let (text, _) = fit("MATCH and more", 0..5, 6, true).unwrap();
+        assert_eq!(text, "MATCH [...]");
+        let (text, _) = fit_line("ééééé", &[], 3, true).unwrap();
+        assert_eq!(text, "é[...]");
+    }
+
+    #[test]
+    fn test_match_windows_keep_the_text_around_each_match() {
+        let line = r#"{"a":"xxxxxxxxxx","msg":"disk error","b":"yyyyyyyyyy","c":"error"}"#;
+        let spans: Vec<Range<usize>> = line
+            .match_indices("error")
+            .map(|(i, m)| i..i + m.len())
+            .collect();
+        let (text, moved) = match_windows(line, &spans, 4).unwrap();
+        assert_eq!(text, "\u{2026}isk error\",\"b\u{2026}c\":\"error\"}");
+        assert!(moved.iter().all(|s| &text[s.clone()] == "error"));
+
+        // Windows count characters, and lines with nothing left out are kept whole
+        let x = |line: &str| line.find('X').map(|i| i..i + 1);
+        let (text, _) = match_windows("ééééXéééé", x("ééééXéééé").as_slice(), 2).unwrap();
+        assert_eq!(text, "\u{2026}ééXéé\u{2026}");
+        assert_eq!(match_windows("a X b", x("a X b").as_slice(), 2), None);
+        let (text, _) = match_windows("context line", &[], 2).unwrap();
+        assert_eq!(text, "cont\u{2026}");
+    }
 }
@@ -0,0 +1,126 @@
+//! Unified diffs of line substitutions (`--diff`).
+//!
+//! The changes made by `--replace` keep every line in place: line `i` of the new text
+//! replaces line `i` of the old one (and may itself span several lines when the template
+//! contains a newline). A diff therefore needs no sequence alignment; the changed lines
+//! are grouped into hunks with `context` unchanged lines around them, in the format read
+//! by `patch` and `git apply`:
+//!
+//! ```text
+//! --- app.conf
+//! +++ app.conf
+//! @@ -1,3 +1,3 @@
+//! -port=80
+//! +port=8080
+//!  host=a
+//! ```
+
+use std::fmt::Write as _;
+
+/// Unchanged lines shown around each change, as in `diff -u`.
+pub const DEFAULT_CONTEXT: usize = 3;
+
+/// Append the unified diff between `old` and `new` for the file `path` to `out`.
+///
+/// `old` holds the lines of the original text with their line endings, and `new[i]` the
+/// replacement of `old[i]`. Nothing is appended when no line changed.
+pub fn append_unified_diff(
+    out: &mut String,
+    path: &str,
+    old: &[&str],
+    new: &[String],
+    context: usize,
+) {
+    let changed: Vec<usize> = (0..old.len()).filter(|&i| old[i] != new[i]).collect();
+    if changed.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "--- {}\n+++ {}", path, path);
+
+    // Lines the new text spends on old line `i`, to number the new side of each hunk
+    let new_len = |i: usize| new[i].split_inclusive('\n').count();
+    // Changes closer than twice the context share one hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(old.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut new_before = 0;
+    let mut counted = 0;
+    for (start, end) in hunks {
+        new_before += (counted..start).map(new_len).sum::<usize>();
+        counted = start;
+        let old_count = end - start;
+        let new_count: usize = (start..end).map(new_len).sum();
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            hunk_range(start, old_count),
+            hunk_range(new_before, new_count)
+        );
+        for i in start..end {
+            if old[i] == new[i] {
+                append_diff_line(out, ' ', old[i]);
+            } else {
+                append_diff_line(out, '-', old[i]);
+                for line in new[i].split_inclusive('\n') {
+                    append_diff_line(out, '+', line);
+                }
+            }
+        }
+    }
+}
+
+/// `start,count` of a hunk beginning after `before` lines; an empty range names the line
+/// before it, as in `diff -u`.
+fn hunk_range(before: usize, count: usize) -> String {
+    if count == 0 {
+        format!("{},0", before)
+    } else {
+        format!("{},{}", before + 1, count)
+    }
+}
+
+fn append_diff_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiline_replacement_shifts_new_line_numbers() {
+        let old = ["x\n", "a\n", "x\n"];
+        let new = [
+            "y\nz\n".to_string(),
+            "a\n".to_string(),
+            "y\nz\n".to_string(),
+        ];
+        let mut out = String::new();
+        append_unified_diff(&mut out, "f", &old, &new, 0);
+        assert_eq!(
+            out,
+            "--- f\n+++ f\n@@ -1,1 +1,2 @@\n-x\n+y\n+z\n@@ -3,1 +4,2 @@\n-x\n+y\n+z\n"
+        );
+
+        let mut unchanged = String::new();
+        append_unified_diff(
+            &mut unchanged,
+            "f",
+            &old,
+            &["x\n".into(), "a\n".into(), "x\n".into()],
+            3,
+        );
+        assert!(unchanged.is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+//! JSON Lines serialization of search events.
+//!
+//! Each event is written as one JSON object per line. A search over a file produces a
+//! `begin` event, one `match` or `context` event per printed line, and an `end` event;
+//! multi-file runs finish with a `summary` event. `path` is `null` for stdin. With
+//! `Config::path_or_content`, a file with no matching lines but a matching path gets a
+//! `path_match` event before its `end`. With `--no-match-message`, a run without matches
+//! ends in a `no_match` event carrying the message. A file that cannot be read gets an
+//! `error` event with the reason (see `run_json_lines`). `end` and `summary` events
+//! count both the matching lines and the matches in them (see `Stats::matches`), and the
+//! lines skipped with `--match-timeout` (see `Stats::timeouts`).
+//!
+//! Match events of `&` and Boolean expressions list the terms found on the line in
+//! `terms` (e.g. `"terms":["panic"]` for `error|panic`), and `--def` matches name the kind
+//! of definition in `def`. Matches of journal and syslog entries (`--journald`,
+//! `--syslog`) carry the entry's other fields in `fields`. With `--context-hash`, match
+//! events carry the fingerprint of the line and the lines around it in `context_hash`
+//! (see `search::context_hash`), for tracking a finding across line shifts.
+//!
+//! ```text
+//! {"type":"begin","path":"app.log"}
+//! {"type":"match","path":"app.log","line_number":3,"byte_offset":42,"text":"an error","submatches":[{"match":"error","start":3,"end":8}]}
+//! {"type":"end","path":"app.log","matched_lines":1,"matches":1,"timeouts":0}
+//! {"type":"summary","files_searched":1,"files_with_matches":1,"matched_lines":1,"matches":1,"timeouts":0}
+//! ```
+
+use std::fmt::Write as _;
+
+use crate::search::Match;
+
+/// Escape `s` as the contents of a JSON string (without the surrounding quotes).
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render an optional path as a JSON string or `null`.
+fn path_value(path: Option<&str>) -> String {
+    match path {
+        Some(p) => format!("\"{}\"", escape(p)),
+        None => "null".to_string(),
+    }
+}
+
+/// Append a `begin` event marking the start of a file.
+pub fn append_begin(out: &mut String, path: Option<&str>) {
+    let _ = writeln!(out, "{{\"type\":\"begin\",\"path\":{}}}", path_value(path));
+}
+
+/// Append a `match` event (when `is_match`) or a `context` event for a single line.
+pub fn append_line(out: &mut String, path: Option<&str>, m: &Match, is_match: bool) {
+    append_line_tagged(out, path, m, is_match, None);
+}
+
+/// Like `append_line`, with a `def` field naming the kind of symbol the line defines
+/// (`--def`), if any.
+pub fn append_line_tagged(
+    out: &mut String,
+    path: Option<&str>,
+    m: &Match,
+    is_match: bool,
+    def: Option<&str>,
+) {
+    append_line_event(out, path, m, is_match, def, &[]);
+}
+
+/// Like `append_line`, with the fields of a journal or syslog entry (see `journal`) in a
+/// `fields` object.
+pub fn append_line_with_fields(
+    out: &mut String,
+    path: Option<&str>,
+    m: &Match,
+    is_match: bool,
+    fields: &[(String, String)],
+) {
+    append_line_event(out, path, m, is_match, None, fields);
+}
+
+fn append_line_event(
+    out: &mut String,
+    path: Option<&str>,
+    m: &Match,
+    is_match: bool,
+    def: Option<&str>,
+    fields: &[(String, String)],
+) {
+    let kind = if is_match { "match" } else { "context" };
+    let _ = write!(
+        out,
+        "{{\"type\":\"{}\",\"path\":{},\"line_number\":{},\"byte_offset\":{},\"text\":\"{}\",\"submatches\":[",
+        kind,
+        path_value(path),
+        m.line_number,
+        m.byte_offset,
+        escape(&m.line)
+    );
+    for (i, span) in m.spans.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(
+            out,
+            "{{\"match\":\"{}\",\"start\":{},\"end\":{}}}",
+            escape(&m.line[span.clone()]),
+            span.start,
+            span.end
+        );
+    }
+    out.push(']');
+    if !m.terms.is_empty() {
+        out.push_str(",\"terms\":[");
+        for (i, term) in m.terms.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "\"{}\"", escape(term));
+        }
+        out.push(']');
+    }
+    if let Some(def) = def {
+        let _ = write!(out, ",\"def\":\"{}\"", escape(def));
+    }
+    if let Some(hash) = m.context_hash {
+        let _ = write!(out, ",\"context_hash\":\"{:016x}\"", hash);
+    }
+    if !fields.is_empty() {
+        out.push_str(",\"fields\":{");
+        for (i, (name, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "\"{}\":\"{}\"", escape(name), escape(value));
+        }
+        out.push('}');
+    }
+    out.push_str("}\n");
+}
+
+/// Append an `end` event carrying the number of matching lines in the file, of the
+/// matches in them (see `Stats::matches`), and of the lines that timed out.
+pub fn append_end(
+    out: &mut String,
+    path: Option<&str>,
+    matched_lines: usize,
+    matches: usize,
+    timeouts: usize,
+) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"end\",\"path\":{},\"matched_lines\":{},\"matches\":{},\"timeouts\":{}}}",
+        path_value(path),
+        matched_lines,
+        matches,
+        timeouts
+    );
+}
+
+/// Append a `path_match` event for a file whose path matched but no line did
+/// (`Config::path_or_content`).
+pub fn append_path_match(out: &mut String, path: Option<&str>) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"path_match\",\"path\":{}}}",
+        path_value(path)
+    );
+}
+
+/// Append the final `summary` event of a run.
+pub fn append_summary(
+    out: &mut String,
+    files_searched: usize,
+    files_with_matches: usize,
+    matched_lines: usize,
+    matches: usize,
+    timeouts: usize,
+) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"summary\",\"files_searched\":{},\"files_with_matches\":{},\"matched_lines\":{},\"matches\":{},\"timeouts\":{}}}",
+        files_searched, files_with_matches, matched_lines, matches, timeouts
+    );
+}
+
+/// Append an `error` event for a file that could not be opened or read, in place of its
+/// `end` event.
+pub fn append_error(out: &mut String, path: Option<&str>, message: &str) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"error\",\"path\":{},\"message\":\"{}\"}}",
+        path_value(path),
+        escape(message)
+    );
+}
+
+/// Append a `no_match` event with the `--no-match-message` text, after the `summary` of a
+/// run that found nothing.
+pub fn append_no_match(out: &mut String, message: &str) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"no_match\",\"message\":\"{}\"}}",
+        escape(message)
+    );
+}
+
+/// The counts of a `summary` event: `(files_searched, files_with_matches, matched_lines,
+/// matches, timeouts)`.
+pub type SummaryCounts = (usize, usize, usize, usize, usize);
+
+/// Split output ending in a `summary` event into the events before it and the summary's
+/// counts; `None` for other output.
+pub fn split_summary(output: &str) -> Option<(&str, SummaryCounts)> {
+    let body = output.strip_suffix('\n')?;
+    let start = body.rfind('\n').map_or(0, |i| i + 1);
+    let fields = body[start..]
+        .strip_prefix("{\"type\":\"summary\",\"files_searched\":")?
+        .strip_suffix('}')?;
+    let (searched, rest) = fields.split_once(",\"files_with_matches\":")?;
+    let (with_matches, rest) = rest.split_once(",\"matched_lines\":")?;
+    let (lines, rest) = rest.split_once(",\"matches\":")?;
+    let (matches, timeouts) = rest.split_once(",\"timeouts\":")?;
+    let counts = (
+        searched.parse().ok()?,
+        with_matches.parse().ok()?,
+        lines.parse().ok()?,
+        matches.parse().ok()?,
+        timeouts.parse().ok()?,
+    );
+    Some((&output[..start], counts))
+}
@@ -0,0 +1,228 @@
+//! ripgrep's JSON Lines schema (`--json=rg`).
+//!
+//! The same events as the `json` module, serialized the way `rg --json` writes them, so
+//! that editor plugins and tools that parse ripgrep's output can read rgrep's. Every
+//! event has a `type` and its fields in `data`; paths and texts are objects with a `text`
+//! field, lines keep their line terminator, and `end` and `summary` events carry ripgrep's
+//! `stats` object. Stdin is named `<stdin>`.
+//!
+//! ```text
+//! {"type":"begin","data":{"path":{"text":"app.log"}}}
+//! {"type":"match","data":{"path":{"text":"app.log"},"lines":{"text":"an error\n"},"line_number":3,"absolute_offset":42,"submatches":[{"match":{"text":"error"},"start":3,"end":8}]}}
+//! {"type":"end","data":{"path":{"text":"app.log"},"binary_offset":null,"stats":{...}}}
+//! {"type":"summary","data":{"elapsed_total":{...},"stats":{...}}}
+//! ```
+//!
+//! ripgrep's schema has no fields for the matched `terms`, `--def` kinds, or the fields of
+//! journal entries, which are left out, and no events for path-only matches or
+//! `--no-match-message` (see `Config::json_schema`). ripgrep reports files it cannot read
+//! on stderr only; rgrep writes an `error` event for them, in the shape of the others.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use super::json::escape;
+/// The counters of ripgrep's `stats` objects are those of a run.
+pub use crate::config::Stats;
+use crate::search::Match;
+
+/// Path ripgrep reports for standard input.
+const STDIN_PATH: &str = "<stdin>";
+
+fn path_value(path: Option<&str>) -> String {
+    format!("{{\"text\":\"{}\"}}", escape(path.unwrap_or(STDIN_PATH)))
+}
+
+fn duration_value(d: Duration) -> String {
+    format!(
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:.6}s\"}}",
+        d.as_secs(),
+        d.subsec_nanos(),
+        d.as_secs_f64()
+    )
+}
+
+fn stats_value(stats: &Stats) -> String {
+    format!(
+        "{{\"elapsed\":{},\"searches\":{},\"searches_with_match\":{},\"bytes_searched\":{},\"bytes_printed\":{},\"matched_lines\":{},\"matches\":{},\"timeouts\":{}}}",
+        duration_value(stats.elapsed),
+        stats.searches,
+        stats.searches_with_match,
+        stats.bytes_searched,
+        stats.bytes_printed,
+        stats.matched_lines,
+        stats.matches,
+        stats.timeouts
+    )
+}
+
+/// Append a `begin` event marking the start of a file.
+pub fn append_begin(out: &mut String, path: Option<&str>) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"begin\",\"data\":{{\"path\":{}}}}}",
+        path_value(path)
+    );
+}
+
+/// Append a `match` event (when `is_match`) or a `context` event for a single line.
+pub fn append_line(out: &mut String, path: Option<&str>, m: &Match, is_match: bool) {
+    let kind = if is_match { "match" } else { "context" };
+    let _ = write!(
+        out,
+        "{{\"type\":\"{}\",\"data\":{{\"path\":{},\"lines\":{{\"text\":\"{}\\n\"}},\"line_number\":{},\"absolute_offset\":{},\"submatches\":[",
+        kind,
+        path_value(path),
+        escape(&m.line),
+        m.line_number,
+        m.byte_offset
+    );
+    for (i, span) in m.spans.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(
+            out,
+            "{{\"match\":{{\"text\":\"{}\"}},\"start\":{},\"end\":{}}}",
+            escape(&m.line[span.clone()]),
+            span.start,
+            span.end
+        );
+    }
+    out.push_str("]}}\n");
+}
+
+/// Append an `end` event with the `stats` of the file.
+pub fn append_end(out: &mut String, path: Option<&str>, stats: &Stats) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"end\",\"data\":{{\"path\":{},\"binary_offset\":null,\"stats\":{}}}}}",
+        path_value(path),
+        stats_value(stats)
+    );
+}
+
+/// Append the final `summary` event of a run, with the total of the files' `stats` and
+/// the wall-clock time of the whole run.
+pub fn append_summary(out: &mut String, stats: &Stats, elapsed_total: Duration) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"summary\",\"data\":{{\"elapsed_total\":{},\"stats\":{}}}}}",
+        duration_value(elapsed_total),
+        stats_value(stats)
+    );
+}
+
+/// Split output ending in a `summary` event into the events before it, the summary's
+/// `stats`, and its `elapsed_total`; `None` for other output.
+pub fn split_summary(output: &str) -> Option<(&str, Stats, Duration)> {
+    let body = output.strip_suffix('\n')?;
+    let start = body.rfind('\n').map_or(0, |i| i + 1);
+    let data = body[start..]
+        .strip_prefix("{\"type\":\"summary\",\"data\":{\"elapsed_total\":")?
+        .strip_suffix("}}}")?;
+    let (elapsed_total, rest) = parse_duration(data)?;
+    let (elapsed, rest) = parse_duration(rest.strip_prefix(",\"stats\":{\"elapsed\":")?)?;
+    let (searches, rest) = rest
+        .strip_prefix(",\"searches\":")?
+        .split_once(",\"searches_with_match\":")?;
+    let (searches_with_match, rest) = rest.split_once(",\"bytes_searched\":")?;
+    let (bytes_searched, rest) = rest.split_once(",\"bytes_printed\":")?;
+    let (bytes_printed, rest) = rest.split_once(",\"matched_lines\":")?;
+    let (matched_lines, rest) = rest.split_once(",\"matches\":")?;
+    let (matches, timeouts) = rest.split_once(",\"timeouts\":")?;
+    let stats = Stats {
+        elapsed,
+        searches: searches.parse().ok()?,
+        searches_with_match: searches_with_match.parse().ok()?,
+        bytes_searched: bytes_searched.parse().ok()?,
+        bytes_printed: bytes_printed.parse().ok()?,
+        matched_lines: matched_lines.parse().ok()?,
+        matches: matches.parse().ok()?,
+        timeouts: timeouts.parse().ok()?,
+    };
+    Some((&output[..start], stats, elapsed_total))
+}
+
+/// Read a duration written by `duration_value` from the start of `s`, returning the rest.
+fn parse_duration(s: &str) -> Option<(Duration, &str)> {
+    let (secs, rest) = s.strip_prefix("{\"secs\":")?.split_once(",\"nanos\":")?;
+    let (nanos, rest) = rest.split_once(",\"human\":\"")?;
+    let (_, rest) = rest.split_once("\"}")?;
+    Some((Duration::new(secs.parse().ok()?, nanos.parse().ok()?), rest))
+}
+
+/// Append an `error` event for a file that could not be opened or read, which ripgrep's
+/// schema lacks.
+pub fn append_error(out: &mut String, path: Option<&str>, message: &str) {
+    let _ = writeln!(
+        out,
+        "{{\"type\":\"error\",\"data\":{{\"path\":{},\"message\":\"{}\"}}}}",
+        path_value(path),
+        escape(message)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_follow_ripgrep_schema() {
+        let m = Match {
+            line_number: 3,
+            byte_offset: 42,
+            line: "an \"error\"".to_string(),
+            spans: std::iter::once(4..9).collect(),
+            terms: Vec::new(),
+            context_hash: None,
+        };
+        let mut out = String::new();
+        append_begin(&mut out, None);
+        append_line(&mut out, Some("app.log"), &m, true);
+        assert_eq!(
+            out,
+            concat!(
+                "{\"type\":\"begin\",\"data\":{\"path\":{\"text\":\"<stdin>\"}}}\n",
+                "{\"type\":\"match\",\"data\":{\"path\":{\"text\":\"app.log\"},",
+                "\"lines\":{\"text\":\"an \\\"error\\\"\\n\"},\"line_number\":3,",
+                "\"absolute_offset\":42,\"submatches\":[{\"match\":{\"text\":\"error\"},",
+                "\"start\":4,\"end\":9}]}}\n"
+            )
+        );
+
+        let stats = Stats {
+            elapsed: Duration::from_micros(1500),
+            searches: 1,
+            ..Default::default()
+        };
+        let mut end = String::new();
+        append_end(&mut end, Some("a"), &stats);
+        assert!(end.contains(
+            "\"stats\":{\"elapsed\":{\"secs\":0,\"nanos\":1500000,\"human\":\"0.001500s\"},\"searches\":1,"
+        ));
+    }
+
+    #[test]
+    fn test_split_summary_reads_back_appended_summary() {
+        let stats = Stats {
+            elapsed: Duration::from_micros(1500),
+            searches: 2,
+            searches_with_match: 1,
+            bytes_searched: 30,
+            bytes_printed: 12,
+            matched_lines: 3,
+            matches: 4,
+            timeouts: 1,
+        };
+        let mut out = String::new();
+        append_begin(&mut out, Some("a"));
+        let events = out.clone();
+        append_summary(&mut out, &stats, Duration::new(2, 5));
+        assert_eq!(
+            split_summary(&out),
+            Some((events.as_str(), stats, Duration::new(2, 5)))
+        );
+        assert_eq!(split_summary(&events), None);
+    }
+}
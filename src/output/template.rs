@@ -0,0 +1,152 @@
+//! User-defined output templates (`--format`).
+//!
+//! A template is literal text with placeholders that are filled in for every printed
+//! line, e.g. `{path}:{line}:{column}:{text}`:
+//!
+//! - `{path}`: file name (`(standard input)` for stdin)
+//! - `{line}`: 1-based line number
+//! - `{column}`: 1-based column of the first match (1 when there is none)
+//! - `{byte_offset}`: byte offset of the start of the line
+//! - `{match}`: text of the first match (empty for context lines)
+//! - `{text}`: the whole line
+//! - `{captures[n]}`: capture group `n` of the first match (empty when it did not match)
+//!
+//! `{{` and `}}` produce literal braces, and `\t`, `\n`, and `\\` are unescaped so tabs
+//! can be written on the command line.
+
+use regex::Captures;
+
+use crate::search::Match;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Path,
+    Line,
+    Column,
+    ByteOffset,
+    Match,
+    Text,
+    Capture(usize),
+}
+
+/// A parsed output template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+fn placeholder(name: &str) -> Result<Piece, String> {
+    let piece = match name {
+        "path" => Piece::Path,
+        "line" => Piece::Line,
+        "column" => Piece::Column,
+        "byte_offset" => Piece::ByteOffset,
+        "match" => Piece::Match,
+        "text" => Piece::Text,
+        _ => {
+            let index = name
+                .strip_prefix("captures[")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| format!("unknown --format placeholder '{{{}}}'", name))?;
+            Piece::Capture(index)
+        }
+    };
+    Ok(piece)
+}
+
+impl Template {
+    /// Parse `source`, rejecting unknown placeholders and unbalanced braces.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err("unclosed '{' in --format template".into()),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(placeholder(name.trim())?);
+                }
+                '}' => return Err("unmatched '}' in --format template (use '}}')".into()),
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(c) => {
+                        literal.push('\\');
+                        literal.push(c);
+                    }
+                    None => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Highest capture group index used, if any.
+    pub fn max_capture(&self) -> Option<usize> {
+        self.pieces
+            .iter()
+            .filter_map(|p| match p {
+                Piece::Capture(i) => Some(*i),
+                _ => None,
+            })
+            .max()
+    }
+
+    /// Whether rendering needs the captures of the first match.
+    pub fn uses_captures(&self) -> bool {
+        self.max_capture().is_some()
+    }
+
+    /// Append the rendered line for `m` to `out`, followed by a newline.
+    ///
+    /// `caps` are the captures of the first match on the line, when available.
+    pub fn render(&self, out: &mut String, path: &str, m: &Match, caps: Option<&Captures<'_>>) {
+        let first = m.spans.first();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(s) => out.push_str(s),
+                Piece::Path => out.push_str(path),
+                Piece::Line => out.push_str(&m.line_number.to_string()),
+                Piece::Column => out.push_str(&first.map(|s| s.start + 1).unwrap_or(1).to_string()),
+                Piece::ByteOffset => out.push_str(&m.byte_offset.to_string()),
+                Piece::Match => {
+                    if let Some(span) = first {
+                        out.push_str(&m.line[span.clone()]);
+                    }
+                }
+                Piece::Text => out.push_str(&m.line),
+                Piece::Capture(i) => {
+                    if let Some(group) = caps.and_then(|c| c.get(*i)) {
+                        out.push_str(group.as_str());
+                    }
+                }
+            }
+        }
+        out.push('\n');
+    }
+}
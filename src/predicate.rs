@@ -0,0 +1,364 @@
+//! Predicates on capture groups (`--where`).
+//!
+//! A predicate filters lines that the pattern already selected by comparing the
+//! pattern's capture groups against literals or each other.
+//!
+//! Examples:
+//! - `$1 > 500` - the first group, read as a number, exceeds 500
+//! - `$method == "POST" && $ms >= 1000` - named groups
+//! - `!($status == 200 || $status == 304)`
+//!
+//! Comparisons are numeric when both sides parse as numbers and lexicographic otherwise.
+//! A comparison that refers to a group which did not participate in the match is false.
+
+use regex::{Captures, Regex};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ord == Ordering::Equal,
+            CompareOp::Ne => ord != Ordering::Equal,
+            CompareOp::Lt => ord == Ordering::Less,
+            CompareOp::Le => ord != Ordering::Greater,
+            CompareOp::Gt => ord == Ordering::Greater,
+            CompareOp::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// `$N`: capture group by index.
+    Group(usize),
+    /// `$name`: capture group by name.
+    Named(String),
+    /// A number, quoted string, or bare word.
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Compare(Operand, CompareOp, Operand),
+    Not(Box<Predicate>),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+}
+
+fn resolve<'a>(operand: &'a Operand, caps: &'a Captures<'_>) -> Option<&'a str> {
+    match operand {
+        Operand::Group(i) => caps.get(*i).map(|m| m.as_str()),
+        Operand::Named(name) => caps.name(name).map(|m| m.as_str()),
+        Operand::Literal(s) => Some(s),
+    }
+}
+
+fn compare(left: &str, right: &str) -> Option<Ordering> {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(l), Ok(r)) => l.partial_cmp(&r),
+        _ => Some(left.cmp(right)),
+    }
+}
+
+impl Predicate {
+    /// Evaluate against the captures of one match.
+    pub fn eval(&self, caps: &Captures<'_>) -> bool {
+        match self {
+            Predicate::Compare(left, op, right) => {
+                match (resolve(left, caps), resolve(right, caps)) {
+                    (Some(l), Some(r)) => compare(l, r).is_some_and(|ord| op.holds(ord)),
+                    _ => false,
+                }
+            }
+            Predicate::Not(inner) => !inner.eval(caps),
+            Predicate::And(left, right) => left.eval(caps) && right.eval(caps),
+            Predicate::Or(left, right) => left.eval(caps) || right.eval(caps),
+        }
+    }
+
+    /// Whether any match of `re` in `line` satisfies the predicate.
+    pub fn matches(&self, re: &Regex, line: &str) -> bool {
+        re.captures_iter(line).any(|caps| self.eval(&caps))
+    }
+
+    /// Check that every group referenced by the predicate exists in `re`.
+    pub fn validate(&self, re: &Regex) -> Result<(), String> {
+        match self {
+            Predicate::Compare(left, _, right) => {
+                for operand in [left, right] {
+                    match operand {
+                        Operand::Group(i) if *i >= re.captures_len() => {
+                            return Err(format!(
+                                "--where refers to ${} but the pattern has {} capture group(s)",
+                                i,
+                                re.captures_len() - 1
+                            ));
+                        }
+                        Operand::Named(name) if !re.capture_names().any(|n| n == Some(name)) => {
+                            return Err(format!(
+                                "--where refers to ${} but the pattern has no group named '{}'",
+                                name, name
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(())
+            }
+            Predicate::Not(inner) => inner.validate(re),
+            Predicate::And(left, right) | Predicate::Or(left, right) => {
+                left.validate(re)?;
+                right.validate(re)
+            }
+        }
+    }
+}
+
+/// Parse a `--where` expression.
+pub fn parse_predicate(input: &str) -> Result<Predicate, String> {
+    let mut parser = PredicateParser::new(input);
+    let pred = parser.parse_or()?;
+    parser.skip_whitespace();
+    if let Some(ch) = parser.current_char() {
+        return Err(parser.error(&format!("unexpected '{}'", ch)));
+    }
+    Ok(pred)
+}
+
+struct PredicateParser {
+    input: Vec<char>,
+    pos: usize,
+}
+
+impl PredicateParser {
+    fn new(input: &str) -> Self {
+        Self {
+            input: input.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!(
+            "invalid --where expression at position {}: {}",
+            self.pos + 1,
+            msg
+        )
+    }
+
+    fn current_char(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input.get(self.pos + 1).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.current_char();
+        self.pos += 1;
+        ch
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.current_char().is_some_and(char::is_whitespace) {
+            self.advance();
+        }
+    }
+
+    /// Consume the two-character operator `op` if it is next.
+    fn eat(&mut self, op: &str) -> bool {
+        self.skip_whitespace();
+        let mut chars = op.chars();
+        if self.current_char() == chars.next() && self.peek_char() == chars.next() {
+            self.pos += 2;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, String> {
+        let mut left = self.parse_and()?;
+        while self.eat("||") {
+            let right = self.parse_and()?;
+            left = Predicate::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate, String> {
+        let mut left = self.parse_unary()?;
+        while self.eat("&&") {
+            let right = self.parse_unary()?;
+            left = Predicate::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Predicate, String> {
+        self.skip_whitespace();
+        match self.current_char() {
+            Some('!') if self.peek_char() != Some('=') => {
+                self.advance(); // consume '!'
+                Ok(Predicate::Not(Box::new(self.parse_unary()?)))
+            }
+            Some('(') => {
+                self.advance(); // consume '('
+                let inner = self.parse_or()?;
+                self.skip_whitespace();
+                if self.current_char() != Some(')') {
+                    return Err(self.error("expected closing parenthesis"));
+                }
+                self.advance(); // consume ')'
+                Ok(inner)
+            }
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Predicate, String> {
+        let left = self.parse_operand()?;
+        let op = self.parse_op()?;
+        let right = self.parse_operand()?;
+        Ok(Predicate::Compare(left, op, right))
+    }
+
+    fn parse_op(&mut self) -> Result<CompareOp, String> {
+        self.skip_whitespace();
+        let two = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+        ];
+        for (text, op) in two {
+            if self.eat(text) {
+                return Ok(op);
+            }
+        }
+        let op = match self.current_char() {
+            Some('<') => CompareOp::Lt,
+            Some('>') => CompareOp::Gt,
+            Some('=') => CompareOp::Eq,
+            _ => return Err(self.error("expected a comparison operator")),
+        };
+        self.advance();
+        Ok(op)
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        self.skip_whitespace();
+        match self.current_char() {
+            Some('$') => {
+                self.advance(); // consume '$'
+                let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                if name.is_empty() {
+                    return Err(self.error("expected a group number or name after '$'"));
+                }
+                Ok(match name.parse::<usize>() {
+                    Ok(i) => Operand::Group(i),
+                    Err(_) => Operand::Named(name),
+                })
+            }
+            Some(quote @ ('"' | '\'')) => {
+                self.advance(); // consume opening quote
+                let mut s = String::new();
+                loop {
+                    match self.advance() {
+                        Some('\\') => match self.advance() {
+                            Some(ch) => s.push(ch),
+                            None => return Err(self.error("unterminated string")),
+                        },
+                        Some(ch) if ch == quote => break,
+                        Some(ch) => s.push(ch),
+                        None => return Err(self.error("unterminated string")),
+                    }
+                }
+                Ok(Operand::Literal(s))
+            }
+            _ => {
+                let word =
+                    self.take_while(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+                if word.is_empty() {
+                    return Err(self.error("expected a value"));
+                }
+                Ok(Operand::Literal(word))
+            }
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(ch) = self.current_char() {
+            if !pred(ch) {
+                break;
+            }
+            s.push(ch);
+            self.advance();
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str, pattern: &str, line: &str) -> bool {
+        let re = Regex::new(pattern).unwrap();
+        parse_predicate(expr).unwrap().matches(&re, line)
+    }
+
+    #[test]
+    fn test_numeric_comparison() {
+        assert!(eval("$1 > 500", r"took (\d+)ms", "GET / took 750ms"));
+        assert!(!eval("$1 > 500", r"took (\d+)ms", "GET / took 90ms"));
+        // Numeric, not lexicographic: "90" > "500" as strings
+        assert!(eval("$1 < 500", r"took (\d+)ms", "took 90ms"));
+    }
+
+    #[test]
+    fn test_string_and_named_groups() {
+        let pattern = r"(?P<method>[A-Z]+) (?P<ms>\d+)";
+        assert!(eval(
+            r#"$method == "POST" && $ms >= 10"#,
+            pattern,
+            "POST 10"
+        ));
+        assert!(!eval("$method == GET || $ms > 100", pattern, "POST 10"));
+        assert!(eval("!($method == GET)", pattern, "POST 10"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_predicate("$1 >").is_err());
+        assert!(parse_predicate("$1 500").is_err());
+        assert!(parse_predicate("($1 > 5").is_err());
+        assert!(parse_predicate("$ > 5").is_err());
+    }
+
+    #[test]
+    fn test_validate_group_references() {
+        let re = Regex::new(r"(?P<ms>\d+)").unwrap();
+        assert!(parse_predicate("$1 > 5").unwrap().validate(&re).is_ok());
+        assert!(parse_predicate("$ms > 5").unwrap().validate(&re).is_ok());
+        assert!(parse_predicate("$2 > 5").unwrap().validate(&re).is_err());
+        assert!(
+            parse_predicate("$other > 5")
+                .unwrap()
+                .validate(&re)
+                .is_err()
+        );
+    }
+}
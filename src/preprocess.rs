@@ -0,0 +1,94 @@
+//! External preprocessors (`--pre COMMAND`), for searching formats rgrep cannot read
+//! itself, such as PDFs or office documents.
+//!
+//! A file the preprocessor applies to (all files, or those matching a `--pre-glob`) is
+//! not read directly: the command is run with the file's path as an argument, and its
+//! standard output is searched instead, as text. The command is split at whitespace, not
+//! by a shell; a `{}` argument is replaced by the path, which is otherwise appended:
+//!
+//! ```text
+//! rgrep --pre 'pdftotext {} -' --pre-glob '*.pdf' -R -e invoice docs/
+//! rgrep --pre 'jq -r .message' --pre-glob '*.json' -R -e timeout logs/
+//! ```
+//!
+//! A command that cannot be started or exits unsuccessfully fails the search of that
+//! file like an unreadable file, with the first line of its standard error.
+
+use std::process::{Command, Stdio};
+
+use regex::Regex;
+
+use crate::config::Config;
+use crate::ignore::glob_regex;
+use crate::io_utils::error_reason;
+
+/// The preprocessor of a search (`Config::pre` and `Config::pre_globs`).
+pub(crate) struct Preprocessor {
+    program: String,
+    args: Vec<String>,
+    /// The files it applies to; all of them when empty.
+    globs: Vec<Regex>,
+}
+
+impl Preprocessor {
+    /// The preprocessor of `cfg`; `None` without one, and an error for an empty command
+    /// or an invalid glob.
+    pub(crate) fn new(cfg: &Config) -> Result<Option<Self>, String> {
+        let Some(command) = &cfg.pre else {
+            return Ok(None);
+        };
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or("--pre needs a command")?;
+        let globs = cfg
+            .pre_globs
+            .iter()
+            .map(|glob| glob_regex(glob))
+            .collect::<Result<_, _>>()?;
+        Ok(Some(Self {
+            program,
+            args: words.collect(),
+            globs,
+        }))
+    }
+
+    /// Whether the file at `path` is searched through the command.
+    pub(crate) fn applies_to(&self, path: &str) -> bool {
+        if path == "-" {
+            return false;
+        }
+        let path = path.strip_prefix("./").unwrap_or(path).replace('\\', "/");
+        self.globs.is_empty() || self.globs.iter().any(|re| re.is_match(&path))
+    }
+
+    /// Run the command on the file at `path` and return its output, with invalid UTF-8
+    /// replaced by U+FFFD.
+    pub(crate) fn run(&self, path: &str) -> Result<String, String> {
+        let mut cmd = Command::new(&self.program);
+        if self.args.iter().any(|arg| arg == "{}") {
+            cmd.args(
+                self.args
+                    .iter()
+                    .map(|arg| if arg == "{}" { path } else { arg.as_str() }),
+            );
+        } else {
+            cmd.args(&self.args).arg(path);
+        }
+        let output = cmd
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("cannot run {}: {}", self.program, error_reason(&e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().next().unwrap_or_default();
+            return Err(
+                format!("{} failed ({}): {}", self.program, output.status, reason)
+                    .trim_end_matches([' ', ':'])
+                    .to_string(),
+            );
+        }
+        Ok(match String::from_utf8(output.stdout) {
+            Ok(text) => text,
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        })
+    }
+}
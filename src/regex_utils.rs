@@ -3,17 +3,29 @@
 //! These helpers build a unified Regex from the provided patterns and options,
 //! and provide simple ANSI color highlighting of match segments in a line.
 
-use colored::{ColoredString, Colorize};
 use regex::{Regex, RegexBuilder};
 
-use crate::boolean_parser::{BooleanExpr, build_pattern_regexes, parse_boolean_expression};
-use crate::config::Config;
+use crate::boolean_parser::{
+    BooleanExpr, build_pattern_regexes, count_suffix, near_operator, parse_boolean_expression,
+    quoted_literal, regex_group_len,
+};
+use crate::color::{Colors, paint};
+use crate::config::{Config, Syntax};
+use crate::env_expand::resolve_patterns;
+use std::ops::Range;
+
+/// A parsed Boolean expression together with the compiled regex for each of its patterns.
+pub type BooleanMatcher = (BooleanExpr, std::collections::HashMap<String, Regex>);
 
 fn split_unescaped(input: &str, sep: char) -> Vec<String> {
+    let chars: Vec<char> = input.chars().collect();
     let mut parts = Vec::new();
     let mut cur = String::new();
     let mut escaped = false;
-    for ch in input.chars() {
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        i += 1;
         if escaped {
             cur.push(ch);
             escaped = false;
@@ -24,6 +36,12 @@ fn split_unescaped(input: &str, sep: char) -> Vec<String> {
             escaped = true;
             continue;
         }
+        // Separators inside regex groups such as `(?:a&b)` belong to the group
+        if let Some(len) = regex_group_len(&chars[i - 1..]) {
+            cur.extend(&chars[i - 1..i - 1 + len]);
+            i += len - 1;
+            continue;
+        }
         if ch == sep {
             parts.push(cur);
             cur = String::new();
@@ -39,29 +57,35 @@ fn split_unescaped(input: &str, sep: char) -> Vec<String> {
 ///
 /// When the single provided pattern contains '&', it is treated as an AND-expression; for
 /// highlighting we build an alternation of the individual terms. Otherwise, the pattern is
-/// used as-is (multiple `|` inside are treated by the regex engine).
+/// used as-is (multiple `|` inside are treated by the regex engine). For single-regex
+/// patterns (see `is_single_regex`), '&' is always literal.
 pub fn build_regex(cfg: &Config) -> Result<Regex, regex::Error> {
     let raw = cfg.patterns.join("");
-    let parts = if raw.contains('&') {
+    let parts = if raw.contains('&') && !is_single_regex(cfg) {
         Some(split_unescaped(&raw, '&'))
     } else {
         None
     };
 
-    let mut pat = if let Some(ps) = &parts {
+    let pat = if let Some(ps) = &parts {
         ps.iter().map(|s| s.as_str()).collect::<Vec<_>>().join("|")
     } else {
         raw
     };
+    compile_pattern(cfg, pat)
+}
 
-    // Wrap for word/line constraints
-    if cfg.word {
-        pat = format!("\\b(?:{})\\b", pat);
-    }
-    if cfg.line {
-        pat = format!("^(?:{})$", pat);
-    }
+/// Build a Regex matching any of the Boolean expression `terms` (as collected by
+/// `BooleanExpr::get_patterns`), honoring the same options as `build_regex`.
+///
+/// Unlike the raw expression, the terms are always valid regexes, e.g. for quoted terms.
+pub fn build_terms_regex(cfg: &Config, terms: &[String]) -> Result<Regex, regex::Error> {
+    let alternatives: Vec<String> = terms.iter().map(|t| format!("(?:{})", t)).collect();
+    compile_pattern(cfg, alternatives.join("|"))
+}
 
+fn compile_pattern(cfg: &Config, pat: String) -> Result<Regex, regex::Error> {
+    let pat = constrain(cfg, pat);
     let mut builder = RegexBuilder::new(&pat);
     builder.multi_line(true);
     if cfg.case_insensitive {
@@ -70,13 +94,64 @@ pub fn build_regex(cfg: &Config) -> Result<Regex, regex::Error> {
     if cfg.dotall {
         builder.dot_matches_new_line(true);
     }
+    builder.crlf(cfg.crlf);
     builder.build()
 }
 
+/// Wrap `pat` for the word (`-w`) and line (`-x`) constraints in `cfg`.
+fn constrain(cfg: &Config, mut pat: String) -> String {
+    if cfg.word {
+        pat = format!("\\b(?:{})\\b", pat);
+    }
+    if cfg.line {
+        pat = format!("^(?:{})$", pat);
+    }
+    pat
+}
+
+/// Build a fancy-regex (`Engine::Fancy`) from `cfg.patterns`, taken as one regex, honoring
+/// the same options as `build_regex`.
+#[cfg(feature = "fancy")]
+pub fn build_fancy_regex(cfg: &Config) -> Result<fancy_regex::Regex, String> {
+    let flags = if cfg.dotall { "(?ms)" } else { "(?m)" };
+    let pat = format!("{}{}", flags, constrain(cfg, cfg.patterns.join("")));
+    fancy_regex::RegexBuilder::new(&pat)
+        .case_insensitive(cfg.case_insensitive)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Build a pattern matching the programming identifier `name` as a whole token.
+///
+/// Unlike a hand-written `\bname\b`, the boundaries follow the characters at each end
+/// of `name`: letters, digits, and `_` must not continue the identifier, and a name
+/// starting or ending with punctuation (e.g. `$var` or `empty?`) must not be attached to
+/// a word on that side. Matching is always case-sensitive, even with `-i`. `name` matches
+/// at the end of a `::` or `.` qualified path too (`take` in `std::mem::take`); with
+/// `qualified` the match spans the whole path (`std::mem::take`, `self.take`), which only
+/// changes what is highlighted or printed by `-o`, not which lines match.
+pub fn ident_pattern(name: &str, qualified: bool) -> String {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    let boundary = |c: Option<char>| match c {
+        Some(c) if !is_ident_char(c) => r"\B",
+        _ => r"\b",
+    };
+    let start = boundary(name.chars().next());
+    let end = boundary(name.chars().next_back());
+    let path = if qualified { r"(?:\w+(?:::|\.))*" } else { "" };
+    format!("(?-i:{}{}{}{})", start, path, regex::escape(name), end)
+}
+
+/// The terms of an `&`-separated AND expression, in the order of the regexes returned by
+/// `build_and_matchers`.
+pub(crate) fn and_terms(cfg: &Config) -> Vec<String> {
+    split_unescaped(&cfg.patterns.join(""), '&')
+}
+
 /// Build regexes for AND parts if '&' is present; otherwise return None.
 pub fn build_and_matchers(cfg: &Config) -> Result<Option<Vec<Regex>>, regex::Error> {
     let raw = cfg.patterns.join("");
-    if !raw.contains('&') {
+    if !raw.contains('&') || is_single_regex(cfg) {
         return Ok(None);
     }
     let parts = split_unescaped(&raw, '&');
@@ -94,12 +169,22 @@ pub fn build_and_matchers(cfg: &Config) -> Result<Option<Vec<Regex>>, regex::Err
         if cfg.dotall {
             b.dot_matches_new_line(true);
         }
+        b.crlf(cfg.crlf);
         regs.push(b.build()?);
     }
     Ok(Some(regs))
 }
 
+/// Wrap every match of `re` in `line` with ANSI escape codes (bold red).
+///
+/// Escape codes are written unconditionally: callers decide whether color is wanted
+/// (via `Config::color`), independent of whether stdout is a terminal.
 pub fn highlight_segments(line: &str, re: &Regex) -> String {
+    highlight_segments_with(line, re, &Colors::default().matched)
+}
+
+/// Like `highlight_segments`, with the SGR parameters `sgr` (e.g. `1;31`) for matches.
+pub fn highlight_segments_with(line: &str, re: &Regex, sgr: &str) -> String {
     let mut result = String::with_capacity(line.len() + 16);
     let mut last = 0;
     for m in re.find_iter(line) {
@@ -107,8 +192,7 @@ pub fn highlight_segments(line: &str, re: &Regex) -> String {
         if s > last {
             result.push_str(&line[last..s]);
         }
-        let seg: ColoredString = line[s..e].to_string().red().bold();
-        result.push_str(&seg.to_string());
+        paint(&mut result, sgr, &line[s..e]);
         last = e;
     }
     if last < line.len() {
@@ -117,18 +201,179 @@ pub fn highlight_segments(line: &str, re: &Regex) -> String {
     result
 }
 
+/// Wrap the byte ranges `spans` of `line` (sorted and non-overlapping, as returned by
+/// `merge_spans`) with the escape codes for `sgr`.
+pub fn highlight_spans(line: &str, spans: &[Range<usize>], sgr: &str) -> String {
+    let mut result = String::with_capacity(line.len() + 16 * spans.len());
+    let mut last = 0;
+    for span in spans {
+        result.push_str(&line[last..span.start]);
+        paint(&mut result, sgr, &line[span.clone()]);
+        last = span.end;
+    }
+    result.push_str(&line[last..]);
+    result
+}
+
+/// Sort `spans` and merge overlapping ones, e.g. matches of several terms on one line.
+pub fn merge_spans(mut spans: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    spans.sort_by_key(|s| (s.start, s.end));
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start < last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
 /// Check if a pattern contains Boolean operations that require complex parsing
 fn has_complex_boolean_ops(pattern: &str) -> bool {
-    // Check for parentheses or mixed operators
+    // Check for parentheses, mixed operators, negation, NEAR, quoted terms, or count
+    // thresholds outside of regex groups
+    let pattern = &without_regex_groups(pattern);
     pattern.contains('(')
         || pattern.contains(')')
         || (pattern.contains('&') && pattern.contains('|'))
+        || has_negation(pattern)
+        || has_near(pattern)
+        || has_quoted_literal(pattern)
+        || has_count_threshold(pattern)
+}
+
+/// Whether `pattern` contains an unescaped count threshold such as `{>=3}`.
+fn has_count_threshold(pattern: &str) -> bool {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut escaped = false;
+    for i in 0..chars.len() {
+        if escaped {
+            escaped = false;
+        } else if chars[i] == '\\' {
+            escaped = true;
+        } else if count_suffix(&chars[i..]).is_some() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether a term of `pattern` is a quoted literal such as `"a&b"`.
+fn has_quoted_literal(pattern: &str) -> bool {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut operand_start = true;
+    let mut escaped = false;
+    for i in 0..chars.len() {
+        let ch = chars[i];
+        if escaped {
+            escaped = false;
+            operand_start = false;
+            continue;
+        }
+        match ch {
+            '"' if operand_start && quoted_literal(&chars[i..]).is_some() => return true,
+            '\\' => escaped = true,
+            '&' | '|' | '(' | '!' | '~' => operand_start = true,
+            c if c.is_whitespace() => {}
+            _ => operand_start = false,
+        }
+    }
+    false
+}
+
+/// Whether `pattern` contains an unescaped NEAR operator `~N~`.
+fn has_near(pattern: &str) -> bool {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut escaped = false;
+    for i in 0..chars.len() {
+        if escaped {
+            escaped = false;
+        } else if chars[i] == '\\' {
+            escaped = true;
+        } else if near_operator(&chars[i..]).is_some() {
+            return true;
+        }
+    }
+    false
+}
+
+/// `pattern` with its `(?...)` regex groups removed, which never carry Boolean operators.
+fn without_regex_groups(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::with_capacity(pattern.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            out.extend(&chars[i..(i + 2).min(chars.len())]);
+            i += 2;
+        } else if let Some(len) = regex_group_len(&chars[i..]) {
+            i += len;
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Whether an operand of `pattern` starts with the NOT operator '!'.
+fn has_negation(pattern: &str) -> bool {
+    let mut operand_start = true;
+    let mut escaped = false;
+    for ch in pattern.chars() {
+        if escaped {
+            escaped = false;
+            operand_start = false;
+            continue;
+        }
+        match ch {
+            '!' if operand_start => return true,
+            '\\' => escaped = true,
+            '&' | '|' | '(' => operand_start = true,
+            c if c.is_whitespace() => {}
+            _ => operand_start = false,
+        }
+    }
+    false
+}
+
+/// Whether capture groups of the pattern are referenced (`--where`, `--filter-script`, or
+/// `{captures[n]}` in `--format`); its parentheses are then capture groups, not Boolean grouping.
+pub(crate) fn captures_referenced(cfg: &Config) -> bool {
+    cfg.where_expr.is_some()
+        || cfg.filter_script.is_some()
+        || cfg.group.is_some()
+        || cfg
+            .format
+            .as_deref()
+            .is_some_and(|f| f.contains("{captures["))
+}
+
+/// Whether the pattern in `cfg` is one regex in which `&`, `!`, `~N~`, and parentheses have
+/// no Boolean meaning: with multiline matching (`-U`) and literal or basic syntax (`-F`,
+/// `-G`).
+pub(crate) fn is_single_regex(cfg: &Config) -> bool {
+    cfg.multiline || cfg.syntax != Syntax::Extended
+}
+
+/// A caret diagnostic (see `ParseError::diagnostic`) when the pattern in `cfg` is a
+/// Boolean expression that fails to parse; `None` for any other pattern.
+pub fn boolean_diagnostic(cfg: &Config) -> Option<String> {
+    if captures_referenced(cfg) || cfg.definition.is_some() || is_single_regex(cfg) {
+        return None;
+    }
+    let cfg = resolve_patterns(cfg).ok()?;
+    let raw = cfg.patterns.join("");
+    if !has_complex_boolean_ops(&raw) {
+        return None;
+    }
+    parse_boolean_expression(&raw)
+        .err()
+        .map(|e| e.diagnostic(&raw))
 }
 
 /// Parse Boolean expression if complex, otherwise return None
-pub fn parse_boolean_if_complex(
-    cfg: &Config,
-) -> Result<Option<(BooleanExpr, std::collections::HashMap<String, Regex>)>, String> {
+pub fn parse_boolean_if_complex(cfg: &Config) -> Result<Option<BooleanMatcher>, String> {
     if cfg.patterns.is_empty() {
         return Ok(None);
     }
@@ -0,0 +1,181 @@
+//! In-place editing of matching files (`--write`, with `--replace TEMPLATE`).
+//!
+//! `rewrite` replaces the matches in every selected file like `--replace` does on output,
+//! and writes each changed file back atomically: the new content goes to a temporary file
+//! next to it, which then takes its place with a rename, so a reader never sees a
+//! half-written file. The temporary file keeps the original's permissions, and a symlink
+//! keeps pointing at the edited file.
+//!
+//! With `Config::backup_suffix`, the original is first copied to the path with the suffix
+//! appended (`app.conf` to `app.conf.bak`). With `Config::dry_run`, nothing is written and
+//! the result only describes the changes; `Config::diff` also skips writing and adds a
+//! unified diff of every change to the result. Files that are not valid UTF-8 or cannot be
+//! written are left as they were and reported in `RewriteResult::errors`; the other files
+//! are still rewritten. Line endings (`\n` or `\r\n`) are kept.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::config::{Config, ExitStatus};
+use crate::matcher::Matcher;
+use crate::output::diff::{DEFAULT_CONTEXT, append_unified_diff};
+use crate::search::select_files;
+
+/// What rewriting one file changed (or would change, with `Config::dry_run`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRewrite {
+    pub path: String,
+    /// Number of lines changed.
+    pub lines: usize,
+    /// Number of matches replaced.
+    pub replacements: usize,
+    /// Where the original content was kept (`Config::backup_suffix`).
+    pub backup: Option<String>,
+    /// Unified diff of the change (`Config::diff`).
+    pub diff: Option<String>,
+}
+
+/// Outcome of `rewrite`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RewriteResult {
+    /// The changed files, in input order.
+    pub files: Vec<FileRewrite>,
+    /// `path: reason` for every file that could not be rewritten.
+    pub errors: Vec<String>,
+}
+
+impl RewriteResult {
+    /// `MatchFound` when any file was (or would be) changed.
+    pub fn status(&self) -> ExitStatus {
+        if self.files.is_empty() {
+            ExitStatus::NoMatch
+        } else {
+            ExitStatus::MatchFound
+        }
+    }
+}
+
+/// Replace the matches of the pattern with `Config::replace` in the files selected by
+/// `inputs`, writing each changed file back in place (see the module docs).
+///
+/// Errors only when nothing can be rewritten at all (e.g. an invalid pattern or stdin as
+/// input); problems with single files end up in `RewriteResult::errors`.
+pub fn rewrite(cfg: &Config, inputs: &[String]) -> Result<RewriteResult, String> {
+    let Some(template) = &cfg.replace else {
+        return Err("--write needs --replace TEMPLATE".into());
+    };
+    let matcher = Matcher::new(cfg)?;
+    if matcher.is_windowed() {
+        return Err("NEAR (~N~) is not supported with --write".into());
+    }
+    if matcher.is_multiline() {
+        return Err("--write is not supported in multiline mode (-U)".into());
+    }
+    let list = select_files(cfg, inputs)?;
+    if list.files.iter().any(|(_, path)| path == "-") {
+        let mode = if cfg.diff { "--diff" } else { "--write" };
+        return Err(format!(
+            "{} cannot rewrite standard input; name the files to edit",
+            mode
+        ));
+    }
+
+    let mut result = RewriteResult::default();
+    for (_, path) in list.files {
+        match rewrite_file(cfg, &matcher, template, &path) {
+            Ok(Some(file)) => result.files.push(file),
+            Ok(None) => {}
+            Err(e) => result.errors.push(format!("{}: {}", path, e)),
+        }
+    }
+    Ok(result)
+}
+
+/// `text` with the matches on every matching line replaced by `template`, along with the
+/// number of lines changed and of matches replaced.
+pub fn replace_in_text(matcher: &Matcher, template: &str, text: &str) -> (String, usize, usize) {
+    let (old, new, replacements) = replace_lines(matcher, template, text);
+    let lines = old.iter().zip(&new).filter(|(o, n)| o != n).count();
+    (new.concat(), lines, replacements)
+}
+
+/// The lines of `text` with their endings, each line after replacing its matches, and
+/// the number of matches replaced.
+fn replace_lines<'a>(
+    matcher: &Matcher,
+    template: &str,
+    text: &'a str,
+) -> (Vec<&'a str>, Vec<String>, usize) {
+    let old: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut replacements = 0;
+    let new = old
+        .iter()
+        .map(|line| {
+            let body = line.trim_end_matches(['\n', '\r']);
+            if !matcher.is_match(body) {
+                return line.to_string();
+            }
+            let (replaced, spans) = matcher.replace(body, template);
+            replacements += spans.len();
+            replaced + &line[body.len()..]
+        })
+        .collect();
+    (old, new, replacements)
+}
+
+/// Rewrite a single file; `None` when nothing in it changes.
+fn rewrite_file(
+    cfg: &Config,
+    matcher: &Matcher,
+    template: &str,
+    path: &str,
+) -> io::Result<Option<FileRewrite>> {
+    let text = fs::read_to_string(path)?;
+    let (old, new, replacements) = replace_lines(matcher, template, &text);
+    let lines = old.iter().zip(&new).filter(|(o, n)| o != n).count();
+    if lines == 0 {
+        return Ok(None);
+    }
+    let backup = cfg.backup_suffix.as_ref().map(|s| format!("{}{}", path, s));
+    let diff = cfg.diff.then(|| {
+        let mut diff = String::new();
+        append_unified_diff(&mut diff, path, &old, &new, DEFAULT_CONTEXT);
+        diff
+    });
+    if !cfg.dry_run && !cfg.diff {
+        let new_text = new.concat();
+        // Edit the file a symlink points to instead of replacing the link
+        let target = fs::canonicalize(path)?;
+        if let Some(backup) = &backup {
+            fs::copy(&target, backup)?;
+        }
+        write_atomically(&target, new_text.as_bytes())?;
+    }
+    Ok(Some(FileRewrite {
+        path: path.to_string(),
+        lines,
+        replacements,
+        backup,
+        diff,
+    }))
+}
+
+/// Replace `target` with `data` through a temporary file in the same directory, so the
+/// rename stays on one filesystem.
+fn write_atomically(target: &Path, data: &[u8]) -> io::Result<()> {
+    let dir = target.parent().unwrap_or(Path::new("."));
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!(".{}.rgrep-{}.tmp", name, std::process::id()));
+    let written = (|| {
+        let mut file = File::create_new(&tmp)?;
+        file.write_all(data)?;
+        file.set_permissions(fs::metadata(target)?.permissions())?;
+        file.sync_all()?;
+        fs::rename(&tmp, target)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
@@ -0,0 +1,103 @@
+//! User scripts deciding which matching lines to keep (`--filter-script`).
+//!
+//! An escape hatch for filters that `--where` cannot express: a small [Rhai] script is
+//! run for every line the pattern matched, and the line is kept when the script returns
+//! `true`. The script sees these variables:
+//! - `line`: the matching line (after any `--map` transformations)
+//! - `captures`: the capture groups of the first match on the line, `captures[0]` being
+//!   the whole match; groups that did not participate are `()`
+//! - `path`: the file being searched, as printed, or `()` for stdin
+//! - `line_number`: the 1-based line number
+//!
+//! ```text
+//! // keep slow requests outside of the health checks
+//! captures[1].parse_int() > 500 && !line.contains("/health")
+//! ```
+//!
+//! A script that fails at runtime, returns something other than a Boolean, or runs for
+//! more than `MAX_OPERATIONS` operations drops the line.
+//!
+//! [Rhai]: https://rhai.rs
+
+use regex::Regex;
+use rhai::{AST, Array, Dynamic, Engine, Scope};
+
+/// Operations a script may run per line before it is stopped, so that an endless loop
+/// cannot hang the search.
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A compiled filter script.
+pub struct FilterScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl FilterScript {
+    /// Compile `source`; `name` identifies the script in error messages.
+    pub fn compile(name: &str, source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine
+            .compile(source)
+            .map_err(|e| format!("invalid filter script {}: {}", name, e))?;
+        Ok(Self { engine, ast })
+    }
+
+    /// Read and compile the script at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read filter script {}: {}", path, e))?;
+        Self::compile(path, &source)
+    }
+
+    /// Whether the script keeps `line`, whose pattern matches are found with `re`.
+    pub fn keeps(&self, re: &Regex, line: &str, path: Option<&str>, line_number: usize) -> bool {
+        let captures: Array = match re.captures(line) {
+            Some(caps) => caps
+                .iter()
+                .map(|g| g.map_or(Dynamic::UNIT, |m| m.as_str().into()))
+                .collect(),
+            None => Array::new(),
+        };
+        let mut scope = Scope::new();
+        scope.push("line", line.to_string());
+        scope.push("captures", captures);
+        scope.push(
+            "path",
+            path.map_or(Dynamic::UNIT, |p| Dynamic::from(p.to_string())),
+        );
+        scope.push("line_number", line_number as i64);
+        self.engine
+            .eval_ast_with_scope::<bool>(&mut scope, &self.ast)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_sees_line_captures_path_and_number() {
+        let re = Regex::new(r"took (\d+)ms").unwrap();
+        let script = FilterScript::compile(
+            "test",
+            r#"captures[1].parse_int() > 500 && path == "app.log" && line_number > 1"#,
+        )
+        .unwrap();
+        assert!(script.keeps(&re, "GET / took 800ms", Some("app.log"), 2));
+        assert!(!script.keeps(&re, "GET / took 80ms", Some("app.log"), 2));
+        assert!(!script.keeps(&re, "GET / took 800ms", None, 2));
+        assert!(!script.keeps(&re, "GET / took 800ms", Some("app.log"), 1));
+    }
+
+    #[test]
+    fn test_failing_or_non_boolean_script_drops_line() {
+        let re = Regex::new("x").unwrap();
+        for source in ["captures[5] == 1", "42", "loop {}"] {
+            let script = FilterScript::compile("test", source).unwrap();
+            assert!(!script.keeps(&re, "x", None, 1), "{}", source);
+        }
+        assert!(FilterScript::compile("bad.rhai", "if {").is_err());
+    }
+}
@@ -1,327 +1,1749 @@
+#[cfg(feature = "files")]
 use rayon::prelude::*;
+#[cfg(feature = "files")]
+use std::cell::Cell;
+#[cfg(feature = "files")]
+use std::collections::BTreeMap;
 use std::collections::VecDeque;
 use std::fmt::Write as _;
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+#[cfg(feature = "files")]
+use std::sync::Mutex;
+#[cfg(feature = "files")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "files")]
+use std::sync::mpsc;
+use std::time::Duration;
 
-use crate::config::{Config, ExitStatus, RunResult};
-use crate::fs_utils::{expand_inputs, is_binary_path};
-use crate::io_utils::{open_input, read_to_lines};
-use crate::output::append_formatted_line;
-use crate::regex_utils::{build_regex, highlight_segments, parse_boolean_if_complex};
-use regex::Regex;
+use crate::aggregate::{DurationExtractor, format_summary};
+#[cfg(feature = "files")]
+use crate::aggregate::{DurationSummary, summary_cells};
+#[cfg(feature = "files")]
+use crate::batch::run_queries;
+use crate::clock::Instant;
+use crate::color::paint;
+#[cfg(feature = "files")]
+use crate::config::{BinaryFiles, Scheduling, SkipReason, Skipped};
+use crate::config::{
+    Compat, Config, Context, ExitStatus, FileResult, FileTiming, JsonSchema, MatchTimeout,
+    RunResult, Stats,
+};
+#[cfg(feature = "files")]
+use crate::file_list_cache::{self, FileList};
+#[cfg(feature = "files")]
+use crate::fs_utils::{
+    Root, SkippedDir, WalkIssues, WalkKey, binary_files, display_path, input_path, input_roots,
+    is_binary_path, root_label, walk_inputs, walk_inputs_with, walks_in_parallel,
+};
+#[cfg(feature = "files")]
+use crate::ignore::TypeFilter;
+use crate::io_utils::{CountingReader, LineReader, LineSource, Lines};
+#[cfg(feature = "files")]
+use crate::io_utils::{error_reason, is_stream, open_input, open_searchable, read_lossy};
+use crate::matcher::Matcher;
+#[cfg(feature = "files")]
+use crate::merge::{FileOutput, merge_outputs};
+#[cfg(feature = "files")]
+use crate::output::table::{Align, Table};
+use crate::output::template::Template;
+use crate::output::{
+    append_context_line, append_formatted_line, append_group_separator, append_heading,
+    append_path_match, append_styled_line, json, rg_json, shorten_line,
+};
+#[cfg(feature = "files")]
+use crate::preprocess::Preprocessor;
+use crate::regex_utils::highlight_spans;
+#[cfg(feature = "files")]
+use crate::sink::Sink;
+#[cfg(feature = "files")]
+use crate::strategy;
 
-/// Run a search over any `Read` implementor (e.g., a file, stdin, or in-memory buffer).
-///
-/// - `cfg` controls the search behavior (patterns, flags, context, etc.).
-/// - `reader` provides the input text.
-/// - `name` is an optional filename used for prefixes in the formatted output. When `None`,
-///   no filename prefix is added and line numbers start at 1.
-///
-/// Returns a `RunResult` with formatted output (unless `quiet`) and an `ExitStatus` indicating
-/// whether any match was found.
-pub fn run_on_reader<R: Read>(
+/// A line reported by a search, with the positions of the matched text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// 1-based line number.
+    pub line_number: usize,
+    /// Byte offset of the start of the line in the input.
+    pub byte_offset: usize,
+    /// Line content without the trailing newline.
+    pub line: String,
+    /// Byte ranges of matched text within `line`; empty for context and inverted lines.
+    pub spans: Vec<Range<usize>>,
+    /// The terms of an `&` or Boolean expression that matched the line, in expression
+    /// order (see `Matcher::matched_terms`); empty for plain patterns, context, and
+    /// inverted lines.
+    pub terms: Vec<String>,
+    /// With `Config::context_hashes`, the fingerprint of a matching line and the
+    /// `CONTEXT_HASH_RADIUS` lines around it (see `context_hash`); `None` otherwise and
+    /// for context lines.
+    pub context_hash: Option<u64>,
+}
+
+/// Lines before and after a match that its `Match::context_hash` covers.
+pub const CONTEXT_HASH_RADIUS: usize = 2;
+
+/// Fingerprint of `lines`, a matching line with the lines around it: 64-bit FNV-1a over
+/// the lines with surrounding whitespace trimmed, so that it stays the same when edits
+/// elsewhere in the file shift the line or change its indentation. It is stable across
+/// runs, platforms, and releases.
+pub fn context_hash<'a>(lines: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for line in lines {
+        feed(line.trim().as_bytes());
+        feed(b"\n");
+    }
+    hash
+}
+
+/// The `context_hash` of every line of `lines`, with its window clipped at the start
+/// and end of the input.
+fn context_hashes(lines: &Lines) -> Vec<u64> {
+    (0..lines.len())
+        .map(|idx| {
+            let start = idx.saturating_sub(CONTEXT_HASH_RADIUS);
+            let end = (idx + CONTEXT_HASH_RADIUS + 1).min(lines.len());
+            context_hash((start..end).map(|i| lines.text(i)))
+        })
+        .collect()
+}
+
+/// Output and match count of searching a single input.
+#[cfg_attr(not(feature = "files"), allow(dead_code))]
+struct FileSearch {
+    output: String,
+    matched_lines: usize,
+    /// Reported matching lines, for `RunResult::files`.
+    matches: Vec<Match>,
+    /// Values extracted with `Config::duration_stats` from matching lines.
+    durations: Vec<f64>,
+    /// Time spent on the file, recorded with `Config::timings`.
+    timing: Option<FileTiming>,
+    /// With `Config::path_or_content`: the path matched although no line did.
+    path_hit: bool,
+    /// Lines skipped for exceeding `Config::match_timeout_ms`.
+    timeouts: Vec<MatchTimeout>,
+    /// Counters of the search for ripgrep's JSON schema (`JsonSchema::Ripgrep`).
+    stats: Stats,
+}
+
+impl FileSearch {
+    /// Whether the file is reported as a hit, by its content or its path.
+    fn is_hit(&self) -> bool {
+        self.matched_lines > 0 || self.path_hit
+    }
+
+    /// The structured part of the result for the input shown as `path`, found in the
+    /// root labeled `root`.
+    fn file_result(&mut self, path: Option<&str>, root: Option<&str>) -> FileResult {
+        FileResult {
+            path: path.map(str::to_string),
+            root: root.map(str::to_string),
+            matched_lines: self.matched_lines,
+            matches: std::mem::take(&mut self.matches),
+            timeouts: std::mem::take(&mut self.timeouts),
+            error: None,
+        }
+    }
+}
+
+/// Everything compiled once per run from the `Config`.
+pub(crate) struct Searcher {
+    pub(crate) matcher: Matcher,
+    durations: Option<DurationExtractor>,
+    template: Option<Template>,
+    /// `Config::color` resolved once for the whole run.
+    color: bool,
+    /// `Config::pre`.
+    #[cfg(feature = "files")]
+    pub(crate) pre: Option<Preprocessor>,
+}
+
+impl Searcher {
+    pub(crate) fn new(cfg: &Config) -> Result<Self, String> {
+        let durations = match &cfg.duration_stats {
+            Some(pattern) => Some(DurationExtractor::new(pattern)?),
+            None => None,
+        };
+        check_json_schema(cfg)?;
+        #[cfg(not(any(feature = "gzip", feature = "bzip2", feature = "xz", feature = "zstd")))]
+        if cfg.search_zip {
+            return Err(
+                "-z requires rgrep to be built with the `gzip`, `bzip2`, `xz`, or `zstd` feature"
+                    .into(),
+            );
+        }
+        let matcher = Matcher::new(cfg)?;
+        if cfg.replace.is_some() && matcher.is_multiline() {
+            return Err("--replace is not supported in multiline mode (-U)".into());
+        }
+        let template = match &cfg.format {
+            Some(source) => {
+                let template = Template::parse(source)?;
+                let groups = matcher.highlight_regex().captures_len() - 1;
+                if let Some(n) = template.max_capture().filter(|&n| n > groups) {
+                    return Err(format!(
+                        "--format uses {{captures[{}]}} but the pattern has {} capture group(s)",
+                        n, groups
+                    ));
+                }
+                Some(template)
+            }
+            None => None,
+        };
+        Ok(Self {
+            matcher,
+            durations,
+            template,
+            color: cfg.color.should_color(),
+            #[cfg(feature = "files")]
+            pre: Preprocessor::new(cfg)?,
+        })
+    }
+}
+
+/// Check that everything compiled from `cfg` (patterns, `--where`, `--format`,
+/// `--duration-stats`) is valid, without searching anything.
+#[cfg(feature = "files")]
+pub(crate) fn validate(cfg: &Config) -> Result<(), String> {
+    Searcher::new(cfg).map(|_| ())
+}
+
+/// Reject the options that ripgrep's JSON schema has no events for.
+fn check_json_schema(cfg: &Config) -> Result<(), String> {
+    if !cfg.json || cfg.json_schema != JsonSchema::Ripgrep {
+        return Ok(());
+    }
+    if cfg.path_or_content {
+        return Err("--path-or-content is not supported with --json=rg".into());
+    }
+    if cfg.no_match_message.is_some() {
+        return Err("--no-match-message is not supported with --json=rg".into());
+    }
+    Ok(())
+}
+
+/// Append the `begin` event of the input `name` in the JSON schema of `cfg`.
+fn append_json_begin(cfg: &Config, out: &mut String, name: Option<&str>) {
+    match cfg.json_schema {
+        JsonSchema::Rgrep => json::append_begin(out, name),
+        JsonSchema::Ripgrep => rg_json::append_begin(out, name),
+    }
+}
+
+/// Append the `match` or `context` event of a line in the JSON schema of `cfg`.
+fn append_json_line(
     cfg: &Config,
-    mut reader: R,
+    matcher: &Matcher,
+    out: &mut String,
     name: Option<&str>,
-) -> Result<RunResult, String> {
-    if cfg.patterns.is_empty() {
-        return Err("no pattern provided".into());
+    m: &Match,
+    is_match: bool,
+) {
+    match cfg.json_schema {
+        JsonSchema::Rgrep => {
+            let def = is_match.then(|| matcher.definition_kind(&m.line)).flatten();
+            json::append_line_tagged(out, name, m, is_match, def);
+        }
+        JsonSchema::Ripgrep => rg_json::append_line(out, name, m, is_match),
+    }
+}
+
+/// Append the events closing the search of the input `name` in the JSON schema of `cfg`:
+/// its `path_match` event if only the path matched, and its `end` event.
+fn append_json_end(
+    cfg: &Config,
+    out: &mut String,
+    name: Option<&str>,
+    path_hit: bool,
+    stats: &Stats,
+) {
+    match cfg.json_schema {
+        JsonSchema::Rgrep => {
+            if path_hit {
+                json::append_path_match(out, name);
+            }
+            json::append_end(
+                out,
+                name,
+                stats.matched_lines,
+                stats.matches,
+                stats.timeouts,
+            );
+        }
+        JsonSchema::Ripgrep => rg_json::append_end(out, name, stats),
     }
+}
+
+/// Append the `error` event of the input `name`, which could not be searched, in the JSON
+/// schema of `cfg`.
+#[cfg(feature = "files")]
+fn append_json_error(cfg: &Config, out: &mut String, name: Option<&str>, message: &str) {
+    match cfg.json_schema {
+        JsonSchema::Rgrep => json::append_error(out, name, message),
+        JsonSchema::Ripgrep => rg_json::append_error(out, name, message),
+    }
+}
+
+/// Append the `summary` event of a run in the JSON schema of `cfg`; `stats` are the
+/// totals of the `searched` inputs, `files_with_matches` of which were hits.
+#[cfg(feature = "files")]
+fn append_json_summary(
+    cfg: &Config,
+    out: &mut String,
+    searched: usize,
+    files_with_matches: usize,
+    stats: &Stats,
+    elapsed_total: Duration,
+) {
+    match cfg.json_schema {
+        JsonSchema::Rgrep => json::append_summary(
+            out,
+            searched,
+            files_with_matches,
+            stats.matched_lines,
+            stats.matches,
+            stats.timeouts,
+        ),
+        JsonSchema::Ripgrep => rg_json::append_summary(out, stats, elapsed_total),
+    }
+}
 
-    // Check for complex Boolean expressions first
-    let boolean_expr = parse_boolean_if_complex(cfg).map_err(|e| e.to_string())?;
+/// Label used in filename prefixes for stdin.
+pub(crate) const STDIN_LABEL: &str = "(standard input)";
 
-    let (re, and_matchers) = if boolean_expr.is_some() {
-        // For Boolean expressions, we still need a regex for highlighting
-        // Use a simple OR of all patterns for highlighting
-        (build_regex(cfg).map_err(|e| e.to_string())?, None)
+/// Append one reported line in the configured output format.
+///
+/// `name` identifies the input (used by JSON); `prefix` is the filename shown in text
+/// output, if any.
+pub(crate) fn emit_line(
+    cfg: &Config,
+    searcher: &Searcher,
+    out: &mut String,
+    name: Option<&str>,
+    prefix: Option<&str>,
+    m: &Match,
+    is_match: bool,
+) {
+    let matcher = &searcher.matcher;
+    if cfg.json {
+        append_json_line(cfg, matcher, out, name, m, is_match);
+        return;
+    }
+    if let Some(template) = &searcher.template {
+        let caps = if is_match && template.uses_captures() {
+            matcher.highlight_regex().captures(&m.line)
+        } else {
+            None
+        };
+        template.render(out, name.unwrap_or(STDIN_LABEL), m, caps.as_ref());
+        return;
+    }
+    // With --replace, matching lines are printed with their matches replaced, and the
+    // replacements are highlighted instead of the matches
+    let replaced = match &cfg.replace {
+        Some(template) if is_match && !m.spans.is_empty() => {
+            Some(matcher.replace(&m.line, template))
+        }
+        _ => None,
+    };
+    let (text, spans) = match &replaced {
+        Some((text, spans)) => (text.as_str(), spans.as_slice()),
+        None => (m.line.as_str(), m.spans.as_slice()),
+    };
+    // Lines too long to read are shortened, matches and context alike
+    let fitted = if cfg.only_matching {
+        None
+    } else {
+        shorten_line(cfg, text, spans)
+    };
+    let (text, spans) = match &fitted {
+        Some((text, spans)) => (text.as_str(), spans.as_slice()),
+        None => (text, spans),
+    };
+    if cfg.vimgrep {
+        // One `file:line:column:text` entry per match; context lines are not listed
+        if is_match {
+            let idx = Some(m.line_number - 1);
+            // Inverted lines have no match and report column 1
+            let columns: Vec<usize> = if m.spans.is_empty() {
+                vec![1]
+            } else {
+                m.spans.iter().map(|s| s.start + 1).collect()
+            };
+            for col in columns {
+                append_formatted_line(out, prefix, idx, Some(col), text, true, cfg.line);
+            }
+        }
+        return;
+    }
+    let idx = if cfg.line_number {
+        Some(m.line_number - 1)
+    } else {
+        None
+    };
+    let colors = searcher.color.then_some(&cfg.colors);
+    if !is_match {
+        if cfg.compat == Compat::Gnu {
+            append_context_line(out, colors, prefix, idx, text);
+        } else {
+            append_styled_line(out, colors, prefix, idx, None, text);
+        }
+        return;
+    }
+    if cfg.only_matching {
+        // One line per non-empty match (or selected group), holding only its text
+        for span in m.spans.iter().filter(|s| !s.is_empty()) {
+            let column = cfg.column.then_some(span.start + 1);
+            let part = &m.line[span.clone()];
+            if searcher.color {
+                let mut hl = String::new();
+                paint(&mut hl, &cfg.colors.matched, part);
+                append_styled_line(out, colors, prefix, idx, column, &hl);
+            } else {
+                append_styled_line(out, colors, prefix, idx, column, part);
+            }
+        }
+        return;
+    }
+    // Column of the first match; inverted lines have no match and report column 1
+    let column = if cfg.column {
+        Some(m.spans.first().map(|s| s.start + 1).unwrap_or(1))
     } else {
-        // Use existing logic
-        let re = build_regex(cfg).map_err(|e| e.to_string())?;
-        let and_matchers =
-            crate::regex_utils::build_and_matchers(cfg).map_err(|e| e.to_string())?;
-        (re, and_matchers)
+        None
     };
+    if searcher.color {
+        // The spans come from the terms that matched (the whole line with -x)
+        let hl = highlight_spans(text, spans, &cfg.colors.matched);
+        append_styled_line(out, colors, prefix, idx, column, &hl);
+    } else {
+        append_styled_line(out, colors, prefix, idx, column, text);
+    }
+}
 
-    let lines = read_to_lines(&mut reader).map_err(|e| e.to_string())?;
+/// Scan a reader line by line, calling `on_line` for every line that should be reported
+/// (context lines with `false`, matching lines with `true`), in input order.
+///
+/// Each line is reported at most once, even where the context regions of nearby matches
+/// overlap. Nothing is reported in count or quiet mode, and with `Config::preview` only the
+/// first matches (and their context) are. Returns what was found beyond the reported
+/// lines (see `Scanned`).
+///
+/// A skipped line counts as not matching, even with `Config::invert`, but may still be
+/// printed as context. `name` is the input as shown to `--filter-script` (`None` for stdin).
+///
+/// Lines are matched as they are read, holding only the lines that may still be printed
+/// as leading context, so memory does not grow with the input. NEAR expressions,
+/// multiline patterns, `Config::context_hashes`, and context given as a share of the
+/// input (`Context::percent`) need the whole input, which is then read first (see
+/// `needs_whole_input`).
+fn scan_reader<R: Read>(
+    cfg: &Config,
+    matcher: &Matcher,
+    reader: R,
+    name: Option<&str>,
+    on_line: &mut dyn FnMut(&Match, bool) -> io::Result<()>,
+) -> io::Result<Scanned> {
+    if !needs_whole_input(cfg, matcher) {
+        let around = cfg.context.for_lines(0);
+        let whole = WholeInput::default();
+        return scan_lines(
+            cfg,
+            matcher,
+            LineReader::new(reader),
+            around,
+            whole,
+            name,
+            on_line,
+        );
+    }
+    // Lines are only copied out of the buffer when they are reported
+    let (lines, whole) = read_whole(cfg, matcher, reader)?;
+    // With `--context-percent`, the context depends on the length of the input
+    let around = cfg.context.for_lines(lines.len());
+    scan_lines(cfg, matcher, lines.cursor(), around, whole, name, on_line)
+}
 
-    let mut out = String::new();
-    let mut matched_any = false;
+/// Read the whole of `reader`, with what is known of its lines up front: NEAR
+/// expressions and multiline patterns decide matches over the whole input.
+fn read_whole<R: Read>(
+    cfg: &Config,
+    matcher: &Matcher,
+    reader: R,
+) -> io::Result<(Lines, WholeInput)> {
+    let lines = Lines::read(reader)?;
+    let whole = WholeInput {
+        window_hits: matcher
+            .is_windowed()
+            .then(|| matcher.match_lines(&lines.texts())),
+        multiline_spans: matcher
+            .is_multiline()
+            .then(|| matcher.multiline_spans(&lines.texts())),
+        hashes: cfg.context_hashes.then(|| context_hashes(&lines)),
+    };
+    Ok((lines, whole))
+}
 
-    let mut before_buf: VecDeque<(usize, String)> = VecDeque::new();
-    let mut after_remaining = 0usize;
+/// Whether `scan_reader` must read the whole input before matching its lines.
+pub(crate) fn needs_whole_input(cfg: &Config, matcher: &Matcher) -> bool {
+    matcher.is_windowed()
+        || matcher.is_multiline()
+        || cfg.context_hashes
+        || cfg.context.percent.is_some()
+}
 
-    let show_filename = name.is_some();
+/// What `scan_lines` knows of every line up front, when the whole input was read.
+#[derive(Default)]
+struct WholeInput {
+    /// Whether each line matches a NEAR expression.
+    window_hits: Option<Vec<bool>>,
+    /// The spans of a multiline pattern in each line, `None` for lines it does not reach.
+    multiline_spans: Option<Vec<Option<Vec<Range<usize>>>>>,
+    /// `Config::context_hashes` of each line.
+    hashes: Option<Vec<u64>>,
+}
+
+/// What `scan_reader` found in an input, beyond the lines it reported.
+struct Scanned {
+    /// Matching lines, including those not reported.
+    matched_lines: usize,
+    /// The matches in the matching lines, including those not reported (see
+    /// `Selection::count_matches`).
+    matches: usize,
+    /// Lines skipped for exceeding `Config::match_timeout_ms`.
+    timeouts: Vec<MatchTimeout>,
+}
+
+/// The loop of `scan_reader` over the lines of `source`, with `around` lines of context.
+fn scan_lines(
+    cfg: &Config,
+    matcher: &Matcher,
+    mut source: impl LineSource,
+    around: Context,
+    whole: WholeInput,
+    name: Option<&str>,
+    on_line: &mut dyn FnMut(&Match, bool) -> io::Result<()>,
+) -> io::Result<Scanned> {
+    let mut selection = Selection::new(cfg, whole);
+    // The lines that may still be printed as leading context, at most `around.before`;
+    // the texts of lines dropped from the front are reused for new ones
+    let mut before_buf: VecDeque<Match> = VecDeque::with_capacity(around.before);
+    let mut after_remaining = 0usize;
 
     let mut match_count = 0usize;
+    let mut counted_matches = 0usize;
+    let limit = preview_limit(cfg);
 
-    for (idx, raw_line) in lines.iter().enumerate() {
-        let is_match = if let Some((ref expr, ref regexes)) = boolean_expr {
-            // Use Boolean expression evaluation
-            expr.matches(raw_line, regexes)
-        } else if let Some(ref ands) = and_matchers {
-            // Use existing AND logic
-            ands.iter().all(|r| r.is_match(raw_line))
-        } else {
-            // Use simple regex matching
-            re.is_match(raw_line)
-        };
-        let final_match = if cfg.invert { !is_match } else { is_match };
+    let mut idx = 0;
+    while let Some((byte_offset, raw_line)) = source.next_line()? {
+        let hit = selection.hit(matcher, name, idx, raw_line);
+        let is_match = hit == Some(true);
+        let final_match = hit.is_some_and(|hit| hit != cfg.invert);
 
         if final_match {
-            matched_any = true;
             match_count += 1;
+            if stops_input_at_first_match(cfg) {
+                break;
+            }
         }
 
-        if cfg.count {
+        let record = |spans: Vec<Range<usize>>, terms: Vec<String>| Match {
+            line_number: idx + 1,
+            byte_offset,
+            line: raw_line.to_string(),
+            spans,
+            terms,
+            context_hash: None,
+        };
+
+        let past_limit = limit.is_some_and(|n| match_count > n);
+        if cfg.count || cfg.quiet || cfg.files_with_matches {
             // Only counting; continue processing to get per-file total
             // reset context buffers appropriately
-            after_remaining = cfg.context.after; // for consistency though not used in count
+            after_remaining = around.after; // for consistency though not used in count
+            if final_match {
+                counted_matches += selection.count_matches(matcher, idx, raw_line, is_match);
+            }
+        } else if past_limit && (final_match || after_remaining == 0) {
+            // Beyond the preview, lines are only counted
+            after_remaining = 0;
+            if final_match {
+                counted_matches += selection.count_matches(matcher, idx, raw_line, is_match);
+            }
         } else if final_match {
             // Print context before
-            if cfg.context.before > 0 {
-                while let Some((bidx, bline)) = before_buf.pop_front() {
-                    append_formatted_line(&mut out, name, bidx, &bline, false, false);
-                }
+            while let Some(before) = before_buf.pop_front() {
+                on_line(&before, false)?;
             }
             // Print the matching line
-            if cfg.color && !cfg.line {
-                // even if -x, we'll highlight entire line when it matches; but to be precise, highlight matches
-                let hl = highlight_segments(raw_line, &re);
-                append_formatted_line(&mut out, name, idx, &hl, true, cfg.line);
-            } else {
-                append_formatted_line(&mut out, name, idx, raw_line, true, cfg.line);
-            }
+            let (spans, terms) = selection.spans(matcher, idx, raw_line, is_match);
+            counted_matches += spans.len().max(1);
+            let mut found = record(spans, terms);
+            found.context_hash = selection.hash(idx);
+            on_line(&found, true)?;
 
             // Prepare after-context printing for next lines
-            after_remaining = cfg.context.after;
+            after_remaining = around.after;
         } else {
-            // Non-matching line; manage before/after buffers
-            if cfg.context.before > 0 {
-                before_buf.push_back((idx, raw_line.clone()));
-                if before_buf.len() > cfg.context.before {
-                    before_buf.pop_front();
-                }
-            }
-
+            // Non-matching line; manage before/after buffers. A line printed as trailing
+            // context is not buffered, so overlapping regions print each line only once.
             if after_remaining > 0 {
-                append_formatted_line(&mut out, name, idx, raw_line, false, false);
+                on_line(&record(Vec::new(), Vec::new()), false)?;
                 after_remaining -= 1;
+            } else if around.before > 0 {
+                let mut line = if before_buf.len() == around.before {
+                    before_buf.pop_front().map(|m| m.line).unwrap_or_default()
+                } else {
+                    String::new()
+                };
+                line.clear();
+                line.push_str(raw_line);
+                before_buf.push_back(Match {
+                    line_number: idx + 1,
+                    byte_offset,
+                    line,
+                    spans: Vec::new(),
+                    terms: Vec::new(),
+                    context_hash: None,
+                });
             }
         }
+        idx += 1;
+    }
 
-        // Separator between groups when both before and after contexts are used
-        if after_remaining == 0 && !out.is_empty() {
-            // No separators for simplicity (GNU grep uses -- between groups)
+    Ok(Scanned {
+        matched_lines: match_count,
+        matches: counted_matches,
+        timeouts: selection.timeouts,
+    })
+}
+
+/// How `scan_lines` and `MatchIter` decide which lines match, one line at a time.
+struct Selection {
+    whole: WholeInput,
+    /// `Config::match_timeout_ms`.
+    budget: Option<Duration>,
+    /// The lines that ran out of time so far.
+    timeouts: Vec<MatchTimeout>,
+}
+
+impl Selection {
+    fn new(cfg: &Config, whole: WholeInput) -> Self {
+        Selection {
+            whole,
+            budget: cfg.match_timeout_ms.map(Duration::from_millis),
+            timeouts: Vec::new(),
         }
     }
 
-    if cfg.count && !cfg.quiet {
-        if show_filename {
-            let _ = writeln!(&mut out, "{}:{}", name.unwrap(), match_count);
-        } else {
-            let _ = writeln!(&mut out, "{}", match_count);
+    /// Whether line `idx` (from 0) matches, before `Config::invert`; `None` when matching
+    /// it ran out of time, which is recorded in `timeouts`.
+    fn hit(
+        &mut self,
+        matcher: &Matcher,
+        name: Option<&str>,
+        idx: usize,
+        line: &str,
+    ) -> Option<bool> {
+        let hit = match (&self.whole.window_hits, &self.whole.multiline_spans) {
+            (Some(hits), _) => Some(hits[idx]),
+            (_, Some(spans)) => Some(spans[idx].is_some()),
+            _ => match self.budget {
+                Some(budget) => {
+                    let start = Instant::now();
+                    let hit = matcher.is_match_before(line, start + budget);
+                    if hit.is_none() {
+                        self.timeouts.push(MatchTimeout {
+                            line_number: idx + 1,
+                            elapsed: start.elapsed(),
+                        });
+                    }
+                    hit
+                }
+                None => Some(matcher.is_match(line)),
+            },
+        };
+        hit.map(|hit| hit && matcher.keeps(line, name, idx + 1))
+    }
+
+    /// The spans and matched terms of line `idx`, empty unless it matched (`is_match`).
+    fn spans(
+        &mut self,
+        matcher: &Matcher,
+        idx: usize,
+        line: &str,
+        is_match: bool,
+    ) -> (Vec<Range<usize>>, Vec<String>) {
+        match &mut self.whole.multiline_spans {
+            Some(spans) if is_match => (spans[idx].take().unwrap_or_default(), Vec::new()),
+            _ if is_match => (matcher.find_spans(line), matcher.matched_terms(line)),
+            _ => (Vec::new(), Vec::new()),
         }
     }
 
-    let status = if matched_any {
-        ExitStatus::MatchFound
+    /// The number of matches in the selected line `idx`, for `Stats::matches` and
+    /// `Config::count_matches`. Lines selected without matched text, with
+    /// `Config::invert` or by a negation, count once.
+    fn count_matches(
+        &mut self,
+        matcher: &Matcher,
+        idx: usize,
+        line: &str,
+        is_match: bool,
+    ) -> usize {
+        let found = match &mut self.whole.multiline_spans {
+            Some(spans) if is_match => spans[idx].take().map_or(0, |s| s.len()),
+            _ if is_match => matcher.find_spans(line).len(),
+            _ => 0,
+        };
+        found.max(1)
+    }
+
+    /// `Config::context_hashes` of line `idx`.
+    fn hash(&self, idx: usize) -> Option<u64> {
+        self.whole.hashes.as_ref().map(|h| h[idx])
+    }
+}
+
+/// Search a single reader, producing formatted output and the number of matching lines.
+///
+/// `name` identifies the input (`None` for stdin); `show_name` controls whether text output
+/// is prefixed with it. With `live`, the lines are written to it as they are found, unless
+/// they are grouped under a heading, and `FileSearch::output` only holds what follows them
+/// (see `run_to_writer`).
+fn search_reader<R: Read>(
+    cfg: &Config,
+    searcher: &Searcher,
+    reader: R,
+    name: Option<&str>,
+    show_name: bool,
+    mut live: Option<&mut dyn Write>,
+) -> Result<FileSearch, String> {
+    let matcher = &searcher.matcher;
+    let mut out = String::new();
+    let mut durations = Vec::new();
+    let mut matches = Vec::new();
+    let label = name.unwrap_or(STDIN_LABEL);
+    // Vimgrep entries always name their file
+    let show_name = show_name || cfg.vimgrep;
+    // In heading mode the name is printed once above the lines instead of on each one
+    let heading = show_name && uses_headings(cfg);
+    let prefix = if show_name && !heading {
+        Some(label)
     } else {
-        ExitStatus::NoMatch
+        None
+    };
+
+    if cfg.json && !cfg.quiet {
+        append_json_begin(cfg, &mut out, name);
+    }
+
+    let start = Instant::now();
+    let mut bytes_searched = 0u64;
+    let reader = CountingReader::new(reader, &mut bytes_searched);
+    let separate_groups = separates_groups(cfg, searcher);
+    let mut last_line: Option<usize> = None;
+    if cfg.quiet || heading {
+        live = None;
+    }
+    // Bytes already written to `live`
+    let mut written = 0u64;
+    let scanned = scan_reader(cfg, matcher, reader, name, &mut |m, is_match| {
+        if is_match {
+            matches.push(m.clone());
+        }
+        if separate_groups && last_line.is_some_and(|n| m.line_number > n + 1) {
+            append_group_separator(&mut out, searcher.color.then_some(&cfg.colors));
+        }
+        last_line = Some(m.line_number);
+        match &searcher.durations {
+            // Duration mode replaces line output with a summary of the extracted values
+            Some(extractor) => {
+                if is_match {
+                    durations.extend(extractor.extract(&m.line));
+                }
+            }
+            None => emit_line(cfg, searcher, &mut out, name, prefix, m, is_match),
+        }
+        if let Some(live) = live.as_mut() {
+            live.write_all(out.as_bytes())?;
+            written += out.len() as u64;
+            out.clear();
+        }
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    let match_count = scanned.matched_lines;
+
+    // Note how many matches the preview left out; quickfix entries and templated lines
+    // stay machine-readable, and JSON consumers see the full count in the end event
+    let hidden = preview_limit(cfg).map_or(0, |n| match_count.saturating_sub(n));
+    if hidden > 0
+        && !(cfg.count || cfg.quiet || cfg.json || cfg.vimgrep || searcher.template.is_some())
+    {
+        if let Some(n) = prefix {
+            out.push_str(n);
+            out.push(':');
+        }
+        let _ = writeln!(&mut out, "\u{2026} (+{} more matches)", hidden);
+    }
+
+    // Files without matching lines are still reported when their path matches
+    let path_hit = cfg.path_or_content
+        && match_count == 0
+        && name.is_some_and(|n| searcher.matcher.is_match(n));
+
+    let stats = Stats {
+        elapsed: start.elapsed(),
+        searches: 1,
+        searches_with_match: usize::from(match_count > 0),
+        bytes_searched,
+        bytes_printed: written + out.len() as u64,
+        matched_lines: match_count,
+        matches: scanned.matches,
+        timeouts: scanned.timeouts.len(),
     };
 
     if cfg.quiet {
-        Ok(RunResult {
-            output: String::new(),
-            status,
-        })
+        out.clear();
+    } else if cfg.files_with_matches {
+        out.clear();
+        if match_count > 0 {
+            append_heading(&mut out, label, searcher.color.then_some(&cfg.colors));
+        }
+    } else if heading && !out.is_empty() {
+        let mut grouped = String::with_capacity(label.len() + 1 + out.len());
+        append_heading(&mut grouped, label, searcher.color.then_some(&cfg.colors));
+        grouped.push_str(&out);
+        out = grouped;
+    } else if cfg.json {
+        append_json_end(cfg, &mut out, name, path_hit, &stats);
+    } else if searcher.durations.is_some() {
+        match prefix {
+            Some(n) => {
+                let _ = writeln!(&mut out, "{}: {}", n, format_summary(&durations));
+            }
+            None => {
+                let _ = writeln!(&mut out, "{}", format_summary(&durations));
+            }
+        }
+    } else if cfg.count {
+        let count = counted(cfg, match_count, &stats);
+        match prefix {
+            Some(n) => {
+                let _ = writeln!(&mut out, "{}:{}", n, count);
+            }
+            None => {
+                let _ = writeln!(&mut out, "{}", count);
+            }
+        }
+    }
+
+    Ok(FileSearch {
+        output: out,
+        matched_lines: match_count,
+        matches,
+        durations,
+        timing: None,
+        path_hit,
+        timeouts: scanned.timeouts,
+        stats,
+    })
+}
+
+/// The number printed for an input in count mode: its matching lines, or with
+/// `Config::count_matches` its matches.
+fn counted(cfg: &Config, matched_lines: usize, stats: &Stats) -> usize {
+    if cfg.count_matches {
+        stats.matches
     } else {
-        Ok(RunResult {
-            output: out,
-            status,
-        })
+        matched_lines
+    }
+}
+
+/// Number of matching lines reported per file with `Config::preview`; summaries see
+/// every match.
+fn preview_limit(cfg: &Config) -> Option<usize> {
+    cfg.preview.filter(|_| cfg.duration_stats.is_none())
+}
+
+/// Whether line output is grouped under per-file headings (summaries, JSON, and file
+/// lists never are).
+fn uses_headings(cfg: &Config) -> bool {
+    cfg.heading
+        && !cfg.files_with_matches
+        && !cfg.json
+        && !cfg.vimgrep
+        && cfg.format.is_none()
+        && !cfg.count
+        && cfg.duration_stats.is_none()
+}
+
+/// Whether `--` is printed between groups of lines that are not adjacent, as GNU grep
+/// does with context (`Compat::Gnu`).
+fn separates_groups(cfg: &Config, searcher: &Searcher) -> bool {
+    cfg.compat == Compat::Gnu
+        && cfg.context.is_enabled()
+        && !(cfg.count || cfg.quiet || cfg.files_with_matches || cfg.json || cfg.vimgrep)
+        && searcher.template.is_none()
+        && searcher.durations.is_none()
+}
+
+/// Whether the search ends at the first matching line, as `grep -q` does
+/// (`Compat::Gnu` in quiet mode).
+pub(crate) fn stops_at_first_match(cfg: &Config) -> bool {
+    cfg.compat == Compat::Gnu && cfg.quiet
+}
+
+/// Whether each input is searched only up to its first matching line: when the search
+/// ends there, or only the names of files with a match are printed (`-l`).
+fn stops_input_at_first_match(cfg: &Config) -> bool {
+    stops_at_first_match(cfg) || cfg.files_with_matches
+}
+
+/// Whether path-only hits are listed in text output (summaries and JSON report them
+/// differently).
+fn reports_path_hits(cfg: &Config) -> bool {
+    cfg.path_or_content && !cfg.quiet && !cfg.json && !cfg.count && cfg.duration_stats.is_none()
+}
+
+fn status_for(matched_any: bool) -> ExitStatus {
+    if matched_any {
+        ExitStatus::MatchFound
+    } else {
+        ExitStatus::NoMatch
+    }
+}
+
+/// Run a search over any `Read` implementor (e.g., a file, stdin, or in-memory buffer).
+///
+/// - `cfg` controls the search behavior (patterns, flags, context, etc.).
+/// - `reader` provides the input text.
+/// - `name` is an optional filename identifying the input. It is printed as a prefix only
+///   when `cfg.with_filename` is `Some(true)`; line numbers start at 1.
+///
+/// Returns a `RunResult` with formatted output (unless `quiet`) and an `ExitStatus` indicating
+/// whether any match was found.
+pub fn run_on_reader<R: Read>(
+    cfg: &Config,
+    reader: R,
+    name: Option<&str>,
+) -> Result<RunResult, String> {
+    let searcher = Searcher::new(cfg)?;
+    let show_name = cfg.with_filename.unwrap_or(false);
+    let mut found = search_reader(cfg, &searcher, reader, name, show_name, None)?;
+    let hit = found.is_hit();
+    let files = vec![found.file_result(name, None)];
+    let mut output = found.output;
+    if found.path_hit && reports_path_hits(cfg) {
+        append_path_match(&mut output, name.unwrap_or(STDIN_LABEL));
+    }
+    Ok(RunResult {
+        output,
+        status: status_for(hit),
+        warnings: timeout_warnings(&files),
+        timings: Vec::new(),
+        files,
+        errors: Vec::new(),
+        skipped: Vec::new(),
+        stats: found.stats,
+    })
+}
+
+/// Search any `Read` implementor for the lines `cfg` selects, returned lazily as `Match`
+/// values with no output formatted.
+///
+/// Lines are selected as with `run_on_reader` (patterns, `Config::invert`, predicates and
+/// filters), and read only as the iterator is advanced, so stopping early stops reading.
+/// Settings that only shape output, such as context, counting, quiet mode, and previews,
+/// do not apply. NEAR expressions, multiline patterns, and `Config::context_hashes` need
+/// the whole input, which is then read here first. A read error ends the iteration.
+///
+/// Returns an error for an invalid configuration or, when the whole input is read up
+/// front, one reading it.
+pub fn search_iter<'a, R: Read + 'a>(cfg: &'a Config, reader: R) -> Result<MatchIter<'a>, String> {
+    let matcher = Matcher::new(cfg)?;
+    let (source, whole): (Box<dyn LineSource + 'a>, _) = if needs_whole_input(cfg, &matcher) {
+        let (lines, whole) = read_whole(cfg, &matcher, reader).map_err(|e| e.to_string())?;
+        (Box::new(lines.into_cursor()), whole)
+    } else {
+        (Box::new(LineReader::new(reader)), WholeInput::default())
+    };
+    Ok(MatchIter {
+        cfg,
+        selection: Selection::new(cfg, whole),
+        matcher,
+        source,
+        next: 0,
+        done: false,
+    })
+}
+
+/// The selected lines of an input, in input order (see `search_iter`).
+pub struct MatchIter<'a> {
+    cfg: &'a Config,
+    matcher: Matcher,
+    source: Box<dyn LineSource + 'a>,
+    selection: Selection,
+    /// Index of the next line.
+    next: usize,
+    done: bool,
+}
+
+impl MatchIter<'_> {
+    /// The lines so far skipped for exceeding `Config::match_timeout_ms`; they count as
+    /// not matching, even with `Config::invert`.
+    pub fn timeouts(&self) -> &[MatchTimeout] {
+        &self.selection.timeouts
+    }
+}
+
+impl Iterator for MatchIter<'_> {
+    type Item = io::Result<Match>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let (byte_offset, line) = match self.source.next_line() {
+                Ok(Some(next)) => next,
+                Ok(None) => break,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+            let idx = self.next;
+            self.next += 1;
+            let hit = self.selection.hit(&self.matcher, None, idx, line);
+            let selected = hit.is_some_and(|hit| hit != self.cfg.invert);
+            if !selected {
+                continue;
+            }
+            let is_match = hit == Some(true);
+            let (spans, terms) = self.selection.spans(&self.matcher, idx, line, is_match);
+            return Some(Ok(Match {
+                line_number: idx + 1,
+                byte_offset,
+                line: line.to_string(),
+                spans,
+                terms,
+                context_hash: self.selection.hash(idx),
+            }));
+        }
+        self.done = true;
+        None
     }
 }
 
+/// Warnings about the lines skipped for exceeding `Config::match_timeout_ms`.
+fn timeout_warnings(files: &[FileResult]) -> Vec<String> {
+    files
+        .iter()
+        .flat_map(|file| {
+            let path = file.path.as_deref().unwrap_or(STDIN_LABEL);
+            file.timeouts.iter().map(move |t| {
+                format!(
+                    "{}:{}: match timed out after {} ms, line skipped (--match-timeout)",
+                    path,
+                    t.line_number,
+                    t.elapsed.as_millis()
+                )
+            })
+        })
+        .collect()
+}
+
 /// Run a search across input files/paths.
 ///
 /// - If `inputs` contains a single "-", stdin is read.
-/// - Directories are traversed when `cfg.recursive` is set.
-/// - Binary files are skipped.
-/// - With a single file and `cfg.count = true`, the output omits the filename prefix.
+/// - Directories are traversed when `cfg.recursive` is set, in parallel when the rayon
+///   pool allows it, with files searched as the walk finds them (see `streams_walk`).
+/// - Binary files are skipped, except with `Compat::Gnu` (see `Config::compat`).
+/// - Lines are prefixed with their filename when more than one file is searched, unless
+///   overridden by `cfg.with_filename`.
+///
+/// With `cfg.queries`, every query is run in one pass and results are grouped per query
+/// (see `batch`).
 ///
 /// Returns a `RunResult` with aggregated formatted output (unless `quiet`) and combined
 /// `ExitStatus` reflecting whether any match was found across all inputs.
-fn parse_ts_from_formatted_line(line: &str) -> Option<(i32, i32, i32, i32, i32, i32, i32)> {
-    // Expect formatted line like "<lineno>:<content>". We parse timestamp from content.
-    let content = match line.splitn(2, ':').nth(1) {
-        Some(s) => s,
-        None => return None,
-    };
-    // Regex for timestamps: YYYY-MM-DD[ T]HH:MM:SS(.fraction)? (timezone ignored)
-    // Compile regex on each call; acceptable for minimal change. Pattern kept simple.
-    let re = Regex::new(
-        r"(?x)
-        (?P<y>\d{4})-
-        (?P<m>\d{2})-
-        (?P<d>\d{2})
-        [ T]
-        (?P<h>\d{2}):
-        (?P<min>\d{2}):
-        (?P<s>\d{2})
-        (?:\.(?P<frac>\d{1,9}))?
-    ",
-    )
-    .ok()?;
-    if let Some(caps) = re.captures(content) {
-        let y: i32 = caps.name("y")?.as_str().parse().ok()?;
-        let m: i32 = caps.name("m")?.as_str().parse().ok()?;
-        let d: i32 = caps.name("d")?.as_str().parse().ok()?;
-        let h: i32 = caps.name("h")?.as_str().parse().ok()?;
-        let min: i32 = caps.name("min")?.as_str().parse().ok()?;
-        let s: i32 = caps.name("s")?.as_str().parse().ok()?;
-        let frac_str = caps.name("frac").map(|m| m.as_str()).unwrap_or("");
-        let mut nanos: i32 = 0;
-        if !frac_str.is_empty() {
-            // Normalize to nanoseconds by right-padding with zeros up to 9 digits
-            let mut ns = String::from(frac_str);
-            while ns.len() < 9 {
-                ns.push('0');
-            }
-            // Truncate if more than 9
-            let ns = &ns[..9];
-            nanos = ns.parse().unwrap_or(0);
-        }
-        return Some((y, m, d, h, min, s, nanos));
-    }
-    None
-}
-
-// Helper: Collect all lines with optional parsed timestamps from per-file outputs.
-fn collect_all_lines(
-    outputs_per_file: &[(usize, String)],
-) -> Vec<(
-    Option<(i32, i32, i32, i32, i32, i32, i32)>,
-    usize,
-    usize,
-    String,
-)> {
-    let mut all_lines: Vec<(
-        Option<(i32, i32, i32, i32, i32, i32, i32)>,
-        usize,
-        usize,
-        String,
-    )> = Vec::new();
-    for (file_idx, s) in outputs_per_file {
-        for (line_idx, line) in s.lines().enumerate() {
-            let ts = parse_ts_from_formatted_line(line);
-            all_lines.push((ts, *file_idx, line_idx, line.to_string()));
-        }
-    }
-    all_lines
-}
-
-// Helper: Merge lines chronologically if every line has a timestamp; otherwise return None.
-fn merge_chronologically(
-    mut all_lines: Vec<(
-        Option<(i32, i32, i32, i32, i32, i32, i32)>,
-        usize,
-        usize,
-        String,
-    )>,
-) -> Option<String> {
-    if all_lines.is_empty() || all_lines.iter().any(|(ts, _, _, _)| ts.is_none()) {
-        return None;
-    }
-    all_lines.sort_by(|a, b| {
-        let ka = a.0.unwrap();
-        let kb = b.0.unwrap();
-        let ord = ka.cmp(&kb);
-        if ord != std::cmp::Ordering::Equal {
-            return ord;
-        }
-        let ord2 = a.1.cmp(&b.1);
-        if ord2 != std::cmp::Ordering::Equal {
-            return ord2;
-        }
-        a.2.cmp(&b.2)
-    });
-    let mut merged = String::new();
-    for (_, _, _, line) in all_lines {
-        merged.push_str(&line);
-        merged.push('\n');
+#[cfg(feature = "files")]
+pub fn run(cfg: &Config, inputs: &[String]) -> Result<RunResult, String> {
+    run_with(cfg, inputs, None, &AtomicBool::new(false))
+}
+
+/// Run a search like `run`, writing the output to `writer` as it is produced instead of
+/// collecting it in `RunResult::output`, which holds nothing on success.
+///
+/// Files are searched in parallel as with `run`, and the output of each is written, in
+/// one piece, as soon as it and the files before it are searched, so the output is the
+/// same as that of `run`. A single input (a file or stdin) has its lines written as they
+/// are found. Output that is only complete at the end is written at the end: lines merged
+/// across files by timestamp, summary tables, and, with `--queries`, `--files`, or
+/// headings over a single input, everything. When files are written as they are
+/// searched, directories are walked before the search starts, so that the files can be
+/// written in order; otherwise the walk and the search overlap as with `run`.
+///
+/// Returns the `RunResult` of `run` without its output, or an error for write failures as
+/// for failed searches.
+#[cfg(feature = "files")]
+pub fn run_to_writer<W: Write>(
+    cfg: &Config,
+    inputs: &[String],
+    writer: &mut W,
+) -> Result<RunResult, String> {
+    run_to_writer_until(cfg, inputs, writer, &AtomicBool::new(false))
+}
+
+/// Run a search like `run_to_writer`, giving up once `stop` is set: files not yet
+/// searched are left out and an error is returned. For callers that learn by other means
+/// that the output is no longer wanted, e.g. that the reader of a pipe went away while
+/// output merged by timestamp is held back for the end.
+#[cfg(feature = "files")]
+pub fn run_to_writer_until<W: Write>(
+    cfg: &Config,
+    inputs: &[String],
+    writer: &mut W,
+    stop: &AtomicBool,
+) -> Result<RunResult, String> {
+    let mut result = run_with(cfg, inputs, Some(writer), stop)?;
+    writer
+        .write_all(result.output.as_bytes())
+        .and_then(|_| writer.flush())
+        .map_err(|e| e.to_string())?;
+    result.output.clear();
+    Ok(result)
+}
+
+/// The body of `run` and `run_to_writer`.
+#[cfg(feature = "files")]
+fn run_with(
+    cfg: &Config,
+    inputs: &[String],
+    live: Option<&mut dyn Write>,
+    stop: &AtomicBool,
+) -> Result<RunResult, String> {
+    if cfg.list_files {
+        return list_files(cfg, inputs);
+    }
+    if !cfg.queries.is_empty() {
+        return run_queries(cfg, inputs);
     }
-    Some(merged)
+    let searcher = Searcher::new(cfg)?;
+    // Files are written in order as they are searched only once the walk has put them in
+    // order; output that is complete only at the end gains nothing from that
+    let show_names = cfg.with_filename.unwrap_or(true);
+    let walked = if live.is_none() || joining(cfg, &searcher, show_names).is_none() {
+        search_walking(cfg, &searcher, inputs, stop)?
+    } else {
+        None
+    };
+    let (list, mut result) = match walked {
+        Some(done) => done,
+        None => {
+            let list = select_files(cfg, inputs)?;
+            let result = search_files(cfg, &searcher, &list, inputs, live, stop)?;
+            (list, result)
+        }
+    };
+    let mut skipped = skipped_dir_warnings(&list.skipped_dirs);
+    skipped.extend(walk_issue_warnings(cfg, &list.walk_issues));
+    result.warnings.splice(0..0, skipped);
+    result.skipped = list.walk_issues.skipped;
+    result.warnings.extend(timeout_warnings(&result.files));
+    Ok(result)
 }
 
-// Helper: Concatenate outputs in input order.
-fn concat_outputs(outputs_per_file: Vec<(usize, String)>) -> String {
-    let mut out = String::new();
-    for (_idx, s) in outputs_per_file {
-        out.push_str(&s);
+/// Search input files/paths like `run`, returning what was found in each file instead
+/// of formatted output: its matching lines, their number, or why it could not be
+/// searched (`FileResult::error`), which does not fail the others.
+///
+/// Files are listed in input order, one per file searched. Lines are listed as in
+/// `RunResult::files`, so none are in count and quiet mode. Returns an error for an
+/// invalid configuration or an input that cannot be walked.
+#[cfg(feature = "files")]
+pub fn run_detailed(cfg: &Config, inputs: &[String]) -> Result<Vec<FileResult>, String> {
+    let searcher = Searcher::new(cfg)?;
+    let list = select_files(cfg, inputs)?;
+    let roots = input_roots(inputs);
+    let search = |name: &String| {
+        let shown = display_path(&roots, name);
+        let path = (name != "-").then_some(&*shown);
+        let root = root_label(&roots, name);
+        match search_path(cfg, &searcher, name, &roots, false, None) {
+            Ok(mut found) => found.file_result(path, root),
+            Err(e) => FileResult {
+                path: path.map(str::to_string),
+                root: root.map(str::to_string),
+                matched_lines: 0,
+                matches: Vec::new(),
+                timeouts: Vec::new(),
+                error: Some(e),
+            },
+        }
+    };
+    let scheduling = strategy::choose_scheduling(cfg, inputs, &list.files).chosen;
+    Ok(if scheduling == Scheduling::Sequential {
+        list.files.iter().map(|(_, name)| search(name)).collect()
+    } else {
+        list.files
+            .par_iter()
+            .map(|(_, name)| search(name))
+            .collect()
+    })
+}
+
+/// The files a search of `inputs` would read (`Config::list_files`), one per line in
+/// `output` as results name them, after ignore files, `--include`/`--exclude`, file
+/// types, size limits, and binary detection have left files out. Nothing is matched, so
+/// no pattern is needed; stdin is not listed. The status is `MatchFound` when any file is
+/// listed, and `warnings` and `skipped` report the walk as for a search.
+#[cfg(feature = "files")]
+pub fn list_files(cfg: &Config, inputs: &[String]) -> Result<RunResult, String> {
+    let list = select_files(cfg, inputs)?;
+    let roots = input_roots(inputs);
+    let mut output = String::new();
+    for (_, file) in list.files.iter().filter(|(_, f)| f != "-") {
+        output.push_str(&display_path(&roots, file));
+        output.push('\n');
     }
-    out
+    let mut warnings = skipped_dir_warnings(&list.skipped_dirs);
+    warnings.extend(walk_issue_warnings(cfg, &list.walk_issues));
+    Ok(RunResult {
+        status: status_for(!output.is_empty()),
+        output,
+        warnings,
+        timings: Vec::new(),
+        files: Vec::new(),
+        errors: Vec::new(),
+        skipped: list.walk_issues.skipped,
+        stats: Stats::default(),
+    })
 }
 
-pub fn run(cfg: &Config, inputs: &[String]) -> Result<RunResult, String> {
-    let files = expand_inputs(cfg, inputs);
-    if files.len() == 1 && files[0] == "-" {
-        let reader = std::io::stdin();
-        return run_on_reader(cfg, reader, None);
+/// Warnings about the directories left out by `Config::skip_binary_dirs`.
+#[cfg(feature = "files")]
+pub(crate) fn skipped_dir_warnings(skipped: &[SkippedDir]) -> Vec<String> {
+    skipped
+        .iter()
+        .map(|dir| {
+            format!(
+                "{}: skipped the rest of the directory, {} of the first {} files are binary (--skip-binary-dirs)",
+                dir.path, dir.binary, dir.sampled
+            )
+        })
+        .collect()
+}
+
+/// Warnings summing up what kept the walk from reading everything (see `WalkIssues`).
+#[cfg(feature = "files")]
+pub(crate) fn walk_issue_warnings(cfg: &Config, issues: &WalkIssues) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if issues.truncated {
+        warnings.push(format!(
+            "stopped walking after {} entries (--max-entries), results are incomplete",
+            cfg.max_entries.unwrap_or(0)
+        ));
+    }
+    for dir in &issues.cycles {
+        warnings.push(format!(
+            "{}: directory already walked (symlink loop or bind mount), not entered again",
+            dir
+        ));
+    }
+    if cfg.list_skipped {
+        warnings.extend(skipped_warnings(&issues.skipped));
+        return warnings;
+    }
+    if !issues.too_large.is_empty() {
+        warnings.push(format!(
+            "skipped {} file(s) larger than {} bytes (--max-filesize; --list-skipped to name them)",
+            issues.too_large.len(),
+            cfg.max_filesize.unwrap_or(0)
+        ));
     }
+    if issues.errors > 0 {
+        warnings.push(format!(
+            "{} entries could not be read while walking, e.g. {}",
+            issues.errors,
+            issues.examples.join("; ")
+        ));
+    }
+    warnings
+}
 
-    // Filter out binary files
-    let files: Vec<(usize, String)> = files
-        .into_iter()
-        .enumerate()
-        .filter(|(_, f)| !is_binary_path(f))
+/// One warning per entry left out of the search (`Config::list_skipped`), followed by
+/// their number per reason.
+#[cfg(feature = "files")]
+fn skipped_warnings(skipped: &[Skipped]) -> Vec<String> {
+    let mut warnings: Vec<String> = skipped
+        .iter()
+        .map(|s| format!("{}: skipped, {}", s.path, s.reason.describe()))
         .collect();
+    if !skipped.is_empty() {
+        let parts: Vec<String> = SkipReason::LABELS
+            .iter()
+            .filter_map(|label| {
+                let n = skipped
+                    .iter()
+                    .filter(|s| s.reason.label() == *label)
+                    .count();
+                (n > 0).then(|| format!("{} {}", n, label))
+            })
+            .collect();
+        warnings.push(format!(
+            "skipped {} entries: {}",
+            skipped.len(),
+            parts.join(", ")
+        ));
+    }
+    warnings
+}
+
+/// Error of a run given up on through the `stop` flag of `run_to_writer_until`.
+#[cfg(feature = "files")]
+const STOPPED: &str = "search stopped: output no longer wanted";
+
+/// Search the selected `files`, the body of `run` without queries. With `live`, output
+/// is written to it as files are searched when it can be (see `run_to_writer`), and the
+/// `RunResult` only holds the rest. Files are no longer searched once `stop` is set.
+#[cfg(feature = "files")]
+fn search_files(
+    cfg: &Config,
+    searcher: &Searcher,
+    list: &FileList,
+    inputs: &[String],
+    mut live: Option<&mut dyn Write>,
+    stop: &AtomicBool,
+) -> Result<RunResult, String> {
+    let started = Instant::now();
+    let roots = &input_roots(inputs);
+    let FileList {
+        expanded, files, ..
+    } = list;
+    let expanded = *expanded;
+    if expanded == 1 && files.len() == 1 && files[0].1 == "-" {
+        let reader = open_input(None, cfg.search_zip).map_err(|e| e.to_string())?;
+        let show_name = cfg.with_filename.unwrap_or(false);
+        let found = search_reader(cfg, searcher, reader, None, show_name, live)?;
+        return Ok(single_result(cfg, found, None, None));
+    }
+
+    // Filenames are shown by default when more than one file is searched, and, as in GNU
+    // grep, when a GNU-compatible run walks a directory
+    let walks_dir = cfg.compat == Compat::Gnu
+        && cfg.recursive
+        && (inputs.is_empty()
+            || inputs
+                .iter()
+                .any(|i| std::path::Path::new(&input_path(i)).is_dir()));
+    let show_names = cfg.with_filename.unwrap_or(expanded > 1 || walks_dir);
 
     if files.is_empty() {
+        let mut output = String::new();
+        if cfg.json && !cfg.quiet {
+            let stats = Stats::default();
+            append_json_summary(cfg, &mut output, 0, 0, &stats, started.elapsed());
+        }
         return Ok(RunResult {
-            output: String::new(),
+            output,
             status: ExitStatus::NoMatch,
+            warnings: Vec::new(),
+            timings: Vec::new(),
+            files: Vec::new(),
+            errors: Vec::new(),
+            skipped: Vec::new(),
+            stats: Stats {
+                elapsed: started.elapsed(),
+                ..Default::default()
+            },
         });
     }
 
-    if files.len() == 1 {
-        let name = files[0].1.clone();
-        let reader = open_input(Some(&name)).map_err(|e| e.to_string())?;
-        return if cfg.count {
-            // For count-only with a single file, suppress filename prefix
-            run_on_reader(cfg, reader, None)
-        } else {
-            run_on_reader(cfg, reader, Some(&name))
+    // GNU-compatible runs report unreadable files without failing, like several files
+    if files.len() == 1 && cfg.compat == Compat::Rgrep {
+        let path = &files[0].1;
+        let found = search_path(cfg, searcher, path, roots, show_names, live)?;
+        let shown = display_path(roots, path);
+        return Ok(single_result(
+            cfg,
+            found,
+            Some(&shown),
+            root_label(roots, path),
+        ));
+    }
+
+    // Outputs merged by timestamp or summed up in a table are only complete at the end
+    let mut writer = match (live.take(), joining(cfg, searcher, show_names)) {
+        (Some(live), Some(joining)) => Some(OrderedWriter::new(live, joining)),
+        _ => None,
+    };
+    let scheduling = strategy::choose_scheduling(cfg, inputs, files).chosen;
+    // A single thread cannot both search and wait for the results to write them
+    let sequential = scheduling == Scheduling::Sequential
+        || (writer.is_some() && rayon::current_num_threads() < 2);
+    let results: Vec<Searched> = if sequential {
+        // One file after another; when stopping at the first match, nothing is read
+        // after it
+        let stops = stops_at_first_match(cfg);
+        let mut results = Vec::new();
+        for (idx, name) in files {
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let mut found = search_path(cfg, searcher, name, roots, show_names, None);
+            let hit = found.as_ref().is_ok_and(FileSearch::is_hit);
+            if let Some(writer) = writer.as_mut() {
+                writer.write_file(&mut found)?;
+            }
+            results.push((*idx, name.clone(), found));
+            if hit && stops {
+                break;
+            }
+        }
+        results
+    } else if let Some(writer) = writer.as_mut() {
+        search_in_order(cfg, searcher, files, roots, show_names, writer, stop)?
+    } else {
+        // Parallel processing across files; preserve input order in aggregation
+        files
+            .par_iter()
+            .map(|(idx, name)| {
+                let found = if stop.load(Ordering::Relaxed) {
+                    Err(STOPPED.to_string())
+                } else {
+                    search_path(cfg, searcher, name, roots, show_names, None)
+                };
+                (*idx, name.clone(), found)
+            })
+            .collect()
+    };
+    if stop.load(Ordering::Relaxed) {
+        return Err(STOPPED.into());
+    }
+    collect_results(
+        cfg,
+        searcher,
+        results,
+        files.len(),
+        roots,
+        show_names,
+        started,
+    )
+}
+
+/// A file searched by `search_files`: its position in the expanded inputs, its path, and
+/// what searching it gave.
+#[cfg(feature = "files")]
+type Searched = (usize, String, Result<FileSearch, String>);
+
+/// Search `files` in parallel, writing the output of each file with `writer` as soon as
+/// it and every file before it are searched. Searches run in the rayon pool and hand
+/// their results to the calling thread, the only one writing. After a failed write, or
+/// once `stopped` is set, the files not yet started are left out.
+#[cfg(feature = "files")]
+fn search_in_order(
+    cfg: &Config,
+    searcher: &Searcher,
+    files: &[(usize, String)],
+    roots: &[Root],
+    show_names: bool,
+    writer: &mut OrderedWriter,
+    stopped: &AtomicBool,
+) -> Result<Vec<Searched>, String> {
+    let (sender, receiver) = mpsc::channel::<(usize, Result<FileSearch, String>)>();
+    let stop = AtomicBool::new(false);
+    let mut results = Vec::with_capacity(files.len());
+    let mut failed = None;
+    rayon::in_place_scope(|scope| {
+        scope.spawn(|_| {
+            files
+                .par_iter()
+                .enumerate()
+                .for_each_with(sender, |sender, (pos, (_, name))| {
+                    if stop.load(Ordering::Relaxed) || stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let found = search_path(cfg, searcher, name, roots, show_names, None);
+                    let _ = sender.send((pos, found));
+                });
+        });
+        // Results arriving early wait here for those of the files before them
+        let mut pending: BTreeMap<usize, Result<FileSearch, String>> = BTreeMap::new();
+        for (pos, found) in receiver {
+            pending.insert(pos, found);
+            while let Some(mut found) = pending.remove(&results.len()) {
+                if failed.is_none()
+                    && let Err(err) = writer.write_file(&mut found)
+                {
+                    failed = Some(err);
+                    stop.store(true, Ordering::Relaxed);
+                }
+                let (idx, name) = &files[results.len()];
+                results.push((*idx, name.clone(), found));
+            }
+        }
+    });
+    failed.map_or(Ok(results), Err)
+}
+
+/// How the outputs of several files are put together when they follow one another in
+/// input order, as opposed to being merged by timestamp or summed up in a table.
+#[cfg(feature = "files")]
+#[derive(Debug, Clone)]
+enum Joining {
+    /// One after another.
+    Concatenated,
+    /// Separated by a blank line, each under its heading (`Config::heading`).
+    Headings,
+    /// Separated by `--` when context is shown, as GNU grep does (see `separates_groups`).
+    Groups(String),
+}
+
+/// How `collect_results` puts the outputs of several files together, or `None` when they
+/// are not simply joined in input order.
+#[cfg(feature = "files")]
+fn joining(cfg: &Config, searcher: &Searcher, show_names: bool) -> Option<Joining> {
+    let summary_mode = !cfg.files_with_matches && (cfg.count || cfg.duration_stats.is_some());
+    if cfg.quiet
+        || (summary_mode && cfg.table && cfg.compat == Compat::Rgrep && show_names && !cfg.json)
+    {
+        None
+    } else if summary_mode
+        || cfg.files_with_matches
+        || cfg.json
+        || cfg.vimgrep
+        || cfg.format.is_some()
+    {
+        // In count, file list, JSON, and duration modes, just concatenate in input order
+        // (no chronological meaning); quickfix entries and templated lines are listed file
+        // by file
+        Some(Joining::Concatenated)
+    } else if show_names && uses_headings(cfg) {
+        // Heading groups keep each file's lines together, so they are not merged
+        Some(Joining::Headings)
+    } else if cfg.compat == Compat::Gnu {
+        // GNU grep lists files one after another, separating their groups of context
+        let mut separator = String::new();
+        if separates_groups(cfg, searcher) {
+            append_group_separator(&mut separator, searcher.color.then_some(&cfg.colors));
+        }
+        Some(Joining::Groups(separator))
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "files")]
+impl Joining {
+    /// Append `output`, the output of the next file, to `out`; `joined` tells whether
+    /// anything was output before it, and is updated.
+    fn append(&self, out: &mut String, joined: &mut bool, output: &str) {
+        if output.is_empty() {
+            return;
+        }
+        if *joined {
+            match self {
+                Joining::Concatenated => {}
+                Joining::Headings => out.push('\n'),
+                Joining::Groups(separator) => out.push_str(separator),
+            }
+        }
+        out.push_str(output);
+        *joined = true;
+    }
+}
+
+/// Writes the outputs of files in input order, joined as `Joining` says.
+#[cfg(feature = "files")]
+struct OrderedWriter<'a> {
+    live: &'a mut dyn Write,
+    joining: Joining,
+    /// Whether any output was written yet.
+    joined: bool,
+    buf: String,
+}
+
+#[cfg(feature = "files")]
+impl<'a> OrderedWriter<'a> {
+    fn new(live: &'a mut dyn Write, joining: Joining) -> Self {
+        Self {
+            live,
+            joining,
+            joined: false,
+            buf: String::new(),
+        }
+    }
+
+    /// Write the output of the next file, leaving it empty in `found`.
+    fn write_file(&mut self, found: &mut Result<FileSearch, String>) -> Result<(), String> {
+        let Ok(found) = found else {
+            return Ok(());
         };
+        self.buf.clear();
+        self.joining
+            .append(&mut self.buf, &mut self.joined, &found.output);
+        found.output.clear();
+        self.live
+            .write_all(self.buf.as_bytes())
+            .and_then(|_| self.live.flush())
+            .map_err(|e| e.to_string())
     }
+}
 
-    // Parallel processing across files; preserve input order in aggregation
-    let results: Vec<(usize, Result<RunResult, String>)> = files
-        .par_iter()
-        .map(|(idx, name)| {
-            let res = open_input(Some(name))
-                .map_err(|e| e.to_string())
-                .and_then(|rdr| run_on_reader(cfg, rdr, Some(name)));
-            (*idx, res)
-        })
-        .collect();
+/// Whether `run` searches files as the walk finds them (see `search_walking`): when the
+/// walk is parallel and recursive over a directory, without a file list cache to fill,
+/// and the files are not searched one after another (`Scheduling::Sequential`).
+#[cfg(feature = "files")]
+pub(crate) fn streams_walk(cfg: &Config, inputs: &[String]) -> bool {
+    let walks_dir = inputs.is_empty()
+        || inputs
+            .iter()
+            .any(|i| std::path::Path::new(&input_path(i)).is_dir());
+    walks_in_parallel(cfg)
+        && walks_dir
+        && cfg.file_list_cache.is_none()
+        && cfg.scheduling != Some(Scheduling::Sequential)
+        && !stops_at_first_match(cfg)
+        && !inputs.iter().any(|i| i == "-" || is_stream(i))
+}
 
-    let mut matched_any = false;
+/// Search the files of `inputs` in the rayon pool as the parallel walk finds them, when
+/// `streams_walk` allows it, so that searching starts before the walk of a large tree
+/// ends. Results are still put in walk order and printed once every file is searched.
+/// Filenames are shown unless `Config::with_filename` says otherwise, so when the walk
+/// finds at most one file, which would be searched differently, this gives `None` and
+/// the files are selected and searched again by `search_files`. Files found once `stop`
+/// is set are not searched, and the run fails.
+#[cfg(feature = "files")]
+fn search_walking(
+    cfg: &Config,
+    searcher: &Searcher,
+    inputs: &[String],
+    stop: &AtomicBool,
+) -> Result<Option<(FileList, RunResult)>, String> {
+    if !streams_walk(cfg, inputs) {
+        return Ok(None);
+    }
+    TypeFilter::new(cfg)?;
+    let started = Instant::now();
+    let roots = &input_roots(inputs);
+    let show_names = cfg.with_filename.unwrap_or(true);
+    let skips_binary = binary_files(cfg) == BinaryFiles::Skip;
+    // Binary files left out of the search have no result
+    type Found = (WalkKey, Option<Result<FileSearch, String>>);
+    let searched: Mutex<Vec<Found>> = Mutex::new(Vec::new());
+    let on_file = |key: WalkKey, path: &str| {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let found = (!skips_binary
+            || preprocesses(searcher.pre.as_ref(), path)
+            || !is_binary_path(path, cfg.search_zip))
+        .then(|| search_path(cfg, searcher, path, roots, show_names, None));
+        searched.lock().unwrap().push((key, found));
+    };
+    let mut walk = walk_inputs_with(cfg, inputs, Some(&on_file));
+    if stop.load(Ordering::Relaxed) {
+        return Err(STOPPED.into());
+    }
+    let mut searched = searched.into_inner().unwrap_or_default();
+    searched.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut files = Vec::new();
+    let mut results = Vec::new();
+    for (idx, ((_, found), path)) in searched.into_iter().zip(&walk.files).enumerate() {
+        match found {
+            Some(found) => {
+                files.push((idx, path.clone()));
+                results.push((idx, path.clone(), found));
+            }
+            None if cfg.list_skipped => walk.issues.skipped.push(Skipped {
+                path: path.clone(),
+                reason: SkipReason::Binary,
+            }),
+            None => {}
+        }
+    }
+    let names_shown = cfg
+        .with_filename
+        .unwrap_or(walk.files.len() > 1 || cfg.compat == Compat::Gnu);
+    if names_shown != show_names
+        || files.is_empty()
+        || (files.len() == 1 && cfg.compat == Compat::Rgrep)
+    {
+        return Ok(None);
+    }
+    let searched = files.len();
+    let list = FileList {
+        expanded: walk.files.len(),
+        files,
+        skipped_dirs: walk.skipped_dirs,
+        walk_issues: walk.issues,
+    };
+    let result = collect_results(cfg, searcher, results, searched, roots, show_names, started)?;
+    Ok(Some((list, result)))
+}
+
+/// Put together the `results` of searching `searched` files, each with its position in
+/// the expanded inputs, into the `RunResult` of `search_files`.
+#[cfg(feature = "files")]
+fn collect_results(
+    cfg: &Config,
+    searcher: &Searcher,
+    results: Vec<Searched>,
+    searched: usize,
+    roots: &[Root],
+    show_names: bool,
+    started: Instant,
+) -> Result<RunResult, String> {
+    let mut files_with_matches = 0usize;
+    let mut stats = Stats::default();
+    let mut all_durations: Vec<f64> = Vec::new();
+    let mut timings: Vec<FileTiming> = Vec::new();
+    let mut path_hits: Vec<String> = Vec::new();
+    let mut file_results: Vec<FileResult> = Vec::new();
     let mut errs: Vec<String> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
 
-    let mut outputs_per_file: Vec<(usize, String)> = Vec::new();
+    let mut outputs_per_file: Vec<FileOutput> = Vec::new();
+    // (path, matched lines, extracted durations) per file, for summary tables
+    let mut summaries: Vec<(String, usize, Vec<f64>)> = Vec::new();
 
     let mut results_sorted = results;
-    results_sorted.sort_by_key(|(i, _)| *i);
+    results_sorted.sort_by_key(|(i, _, _)| *i);
 
-    for (file_idx, res) in results_sorted {
+    for (file_idx, path, res) in results_sorted {
         match res {
-            Ok(rr) => {
-                if rr.status == ExitStatus::MatchFound {
-                    matched_any = true;
+            Ok(mut found) => {
+                // Results name files below their root label
+                let root = root_label(roots, &path);
+                let path = display_path(roots, &path).into_owned();
+                file_results.push(found.file_result(Some(&path), root));
+                if found.path_hit {
+                    path_hits.push(path.clone());
                 }
-                outputs_per_file.push((file_idx, rr.output));
+                if found.is_hit() {
+                    files_with_matches += 1;
+                }
+                stats.add(&found.stats);
+                all_durations.extend(found.durations.iter().copied());
+                timings.extend(found.timing);
+                let count = counted(cfg, found.matched_lines, &found.stats);
+                summaries.push((path.clone(), count, found.durations));
+                outputs_per_file.push(FileOutput {
+                    idx: file_idx,
+                    path,
+                    output: found.output,
+                });
             }
+            // GNU grep reports the file and goes on with the others
+            Err(e) if cfg.compat == Compat::Gnu => errors.push(e),
             Err(e) => errs.push(e),
         }
     }
@@ -330,47 +1752,492 @@ pub fn run(cfg: &Config, inputs: &[String]) -> Result<RunResult, String> {
         return Err(errs.join("\n"));
     }
 
-    // If quiet, no need to build output at all
-    if cfg.quiet {
-        let status = if matched_any {
-            ExitStatus::MatchFound
-        } else {
-            ExitStatus::NoMatch
-        };
-        return Ok(RunResult {
-            output: String::new(),
-            status,
-        });
+    let status = status_for(files_with_matches > 0);
+
+    let summary_mode = !cfg.files_with_matches && (cfg.count || cfg.duration_stats.is_some());
+    let (mut output, warnings) = if cfg.quiet {
+        // If quiet, no need to build output at all
+        (String::new(), Vec::new())
+    } else if let Some(joining) = joining(cfg, searcher, show_names) {
+        let mut out = String::new();
+        let mut joined = false;
+        for file in outputs_per_file {
+            joining.append(&mut out, &mut joined, &file.output);
+        }
+        if cfg.json {
+            let elapsed_total = started.elapsed();
+            append_json_summary(
+                cfg,
+                &mut out,
+                searched,
+                files_with_matches,
+                &stats,
+                elapsed_total,
+            );
+        } else if cfg.duration_stats.is_some() {
+            let _ = writeln!(&mut out, "total: {}", format_summary(&all_durations));
+        }
+        (out, Vec::new())
+    } else if summary_mode && !cfg.json {
+        // Summaries over named files are rendered as one aligned table
+        (summary_table(cfg, &summaries, &all_durations), Vec::new())
+    } else {
+        // Try to chronologically merge lines across files by timestamp in the content.
+        // Lines are tagged with their source when they do not already carry a filename prefix.
+        merge_outputs(cfg, outputs_per_file, !show_names)
+    };
+
+    // Path-only hits are listed after the matching lines
+    if reports_path_hits(cfg) {
+        for path in &path_hits {
+            append_path_match(&mut output, path);
+        }
     }
 
-    // In count mode, just concatenate as-before (no chronological meaning)
-    if cfg.count {
-        let out = concat_outputs(outputs_per_file);
-        let status = if matched_any {
-            ExitStatus::MatchFound
+    Ok(RunResult {
+        output,
+        status,
+        warnings,
+        timings,
+        files: file_results,
+        errors,
+        skipped: Vec::new(),
+        stats: Stats {
+            elapsed: started.elapsed(),
+            ..stats
+        },
+    })
+}
+
+/// Open and search the file at `name`, timing it when `Config::timings` is set. With
+/// `live`, lines are written to it as they are found (see `search_reader`).
+#[cfg(feature = "files")]
+fn search_path(
+    cfg: &Config,
+    searcher: &Searcher,
+    name: &str,
+    roots: &[Root],
+    show_name: bool,
+    live: Option<&mut dyn Write>,
+) -> Result<FileSearch, String> {
+    let start = Instant::now();
+    let shown = display_path(roots, name);
+    let mut found = if let Some(pre) = searcher.pre.as_ref().filter(|p| p.applies_to(name)) {
+        let text = pre.run(name).map_err(|e| format!("{}: {}", shown, e))?;
+        search_reader(
+            cfg,
+            searcher,
+            text.as_bytes(),
+            Some(&shown),
+            show_name,
+            live,
+        )?
+    } else if reads_lossy(cfg) {
+        search_path_lossy(cfg, searcher, name, &shown, show_name, live)?
+    } else {
+        let reader = open_searchable(name, cfg.search_zip).map_err(|e| e.to_string())?;
+        search_reader(cfg, searcher, reader, Some(&shown), show_name, live)?
+    };
+    if cfg.timings.is_some() {
+        // A stream has no size to look up, only the bytes read from it
+        let bytes = if is_stream(name) {
+            found.stats.bytes_searched
         } else {
-            ExitStatus::NoMatch
+            std::fs::metadata(name).map(|m| m.len()).unwrap_or(0)
         };
-        return Ok(RunResult {
-            output: out,
-            status,
+        found.timing = Some(FileTiming {
+            path: shown.into_owned(),
+            elapsed: start.elapsed(),
+            bytes,
         });
     }
+    Ok(found)
+}
 
-    // Try to chronologically merge lines across files by timestamp in the content.
-    let all_lines = collect_all_lines(&outputs_per_file);
-    let out = match merge_chronologically(all_lines) {
-        Some(merged) => merged,
-        None => concat_outputs(outputs_per_file),
-    };
+/// Whether the file at `path` is searched through the preprocessor `pre`; such files are
+/// never skipped as binary, as the command's output is searched instead.
+#[cfg(feature = "files")]
+fn preprocesses(pre: Option<&Preprocessor>, path: &str) -> bool {
+    pre.is_some_and(|pre| pre.applies_to(path))
+}
 
-    let status = if matched_any {
-        ExitStatus::MatchFound
+/// Whether files are read whole by `search_path_lossy` rather than as UTF-8 line by line.
+#[cfg(feature = "files")]
+pub(crate) fn reads_lossy(cfg: &Config) -> bool {
+    cfg.compat == Compat::Gnu || binary_files(cfg) != BinaryFiles::Skip
+}
+
+/// Search the file at `name` the way GNU grep reads it: invalid UTF-8 does not end the
+/// search, and a file containing a NUL byte is binary, handled as `Config::binary_files`
+/// says: skipped (a stream, which is not sniffed up front), reported as
+/// `Binary file X matches` instead of with the matching lines, or searched as text.
+/// Errors name the file.
+#[cfg(feature = "files")]
+fn search_path_lossy(
+    cfg: &Config,
+    searcher: &Searcher,
+    name: &str,
+    shown: &str,
+    show_name: bool,
+    live: Option<&mut dyn Write>,
+) -> Result<FileSearch, String> {
+    let (mut text, binary) =
+        read_lossy(name, cfg.search_zip).map_err(|e| format!("{}: {}", shown, error_reason(&e)))?;
+    let mode = binary_files(cfg);
+    if binary && mode == BinaryFiles::Skip {
+        text.clear();
+    }
+    // The lines of a binary file that is reported are replaced, so none are written
+    let live = live.filter(|_| !(binary && mode == BinaryFiles::Report));
+    let mut found = search_reader(cfg, searcher, text.as_bytes(), Some(shown), show_name, live)?;
+    let prints_lines = !(cfg.count
+        || cfg.quiet
+        || cfg.files_with_matches
+        || cfg.json
+        || cfg.duration_stats.is_some());
+    if prints_lines && found.matched_lines > 0 && binary && mode == BinaryFiles::Report {
+        found.output = format!("Binary file {} matches\n", shown);
+    }
+    Ok(found)
+}
+
+/// Open the input at `name` for searching it line by line: the output of the
+/// preprocessor `pre` if it applies to the file, else like `open_searchable`, unless
+/// binary files are searched (`Config::binary_files`), in which case the input is read as
+/// text with invalid UTF-8 replaced.
+#[cfg(feature = "files")]
+pub(crate) fn open_file(
+    cfg: &Config,
+    pre: Option<&Preprocessor>,
+    name: &str,
+) -> io::Result<Box<dyn Read>> {
+    if let Some(pre) = pre.filter(|pre| pre.applies_to(name)) {
+        let text = pre.run(name).map_err(io::Error::other)?;
+        return Ok(Box::new(io::Cursor::new(text.into_bytes())));
+    }
+    if binary_files(cfg) == BinaryFiles::Skip {
+        return open_searchable(name, cfg.search_zip);
+    }
+    let (text, _) = read_lossy(name, cfg.search_zip)?;
+    Ok(Box::new(io::Cursor::new(text.into_bytes())))
+}
+
+/// Render per-file counts or duration summaries as a table (`Config::table`).
+#[cfg(feature = "files")]
+fn summary_table(cfg: &Config, summaries: &[(String, usize, Vec<f64>)], all: &[f64]) -> String {
+    let mut out = String::new();
+    if cfg.duration_stats.is_some() {
+        let mut columns = vec![("FILE", Align::Left)];
+        columns.extend(DurationSummary::COLUMNS.map(|c| (c, Align::Right)));
+        let mut table = Table::new(&columns);
+        for (path, _, durations) in summaries {
+            let mut row = vec![path.clone()];
+            row.extend(summary_cells(durations));
+            table.push_row(row);
+        }
+        let mut total = vec!["total".to_string()];
+        total.extend(summary_cells(all));
+        table.push_row(total);
+        table.render(&mut out);
     } else {
-        ExitStatus::NoMatch
+        let mut table = Table::new(&[("FILE", Align::Left), ("COUNT", Align::Right)]);
+        for (path, count, _) in summaries {
+            table.push_row(vec![path.clone(), count.to_string()]);
+        }
+        table.render(&mut out);
+    }
+    out
+}
+
+/// Expand `inputs` and drop binary files (unless `Config::binary_files` keeps them), reusing `Config::file_list_cache` when it is fresh.
+#[cfg(feature = "files")]
+pub(crate) fn select_files(cfg: &Config, inputs: &[String]) -> Result<FileList, String> {
+    TypeFilter::new(cfg)?;
+    let cache = cfg.file_list_cache.as_deref().map(std::path::Path::new);
+    let key = cache.map(|_| file_list_cache::cache_key(cfg, inputs));
+    if let (Some(cache), Some(key)) = (cache, &key)
+        && let Some(list) = file_list_cache::load(cache, key)
+    {
+        return Ok(list);
+    }
+
+    let pre = Preprocessor::new(cfg)?;
+    let mut walk = walk_inputs(cfg, inputs);
+    let skips_binary = binary_files(cfg) == BinaryFiles::Skip;
+    let mut files = Vec::with_capacity(walk.files.len());
+    for (idx, file) in walk.files.iter().enumerate() {
+        if !skips_binary
+            || preprocesses(pre.as_ref(), file)
+            || !is_binary_path(file, cfg.search_zip)
+        {
+            files.push((idx, file.clone()));
+        } else if cfg.list_skipped {
+            walk.issues.skipped.push(Skipped {
+                path: file.clone(),
+                reason: SkipReason::Binary,
+            });
+        }
+    }
+    let list = FileList {
+        expanded: walk.files.len(),
+        files,
+        skipped_dirs: walk.skipped_dirs,
+        walk_issues: walk.issues,
     };
-    Ok(RunResult {
-        output: out,
-        status,
-    })
+
+    // Stdin and streams are read fresh every time, so there is nothing to cache. File
+    // sizes are not stamped, so lists filtered by size are not cached either.
+    let reads_stream = list.files.iter().any(|(_, f)| f == "-" || is_stream(f));
+    if let (Some(cache), Some(key)) = (cache, &key)
+        && !reads_stream
+        && cfg.max_filesize.is_none()
+        && list.walk_issues.is_empty()
+    {
+        // Binary files were told apart by their contents, which can change in place
+        let sniffed = if skips_binary { walk.files } else { Vec::new() };
+        let stamped: Vec<String> = walk
+            .dirs
+            .into_iter()
+            .chain(walk.ignore_files)
+            .chain(sniffed)
+            .collect();
+        file_list_cache::store(cache, key, inputs, &stamped, &list)
+            .map_err(|e| format!("{}: cannot write file list cache: {}", cache.display(), e))?;
+    }
+    Ok(list)
+}
+
+/// Build the `RunResult` for a run that searched exactly one input (`path` is `None` for
+/// stdin).
+#[cfg(feature = "files")]
+fn single_result(
+    cfg: &Config,
+    mut found: FileSearch,
+    path: Option<&str>,
+    root: Option<&str>,
+) -> RunResult {
+    let hit = found.is_hit();
+    let files = vec![found.file_result(path, root)];
+    let mut output = found.output;
+    if cfg.json && !cfg.quiet {
+        let with_matches = usize::from(hit);
+        let elapsed_total = found.stats.elapsed;
+        append_json_summary(
+            cfg,
+            &mut output,
+            1,
+            with_matches,
+            &found.stats,
+            elapsed_total,
+        );
+    }
+    if found.path_hit
+        && reports_path_hits(cfg)
+        && let Some(path) = path
+    {
+        append_path_match(&mut output, path);
+    }
+    RunResult {
+        output,
+        status: status_for(hit),
+        warnings: Vec::new(),
+        timings: found.timing.into_iter().collect(),
+        files,
+        errors: Vec::new(),
+        skipped: Vec::new(),
+        stats: found.stats,
+    }
+}
+
+/// Stream JSON Lines events for a search across input files/paths into `writer`.
+///
+/// Unlike `run` with `Config::json`, events are written as soon as each line is found
+/// rather than collected into one output `String`, so results from huge inputs can be
+/// consumed incrementally. Files are searched one after another in input order, and the
+/// event schema is that of `--json` (see `output::json`), or ripgrep's with
+/// `JsonSchema::Ripgrep` (see `output::rg_json`). Nothing is written in quiet mode.
+///
+/// A file that cannot be opened or read, or whose `Config::pre` command fails, gets an
+/// `error` event instead of its `end` event, and the search goes on with the next file;
+/// the summary counts the files that were searched.
+///
+/// Returns the combined `ExitStatus`, or an error for invalid options and write failures.
+#[cfg(feature = "files")]
+pub fn run_json_lines<W: Write>(
+    cfg: &Config,
+    inputs: &[String],
+    writer: &mut W,
+) -> Result<ExitStatus, String> {
+    let started = Instant::now();
+    let searcher = Searcher::new(cfg)?;
+    let matcher = &searcher.matcher;
+    let roots = input_roots(inputs);
+    let files: Vec<String> = select_files(cfg, inputs)?
+        .files
+        .into_iter()
+        .map(|(_, f)| f)
+        .collect();
+
+    let mut searched = 0usize;
+    let mut files_with_matches = 0usize;
+    let mut total = Stats::default();
+    let mut buf = String::new();
+
+    for file in &files {
+        let start = Instant::now();
+        let shown = display_path(&roots, file);
+        let name = if file == "-" { None } else { Some(&*shown) };
+
+        let printed = Cell::new(0u64);
+        let mut emit = |buf: &mut String| -> io::Result<()> {
+            printed.set(printed.get() + buf.len() as u64);
+            if !cfg.quiet {
+                writer.write_all(buf.as_bytes())?;
+            }
+            buf.clear();
+            Ok(())
+        };
+
+        let mut bytes_searched = 0u64;
+        let reader = match open_file(cfg, searcher.pre.as_ref(), file) {
+            Ok(reader) => CountingReader::new(reader, &mut bytes_searched),
+            Err(e) => {
+                append_json_error(cfg, &mut buf, name, &error_reason(&e));
+                emit(&mut buf).map_err(|e| e.to_string())?;
+                continue;
+            }
+        };
+
+        append_json_begin(cfg, &mut buf, name);
+        emit(&mut buf).map_err(|e| e.to_string())?;
+
+        // Lines that time out are left out; there is no channel for warnings here, so they
+        // are only counted in the `end` event. Write failures are kept apart from those
+        // reading the file
+        let mut failed = None;
+        let scanned = scan_reader(cfg, matcher, reader, name, &mut |m, is_match| {
+            append_json_line(cfg, matcher, &mut buf, name, m, is_match);
+            emit(&mut buf).map_err(|e| {
+                let stop = io::Error::new(e.kind(), "stopped by a write failure");
+                failed = Some(e);
+                stop
+            })
+        });
+        if let Some(e) = failed {
+            return Err(e.to_string());
+        }
+        let scanned = match scanned {
+            Ok(scanned) => scanned,
+            Err(e) => {
+                append_json_error(cfg, &mut buf, name, &error_reason(&e));
+                emit(&mut buf).map_err(|e| e.to_string())?;
+                continue;
+            }
+        };
+        searched += 1;
+        let count = scanned.matched_lines;
+
+        let path_hit =
+            cfg.path_or_content && count == 0 && name.is_some_and(|n| matcher.is_match(n));
+        let stats = Stats {
+            elapsed: start.elapsed(),
+            searches: 1,
+            searches_with_match: usize::from(count > 0),
+            bytes_searched,
+            bytes_printed: printed.get(),
+            matched_lines: count,
+            matches: scanned.matches,
+            timeouts: scanned.timeouts.len(),
+        };
+        append_json_end(cfg, &mut buf, name, path_hit, &stats);
+        emit(&mut buf).map_err(|e| e.to_string())?;
+
+        if count > 0 || path_hit {
+            files_with_matches += 1;
+        }
+        total.add(&stats);
+    }
+
+    if !cfg.quiet {
+        let elapsed_total = started.elapsed();
+        append_json_summary(
+            cfg,
+            &mut buf,
+            searched,
+            files_with_matches,
+            &total,
+            elapsed_total,
+        );
+        writer
+            .write_all(buf.as_bytes())
+            .map_err(|e| e.to_string())?;
+        writer.flush().map_err(|e| e.to_string())?;
+    }
+
+    Ok(status_for(files_with_matches > 0))
+}
+
+/// Search input files/paths, passing each file, line, and error to `sink` as it is found
+/// (see `sink::Sink`) instead of formatting output.
+///
+/// Files are searched one after another in input order, and lines are selected as with
+/// `run`, with `Config::context` lines around them. A file that cannot be opened or read
+/// goes to `Sink::on_error` rather than failing the search; an error returned by the sink
+/// stops it and is returned. Lines that exceed `Config::match_timeout_ms` are left out.
+///
+/// Returns `MatchFound` if any file had a selected line.
+#[cfg(feature = "files")]
+pub fn run_with_sink(
+    cfg: &Config,
+    inputs: &[String],
+    sink: &mut dyn Sink,
+) -> Result<ExitStatus, String> {
+    let searcher = Searcher::new(cfg)?;
+    let matcher = &searcher.matcher;
+    let roots = input_roots(inputs);
+    let files = select_files(cfg, inputs)?.files;
+
+    let mut matched_any = false;
+    for (_, file) in &files {
+        let shown = display_path(&roots, file);
+        let name = if file == "-" { None } else { Some(&*shown) };
+        let reader = match open_file(cfg, searcher.pre.as_ref(), file) {
+            Ok(reader) => reader,
+            Err(e) => {
+                sink.on_error(name, &e.to_string())
+                    .map_err(|e| e.to_string())?;
+                continue;
+            }
+        };
+        sink.on_file_start(name).map_err(|e| e.to_string())?;
+
+        // Keep the sink's own errors apart from those reading the file
+        let mut failed = None;
+        let scanned = scan_reader(cfg, matcher, reader, name, &mut |m, is_match| {
+            let sent = if is_match {
+                sink.on_match(name, m)
+            } else {
+                sink.on_context(name, m)
+            };
+            sent.map_err(|e| {
+                let stop = io::Error::new(e.kind(), "stopped by the sink");
+                failed = Some(e);
+                stop
+            })
+        });
+        if let Some(e) = failed {
+            return Err(e.to_string());
+        }
+        match scanned {
+            Ok(scanned) => {
+                matched_any |= scanned.matched_lines > 0;
+                sink.on_file_end(name, scanned.matched_lines)
+                    .map_err(|e| e.to_string())?;
+            }
+            Err(e) => sink
+                .on_error(name, &e.to_string())
+                .map_err(|e| e.to_string())?,
+        }
+    }
+    Ok(status_for(matched_any))
 }
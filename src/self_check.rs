@@ -0,0 +1,215 @@
+//! Determinism check of the parallel search (`--self-check`).
+//!
+//! `self_check` runs the same search twice: on a thread pool with several threads, where
+//! files are searched concurrently and finish in any order, and on a single thread. Both
+//! runs must produce the same output, status, warnings, errors, and per-file results; anything
+//! that differs is reported as a `Divergence`. Timings are not compared.
+//!
+//! The parallel run uses at least `MIN_PARALLEL_THREADS` threads, so files are searched
+//! concurrently even on machines with a single core, and schedules files in parallel
+//! however small they are, unless `--strategy` says otherwise.
+
+use std::fmt::Write as _;
+
+use crate::config::{Config, FileResult, RunResult, Scheduling};
+use crate::search::run;
+
+/// Fewest threads the parallel run uses.
+pub const MIN_PARALLEL_THREADS: usize = 4;
+
+/// A difference between the parallel and the single-threaded run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// What differs, e.g. `output line 3` or `matches of app.log`.
+    pub what: String,
+    pub parallel: String,
+    pub sequential: String,
+}
+
+/// Outcome of `self_check`.
+#[derive(Debug, Clone)]
+pub struct SelfCheck {
+    /// Threads used by the parallel run.
+    pub threads: usize,
+    /// Result of the single-threaded run.
+    pub result: RunResult,
+    /// Empty when both runs agree.
+    pub divergences: Vec<Divergence>,
+}
+
+/// Run the search for `inputs` in parallel and on a single thread and compare the
+/// results. Errors when either run fails, or for standard input, which cannot be read
+/// twice.
+pub fn self_check(cfg: &Config, inputs: &[String]) -> Result<SelfCheck, String> {
+    let reads_stdin = (inputs.is_empty() && !cfg.recursive) || inputs.iter().any(|i| i == "-");
+    if reads_stdin {
+        return Err("--self-check searches its inputs twice and cannot read standard input".into());
+    }
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = available.max(MIN_PARALLEL_THREADS);
+    let parallel_cfg = Config {
+        scheduling: cfg.scheduling.or(Some(Scheduling::Parallel)),
+        ..cfg.clone()
+    };
+    let parallel = run_on_threads(&parallel_cfg, inputs, threads)?;
+    let sequential = run_on_threads(cfg, inputs, 1)?;
+    Ok(SelfCheck {
+        threads,
+        divergences: compare(&parallel, &sequential),
+        result: sequential,
+    })
+}
+
+fn run_on_threads(cfg: &Config, inputs: &[String], threads: usize) -> Result<RunResult, String> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| format!("cannot start {} search thread(s): {}", threads, e))?
+        .install(|| run(cfg, inputs))
+}
+
+/// The differences between a parallel and a sequential result.
+pub fn compare(parallel: &RunResult, sequential: &RunResult) -> Vec<Divergence> {
+    let mut found = Vec::new();
+    let mut differ = |what: String, p: String, s: String| {
+        if p != s {
+            found.push(Divergence {
+                what,
+                parallel: p,
+                sequential: s,
+            });
+        }
+    };
+
+    differ(
+        "status".into(),
+        format!("{:?}", parallel.status),
+        format!("{:?}", sequential.status),
+    );
+    // The first line that differs locates the problem better than the whole output
+    let (p_lines, s_lines): (Vec<&str>, Vec<&str>) = (
+        parallel.output.lines().collect(),
+        sequential.output.lines().collect(),
+    );
+    if let Some(i) =
+        (0..p_lines.len().max(s_lines.len())).find(|&i| p_lines.get(i) != s_lines.get(i))
+    {
+        let line = |lines: &[&str]| {
+            lines
+                .get(i)
+                .map_or("(end of output)".into(), |l| l.to_string())
+        };
+        differ(
+            format!("output line {}", i + 1),
+            line(&p_lines),
+            line(&s_lines),
+        );
+    }
+    differ(
+        "warnings".into(),
+        parallel.warnings.join("; "),
+        sequential.warnings.join("; "),
+    );
+    differ(
+        "errors".into(),
+        parallel.errors.join("; "),
+        sequential.errors.join("; "),
+    );
+    differ(
+        "searched files".into(),
+        file_names(&parallel.files),
+        file_names(&sequential.files),
+    );
+    for (p, s) in parallel.files.iter().zip(&sequential.files) {
+        let name = p.path.as_deref().unwrap_or("(standard input)");
+        differ(
+            format!("matched lines of {}", name),
+            p.matched_lines.to_string(),
+            s.matched_lines.to_string(),
+        );
+        differ(
+            format!("matches of {}", name),
+            format!("{:?}", p.matches),
+            format!("{:?}", s.matches),
+        );
+    }
+    found
+}
+
+fn file_names(files: &[FileResult]) -> String {
+    let names: Vec<&str> = files
+        .iter()
+        .map(|f| f.path.as_deref().unwrap_or("-"))
+        .collect();
+    names.join(", ")
+}
+
+/// The report printed by `--self-check`.
+pub fn format_report(check: &SelfCheck) -> String {
+    let mut out = String::new();
+    if check.divergences.is_empty() {
+        let lines: usize = check.result.files.iter().map(|f| f.matched_lines).sum();
+        let _ = writeln!(
+            out,
+            "self-check: ok, the parallel ({} threads) and single-threaded runs agree ({} file(s), {} matching line(s))",
+            check.threads,
+            check.result.files.len(),
+            lines
+        );
+        return out;
+    }
+    let _ = writeln!(
+        out,
+        "self-check: FAILED, the parallel ({} threads) and single-threaded runs differ:",
+        check.threads
+    );
+    for d in &check.divergences {
+        let _ = writeln!(
+            out,
+            "  {}:\n    parallel:        {}\n    single-threaded: {}",
+            d.what, d.parallel, d.sequential
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExitStatus;
+
+    fn result(output: &str) -> RunResult {
+        RunResult {
+            output: output.to_string(),
+            status: ExitStatus::MatchFound,
+            warnings: Vec::new(),
+            timings: Vec::new(),
+            files: Vec::new(),
+            errors: Vec::new(),
+            skipped: Vec::new(),
+            stats: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_compare_reports_first_differing_line() {
+        assert!(compare(&result("a\nb\n"), &result("a\nb\n")).is_empty());
+
+        let found = compare(&result("a\nc\nd\n"), &result("a\nb\n"));
+        assert_eq!(
+            found,
+            vec![Divergence {
+                what: "output line 2".into(),
+                parallel: "c".into(),
+                sequential: "b".into(),
+            }]
+        );
+        let report = format_report(&SelfCheck {
+            threads: 4,
+            result: result(""),
+            divergences: found,
+        });
+        assert!(report.starts_with("self-check: FAILED"));
+        assert!(report.contains("single-threaded: b"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Structured search events for embedders (see `search::run_with_sink`).
+//!
+//! A `Sink` receives each file, line, and error of a search as it is found, so library
+//! consumers need not parse the formatted output of `run`. Every method has a default
+//! that does nothing; implement the ones you need. Paths are as the output would show
+//! them, and `None` stands for standard input.
+
+use std::io;
+
+use crate::search::Match;
+
+/// Receiver of the events of a search, called in input order from the searching thread.
+///
+/// Returning an error from any method stops the search, and `run_with_sink` returns that
+/// error.
+pub trait Sink {
+    /// A file is about to be searched.
+    fn on_file_start(&mut self, _path: Option<&str>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// A selected line: one that matched, or with `Config::invert` one that did not.
+    fn on_match(&mut self, _path: Option<&str>, _line: &Match) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// A line shown around a selected one (`Config::context`).
+    fn on_context(&mut self, _path: Option<&str>, _line: &Match) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// A file was searched to the end, with the number of lines selected in it.
+    fn on_file_end(&mut self, _path: Option<&str>, _matched_lines: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// A file could not be opened or read. No `on_file_end` follows, and the search goes
+    /// on with the next file.
+    fn on_error(&mut self, _path: Option<&str>, _error: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
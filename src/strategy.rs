@@ -0,0 +1,254 @@
+//! Choosing how a search is executed (`--strategy`, `--explain`).
+//!
+//! Two choices are made for every search, each of which `--strategy` can force:
+//! - matching (`Matching`): a pattern that is plain text, with no option needing the
+//!   regex engine, is found by substring search with `memchr` (see `literal`), ignoring
+//!   case if the text is ASCII; anything else by the regex engine.
+//! - scheduling (`Scheduling`): several files are searched in parallel once they add up
+//!   to `PARALLEL_MIN_BYTES`; fewer bytes are searched one file after another, where
+//!   starting threads would cost more than it saves. Files found by a parallel walk are
+//!   searched as they are found, without waiting for the walk to add up their sizes.
+//!
+//! Lines are matched as they are read (`io_utils::LineReader`), unless the search needs
+//! the whole input (see `search::needs_whole_input`); `explain` reports which. Nothing
+//! else is chosen per search: inputs are always read through a buffer rather than memory
+//! mapped, the regex engine decides on its own literal prefilters, and each file is
+//! searched by a single thread.
+
+#[cfg(feature = "files")]
+use std::fmt::Write as _;
+
+#[cfg(feature = "files")]
+use crate::config::Scheduling;
+use crate::config::{Config, Engine, Matching};
+use crate::regex_utils::captures_referenced;
+
+/// Total size from which the files of a search are searched in parallel.
+pub const PARALLEL_MIN_BYTES: u64 = 1 << 20;
+
+/// A strategy decision and why it was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice<T> {
+    pub chosen: T,
+    /// Whether `--strategy` forced the choice.
+    pub forced: bool,
+    /// Why it was made, e.g. `the pattern is plain text`.
+    pub reason: String,
+}
+
+impl<T> Choice<T> {
+    fn auto(chosen: T, reason: impl Into<String>) -> Self {
+        Self {
+            chosen,
+            forced: false,
+            reason: reason.into(),
+        }
+    }
+
+    fn forced(chosen: T) -> Self {
+        Self {
+            chosen,
+            forced: true,
+            reason: "forced with --strategy".into(),
+        }
+    }
+}
+
+/// How lines are matched for the resolved pattern in `cfg` (after `--syntax`
+/// translation), with the text to search for when that is a substring search.
+pub(crate) fn choose_matching(cfg: &Config) -> Result<(Choice<Matching>, Option<String>), String> {
+    match (cfg.matching, literal_text(cfg)) {
+        (Some(Matching::Regex), _) => Ok((Choice::forced(Matching::Regex), None)),
+        (Some(Matching::Literal), Ok(text)) => Ok((Choice::forced(Matching::Literal), Some(text))),
+        (Some(Matching::Literal), Err(why)) => {
+            Err(format!("--strategy literal cannot be used: {}", why))
+        }
+        (None, Ok(text)) => Ok((
+            Choice::auto(Matching::Literal, "the pattern is plain text"),
+            Some(text),
+        )),
+        (None, Err(why)) => Ok((Choice::auto(Matching::Regex, why), None)),
+    }
+}
+
+/// The text a line must contain to match the pattern in `cfg`, or why matching needs the
+/// regex engine.
+fn literal_text(cfg: &Config) -> Result<String, &'static str> {
+    if cfg.engine == Engine::Fancy {
+        return Err("--engine fancy is used");
+    }
+    if cfg.definition.is_some() {
+        return Err("--def matches definitions with a regex");
+    }
+    if cfg.multiline {
+        return Err("matches may span lines (-U)");
+    }
+    if cfg.word || cfg.line {
+        return Err("-w and -x need word or line boundaries");
+    }
+    if captures_referenced(cfg) {
+        return Err("capture groups are referenced");
+    }
+    let raw = cfg.patterns.join("");
+    if raw.is_empty() {
+        return Err("the pattern is empty");
+    }
+    let text = unescape(&raw).ok_or("the pattern has regex syntax or Boolean operators")?;
+    if cfg.case_insensitive && !text.is_ascii() {
+        return Err("matching ignores case in non-ASCII text");
+    }
+    Ok(text)
+}
+
+/// `pattern` as plain text: escaped punctuation stands for itself, and any other regex or
+/// Boolean syntax gives `None`.
+fn unescape(pattern: &str) -> Option<String> {
+    let mut text = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(next) if next.is_ascii_punctuation() => text.push(next),
+                _ => return None,
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' | '&'
+            | '!' | '~' | '"' => return None,
+            _ => text.push(ch),
+        }
+    }
+    Some(text)
+}
+
+/// How the `files` selected for a search are scheduled.
+#[cfg(feature = "files")]
+pub(crate) fn choose_scheduling(
+    cfg: &Config,
+    inputs: &[String],
+    files: &[(usize, String)],
+) -> Choice<Scheduling> {
+    if crate::search::stops_at_first_match(cfg) {
+        return Choice::auto(
+            Scheduling::Sequential,
+            "the search stops at the first match (-q with --compat=gnu)",
+        );
+    }
+    if let Some(forced) = cfg.scheduling {
+        return Choice::forced(forced);
+    }
+    if files.len() < 2 {
+        return Choice::auto(Scheduling::Sequential, "there is at most one file");
+    }
+    if crate::search::streams_walk(cfg, inputs) {
+        return Choice::auto(
+            Scheduling::Parallel,
+            format!(
+                "{} files, searched as the parallel walk finds them",
+                files.len()
+            ),
+        );
+    }
+    // Stop adding up at the threshold, so that large trees are not stat'ed twice
+    let mut total = 0u64;
+    for (_, path) in files {
+        total += std::fs::metadata(path).map_or(0, |m| m.len());
+        if total >= PARALLEL_MIN_BYTES {
+            return Choice::auto(
+                Scheduling::Parallel,
+                format!(
+                    "{} files, at least {} bytes in total",
+                    files.len(),
+                    PARALLEL_MIN_BYTES
+                ),
+            );
+        }
+    }
+    Choice::auto(
+        Scheduling::Sequential,
+        format!(
+            "{} files of {} bytes in total, fewer than {}",
+            files.len(),
+            total,
+            PARALLEL_MIN_BYTES
+        ),
+    )
+}
+
+/// A report of the strategy a search of `inputs` with `cfg` would use, one line per
+/// decision. Nothing is searched.
+#[cfg(feature = "files")]
+pub fn explain(cfg: &Config, inputs: &[String]) -> Result<String, String> {
+    let matcher = crate::matcher::Matcher::new(cfg)?;
+    let matching = matcher.matching();
+    let files = crate::search::select_files(cfg, inputs)?.files;
+    let scheduling = choose_scheduling(cfg, inputs, &files);
+
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "matching: {} ({})",
+        match matching.chosen {
+            Matching::Literal => "literal",
+            Matching::Regex => "regex",
+        },
+        matching.reason
+    );
+    let _ = writeln!(
+        report,
+        "scheduling: {} ({})",
+        match scheduling.chosen {
+            Scheduling::Sequential => "sequential",
+            Scheduling::Parallel => "parallel",
+        },
+        scheduling.reason
+    );
+    let _ = writeln!(report, "walking: {}", walking(cfg));
+    let _ = writeln!(
+        report,
+        "reading: {} (buffered, one thread per file)",
+        if crate::search::needs_whole_input(cfg, &matcher) || crate::search::reads_lossy(cfg) {
+            "whole inputs"
+        } else {
+            "line by line"
+        }
+    );
+    Ok(report)
+}
+
+/// How directories are walked (see `fs_utils::walks_in_parallel`).
+#[cfg(feature = "files")]
+fn walking(cfg: &Config) -> String {
+    let sequential = if !cfg.recursive {
+        "no directory is walked"
+    } else if cfg.skip_binary_dirs {
+        "--skip-binary-dirs samples the first files of each directory"
+    } else if cfg.max_entries.is_some() {
+        "--max-entries stops after the first entries"
+    } else if cfg.follow_symlinks {
+        "symlinks are followed"
+    } else if rayon::current_num_threads() < 2 {
+        "there is one thread"
+    } else {
+        return format!("parallel ({} threads)", rayon::current_num_threads());
+    };
+    format!("sequential ({})", sequential)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape_takes_only_plain_text() {
+        assert_eq!(
+            unescape("connection reset").as_deref(),
+            Some("connection reset")
+        );
+        assert_eq!(unescape(r"v1\.2 \(beta\)").as_deref(), Some("v1.2 (beta)"));
+        assert_eq!(unescape("a-b: c/d").as_deref(), Some("a-b: c/d"));
+        for pattern in [
+            "err.r", r"\d+", "a|b", "a&b", "!a", "a~3~b", r#""a b""#, "x\\",
+        ] {
+            assert_eq!(unescape(pattern), None, "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Searches as Streams of events, for async services (the `tokio` feature).
+//!
+//! `run_async` and `follow_async` run the same searches as `run_with_sink` and `follow`
+//! on tokio's blocking thread pool, the way `tokio::fs` runs file I/O, so they never hold
+//! up async worker threads. Events are sent over a bounded channel: a slow consumer
+//! pauses the search, and dropping the stream stops it.
+//!
+//! ```no_run
+//! use rgrep::Config;
+//! use rgrep::stream::{SearchEvent, run_async};
+//! use tokio_stream::StreamExt;
+//!
+//! # async fn example() {
+//! let mut cfg = Config::default();
+//! cfg.patterns = vec!["error".into()];
+//! cfg.recursive = true;
+//! let mut events = run_async(cfg, vec!["/var/log".into()]);
+//! while let Some(event) = events.next().await {
+//!     if let Ok(SearchEvent::Match { path, line }) = event {
+//!         println!("{:?}:{}: {}", path, line.line_number, line.line);
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::io;
+
+use tokio::sync::mpsc::{self, Sender};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::config::Config;
+use crate::follow::{FollowEvent, FollowSink, follow_with_sink};
+use crate::search::{Match, run_with_sink};
+use crate::sink::Sink;
+
+/// Events buffered between the search and a consumer that has not taken them yet.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// An event of `run_async`: what a `Sink` is told, as a value. Paths are `None` for
+/// standard input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchEvent {
+    /// A file is about to be searched.
+    FileStart { path: Option<String> },
+    /// A selected line (see `Sink::on_match`).
+    Match { path: Option<String>, line: Match },
+    /// A line around a selected one (`Config::context`).
+    Context { path: Option<String>, line: Match },
+    /// A file was searched to the end, with the number of lines selected in it.
+    FileEnd {
+        path: Option<String>,
+        matched_lines: usize,
+    },
+    /// A file could not be opened or read; the search goes on.
+    Error { path: Option<String>, error: String },
+}
+
+/// Search input files/paths like `run_with_sink`, yielding its events in input order.
+///
+/// The stream ends after the last file, or with an `Err` if the search could not run
+/// (e.g. an invalid pattern or an input that cannot be walked). Must be called within a
+/// tokio runtime.
+pub fn run_async(
+    cfg: Config,
+    inputs: Vec<String>,
+) -> impl Stream<Item = Result<SearchEvent, String>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::task::spawn_blocking(move || {
+        let mut sink = ChannelSink { tx: &tx };
+        if let Err(e) = run_with_sink(&cfg, &inputs, &mut sink) {
+            // Nobody to tell when the stream was dropped
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Follow a single growing file like `follow`, yielding the lines it would print.
+///
+/// Following goes on until the stream is dropped. The stream ends with an `Err` if the
+/// file cannot be followed (see `follow`); `Config::metrics_file` is written as with
+/// `follow`. Must be called within a tokio runtime.
+pub fn follow_async(
+    cfg: Config,
+    inputs: Vec<String>,
+) -> impl Stream<Item = Result<FollowEvent, String>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::task::spawn_blocking(move || {
+        let mut out = ChannelSink { tx: &tx };
+        if let Err(e) = follow_with_sink(&cfg, &inputs, &mut out) {
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Sends events into a channel, failing once its receiver is gone.
+struct ChannelSink<'a, T> {
+    tx: &'a Sender<Result<T, String>>,
+}
+
+impl<T> ChannelSink<'_, T> {
+    fn send(&self, event: T) -> io::Result<()> {
+        self.tx
+            .blocking_send(Ok(event))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "event stream dropped"))
+    }
+}
+
+impl Sink for ChannelSink<'_, SearchEvent> {
+    fn on_file_start(&mut self, path: Option<&str>) -> io::Result<()> {
+        self.send(SearchEvent::FileStart {
+            path: path.map(str::to_string),
+        })
+    }
+
+    fn on_match(&mut self, path: Option<&str>, line: &Match) -> io::Result<()> {
+        self.send(SearchEvent::Match {
+            path: path.map(str::to_string),
+            line: line.clone(),
+        })
+    }
+
+    fn on_context(&mut self, path: Option<&str>, line: &Match) -> io::Result<()> {
+        self.send(SearchEvent::Context {
+            path: path.map(str::to_string),
+            line: line.clone(),
+        })
+    }
+
+    fn on_file_end(&mut self, path: Option<&str>, matched_lines: usize) -> io::Result<()> {
+        self.send(SearchEvent::FileEnd {
+            path: path.map(str::to_string),
+            matched_lines,
+        })
+    }
+
+    fn on_error(&mut self, path: Option<&str>, error: &str) -> io::Result<()> {
+        self.send(SearchEvent::Error {
+            path: path.map(str::to_string),
+            error: error.to_string(),
+        })
+    }
+}
+
+impl FollowSink for ChannelSink<'_, FollowEvent> {
+    fn on_line(&mut self, event: FollowEvent) -> io::Result<()> {
+        self.send(event)
+    }
+
+    fn closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
@@ -0,0 +1,171 @@
+//! Pattern syntaxes (`--syntax`, `-F`/`-G`/`-E`).
+//!
+//! Extended patterns are the regex crate's syntax, with rgrep's Boolean operators on top.
+//! Literal (`-F`) and basic (`-G`) patterns are translated into a single regex of that
+//! syntax, so that `&`, `!`, `~N~`, and parentheses keep their grep meaning:
+//!
+//! - literal: the pattern is plain text; every character stands for itself.
+//! - basic: POSIX basic regular expressions as in `grep -G`. `\(` `\)` group, `\{m,n\}`
+//!   repeats, and the GNU extensions `\|`, `\+`, and `\?` alternate and repeat, while the
+//!   unescaped `( ) { } | + ?` are literal. `*` at the start of an expression or group is
+//!   literal, and `^` and `$` anchor only at the start and end. Bracket expressions take
+//!   backslashes literally and support classes such as `[[:digit:]]`.
+
+use crate::config::Syntax;
+
+/// Translate `pattern` from `syntax` into the regex crate's syntax.
+pub fn translate(pattern: &str, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Literal => regex::escape(pattern),
+        Syntax::Basic => bre_to_regex(pattern),
+        Syntax::Extended => pattern.to_string(),
+    }
+}
+
+/// Translate a POSIX basic regular expression into the regex crate's syntax.
+pub fn bre_to_regex(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::with_capacity(pattern.len() + 8);
+    // Whether the next character starts an expression, where `*` is literal
+    let mut at_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        let starts_next = match ch {
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                i += 1;
+                match next {
+                    '(' | '|' => {
+                        out.push(next);
+                        true
+                    }
+                    ')' | '{' | '}' | '+' | '?' => {
+                        out.push(next);
+                        false
+                    }
+                    // Other escapes (`\.`, `\*`, `\w`, `\<`, ...) mean the same
+                    _ => {
+                        out.push('\\');
+                        out.push(next);
+                        false
+                    }
+                }
+            }
+            '\\' => {
+                out.push_str(r"\\");
+                false
+            }
+            '[' => {
+                i += bracket_expression(&chars[i..], &mut out) - 1;
+                false
+            }
+            '*' if at_start => {
+                out.push_str(r"\*");
+                false
+            }
+            '^' if at_start => {
+                out.push('^');
+                // `^*` matches a literal star at the start of the line
+                true
+            }
+            '$' if at_end(&chars[i + 1..]) => {
+                out.push('$');
+                false
+            }
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' | '^' | '$' => {
+                out.push('\\');
+                out.push(ch);
+                false
+            }
+            _ => {
+                out.push(ch);
+                false
+            }
+        };
+        at_start = starts_next;
+        i += 1;
+    }
+    out
+}
+
+/// Whether `rest`, the text after a `$`, ends the expression (the pattern, a group, or
+/// an alternative), so that the `$` is an anchor.
+fn at_end(rest: &[char]) -> bool {
+    matches!(rest, [] | ['\\', ')' | '|', ..])
+}
+
+/// Translate the bracket expression at the start of `chars` into `out`, returning the
+/// number of characters it spans. An unclosed `[` is a literal bracket.
+fn bracket_expression(chars: &[char], out: &mut String) -> usize {
+    let mut i = 1;
+    let mut class = String::from("[");
+    if chars.get(i) == Some(&'^') {
+        class.push('^');
+        i += 1;
+    }
+    // A `]` first in the list is literal
+    if chars.get(i) == Some(&']') {
+        class.push_str(r"\]");
+        i += 1;
+    }
+    while i < chars.len() {
+        match chars[i] {
+            ']' => {
+                class.push(']');
+                out.push_str(&class);
+                return i + 1;
+            }
+            // Character classes such as `[:alpha:]` are copied whole
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let end = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == ':' && chars[j + 1] == ']');
+                match end {
+                    Some(end) => {
+                        class.extend(&chars[i..end + 2]);
+                        i = end + 2;
+                        continue;
+                    }
+                    None => class.push_str(r"\["),
+                }
+            }
+            // Backslashes are literal, and the regex crate's nested classes and set
+            // operators (`[`, `&&`, `~~`) are not operators in POSIX
+            c @ ('\\' | '[' | '&' | '~') => {
+                class.push('\\');
+                class.push(c);
+            }
+            c => class.push(c),
+        }
+        i += 1;
+    }
+    out.push_str(r"\[");
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bre_translation() {
+        assert_eq!(bre_to_regex(r"\(ab\)\{2\}"), "(ab){2}");
+        assert_eq!(bre_to_regex(r"a\|b\+c\?"), "a|b+c?");
+        assert_eq!(bre_to_regex("f(x) {y} a|b+c?"), r"f\(x\) \{y\} a\|b\+c\?");
+        assert_eq!(bre_to_regex("*a*"), r"\*a*");
+        assert_eq!(bre_to_regex(r"^*x\(*y\)"), r"^\*x(\*y)");
+        assert_eq!(bre_to_regex("a^b$c$"), r"a\^b\$c$");
+        assert_eq!(bre_to_regex(r"\(a$\|^b\)"), r"(a$|^b)");
+        assert_eq!(bre_to_regex(r"\.\*\w"), r"\.\*\w");
+    }
+
+    #[test]
+    fn test_bre_bracket_expressions() {
+        assert_eq!(bre_to_regex(r"[\n]"), r"[\\n]");
+        assert_eq!(bre_to_regex("[]a]"), r"[\]a]");
+        assert_eq!(bre_to_regex("[^]a-z]"), r"[^\]a-z]");
+        assert_eq!(bre_to_regex("[[:digit:]x]"), "[[:digit:]x]");
+        assert_eq!(bre_to_regex("[a&&b]"), r"[a\&\&b]");
+        assert_eq!(bre_to_regex("a[b"), r"a\[b");
+    }
+}
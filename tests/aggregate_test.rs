@@ -0,0 +1,300 @@
+#![cfg(feature = "files")]
+
+use rgrep::aggregate::{format_timings, summarize};
+use rgrep::{ColorChoice, Config, FileTiming, run, run_on_reader};
+use std::fs;
+use std::io::Cursor;
+use std::time::Duration;
+
+fn create_duration_config(pattern: &str, extract: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        duration_stats: Some(extract.to_string()),
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+// ============ DURATION STATS TESTS ============
+
+#[test]
+fn test_summarize_percentiles() {
+    let values: Vec<f64> = (1..=20).map(f64::from).collect();
+    let s = summarize(&values).unwrap();
+    assert_eq!(s.count, 20);
+    assert_eq!(s.min, 1.0);
+    assert_eq!(s.max, 20.0);
+    assert_eq!(s.avg, 10.5);
+    assert_eq!(s.p50, 10.0);
+    assert_eq!(s.p95, 19.0);
+    assert!(summarize(&[]).is_none());
+}
+
+#[test]
+fn test_duration_stats_single_reader() {
+    let cfg = create_duration_config("GET", r"took (\d+)ms");
+    let data = "GET /a took 10ms\nPOST /b took 99ms\nGET /c took 30ms\nGET /d no timing\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    assert_eq!(
+        result.output,
+        "count=2 min=10 avg=20 p50=10 p95=30 max=30\n"
+    );
+}
+
+#[test]
+fn test_duration_stats_per_file_and_total() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, b"took 1.5ms\ntook 2.5ms\n").unwrap();
+    fs::write(&b, b"nothing\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_duration_config("took|nothing", r"took ([\d.]+)ms");
+    cfg.table = false;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("a.log: count=2 min=1.50 avg=2 p50=1.50 p95=2.50 max=2.50"));
+    assert!(lines[1].ends_with("b.log: count=0"));
+    assert_eq!(
+        lines[2],
+        "total: count=2 min=1.50 avg=2 p50=1.50 p95=2.50 max=2.50"
+    );
+}
+
+#[test]
+fn test_duration_stats_requires_capture_group() {
+    let cfg = create_duration_config("took", r"took \d+ms");
+    let result = run_on_reader(&cfg, Cursor::new("took 5ms\n"), None);
+    assert!(result.is_err());
+}
+
+// ============ TIMINGS TESTS ============
+
+#[test]
+fn test_timings_recorded_per_file() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, b"hit\n").unwrap();
+    fs::write(&b, b"miss\nmiss\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let cfg = Config {
+        patterns: vec!["hit".to_string()],
+        timings: Some(5),
+        ..Default::default()
+    };
+    let result = run(&cfg, &inputs).unwrap();
+
+    let recorded: Vec<(&str, u64)> = result
+        .timings
+        .iter()
+        .map(|t| (t.path.as_str(), t.bytes))
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![(inputs[0].as_str(), 4), (inputs[1].as_str(), 10)]
+    );
+
+    // Not recorded unless requested
+    let cfg = Config {
+        timings: None,
+        ..cfg
+    };
+    assert!(run(&cfg, &inputs).unwrap().timings.is_empty());
+}
+
+#[test]
+fn test_format_timings_slowest_first() {
+    let timings = vec![
+        FileTiming {
+            path: "fast.log".into(),
+            elapsed: Duration::from_millis(1),
+            bytes: 1_000,
+        },
+        FileTiming {
+            path: "slow.log".into(),
+            elapsed: Duration::from_secs(2),
+            bytes: 4_000_000,
+        },
+        FileTiming {
+            path: "mid.log".into(),
+            elapsed: Duration::from_millis(500),
+            bytes: 0,
+        },
+    ];
+
+    let report = format_timings(&timings, 2);
+    let rows: Vec<Vec<&str>> = report
+        .lines()
+        .map(|l| l.split_whitespace().collect())
+        .collect();
+    assert_eq!(rows[0], vec!["FILE", "TIME", "BYTES", "MB/S"]);
+    assert_eq!(rows[1], vec!["slow.log", "2000.00ms", "4000000", "2.00"]);
+    assert_eq!(rows[2], vec!["mid.log", "500.00ms", "0", "0.00"]);
+    assert_eq!(rows.len(), 3);
+}
+
+// ============ STATS TESTS ============
+
+#[test]
+fn test_run_stats_count_the_whole_run() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), b"error error\nok\n").unwrap();
+    fs::write(td.path().join("b.log"), b"error\n").unwrap();
+    fs::write(td.path().join("c.log"), b"ok\n").unwrap();
+    let inputs = vec![td.path().to_string_lossy().to_string()];
+    let cfg = Config {
+        patterns: vec!["error".to_string()],
+        recursive: true,
+        ..Default::default()
+    };
+
+    let stats = run(&cfg, &inputs).unwrap().stats;
+    assert_eq!(
+        (
+            stats.searches,
+            stats.searches_with_match,
+            stats.matched_lines,
+            stats.matches,
+            stats.bytes_searched
+        ),
+        (3, 2, 2, 3, 24)
+    );
+    assert!(stats.elapsed > Duration::ZERO);
+
+    let stats = run_on_reader(&cfg, Cursor::new("error\n"), None)
+        .unwrap()
+        .stats;
+    assert_eq!((stats.searches, stats.matched_lines), (1, 1));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_stats_flag_prints_summary_after_results() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), b"error error\nok\n").unwrap();
+    fs::write(td.path().join("b.log"), b"ok\n").unwrap();
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["--stats", "-R", "-r", "error"])
+        .arg(td.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let (results, summary) = stdout.split_once("\n\n").unwrap();
+    assert!(results.ends_with("1:error error"), "{}", stdout);
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(
+        lines[..6],
+        [
+            "STAT                   VALUE",
+            "files searched             2",
+            "files with matches         1",
+            "matched lines              1",
+            "matches                    2",
+            "bytes searched            18",
+        ]
+    );
+    assert!(lines[6].starts_with("seconds "), "{}", stdout);
+    assert_eq!(lines.len(), 7);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_stats_no_table_prints_name_value_lines() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), b"error error\nok\n").unwrap();
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["--stats", "--no-table", "-r", "error", "a.log"])
+        .current_dir(td.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let (_, summary) = stdout.split_once("\n\n").unwrap();
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(
+        lines[..5],
+        [
+            "files_searched:1",
+            "files_with_matches:1",
+            "matched_lines:1",
+            "matches:2",
+            "bytes_searched:15",
+        ]
+    );
+    assert!(lines[5].starts_with("seconds:"), "{}", stdout);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_stats_counts_timed_out_lines() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), b"error one\nerror two\n").unwrap();
+    let run = |args: &[&str]| {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+            .args(["--stats", "--no-table", "-r", "error&two"])
+            .args(args)
+            .arg("a.log")
+            .current_dir(td.path())
+            .output()
+            .unwrap();
+        String::from_utf8(out.stdout).unwrap()
+    };
+    // No line can be matched within a zero budget
+    let stdout = run(&["--match-timeout", "0"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[1..6],
+        [
+            "files_searched:1",
+            "files_with_matches:0",
+            "matched_lines:0",
+            "matches:0",
+            "timeouts:2",
+        ]
+    );
+    // Without timeouts, the line is left out
+    assert!(!run(&[]).contains("timeouts"));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_stats_counts_matches_in_count_mode() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(
+        td.path().join("a.log"),
+        b"error error
+ok
+error
+",
+    )
+    .unwrap();
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["--stats", "-c", "-r", "error", "a.log"])
+        .current_dir(td.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let (results, summary) = stdout.split_once("\n\n").unwrap();
+    assert_eq!(results, "2");
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(
+        lines[1..5],
+        [
+            "files searched             1",
+            "files with matches         1",
+            "matched lines              2",
+            "matches                    3",
+        ]
+    );
+}
@@ -103,3 +103,22 @@ fn test_and_pattern_with_word_boundaries() {
         "Word boundary AND pattern should match 2 lines"
     );
 }
+
+#[test]
+fn test_and_pattern_with_squashed_whitespace() {
+    let test_data = "2025-09-17 14:15:42.413 +02:00  DBG     LineConnectDriver_       Transmit   message to device: oINLK:1\n\
+                     2025-09-17 14:15:43.455 +02:00  DBG     LineConnectDriver_       Received message from device: iINLK:1\n";
+
+    let mut cfg = create_test_config("DBG LineConnectDriver_ Transmit&message to device");
+    let result = rgrep::run_on_reader(&cfg, Cursor::new(test_data), None).unwrap();
+    assert_eq!(result.output, "", "Padding should prevent a match");
+
+    cfg.squash_whitespace = true;
+    cfg.line_number = false;
+    let result = rgrep::run_on_reader(&cfg, Cursor::new(test_data), None).unwrap();
+    // The line is printed with its original padding
+    assert_eq!(
+        result.output,
+        test_data.lines().next().unwrap().to_string() + "\n"
+    );
+}
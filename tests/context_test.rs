@@ -1,11 +1,15 @@
-use rgrep::{Config, Context, run_on_reader};
+use rgrep::{ColorChoice, Config, Context, run_on_reader};
 use std::io::Cursor;
 
 fn create_config_with_context(pattern: &str, before: usize, after: usize) -> Config {
     Config {
         patterns: vec![pattern.to_string()],
-        context: Context { before, after },
-        color: false,
+        context: Context {
+            before,
+            after,
+            ..Default::default()
+        },
+        color: ColorChoice::Never,
         ..Default::default()
     }
 }
@@ -143,6 +147,26 @@ fn test_overlapping_context_regions() {
         lines.len() >= 4,
         "Should show both matches with their contexts"
     );
+    // Each physical line is printed once
+    assert_eq!(
+        lines,
+        vec!["1:line1", "2:match", "3:line3", "4:match", "5:line5"]
+    );
+}
+
+#[test]
+fn test_after_context_not_repeated_as_before_context() {
+    let cfg = create_config_with_context("m", 3, 1);
+    let data = "a
+m1
+b
+c
+m2
+";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines, vec!["1:a", "2:m1", "3:b", "4:c", "5:m2"]);
 }
 
 #[test]
@@ -242,3 +266,73 @@ fn test_consecutive_matches_with_context() {
     assert!(result.output.contains("line0"));
     assert!(result.output.contains("line4"));
 }
+
+#[test]
+fn test_context_percent_scales_with_input() {
+    let mut cfg = create_config_with_context("match", 0, 1);
+    cfg.context.percent = Some(20);
+    cfg.line_number = false;
+    // 20% of 10 lines is 2 lines on each side; -A 1 is less than that
+    let data = "1\n2\n3\n4\nmatch\n6\n7\n8\n9\n10\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "3\n4\nmatch\n6\n7\n");
+
+    // On a short input the share rounds down, and the fixed context remains
+    let result = run_on_reader(&cfg, Cursor::new("a\nmatch\nb\n"), None).unwrap();
+    assert_eq!(result.output, "match\nb\n");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_asymmetric_and_repeated_context_option() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("f.txt");
+    std::fs::write(&file, "1\n2\n3\nmatch\n5\n6\n7\n").unwrap();
+    let rgrep = |args: &[&str]| {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+            .args(args)
+            .args(["-n", "-r", "match"])
+            .arg(&file)
+            .output()
+            .unwrap();
+        (
+            String::from_utf8_lossy(&out.stdout).into_owned(),
+            out.status.code(),
+        )
+    };
+    assert_eq!(
+        rgrep(&["--color=never", "-C", "1,2"]).0,
+        "3:3\n4:match\n5:5\n6:6\n"
+    );
+    // The last -C wins
+    assert_eq!(
+        rgrep(&["--color=never", "-C", "3", "-C", "0,1"]).0,
+        "4:match\n5:5\n"
+    );
+    assert_eq!(rgrep(&["-C", "1,x"]).1, Some(2));
+}
+
+#[test]
+fn test_lines_are_matched_as_they_are_read() {
+    use std::io::Read;
+    // The input never ends; only a search that stops at the first match can return
+    let mut cfg = create_config_with_context("needle", 2, 0);
+    cfg.compat = rgrep::Compat::Gnu;
+    cfg.quiet = true;
+    let endless = Cursor::new("a\nneedle\n").chain(std::io::repeat(b'a'));
+    let result = run_on_reader(&cfg, endless, None).unwrap();
+    assert_eq!(result.status, rgrep::ExitStatus::MatchFound);
+
+    // Leading context comes from the lines held back, however long the input
+    let data: String = (1..=100_000)
+        .map(|n| format!("line {}\n", n))
+        .chain(["needle\n".to_string()])
+        .collect();
+    let mut cfg = create_config_with_context("needle", 2, 0);
+    cfg.line_number = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(
+        result.output,
+        "99999:line 99999\n100000:line 100000\n100001:needle\n"
+    );
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "files")]
+
+use rgrep::batch::Query;
+use rgrep::doctor::{
+    Severity, check_color, check_file_list_cache, check_inputs, check_pattern, format_report,
+    has_errors,
+};
+use rgrep::{ColorChoice, Config, run};
+use std::fs;
+
+fn create_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+// ============ COLOR TESTS ============
+
+#[test]
+fn test_color_ok_on_capable_terminal() {
+    let cfg = Config::default();
+    let check = check_color(&cfg, true, |name| {
+        (name == "TERM").then(|| "xterm-256color".to_string())
+    });
+    assert_eq!(check.severity, Severity::Ok);
+    assert!(check.detail.contains("xterm-256color"));
+}
+
+#[test]
+fn test_color_auto_disabled_when_piped_or_no_color() {
+    let mut cfg = Config::default();
+    let piped = check_color(&cfg, false, |_| Some("xterm".to_string()));
+    assert_eq!(piped.severity, Severity::Ok);
+    assert!(piped.detail.contains("disabled"));
+
+    let no_color = check_color(&cfg, true, |name| {
+        (name == "NO_COLOR").then(|| "1".to_string())
+    });
+    assert_eq!(no_color.severity, Severity::Warning);
+    assert!(no_color.detail.contains("NO_COLOR"));
+
+    let dumb = check_color(&cfg, true, |name| {
+        (name == "TERM").then(|| "dumb".to_string())
+    });
+    assert_eq!(dumb.severity, Severity::Warning);
+
+    // Forcing colors into a pipe is worth a warning
+    cfg.color = ColorChoice::Always;
+    let forced = check_color(&cfg, false, |_| None);
+    assert_eq!(forced.severity, Severity::Warning);
+    assert!(forced.hint.as_deref().unwrap().contains("--color=auto"));
+}
+
+// ============ PATTERN TESTS ============
+
+#[test]
+fn test_pattern_errors_are_reported() {
+    assert_eq!(
+        check_pattern(&create_config("err(or")).severity,
+        Severity::Error
+    );
+
+    let mut cfg = create_config(r"took (\d+)ms");
+    cfg.where_expr = Some("$1 >".into());
+    assert_eq!(check_pattern(&cfg).severity, Severity::Error);
+
+    cfg.where_expr = Some("$1 > 500".into());
+    assert_eq!(check_pattern(&cfg).severity, Severity::Ok);
+
+    let mut cfg = Config::default();
+    cfg.queries = vec![Query::new("error"), Query::new("bad[")];
+    let check = check_pattern(&cfg);
+    assert_eq!(check.severity, Severity::Error);
+    assert!(check.detail.contains("query 'bad['"));
+}
+
+// ============ INPUT TESTS ============
+
+#[test]
+fn test_inputs_missing_and_directory_without_recursion() {
+    let td = tempfile::tempdir().unwrap();
+    let file = td.path().join("a.log");
+    fs::write(&file, "x\n").unwrap();
+    let inputs = vec![
+        file.to_string_lossy().to_string(),
+        td.path().join("missing.log").to_string_lossy().to_string(),
+        td.path().to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("x");
+    let checks = check_inputs(&cfg, &inputs);
+    let severities: Vec<Severity> = checks.iter().map(|c| c.severity).collect();
+    assert_eq!(
+        severities,
+        vec![Severity::Ok, Severity::Error, Severity::Warning]
+    );
+    assert!(checks[2].hint.as_deref().unwrap().contains("-R"));
+
+    cfg.recursive = true;
+    assert_eq!(check_inputs(&cfg, &inputs)[2].severity, Severity::Ok);
+}
+
+// ============ FILE LIST CACHE TESTS ============
+
+#[test]
+fn test_file_list_cache_states() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), "x\n").unwrap();
+    // Outside the searched directory, so writing it does not make the cache stale
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = cache_dir.path().join("files.cache");
+    let inputs = vec![td.path().to_string_lossy().to_string()];
+
+    let mut cfg = create_config("x");
+    cfg.recursive = true;
+    assert!(check_file_list_cache(&cfg, &inputs).is_none());
+
+    cfg.file_list_cache = Some(cache.to_string_lossy().to_string());
+    let missing = check_file_list_cache(&cfg, &inputs).unwrap();
+    assert_eq!(missing.severity, Severity::Ok);
+    assert!(missing.detail.contains("does not exist"));
+
+    run(&cfg, &inputs).unwrap();
+    let fresh = check_file_list_cache(&cfg, &inputs).unwrap();
+    assert_eq!(fresh.severity, Severity::Ok);
+    assert!(fresh.detail.contains("up to date"), "{}", fresh.detail);
+
+    let other = check_file_list_cache(&cfg, &[]).unwrap();
+    assert_eq!(other.severity, Severity::Warning);
+
+    fs::write(&cache, "not a cache\n").unwrap();
+    let corrupt = check_file_list_cache(&cfg, &inputs).unwrap();
+    assert_eq!(corrupt.severity, Severity::Error);
+}
+
+// ============ REPORT TESTS ============
+
+#[test]
+fn test_report_lists_hints_and_problem_count() {
+    let cfg = create_config("err(or");
+    let checks = vec![check_pattern(&cfg), check_pattern(&create_config("ok"))];
+    assert!(has_errors(&checks));
+
+    let report = format_report(&checks);
+    assert!(report.starts_with("error  pattern: "));
+    assert!(report.contains("       hint: "));
+    assert!(report.contains("ok     pattern: 'ok' compiles\n"));
+    assert!(report.ends_with("1 problem(s) found\n"));
+
+    assert_eq!(
+        format_report(&checks[1..]),
+        "ok     pattern: 'ok' compiles\nno problems found\n"
+    );
+}
@@ -1,10 +1,11 @@
-use rgrep::{Config, run_on_reader};
+use rgrep::regex_utils::boolean_diagnostic;
+use rgrep::{ColorChoice, Config, run_on_reader};
 use std::io::Cursor;
 
 fn create_config(pattern: &str) -> Config {
     Config {
         patterns: vec![pattern.to_string()],
-        color: false,
+        color: ColorChoice::Never,
         ..Default::default()
     }
 }
@@ -98,6 +99,25 @@ fn test_only_operators() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_parse_error_reports_position() {
+    let cfg = create_config("error&(timeout|retry");
+    let err = run_on_reader(&cfg, Cursor::new("error"), None).expect_err("unclosed group");
+    assert!(
+        err.ends_with("Expected closing parenthesis at offset 20"),
+        "{}",
+        err
+    );
+
+    assert_eq!(
+        boolean_diagnostic(&cfg).unwrap(),
+        "  error&(timeout|retry\n                      ^ Expected closing parenthesis\n"
+    );
+    // Valid expressions and plain regexes have no diagnostic
+    assert_eq!(boolean_diagnostic(&create_config("a&(b|c)")), None);
+    assert_eq!(boolean_diagnostic(&create_config("a[")), None);
+}
+
 // ============ VALID BOOLEAN EDGE CASES ============
 
 #[test]
@@ -0,0 +1,105 @@
+#![cfg(feature = "ffi")]
+
+use rgrep::ffi::*;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::ptr;
+
+/// Path, line number, and text of a match.
+type Line = (Option<String>, usize, String);
+
+/// Every match in `results`, and their error; frees `results`.
+fn read(results: *mut RgrepResults) -> (Vec<Line>, Option<String>) {
+    let text = |s: *const std::ffi::c_char| {
+        (!s.is_null()).then(|| unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string())
+    };
+    unsafe {
+        let lines = (0..rgrep_results_len(results))
+            .map(|i| {
+                let m = &*rgrep_results_get(results, i);
+                (text(m.path), m.line_number, text(m.line).unwrap())
+            })
+            .collect();
+        assert!(rgrep_results_get(results, rgrep_results_len(results)).is_null());
+        let error = text(rgrep_results_error(results));
+        rgrep_results_free(results);
+        (lines, error)
+    }
+}
+
+// ============ FFI TESTS ============
+
+#[test]
+fn test_ffi_search_buffer_and_path() {
+    let cfg = rgrep_config_new();
+    let pattern = CString::new("ERROR").unwrap();
+    unsafe {
+        assert_eq!(rgrep_config_add_pattern(cfg, pattern.as_ptr()), 0);
+        assert_eq!(rgrep_config_add_pattern(cfg, ptr::null()), -1);
+        rgrep_config_set_options(cfg, 1, 0, 0, 0, 0, 1);
+    }
+
+    let data = b"ok\nan error here\n";
+    let results = unsafe { rgrep_search_buffer(cfg, data.as_ptr(), data.len()) };
+    unsafe {
+        let m = &*rgrep_results_get(results, 0);
+        assert_eq!((m.byte_offset, m.line_len), (3, 13));
+        assert_eq!((m.match_start, m.match_end), (3, 8));
+    }
+    let (lines, error) = read(results);
+    assert_eq!(lines, vec![(None, 2, "an error here".to_string())]);
+    assert_eq!(error, None);
+
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), "error one\n").unwrap();
+    fs::write(td.path().join("b.log"), b"\xff\n").unwrap();
+    let dir = CString::new(td.path().to_str().unwrap()).unwrap();
+    let (lines, error) = read(unsafe { rgrep_search_path(cfg, dir.as_ptr()) });
+    let a = td.path().join("a.log").to_string_lossy().to_string();
+    assert_eq!(lines, vec![(Some(a), 1, "error one".to_string())]);
+    // A file that cannot be read is reported, and the others are still searched
+    assert!(error.unwrap().contains("b.log: "));
+
+    unsafe { rgrep_config_free(cfg) };
+}
+
+#[test]
+fn test_ffi_alternatives_are_one_pattern() {
+    let cfg = rgrep_config_new();
+    let pattern = CString::new("foo|bar").unwrap();
+    let second = CString::new("baz").unwrap();
+    unsafe {
+        assert_eq!(rgrep_config_add_pattern(cfg, pattern.as_ptr()), 0);
+        // A second pattern would be joined to the first instead of added as an alternative
+        assert_eq!(rgrep_config_add_pattern(cfg, second.as_ptr()), -1);
+    }
+
+    let data = b"foo\nbar\nfoobar\nbaz\n";
+    let (lines, error) = read(unsafe { rgrep_search_buffer(cfg, data.as_ptr(), data.len()) });
+    let numbers: Vec<usize> = lines.iter().map(|(_, n, _)| *n).collect();
+    assert_eq!(numbers, vec![1, 2, 3]);
+    assert_eq!(error, None);
+
+    unsafe { rgrep_config_free(cfg) };
+}
+
+#[test]
+fn test_ffi_reports_invalid_arguments() {
+    let cfg = rgrep_config_new();
+    let pattern = CString::new("(").unwrap();
+    unsafe {
+        rgrep_config_add_pattern(cfg, pattern.as_ptr());
+        assert!(rgrep_search_path(ptr::null(), ptr::null()).is_null());
+        assert_eq!(rgrep_results_len(ptr::null()), 0);
+    }
+    let (lines, error) = read(unsafe { rgrep_search_buffer(cfg, ptr::null(), 0) });
+    assert!(lines.is_empty());
+    assert!(error.is_some());
+    let (_, error) = read(unsafe { rgrep_search_path(cfg, ptr::null()) });
+    assert_eq!(error.as_deref(), Some("path is NULL or not UTF-8"));
+    unsafe {
+        rgrep_config_free(cfg);
+        rgrep_config_free(ptr::null_mut());
+        rgrep_results_free(ptr::null_mut());
+    }
+}
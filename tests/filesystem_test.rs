@@ -1,11 +1,14 @@
-use rgrep::{Config, ExitStatus, run};
+#![cfg(feature = "files")]
+
+use rgrep::fs_utils::{BINARY_DIR_SAMPLE, walk_inputs};
+use rgrep::{ColorChoice, Config, ExitStatus, FileGlob, run};
 use std::fs;
-use tempfile;
+use std::path::Path;
 
 fn create_config(pattern: &str) -> Config {
     Config {
         patterns: vec![pattern.to_string()],
-        color: false,
+        color: ColorChoice::Never,
         ..Default::default()
     }
 }
@@ -19,7 +22,7 @@ fn test_binary_file_skipped() {
 
     // Create a binary file with NUL byte
     let bin_file = root.join("binary.dat");
-    fs::write(&bin_file, &[0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f]).unwrap();
+    fs::write(&bin_file, [0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f]).unwrap();
 
     // Create a text file
     let txt_file = root.join("text.txt");
@@ -44,8 +47,8 @@ fn test_all_binary_files_returns_no_match() {
     let bin1 = root.join("file1.bin");
     let bin2 = root.join("file2.bin");
 
-    fs::write(&bin1, &[0x00, 0xFF, 0xFE]).unwrap();
-    fs::write(&bin2, &[0x00, 0x01, 0x02]).unwrap();
+    fs::write(&bin1, [0x00, 0xFF, 0xFE]).unwrap();
+    fs::write(&bin2, [0x00, 0x01, 0x02]).unwrap();
 
     let mut cfg = create_config("pattern");
     cfg.recursive = true;
@@ -88,6 +91,356 @@ fn test_utf8_file_is_not_binary() {
     assert_eq!(result.status, ExitStatus::MatchFound);
 }
 
+#[test]
+fn test_skip_binary_dirs() {
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path();
+    let assets = root.join("assets");
+    let docs = root.join("docs");
+    fs::create_dir_all(assets.join("nested")).unwrap();
+    fs::create_dir_all(&docs).unwrap();
+    for i in 0..30 {
+        fs::write(assets.join(format!("{}.bin", i)), [0x00, 0x01]).unwrap();
+        fs::write(assets.join("nested").join(format!("{}.bin", i)), [0x00]).unwrap();
+        fs::write(docs.join(format!("{}.txt", i)), "match").unwrap();
+    }
+    fs::write(docs.join("image.png"), [0x00]).unwrap();
+    fs::write(root.join("top.txt"), "match").unwrap();
+
+    let mut cfg = create_config("match");
+    cfg.recursive = true;
+    cfg.skip_binary_dirs = true;
+    let inputs = vec![root.to_string_lossy().to_string()];
+
+    let walk = walk_inputs(&cfg, &inputs);
+    assert_eq!(walk.skipped_dirs.len(), 1);
+    assert_eq!(walk.skipped_dirs[0].path, assets.to_string_lossy());
+    assert_eq!(walk.skipped_dirs[0].sampled, BINARY_DIR_SAMPLE);
+    let in_assets = walk.files.iter().filter(|f| f.contains("assets")).count();
+    assert_eq!(in_assets, BINARY_DIR_SAMPLE);
+    // Mostly-text directories are walked completely
+    let in_docs = walk.files.iter().filter(|f| f.contains("docs")).count();
+    assert_eq!(in_docs, 31);
+
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(result.output.lines().count(), 31);
+    assert_eq!(result.warnings.len(), 1);
+    assert!(result.warnings[0].contains("--skip-binary-dirs"));
+
+    // Directories given explicitly are never skipped
+    let walk = walk_inputs(&cfg, &[assets.to_string_lossy().to_string()]);
+    assert!(walk.skipped_dirs.iter().all(|d| d.path.ends_with("nested")));
+    assert!(walk.files.len() >= 30 + BINARY_DIR_SAMPLE);
+
+    cfg.skip_binary_dirs = false;
+    assert_eq!(walk_inputs(&cfg, &inputs).files.len(), 92);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fifo_input_searched_in_full() {
+    // A named pipe can be read only once: the bytes sniffed for binary content must
+    // still reach the search
+    let td = tempfile::tempdir().unwrap();
+    let search_fifo = |content: Vec<u8>| {
+        let fifo = td.path().join("pipe");
+        let _ = fs::remove_file(&fifo);
+        let made = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(made.success());
+        let path = fifo.clone();
+        let writer = std::thread::spawn(move || fs::write(path, content).unwrap());
+        let mut cfg = create_config("^a|needle");
+        cfg.line_number = true;
+        let result = run(&cfg, &[fifo.to_string_lossy().to_string()]).unwrap();
+        writer.join().unwrap();
+        result
+    };
+
+    let mut text = "a".repeat(10_000).into_bytes();
+    text.extend_from_slice(b"\nneedle\n");
+    let result = search_fifo(text);
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("1:aaaa"));
+    assert_eq!(lines[1], "2:needle");
+
+    let result = search_fifo(b"needle\0\n".to_vec());
+    assert_eq!(result.status, ExitStatus::NoMatch);
+}
+
+#[test]
+fn test_binary_files_modes() {
+    use rgrep::{BinaryFiles, Compat};
+    let td = tempfile::tempdir().unwrap();
+    let bin = td.path().join("data.bin");
+    fs::write(&bin, b"header\0\xff\nneedle here\n").unwrap();
+    fs::write(td.path().join("notes.txt"), "needle too\n").unwrap();
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    cfg.with_filename = Some(false);
+    let search = |cfg: &Config| {
+        let result = run(cfg, std::slice::from_ref(&root)).unwrap();
+        let mut lines: Vec<String> = result.output.lines().map(String::from).collect();
+        lines.sort();
+        lines
+    };
+
+    assert_eq!(search(&cfg), vec!["1:needle too"]);
+    cfg.binary_files = Some(BinaryFiles::Text);
+    assert_eq!(search(&cfg), vec!["1:needle too", "2:needle here"]);
+    cfg.binary_files = Some(BinaryFiles::Report);
+    assert_eq!(
+        search(&cfg),
+        vec![
+            "1:needle too".to_string(),
+            format!("Binary file {} matches", bin.display())
+        ]
+    );
+
+    // GNU-compatible runs report binary files unless told otherwise
+    cfg.compat = Compat::Gnu;
+    cfg.line_number = false;
+    cfg.binary_files = None;
+    assert_eq!(search(&cfg).len(), 2);
+    assert!(search(&cfg)[0].starts_with("Binary file"));
+    cfg.binary_files = Some(BinaryFiles::Skip);
+    assert_eq!(search(&cfg), vec!["needle too"]);
+}
+
+// ============ COMPRESSED FILE TESTS ============
+
+#[cfg(feature = "gzip")]
+fn gzip(text: &str) -> Vec<u8> {
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn test_search_zip_decompresses_gzip_files() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("app.log"), "ok\nerror one\n").unwrap();
+    fs::write(
+        td.path().join("app.log.1.gz"),
+        gzip("old error two\nfine\n"),
+    )
+    .unwrap();
+    // Concatenated members are one input, as `zcat` reads them
+    let mut members = gzip("a error\n");
+    members.extend(gzip("b error\n"));
+    fs::write(td.path().join("multi"), members).unwrap();
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("error");
+    cfg.recursive = true;
+    cfg.with_filename = Some(false);
+    let search = |cfg: &Config| {
+        let result = run(cfg, std::slice::from_ref(&root)).unwrap();
+        let mut lines: Vec<String> = result.output.lines().map(String::from).collect();
+        lines.sort();
+        lines
+    };
+
+    // Compressed files look binary and are skipped without -z
+    assert_eq!(search(&cfg), vec!["2:error one"]);
+    cfg.search_zip = true;
+    assert_eq!(
+        search(&cfg),
+        vec!["1:a error", "1:old error two", "2:b error", "2:error one"]
+    );
+    cfg.skip_binary_dirs = true;
+    assert_eq!(search(&cfg).len(), 4);
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn test_search_zip_fails_on_corrupt_gzip_files() {
+    let td = tempfile::tempdir().unwrap();
+    let mut data = gzip("error\n".repeat(100).as_str());
+    data.truncate(data.len() / 2);
+    let file = td.path().join("cut.gz");
+    fs::write(&file, data).unwrap();
+    let mut cfg = create_config("error");
+    cfg.search_zip = true;
+
+    // A truncated stream is a read error, like any other
+    assert!(run(&cfg, &[file.to_string_lossy().to_string()]).is_err());
+}
+
+#[test]
+#[cfg(any(feature = "bzip2", feature = "xz", feature = "zstd"))]
+fn test_search_zip_decompresses_bzip2_xz_and_zstd() {
+    let td = tempfile::tempdir().unwrap();
+    let text = "boot ok\nkernel: error\n";
+    let mut expected = Vec::new();
+    #[cfg(feature = "bzip2")]
+    {
+        use std::io::Write;
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        fs::write(td.path().join("syslog.2.bz2"), encoder.finish().unwrap()).unwrap();
+        expected.push("syslog.2.bz2:2:kernel: error");
+    }
+    #[cfg(feature = "xz")]
+    {
+        use std::io::Write;
+        let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(text.as_bytes()).unwrap();
+        fs::write(td.path().join("syslog.3.xz"), encoder.finish().unwrap()).unwrap();
+        expected.push("syslog.3.xz:2:kernel: error");
+    }
+    #[cfg(feature = "zstd")]
+    {
+        // Named without an extension: the format is told by its first bytes
+        let data = zstd::encode_all(text.as_bytes(), 0).unwrap();
+        fs::write(td.path().join("syslog.4"), data).unwrap();
+        expected.push("syslog.4:2:kernel: error");
+    }
+    let mut cfg = create_config("error");
+    cfg.recursive = true;
+    cfg.search_zip = true;
+    cfg.with_filename = Some(true);
+    let root = td.path().to_string_lossy().to_string();
+
+    let result = run(&cfg, std::slice::from_ref(&root)).unwrap();
+    let prefix = format!("{}/", root);
+    let mut lines: Vec<&str> = result
+        .output
+        .lines()
+        .map(|l| l.strip_prefix(&prefix).unwrap_or(l))
+        .collect();
+    lines.sort();
+    assert_eq!(lines, expected);
+}
+
+#[test]
+#[cfg(not(any(feature = "gzip", feature = "bzip2", feature = "xz", feature = "zstd")))]
+fn test_search_zip_requires_a_compression_feature() {
+    let mut cfg = create_config("error");
+    cfg.search_zip = true;
+    let err = run(&cfg, &["-".to_string()]).unwrap_err();
+    assert!(err.contains("`gzip`"), "{}", err);
+    cfg.json = true;
+    let err = rgrep::run_json_lines(&cfg, &["-".to_string()], &mut Vec::new()).unwrap_err();
+    assert!(err.contains("`gzip`"), "{}", err);
+}
+
+// ============ PREPROCESSOR TESTS ============
+
+#[test]
+#[cfg(unix)]
+fn test_pre_searches_command_output() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("report.pdf"), b"%PDF\0invoice 42\n").unwrap();
+    fs::write(td.path().join("notes.txt"), "invoice draft\n").unwrap();
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("invoice");
+    cfg.recursive = true;
+    cfg.with_filename = Some(false);
+    let search = |cfg: &Config| {
+        let result = run(cfg, std::slice::from_ref(&root)).unwrap();
+        let mut lines: Vec<String> = result.output.lines().map(String::from).collect();
+        lines.sort();
+        lines
+    };
+
+    assert_eq!(search(&cfg), vec!["1:invoice draft"]);
+    // The binary file is searched through the command, the other one as it is
+    cfg.pre = Some("sed s/^/pre:/".to_string());
+    cfg.pre_globs = vec!["*.pdf".to_string()];
+    assert_eq!(
+        search(&cfg),
+        vec!["1:invoice draft", "1:pre:%PDF\0invoice 42"]
+    );
+    // `{}` places the path among the arguments
+    cfg.pre = Some("cp {} /dev/stdout".to_string());
+    cfg.pre_globs.clear();
+    assert_eq!(search(&cfg), vec!["1:%PDF\0invoice 42", "1:invoice draft"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pre_command_failure_names_the_file() {
+    let td = tempfile::tempdir().unwrap();
+    let file = td.path().join("report.pdf");
+    fs::write(&file, "invoice\n").unwrap();
+    let mut cfg = create_config("invoice");
+    cfg.pre = Some("false".to_string());
+
+    let err = run(&cfg, &[file.to_string_lossy().to_string()]).unwrap_err();
+    assert!(err.contains("report.pdf: false failed"), "{}", err);
+    cfg.pre = Some("  ".to_string());
+    let err = run(&cfg, &[file.to_string_lossy().to_string()]).unwrap_err();
+    assert_eq!(err, "--pre needs a command");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pre_applies_to_json_lines_and_sinks() {
+    struct Lines(Vec<String>);
+    impl rgrep::Sink for Lines {
+        fn on_match(&mut self, _path: Option<&str>, line: &rgrep::Match) -> std::io::Result<()> {
+            self.0.push(line.line.clone());
+            Ok(())
+        }
+        fn on_error(&mut self, _path: Option<&str>, error: &str) -> std::io::Result<()> {
+            self.0.push(format!("error {}", error));
+            Ok(())
+        }
+    }
+
+    let td = tempfile::tempdir().unwrap();
+    let report = td.path().join("report.pdf");
+    let notes = td.path().join("notes.txt");
+    fs::write(&report, "invoice 42\n").unwrap();
+    fs::write(&notes, "invoice draft\n").unwrap();
+    let inputs = vec![
+        report.to_string_lossy().to_string(),
+        notes.to_string_lossy().to_string(),
+    ];
+    let mut cfg = create_config("invoice");
+    cfg.pre = Some("sed s/^/pre:/".to_string());
+    cfg.pre_globs = vec!["*.pdf".to_string()];
+
+    let mut sink = Lines(Vec::new());
+    rgrep::run_with_sink(&cfg, &inputs, &mut sink).unwrap();
+    assert_eq!(sink.0, vec!["pre:invoice 42", "invoice draft"]);
+
+    cfg.json = true;
+    let mut out = Vec::new();
+    rgrep::run_json_lines(&cfg, &inputs, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(r#""text":"pre:invoice 42""#), "{}", out);
+
+    // A failing command is reported for its file, and the others are still searched
+    cfg.pre = Some("false".to_string());
+    let mut out = Vec::new();
+    let status = rgrep::run_json_lines(&cfg, &inputs, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(status, ExitStatus::MatchFound);
+    assert!(
+        lines[0].starts_with(r#"{"type":"error","path":""#)
+            && lines[0].ends_with(r#"report.pdf","message":"false failed (exit status: 1)"}"#),
+        "{}",
+        out
+    );
+    assert!(lines[2].contains(r#""text":"invoice draft""#), "{}", out);
+    assert!(lines[4].starts_with(r#"{"type":"summary","files_searched":1,"#));
+    let mut sink = Lines(Vec::new());
+    rgrep::run_with_sink(&cfg, &inputs, &mut sink).unwrap();
+    assert_eq!(
+        sink.0,
+        vec!["error false failed (exit status: 1)", "invoice draft"]
+    );
+}
+
 // ============ RECURSIVE SEARCH TESTS ============
 
 #[test]
@@ -237,6 +590,64 @@ fn test_recursive_mixed_files_and_directories() {
 
 // ============ PATH EXPANSION TESTS ============
 
+#[test]
+fn test_read_file_list() {
+    use rgrep::fs_utils::read_file_list;
+    let td = tempfile::tempdir().unwrap();
+    let lines = td.path().join("list.txt");
+    fs::write(&lines, "a.log\n\nsub dir/b.log\n").unwrap();
+    let nul = td.path().join("list0.txt");
+    fs::write(&nul, "a.log\0with\nnewline.log\0").unwrap();
+    let empty = td.path().join("empty.txt");
+    fs::write(&empty, "\n").unwrap();
+
+    assert_eq!(
+        read_file_list(&lines.to_string_lossy(), false).unwrap(),
+        vec!["a.log", "sub dir/b.log"]
+    );
+    assert_eq!(
+        read_file_list(&nul.to_string_lossy(), true).unwrap(),
+        vec!["a.log", "with\nnewline.log"]
+    );
+    let err = read_file_list(&empty.to_string_lossy(), false).unwrap_err();
+    assert!(err.ends_with("no files listed"), "{}", err);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_files_from_stdin() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), "needle a\n").unwrap();
+    fs::write(td.path().join("b.log"), "needle b\n").unwrap();
+    fs::write(td.path().join("c.log"), "needle c\n").unwrap();
+    let rgrep = |flag: &str, list: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rgrep"))
+            .current_dir(td.path())
+            .args(["--color=never", "-r", "needle", flag, "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(list.as_bytes())
+            .unwrap();
+        let out = child.wait_with_output().unwrap();
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+    let mut lines: Vec<String> = rgrep("--files-from", "a.log\nc.log\n")
+        .lines()
+        .map(String::from)
+        .collect();
+    lines.sort();
+    assert_eq!(lines, vec!["a.log:1:needle a", "c.log:1:needle c"]);
+    assert_eq!(rgrep("--files-from0", "b.log\0"), "1:needle b\n");
+}
+
 #[test]
 fn test_stdin_default_when_no_inputs_non_recursive() {
     let cfg = create_config("test");
@@ -303,6 +714,745 @@ fn test_multiple_files_explicit_paths() {
     assert!(!result.output.contains("nothing here"));
 }
 
+// ============ ROOT LABEL TESTS ============
+
+#[test]
+fn test_labeled_roots_name_results() {
+    let td = tempfile::tempdir().unwrap();
+    let api = td.path().join("var/log/api");
+    let web = td.path().join("var/log/web");
+    fs::create_dir_all(api.join("2025")).unwrap();
+    fs::create_dir_all(&web).unwrap();
+    fs::write(api.join("2025/app.log"), b"error in api\n").unwrap();
+    fs::write(web.join("access.log"), b"error in web\n").unwrap();
+
+    let mut cfg = create_config("error");
+    cfg.recursive = true;
+    cfg.line_number = false;
+    let inputs = vec![
+        format!("api={}", api.display()),
+        format!("web={}", web.join("access.log").display()),
+    ];
+    let result = run(&cfg, &inputs).unwrap();
+
+    assert_eq!(
+        result.output,
+        "api/2025/app.log:error in api\nweb:error in web\n"
+    );
+    let files: Vec<_> = result
+        .files
+        .iter()
+        .map(|f| (f.path.as_deref(), f.root.as_deref()))
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            (Some("api/2025/app.log"), Some("api")),
+            (Some("web"), Some("web"))
+        ]
+    );
+
+    cfg.count = true;
+    cfg.table = false;
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(result.output, "api/2025/app.log:1\nweb:1\n");
+}
+
+#[test]
+fn test_existing_paths_with_equals_are_not_roots() {
+    let td = tempfile::tempdir().unwrap();
+    let odd = td.path().join("a=b.txt");
+    fs::write(&odd, b"found\n").unwrap();
+
+    let inputs = vec![odd.to_string_lossy().to_string()];
+    let result = run(&create_config("found"), &inputs).unwrap();
+    assert_eq!(result.files[0].root, None);
+
+    // Walking a labeled root reads its path
+    let walk = walk_inputs(&Config::default(), &[format!("x={}", odd.display())]);
+    assert_eq!(walk.files, vec![odd.to_string_lossy().to_string()]);
+}
+
+// ============ GITIGNORE TESTS ============
+
+#[test]
+fn test_recursive_walk_respects_gitignore() {
+    let td = tempfile::tempdir().unwrap();
+    let repo = td.path();
+    for dir in [".git/info", "target/debug", "src/gen", "logs"] {
+        fs::create_dir_all(repo.join(dir)).unwrap();
+    }
+    fs::write(repo.join(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+    fs::write(repo.join("src/.gitignore"), "/gen\n").unwrap();
+    fs::write(repo.join(".git/info/exclude"), "notes.txt\n").unwrap();
+    for file in [
+        ".git/HEAD",
+        "target/debug/out.txt",
+        "src/main.rs",
+        "src/gen/code.rs",
+        "logs/app.log",
+        "logs/keep.log",
+        "notes.txt",
+    ] {
+        fs::write(repo.join(file), "needle\n").unwrap();
+    }
+
+    let root = repo.to_string_lossy().to_string();
+    let found = |cfg: &Config| {
+        let mut files: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| f[root.len() + 1..].to_string())
+            .filter(|f| !f.ends_with(".gitignore") && !f.ends_with("exclude"))
+            .collect();
+        files.sort();
+        files
+    };
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    assert_eq!(found(&cfg), vec!["logs/keep.log", "src/main.rs"]);
+
+    // Files named explicitly are searched even when ignored
+    let result = run(
+        &cfg,
+        &[repo.join("notes.txt").to_string_lossy().to_string()],
+    )
+    .unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+
+    // Without the repository-wide excludes, only the .gitignore files apply
+    cfg.no_global_ignore = true;
+    assert_eq!(
+        found(&cfg),
+        vec!["logs/keep.log", "notes.txt", "src/main.rs"]
+    );
+
+    cfg.no_global_ignore = false;
+    cfg.no_ignore = true;
+    cfg.hidden = true;
+    assert_eq!(found(&cfg).len(), 7);
+}
+
+#[test]
+fn test_no_global_ignore_skips_info_exclude_and_core_excludes_file() {
+    let td = tempfile::tempdir().unwrap();
+    let repo = td.path().join("repo");
+    fs::create_dir_all(repo.join(".git/info")).unwrap();
+    // The repository's config takes precedence over the user's
+    let excludes = td.path().join("global-ignore");
+    fs::write(&excludes, "*.tmp\n").unwrap();
+    fs::write(
+        repo.join(".git/config"),
+        format!("[core]\n\texcludesFile = {}\n", excludes.display()),
+    )
+    .unwrap();
+    fs::write(repo.join(".git/info/exclude"), "scratch/\n").unwrap();
+    fs::create_dir_all(repo.join("scratch")).unwrap();
+    for file in ["main.rs", "build.tmp", "scratch/notes.txt"] {
+        fs::write(repo.join(file), "needle\n").unwrap();
+    }
+
+    let root = repo.to_string_lossy().to_string();
+    let found = |cfg: &Config| {
+        let mut files: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| f[root.len() + 1..].to_string())
+            .collect();
+        files.sort();
+        files
+    };
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    assert_eq!(found(&cfg), vec!["main.rs"]);
+
+    cfg.no_global_ignore = true;
+    assert_eq!(
+        found(&cfg),
+        vec!["build.tmp", "main.rs", "scratch/notes.txt"]
+    );
+}
+
+#[test]
+fn test_rgrepignore_and_ignore_file() {
+    let td = tempfile::tempdir().unwrap();
+    let dir = td.path();
+    for sub in ["vendor", "src/fixtures"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    // Outside a git repository only rgrep's ignore files apply
+    fs::write(dir.join(".rgrepignore"), "vendor/\n*.snap\n").unwrap();
+    fs::write(dir.join("src/.rgrepignore"), "!keep.snap\n").unwrap();
+    let extra = td.path().join("extra-ignore");
+    fs::write(&extra, "/src/fixtures\n").unwrap();
+    for file in [
+        "vendor/lib.rs",
+        "src/main.rs",
+        "src/a.snap",
+        "src/keep.snap",
+        "src/fixtures/big.txt",
+        "extra.txt",
+    ] {
+        fs::write(dir.join(file), "needle\n").unwrap();
+    }
+
+    let root = dir.to_string_lossy().to_string();
+    let found = |cfg: &Config| {
+        let mut files: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| f[root.len() + 1..].to_string())
+            .filter(|f| !f.ends_with(".rgrepignore") && !f.ends_with("ignore"))
+            .collect();
+        files.sort();
+        files
+    };
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    assert_eq!(
+        found(&cfg),
+        vec![
+            "extra.txt",
+            "src/fixtures/big.txt",
+            "src/keep.snap",
+            "src/main.rs"
+        ]
+    );
+
+    cfg.ignore_files = vec![extra.to_string_lossy().to_string()];
+    assert_eq!(
+        found(&cfg),
+        vec!["extra.txt", "src/keep.snap", "src/main.rs"]
+    );
+
+    // --no-ignore drops .rgrepignore but keeps the files named with --ignore-file
+    cfg.no_ignore = true;
+    assert_eq!(found(&cfg).len(), 5);
+}
+
+// ============ INCLUDE/EXCLUDE GLOB TESTS ============
+
+#[test]
+fn test_include_exclude_globs() {
+    let td = tempfile::tempdir().unwrap();
+    let dir = td.path();
+    fs::create_dir_all(dir.join("web/vendor")).unwrap();
+    for file in [
+        "main.rs",
+        "web/app.js",
+        "web/app.min.js",
+        "web/vendor/lib.min.js",
+    ] {
+        fs::write(dir.join(file), "needle\n").unwrap();
+    }
+    let root = dir.to_string_lossy().to_string();
+    let found = |cfg: &Config| {
+        let mut files: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| f[root.len() + 1..].to_string())
+            .collect();
+        files.sort();
+        files
+    };
+
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    cfg.globs = vec![
+        FileGlob::Include("*.js".into()),
+        FileGlob::Exclude("*.min.js".into()),
+    ];
+    assert_eq!(found(&cfg), vec!["web/app.js"]);
+
+    // A later glob overrides an earlier one for the files both match
+    cfg.globs.push(FileGlob::Include("web/vendor/*".into()));
+    assert_eq!(found(&cfg), vec!["web/app.js", "web/vendor/lib.min.js"]);
+
+    cfg.globs = vec![FileGlob::Exclude("*.js".into())];
+    assert_eq!(found(&cfg), vec!["main.rs"]);
+
+    // Named files are filtered as well
+    cfg.recursive = false;
+    let named = vec![
+        dir.join("main.rs").to_string_lossy().to_string(),
+        dir.join("web/app.js").to_string_lossy().to_string(),
+    ];
+    let result = run(&cfg, &named).unwrap();
+    assert_eq!(result.files.len(), 1);
+    assert!(result.files[0].path.as_ref().unwrap().ends_with("main.rs"));
+}
+
+#[test]
+fn test_exclude_dir_prunes_walk() {
+    let td = tempfile::tempdir().unwrap();
+    let dir = td.path();
+    for sub in ["src", "target/debug", ".cache/deep", "web/target"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+        fs::write(dir.join(sub).join("f.txt"), "needle\n").unwrap();
+    }
+    let root = dir.to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    cfg.hidden = true;
+    cfg.exclude_dirs = vec!["target".into(), ".*".into()];
+
+    let walk = walk_inputs(&cfg, std::slice::from_ref(&root));
+    let rel = |paths: &[String]| {
+        let mut rel: Vec<String> = paths
+            .iter()
+            .filter(|p| p.len() > root.len())
+            .map(|p| p[root.len() + 1..].to_string())
+            .collect();
+        rel.sort();
+        rel
+    };
+    assert_eq!(rel(&walk.files), vec!["src/f.txt"]);
+    // Excluded directories are not entered at all
+    assert_eq!(rel(&walk.dirs), vec!["src", "web"]);
+
+    // A glob with a `/` matches from the walked directory only
+    cfg.exclude_dirs = vec!["web/target".into()];
+    let walk = walk_inputs(&cfg, std::slice::from_ref(&root));
+    assert_eq!(
+        rel(&walk.files),
+        vec![".cache/deep/f.txt", "src/f.txt", "target/debug/f.txt"]
+    );
+}
+
+#[test]
+fn test_hidden_entries_skipped_unless_requested() {
+    let td = tempfile::tempdir().unwrap();
+    let dir = td.path();
+    fs::create_dir_all(dir.join(".config")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    for file in [".env", ".config/app.toml", "src/.hidden.rs", "src/main.rs"] {
+        fs::write(dir.join(file), "needle\n").unwrap();
+    }
+    let root = dir.to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    let found = |cfg: &Config| {
+        let mut files: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| f[root.len() + 1..].to_string())
+            .collect();
+        files.sort();
+        files
+    };
+    assert_eq!(found(&cfg), vec!["src/main.rs"]);
+
+    // Hidden inputs named explicitly are searched
+    let named = dir.join(".config").to_string_lossy().to_string();
+    let result = run(&cfg, &[named]).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+
+    cfg.hidden = true;
+    assert_eq!(
+        found(&cfg),
+        vec![".config/app.toml", ".env", "src/.hidden.rs", "src/main.rs"]
+    );
+}
+
+// ============ WALK LIMIT TESTS ============
+
+#[test]
+fn test_deeply_nested_tree_is_walked() {
+    let td = tempfile::tempdir().unwrap();
+    let mut deepest = td.path().to_path_buf();
+    for _ in 0..1000 {
+        deepest.push("d");
+    }
+    fs::create_dir_all(&deepest).unwrap();
+    fs::write(deepest.join("bottom.txt"), "needle\n").unwrap();
+
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    let result = run(&cfg, &[td.path().to_string_lossy().to_string()]).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert!(result.warnings.is_empty());
+}
+
+#[test]
+fn test_max_entries_stops_walk_with_warning() {
+    let td = tempfile::tempdir().unwrap();
+    for i in 0..50 {
+        let dir = td.path().join(format!("dir{}", i));
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("f.txt"), "needle\n").unwrap();
+    }
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    cfg.max_entries = Some(10);
+
+    let walk = walk_inputs(&cfg, std::slice::from_ref(&root));
+    assert_eq!(walk.entries, 10);
+    assert!(walk.issues.truncated);
+    assert!(walk.files.len() < 10);
+
+    let result = run(&cfg, &[root]).unwrap();
+    assert_eq!(
+        result.warnings,
+        vec!["stopped walking after 10 entries (--max-entries), results are incomplete"]
+    );
+}
+
+#[test]
+fn test_max_depth_limits_descent() {
+    let td = tempfile::tempdir().unwrap();
+    let deep = td.path().join("a").join("b");
+    fs::create_dir_all(&deep).unwrap();
+    fs::write(td.path().join("top.txt"), "needle\n").unwrap();
+    fs::write(td.path().join("a").join("mid.txt"), "needle\n").unwrap();
+    fs::write(deep.join("low.txt"), "needle\n").unwrap();
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+
+    let names = |cfg: &Config| {
+        let mut names: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| {
+                Path::new(f)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names(&cfg), ["low.txt", "mid.txt", "top.txt"]);
+    cfg.max_depth = Some(1);
+    assert_eq!(names(&cfg), ["top.txt"]);
+    cfg.max_depth = Some(2);
+    assert_eq!(names(&cfg), ["mid.txt", "top.txt"]);
+}
+
+#[test]
+fn test_max_filesize_skips_large_files() {
+    let td = tempfile::tempdir().unwrap();
+    let big = td.path().join("big.log");
+    fs::write(td.path().join("small.log"), "needle\n").unwrap();
+    fs::write(&big, "needle\n".repeat(1000)).unwrap();
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    cfg.max_filesize = Some(1024);
+
+    let walk = walk_inputs(&cfg, std::slice::from_ref(&root));
+    assert_eq!(walk.files.len(), 1);
+    assert!(walk.files[0].ends_with("small.log"));
+    assert_eq!(
+        walk.issues.too_large,
+        vec![(big.to_string_lossy().to_string(), 7000)]
+    );
+
+    let result = run(&cfg, std::slice::from_ref(&root)).unwrap();
+    assert_eq!(
+        result.warnings,
+        vec![
+            "skipped 1 file(s) larger than 1024 bytes (--max-filesize; --list-skipped to name them)"
+        ]
+    );
+    cfg.list_skipped = true;
+    let result = run(&cfg, std::slice::from_ref(&root)).unwrap();
+    assert_eq!(
+        result.warnings,
+        vec![
+            format!(
+                "{}: skipped, 7000 bytes is over --max-filesize",
+                big.display()
+            ),
+            "skipped 1 entries: 1 too large".to_string()
+        ]
+    );
+
+    // Files named explicitly are searched whatever their size
+    let result = run(&cfg, &[big.to_string_lossy().to_string()]).unwrap();
+    assert_eq!(result.output.lines().count(), 1000);
+}
+
+#[test]
+fn test_list_files_without_searching() {
+    use rgrep::list_files;
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path();
+    fs::create_dir_all(root.join("build")).unwrap();
+    fs::write(root.join(".rgrepignore"), "build/\n").unwrap();
+    fs::write(root.join("build/out.log"), "x\n").unwrap();
+    fs::write(root.join("blob.log"), b"x\0").unwrap();
+    fs::write(root.join("app.log"), "x\n").unwrap();
+    fs::write(root.join("notes.txt"), "x\n").unwrap();
+    let mut cfg = Config {
+        recursive: true,
+        globs: vec![FileGlob::Include("*.log".into())],
+        ..Default::default()
+    };
+
+    let result = list_files(&cfg, &[format!("logs={}", root.display())]).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.output, "logs/app.log\n");
+    // run() does the same without needing a pattern
+    cfg.list_files = true;
+    let input = root.to_string_lossy().to_string();
+    let result = run(&cfg, std::slice::from_ref(&input)).unwrap();
+    assert_eq!(
+        result.output,
+        format!("{}\n", root.join("app.log").display())
+    );
+
+    cfg.globs = vec![FileGlob::Include("*.md".into())];
+    let result = run(&cfg, std::slice::from_ref(&input)).unwrap();
+    assert_eq!(result.status, ExitStatus::NoMatch);
+    assert_eq!(result.output, "");
+}
+
+#[test]
+fn test_list_skipped_names_every_skipped_entry() {
+    use rgrep::{SkipReason, Skipped};
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path();
+    fs::create_dir_all(root.join("build")).unwrap();
+    fs::create_dir_all(root.join(".cache")).unwrap();
+    fs::write(root.join(".rgrepignore"), "build/\n").unwrap();
+    fs::write(root.join("build/out.log"), "needle\n").unwrap();
+    fs::write(root.join(".cache/x.log"), "needle\n").unwrap();
+    fs::write(root.join("blob.bin"), b"needle\0").unwrap();
+    fs::write(root.join("app.log"), "needle\n").unwrap();
+    let input = root.to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+
+    let result = run(&cfg, std::slice::from_ref(&input)).unwrap();
+    assert!(result.skipped.is_empty());
+    assert!(result.warnings.is_empty());
+
+    cfg.list_skipped = true;
+    let result = run(&cfg, std::slice::from_ref(&input)).unwrap();
+    let mut skipped = result.skipped.clone();
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
+    let entry = |name: &str, reason| Skipped {
+        path: root.join(name).to_string_lossy().to_string(),
+        reason,
+    };
+    assert_eq!(
+        skipped,
+        vec![
+            entry(".cache", SkipReason::Hidden),
+            entry(".rgrepignore", SkipReason::Hidden),
+            entry("blob.bin", SkipReason::Binary),
+            entry("build", SkipReason::Ignored),
+        ]
+    );
+    assert!(result.warnings.contains(&format!(
+        "{}: skipped, binary file (-a to search it)",
+        root.join("blob.bin").display()
+    )));
+    assert_eq!(
+        result.warnings.last().unwrap(),
+        "skipped 4 entries: 1 binary, 1 ignored, 2 hidden"
+    );
+    assert_eq!(result.output.lines().count(), 1);
+}
+
+#[test]
+fn test_explain_reports_the_chosen_strategy() {
+    use rgrep::strategy::{PARALLEL_MIN_BYTES, explain};
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), "needle\n").unwrap();
+    fs::write(td.path().join("b.log"), "needle\n").unwrap();
+    let root = td.path().to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+
+    let report = in_pool(1, || explain(&cfg, std::slice::from_ref(&root))).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "matching: literal (the pattern is plain text)");
+    assert_eq!(
+        lines[1],
+        format!(
+            "scheduling: sequential (2 files of 14 bytes in total, fewer than {})",
+            PARALLEL_MIN_BYTES
+        )
+    );
+    assert_eq!(lines[2], "walking: sequential (there is one thread)");
+    assert_eq!(
+        lines[3],
+        "reading: line by line (buffered, one thread per file)"
+    );
+    let report = in_pool(4, || explain(&cfg, std::slice::from_ref(&root))).unwrap();
+    assert!(
+        report.contains("scheduling: parallel (2 files, searched as the parallel walk finds them)")
+    );
+    assert!(report.contains("walking: parallel (4 threads)"));
+
+    cfg.patterns = vec!["need.e".to_string()];
+    cfg.scheduling = Some(rgrep::Scheduling::Parallel);
+    let report = explain(&cfg, std::slice::from_ref(&root)).unwrap();
+    assert!(report.contains("matching: regex (the pattern has regex syntax"));
+    assert!(report.contains("scheduling: parallel (forced with --strategy)"));
+
+    // Either schedule finds the same lines
+    let parallel = run(&cfg, std::slice::from_ref(&root)).unwrap();
+    cfg.scheduling = None;
+    let sequential = run(&cfg, std::slice::from_ref(&root)).unwrap();
+    assert_eq!(parallel.output, sequential.output);
+}
+
+/// Run `f` in a rayon pool of `threads` threads.
+fn in_pool<T: Send>(threads: usize, f: impl FnOnce() -> T + Send) -> T {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(f)
+}
+
+#[test]
+fn test_parallel_walk_matches_sequential_walk() {
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path();
+    fs::create_dir(root.join(".git")).unwrap();
+    fs::write(root.join(".gitignore"), "*.tmp\ntarget/\n").unwrap();
+    for dir in ["src/a/b/c", "src/d", "target/debug", "docs/skip", ".hidden"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("src/.rgrepignore"), "generated.rs\n").unwrap();
+    for (i, dir) in ["src", "src/a", "src/a/b", "src/a/b/c", "src/d", "docs"]
+        .iter()
+        .enumerate()
+    {
+        for n in 0..5 {
+            let text = format!("needle {} {}\n", i, n);
+            fs::write(root.join(dir).join(format!("f{}.txt", n)), text).unwrap();
+        }
+    }
+    fs::write(root.join("src/generated.rs"), "needle\n").unwrap();
+    fs::write(root.join("src/a/x.tmp"), "needle\n").unwrap();
+    fs::write(root.join("src/d/big.txt"), "needle\n".repeat(100)).unwrap();
+    fs::write(root.join("src/d/blob.bin"), b"needle\0").unwrap();
+    fs::write(root.join("docs/skip/f.txt"), "needle\n").unwrap();
+    fs::write(root.join(".hidden/f.txt"), "needle\n").unwrap();
+    let inputs = vec![
+        root.join("src").to_string_lossy().to_string(),
+        root.join("docs/f0.txt").to_string_lossy().to_string(),
+        root.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    let mut configs = vec![cfg.clone()];
+    cfg.exclude_dirs = vec!["skip".into()];
+    cfg.max_filesize = Some(100);
+    cfg.list_skipped = true;
+    configs.push(cfg.clone());
+    cfg.max_depth = Some(2);
+    configs.push(cfg.clone());
+    cfg.compat = rgrep::Compat::Gnu;
+    cfg.binary_files = Some(rgrep::BinaryFiles::Report);
+    configs.push(cfg);
+
+    for cfg in &configs {
+        let walk = |threads| {
+            let mut walk = in_pool(threads, || walk_inputs(cfg, &inputs));
+            walk.ignore_files.sort();
+            walk
+        };
+        let sequential = walk(1);
+        assert!(sequential.files.len() > 20);
+        assert_eq!(walk(4), sequential);
+
+        // Files searched as the walk finds them are reported in walk order
+        let sequential = in_pool(1, || run(cfg, &inputs)).unwrap();
+        let parallel = in_pool(4, || run(cfg, &inputs)).unwrap();
+        assert_eq!(parallel.output, sequential.output);
+        assert_eq!(parallel.warnings, sequential.warnings);
+        assert_eq!(parallel.skipped, sequential.skipped);
+        assert_eq!(parallel.files, sequential.files);
+    }
+}
+
+#[test]
+fn test_run_to_writer_writes_what_run_returns() {
+    use rgrep::run_to_writer;
+    let td = tempfile::tempdir().unwrap();
+    for n in 0..12 {
+        let text: String = (0..50)
+            .map(|i| format!("2024-01-01T00:00:{:02} line {} of {}\n", i, i, n))
+            .collect();
+        fs::write(td.path().join(format!("f{:02}.log", n)), text).unwrap();
+    }
+    let dir = td.path().to_string_lossy().to_string();
+    let one = td.path().join("f03.log").to_string_lossy().to_string();
+
+    let mut cfg = create_config("line [12]");
+    cfg.recursive = true;
+    let mut configs = vec![cfg.clone()];
+    cfg.compat = rgrep::Compat::Gnu;
+    cfg.context.after = 1;
+    configs.push(cfg.clone());
+    cfg.compat = rgrep::Compat::Rgrep;
+    cfg.heading = true;
+    configs.push(cfg.clone());
+    cfg.count = true;
+    configs.push(cfg.clone());
+    cfg.count = false;
+    cfg.json = true;
+    configs.push(cfg);
+
+    for cfg in &configs {
+        for inputs in [vec![dir.clone()], vec![one.clone()]] {
+            for threads in [1, 4] {
+                let expected = in_pool(threads, || run(cfg, &inputs)).unwrap();
+                let mut written = Vec::new();
+                let result = in_pool(threads, || run_to_writer(cfg, &inputs, &mut written));
+                let result = result.unwrap();
+                assert_eq!(String::from_utf8(written).unwrap(), expected.output);
+                assert_eq!(result.output, "");
+                assert_eq!(result.status, expected.status);
+                assert_eq!(result.files, expected.files);
+            }
+        }
+    }
+
+    // A failed write ends the run with its error
+    struct Closed;
+    impl std::io::Write for Closed {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let err = in_pool(4, || {
+        run_to_writer(&configs[1], std::slice::from_ref(&dir), &mut Closed)
+    });
+    assert!(err.is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinked_root_walked_once() {
+    // Two inputs reaching the same directory are separate walks; within one walk a
+    // directory is never entered twice
+    let td = tempfile::tempdir().unwrap();
+    let real = td.path().join("real");
+    fs::create_dir(&real).unwrap();
+    fs::write(real.join("f.txt"), "needle\n").unwrap();
+    std::os::unix::fs::symlink(&real, td.path().join("link")).unwrap();
+
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    let walk = walk_inputs(&cfg, &[td.path().to_string_lossy().to_string()]);
+    assert_eq!(walk.files.len(), 1);
+    assert!(walk.issues.is_empty());
+}
+
 // ============ FILE TYPE TESTS ============
 
 #[test]
@@ -375,6 +1525,52 @@ fn test_large_number_of_files() {
     assert_eq!(match_count, 10, "Should find exactly 10 matches");
 }
 
+#[test]
+fn test_type_filters() {
+    let td = tempfile::tempdir().unwrap();
+    let dir = td.path();
+    fs::create_dir(dir.join("src")).unwrap();
+    for file in [
+        "src/lib.rs",
+        "src/gen.rs.in",
+        "tool.py",
+        "Makefile",
+        "notes.md",
+    ] {
+        fs::write(dir.join(file), "needle\n").unwrap();
+    }
+    let root = dir.to_string_lossy().to_string();
+    let found = |cfg: &Config| {
+        let mut files: Vec<String> = walk_inputs(cfg, std::slice::from_ref(&root))
+            .files
+            .iter()
+            .map(|f| f[root.len() + 1..].to_string())
+            .collect();
+        files.sort();
+        files
+    };
+
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    cfg.types = vec!["rust".into(), "make".into()];
+    assert_eq!(found(&cfg), vec!["Makefile", "src/lib.rs"]);
+
+    // --type-add extends a built-in type or defines a new one
+    cfg.type_defs = vec![
+        ("rust".into(), "*.rs.in".into()),
+        ("notes".into(), "*.md".into()),
+    ];
+    assert_eq!(found(&cfg), vec!["Makefile", "src/gen.rs.in", "src/lib.rs"]);
+
+    cfg.types = Vec::new();
+    cfg.types_not = vec!["rust".into(), "notes".into()];
+    assert_eq!(found(&cfg), vec!["Makefile", "tool.py"]);
+
+    cfg.types = vec!["cobol".into()];
+    let err = run(&cfg, std::slice::from_ref(&root)).unwrap_err();
+    assert_eq!(err, "unknown file type 'cobol' (see --type-list)");
+}
+
 // ============ SYMLINK TESTS (if supported) ============
 
 #[test]
@@ -400,6 +1596,39 @@ fn test_symlink_to_file() {
     assert!(result.output.contains("match content"));
 }
 
+#[test]
+#[cfg(unix)]
+fn test_follow_symlinks_cuts_loops() {
+    use std::os::unix::fs::symlink;
+
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path().join("root");
+    let outside = td.path().join("outside");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::create_dir(&outside).unwrap();
+    fs::write(root.join("sub").join("f.txt"), "needle\n").unwrap();
+    fs::write(outside.join("o.txt"), "needle\n").unwrap();
+    symlink(&outside, root.join("out")).unwrap();
+    symlink(outside.join("o.txt"), root.join("o-link.txt")).unwrap();
+    // A loop back to the walked directory
+    symlink(&root, root.join("sub").join("up")).unwrap();
+
+    let input = root.to_string_lossy().to_string();
+    let mut cfg = create_config("needle");
+    cfg.recursive = true;
+    let walk = walk_inputs(&cfg, std::slice::from_ref(&input));
+    assert_eq!(walk.files.len(), 1);
+    assert!(walk.issues.is_empty());
+
+    cfg.follow_symlinks = true;
+    let walk = walk_inputs(&cfg, std::slice::from_ref(&input));
+    let mut files: Vec<&str> = walk.files.iter().map(|f| &f[input.len() + 1..]).collect();
+    files.sort();
+    assert_eq!(files, vec!["o-link.txt", "out/o.txt", "sub/f.txt"]);
+    assert_eq!(walk.issues.cycles.len(), 1);
+    assert!(walk.issues.cycles[0].ends_with("up"));
+}
+
 // ============ PERMISSIONS TESTS ============
 
 #[test]
@@ -500,7 +1729,7 @@ fn test_recursive_skips_binary_in_subdirs() {
     fs::create_dir(&subdir).unwrap();
 
     fs::write(root.join("text.txt"), b"match").unwrap();
-    fs::write(subdir.join("binary.bin"), &[0x00, 0xFF]).unwrap();
+    fs::write(subdir.join("binary.bin"), [0x00, 0xFF]).unwrap();
 
     let mut cfg = create_config("match");
     cfg.recursive = true;
@@ -511,3 +1740,180 @@ fn test_recursive_skips_binary_in_subdirs() {
     assert_eq!(result.status, ExitStatus::MatchFound);
     assert!(result.output.contains("match"));
 }
+
+// ============ FILE LIST CACHE TESTS ============
+
+#[test]
+fn test_file_list_cache_reused_until_directory_changes() {
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path().join("tree");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("a.txt"), b"hit\n").unwrap();
+    fs::write(root.join("b.txt"), b"hit\n").unwrap();
+    fs::write(root.join("bin.dat"), b"hit\0\n").unwrap();
+    let cache = td.path().join("files.cache");
+
+    let mut cfg = create_config("hit");
+    cfg.recursive = true;
+    cfg.count = true;
+    cfg.table = false;
+    cfg.file_list_cache = Some(cache.to_string_lossy().to_string());
+    let inputs = vec![root.to_string_lossy().to_string()];
+
+    let first = run(&cfg, &inputs).unwrap();
+    assert_eq!(first.output.lines().count(), 2);
+    let saved = fs::read_to_string(&cache).unwrap();
+    assert!(saved.contains("a.txt") && saved.contains("b.txt"));
+    assert!(
+        !saved
+            .lines()
+            .any(|l| l.starts_with("file\t") && l.ends_with("bin.dat")),
+        "binary files are filtered before saving"
+    );
+
+    // A fresh cache is used as-is: drop b.txt from it and it is no longer searched
+    let edited: String = saved
+        .lines()
+        .filter(|l| !l.ends_with("b.txt"))
+        .map(|l| format!("{}\n", l))
+        .collect();
+    fs::write(&cache, edited).unwrap();
+    let reused = run(&cfg, &inputs).unwrap();
+    assert!(reused.output.contains("a.txt"));
+    assert!(!reused.output.contains("b.txt"));
+
+    // Adding a file changes the directory, so the tree is walked again
+    fs::write(root.join("c.txt"), b"hit\n").unwrap();
+    let rewalked = run(&cfg, &inputs).unwrap();
+    assert_eq!(rewalked.output.lines().count(), 3);
+}
+
+#[test]
+fn test_file_list_cache_notices_files_turning_binary_or_text() {
+    let td = tempfile::tempdir().unwrap();
+    let root = td.path().join("d");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("a.txt"), b"hit\n").unwrap();
+    fs::write(root.join("b.txt"), b"hit\0\n").unwrap();
+    let cache = td.path().join("files.cache");
+
+    let mut cfg = create_config("hit");
+    cfg.recursive = true;
+    cfg.file_list_cache = Some(cache.to_string_lossy().to_string());
+    cfg.with_filename = Some(false);
+    let inputs = vec![root.to_string_lossy().to_string()];
+    let searched = |cfg: &Config| run(cfg, &inputs).unwrap().files.len();
+
+    assert_eq!(searched(&cfg), 1);
+    // Rewritten in place, the directory is unchanged, but b.txt is text now
+    fs::write(root.join("b.txt"), b"hit\n").unwrap();
+    assert_eq!(searched(&cfg), 2);
+    // And binary again
+    fs::write(root.join("a.txt"), b"hit\0\n").unwrap();
+    assert_eq!(searched(&cfg), 1);
+    let saved = fs::read_to_string(&cache).unwrap();
+    assert!(
+        saved
+            .lines()
+            .any(|l| l.starts_with("file\t") && l.ends_with("b.txt"))
+    );
+    assert!(
+        !saved
+            .lines()
+            .any(|l| l.starts_with("file\t") && l.ends_with("a.txt"))
+    );
+}
+
+#[test]
+fn test_file_list_cache_ignores_other_inputs() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("b.txt");
+    fs::write(&a, b"hit\n").unwrap();
+    fs::write(&b, b"hit\n").unwrap();
+    let cache = td.path().join("files.cache");
+
+    let mut cfg = create_config("hit");
+    cfg.file_list_cache = Some(cache.to_string_lossy().to_string());
+
+    let only_a = run(&cfg, &[a.to_string_lossy().to_string()]).unwrap();
+    assert_eq!(only_a.output, "1:hit\n");
+    let only_b = run(&cfg, &[b.to_string_lossy().to_string()]).unwrap();
+    assert_eq!(only_b.status, ExitStatus::MatchFound);
+    assert!(fs::read_to_string(&cache).unwrap().contains("b.txt"));
+}
+
+// ============ STOPPED SEARCH TESTS ============
+
+/// `count` files with timestamped lines, searched through a `--pre` script that takes
+/// 50 ms and logs each file it is run on to `searched.log`.
+#[cfg(unix)]
+fn logged_tree(count: usize) -> (tempfile::TempDir, String, std::path::PathBuf) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let td = tempfile::tempdir().unwrap();
+    let tree = td.path().join("tree");
+    fs::create_dir(&tree).unwrap();
+    for i in 0..count {
+        fs::write(
+            tree.join(format!("{:04}.log", i)),
+            format!("2026-01-01 10:00:{:02} hit {}\n", i % 60, i),
+        )
+        .unwrap();
+    }
+    let log = td.path().join("searched.log");
+    let script = td.path().join("pre.sh");
+    fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho \"$1\" >> '{}'\nsleep 0.05\ncat \"$1\"\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    (td, script.to_string_lossy().to_string(), log)
+}
+
+#[test]
+#[cfg(unix)]
+fn test_run_to_writer_until_searches_nothing_once_stopped() {
+    use std::sync::atomic::AtomicBool;
+
+    let (td, script, log) = logged_tree(20);
+    let mut cfg = create_config("hit");
+    cfg.recursive = true;
+    cfg.pre = Some(script);
+    let inputs = vec![td.path().join("tree").to_string_lossy().to_string()];
+
+    let mut out = Vec::new();
+    let err =
+        rgrep::run_to_writer_until(&cfg, &inputs, &mut out, &AtomicBool::new(true)).unwrap_err();
+    assert!(err.contains("stopped"), "{}", err);
+    assert!(out.is_empty());
+    assert!(!log.exists());
+
+    let found = rgrep::run_to_writer_until(&cfg, &inputs, &mut out, &AtomicBool::new(false));
+    assert_eq!(found.unwrap().status, ExitStatus::MatchFound);
+    assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 20);
+}
+
+#[cfg(all(feature = "cli", target_os = "linux"))]
+#[test]
+fn test_search_stops_when_stdout_closes_before_merged_output() {
+    let (td, script, log) = logged_tree(1000);
+    // Lines merged by timestamp are only written at the end, so only watching the pipe
+    // tells that its reader went away
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["-R", "-r", "hit", "--pre", &script])
+        .arg(td.path().join("tree"))
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let status = child.wait().unwrap();
+
+    assert_eq!(status.code(), Some(141));
+    let searched = fs::read_to_string(&log).unwrap_or_default().lines().count();
+    assert!(searched < 500, "{} of 1000 files searched", searched);
+}
@@ -1,7 +1,8 @@
-use rgrep::{Config, Context};
+use rgrep::{ColorChoice, Config, Context};
 
 // We test the internal follow context logic by constructing a minimal engine via a
-// small re-export in tests (using the public follow API would block forever).
+// small re-export in tests (the public follow API runs until its sink is closed; see
+// the tests of follow_with_sink at the end).
 // To keep changes minimal, we simulate the per-line handling through a local copy
 // matching the implementation in src/follow.rs.
 
@@ -50,7 +51,7 @@ impl TestEngine {
 fn cfg() -> Config {
     Config {
         patterns: vec!["hund".to_string()],
-        color: false,
+        color: ColorChoice::Never,
         ..Default::default()
     }
 }
@@ -61,6 +62,7 @@ fn follow_c_prints_before_and_match() {
     c.context = Context {
         before: 2,
         after: 2,
+        ..Default::default()
     };
 
     let mut eng = TestEngine::new(c.context.before, c.context.after);
@@ -81,6 +83,7 @@ fn follow_b_prints_before_and_match() {
     c.context = Context {
         before: 2,
         after: 0,
+        ..Default::default()
     };
 
     let mut eng = TestEngine::new(c.context.before, c.context.after);
@@ -101,6 +104,7 @@ fn follow_a_prints_match_and_after() {
     c.context = Context {
         before: 0,
         after: 2,
+        ..Default::default()
     };
 
     let mut eng = TestEngine::new(c.context.before, c.context.after);
@@ -139,3 +143,237 @@ fn follow_context_scoped_per_batch_c2() {
     }
     assert_eq!(out2, vec!["hund", "chimpanzee", "bird"]);
 }
+
+#[cfg(feature = "files")]
+#[test]
+fn follow_with_sink_receives_events_until_closed() {
+    use rgrep::{FollowEnd, FollowEvent, FollowSink, follow_with_sink};
+    use std::io::Write;
+
+    struct Collect(Vec<FollowEvent>);
+    impl FollowSink for Collect {
+        fn on_line(&mut self, event: FollowEvent) -> std::io::Result<()> {
+            self.0.push(event);
+            Ok(())
+        }
+        fn closed(&self) -> bool {
+            self.0.len() >= 2
+        }
+    }
+
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    std::fs::write(&log, "").unwrap();
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let writer = {
+        let (log, done) = (log.clone(), done.clone());
+        std::thread::spawn(move || {
+            while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+                file.write_all(b"quiet\nthe hund\n").unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+        })
+    };
+
+    let mut c = cfg();
+    c.follow = true;
+    c.context = Context {
+        before: 1,
+        after: 0,
+        ..Default::default()
+    };
+    let mut sink = Collect(Vec::new());
+    let result = follow_with_sink(&c, &[log.to_string_lossy().to_string()], &mut sink);
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    writer.join().unwrap();
+
+    assert_eq!(result, Ok(FollowEnd::Closed));
+    let FollowEvent::Context {
+        path,
+        line_number: context_number,
+        line,
+    } = &sink.0[0]
+    else {
+        panic!("{:?}", sink.0);
+    };
+    assert_eq!(line, "quiet");
+    assert_eq!(path.as_deref(), Some(log.to_string_lossy().as_ref()));
+    let FollowEvent::Match {
+        line_number,
+        line,
+        spans,
+        ..
+    } = &sink.0[1]
+    else {
+        panic!("{:?}", sink.0);
+    };
+    assert_eq!(line, "the hund");
+    assert_eq!(spans[0], 4..8);
+    assert_eq!(*line_number, context_number + 1);
+}
+
+/// Sends followed lines to a channel, as `match N:LINE` and `context N:LINE` so that
+/// tests compare plain strings, until `stop` is set.
+#[cfg(feature = "files")]
+struct Forward {
+    tx: std::sync::mpsc::Sender<String>,
+    stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(feature = "files")]
+impl rgrep::FollowSink for Forward {
+    fn on_line(&mut self, event: rgrep::FollowEvent) -> std::io::Result<()> {
+        let line = match event {
+            rgrep::FollowEvent::Match {
+                line_number, line, ..
+            } => format!("match {}:{}", line_number, line),
+            rgrep::FollowEvent::Context {
+                line_number, line, ..
+            } => format!("context {}:{}", line_number, line),
+        };
+        self.tx
+            .send(line)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "receiver gone"))
+    }
+    fn closed(&self) -> bool {
+        self.stop.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+/// Follows `path` with `follow_with_sink` on a thread of its own (see `Forward`);
+/// setting the returned flag ends following.
+#[cfg(feature = "files")]
+fn follow_on_thread(
+    c: Config,
+    path: &std::path::Path,
+) -> (
+    std::sync::mpsc::Receiver<String>,
+    std::sync::Arc<std::sync::atomic::AtomicBool>,
+    std::thread::JoinHandle<Result<rgrep::FollowEnd, String>>,
+) {
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (tx, lines) = std::sync::mpsc::channel();
+    let inputs = vec![path.to_string_lossy().to_string()];
+    let mut sink = Forward {
+        tx,
+        stop: stop.clone(),
+    };
+    let handle = std::thread::spawn(move || rgrep::follow_with_sink(&c, &inputs, &mut sink));
+    (lines, stop, handle)
+}
+
+#[cfg(feature = "files")]
+fn next_line(lines: &std::sync::mpsc::Receiver<String>) -> String {
+    lines
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .expect("no followed line within 10s")
+}
+
+#[cfg(feature = "files")]
+fn append(path: &std::path::Path, text: &str) {
+    use std::io::Write;
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .unwrap()
+        .write_all(text.as_bytes())
+        .unwrap();
+}
+
+#[cfg(feature = "files")]
+#[test]
+fn follow_with_sink_survives_rotation_and_truncation() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    let rotated = td.path().join("app.log.1");
+    std::fs::write(&log, "first hund\n").unwrap();
+
+    let mut c = cfg();
+    c.follow = true;
+    // The first line shows that following has started
+    c.follow_from_start = true;
+    let (lines, stop, handle) = follow_on_thread(c, &log);
+    assert_eq!(next_line(&lines), "match 1:first hund");
+
+    // A line is only followed once its newline is written
+    append(&log, "part");
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    assert!(lines.try_recv().is_err());
+    append(&log, "ial hund\n");
+    assert_eq!(next_line(&lines), "match 2:partial hund");
+
+    // Rotation: the old file is renamed and still written to, its last line left
+    // unfinished, and a new file takes its place
+    append(&log, "before hund\nunfinished ");
+    std::fs::rename(&log, &rotated).unwrap();
+    append(&rotated, "late hund");
+    std::fs::write(&log, "new hund\n").unwrap();
+    assert_eq!(next_line(&lines), "match 3:before hund");
+    assert_eq!(next_line(&lines), "match 4:unfinished late hund");
+    assert_eq!(next_line(&lines), "match 1:new hund");
+
+    // Truncated in place, as by `copytruncate`, and written again with less than was read
+    std::fs::write(&log, "s hund\n").unwrap();
+    assert_eq!(next_line(&lines), "match 1:s hund");
+
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    assert_eq!(handle.join().unwrap(), Ok(rgrep::FollowEnd::Closed));
+}
+
+#[cfg(feature = "files")]
+#[test]
+fn follow_with_sink_starts_with_the_last_matching_lines() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    std::fs::write(
+        &log,
+        "a hund\nafter a\nb hund\nafter b\nskipped\nbefore c\nc hund\n",
+    )
+    .unwrap();
+
+    let mut c = cfg();
+    c.follow = true;
+    c.tail_lines = Some(2);
+    c.context = Context {
+        before: 1,
+        after: 1,
+        ..Default::default()
+    };
+    let (lines, stop, handle) = follow_on_thread(c, &log);
+    let tail: Vec<String> = (0..4).map(|_| next_line(&lines)).collect();
+    assert_eq!(
+        tail,
+        vec![
+            "match 3:b hund",
+            "context 4:after b",
+            "context 6:before c",
+            "match 7:c hund",
+        ]
+    );
+    // Then lines are followed as they are written
+    append(&log, "d hund\n");
+    assert_eq!(next_line(&lines), "match 8:d hund");
+
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    assert_eq!(handle.join().unwrap(), Ok(rgrep::FollowEnd::Closed));
+}
+
+#[cfg(feature = "files")]
+#[test]
+fn follow_with_sink_from_start_reads_existing_lines() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    std::fs::write(&log, "old hund\nold miss\n").unwrap();
+
+    let mut c = cfg();
+    c.follow = true;
+    c.follow_from_start = true;
+    let (lines, stop, handle) = follow_on_thread(c, &log);
+    assert_eq!(next_line(&lines), "match 1:old hund");
+    append(&log, "new hund\n");
+    assert_eq!(next_line(&lines), "match 3:new hund");
+
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    assert_eq!(handle.join().unwrap(), Ok(rgrep::FollowEnd::Closed));
+}
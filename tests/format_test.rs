@@ -0,0 +1,163 @@
+#![cfg(feature = "files")]
+
+use rgrep::output::template::Template;
+use rgrep::{ColorChoice, Config, ExitStatus, run, run_on_reader};
+use std::fs;
+use std::io::Cursor;
+
+fn create_config(pattern: &str, format: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        format: Some(format.to_string()),
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+// ============ TEMPLATE RENDERING TESTS ============
+
+#[test]
+fn test_format_placeholders() {
+    let cfg = create_config(
+        r"user=(\w+)",
+        "{path}|{line}|{column}|{byte_offset}|{match}|{captures[1]}|{text}",
+    );
+    let data = "skip\nlogin user=alice ok\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), Some("auth.log")).unwrap();
+
+    assert_eq!(
+        result.output,
+        "auth.log|2|7|5|user=alice|alice|login user=alice ok\n"
+    );
+}
+
+#[test]
+fn test_format_escapes_and_context_lines() {
+    let mut cfg = create_config("b", r"{{{line}}}\t{match}");
+    cfg.context.before = 1;
+    let result = run_on_reader(&cfg, Cursor::new("a\nb\n"), None).unwrap();
+
+    // Context lines have no match text
+    assert_eq!(result.output, "{1}\t\n{2}\tb\n");
+}
+
+#[test]
+fn test_format_parse_errors() {
+    assert!(Template::parse("{nope}").is_err());
+    assert!(Template::parse("{line").is_err());
+    assert!(Template::parse("line}").is_err());
+    assert!(Template::parse("{captures[x]}").is_err());
+
+    let cfg = create_config(r"(\d+)", "{captures[2]}");
+    let err = run_on_reader(&cfg, Cursor::new("1\n"), None).expect_err("missing group is rejected");
+    assert!(err.contains("captures[2]"));
+}
+
+// ============ REPLACE TESTS ============
+
+#[test]
+fn test_replace_expands_capture_groups() {
+    let cfg = Config {
+        patterns: vec![r"user=(?P<name>\w+) id=(\d+)".to_string()],
+        replace: Some("${name}#$2".to_string()),
+        color: ColorChoice::Never,
+        ..Default::default()
+    };
+    let data = "login user=alice id=42 ok\nnothing\nuser=bob id=7, user=eve id=9\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    assert_eq!(result.output, "1:login alice#42 ok\n3:bob#7, eve#9\n");
+}
+
+#[test]
+fn test_replace_highlights_replacements_and_keeps_context() {
+    let mut cfg = Config {
+        patterns: vec!["id".to_string()],
+        replace: Some("ID".to_string()),
+        color: ColorChoice::Always,
+        ..Default::default()
+    };
+    cfg.context.before = 1;
+    let result = run_on_reader(&cfg, Cursor::new("id\nx id\n"), None).unwrap();
+    let plain = regex::Regex::new("\x1b\\[[0-9;]*m")
+        .unwrap()
+        .replace_all(&result.output, "");
+
+    assert!(
+        result
+            .output
+            .contains(&format!("\x1b[{}mID", cfg.colors.matched))
+    );
+    assert_eq!(plain, "1:ID\n2:x ID\n");
+
+    // Context and inverted lines are printed unchanged
+    cfg.color = ColorChoice::Never;
+    cfg.invert = true;
+    let result = run_on_reader(&cfg, Cursor::new("id\nother\n"), None).unwrap();
+    assert_eq!(result.output, "1:id\n2:other\n");
+}
+
+// ============ ONLY-MATCHING TESTS ============
+
+#[test]
+fn test_only_matching_prints_each_match() {
+    let cfg = Config {
+        patterns: vec![r"id=\d+".to_string()],
+        only_matching: true,
+        column: true,
+        color: ColorChoice::Never,
+        ..Default::default()
+    };
+    let data = "user=alice id=42\nnothing\nid=7, id=9\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    assert_eq!(result.output, "1:12:id=42\n3:1:id=7\n3:7:id=9\n");
+}
+
+#[test]
+fn test_only_matching_selects_capture_group() {
+    let mut cfg = Config {
+        patterns: vec![r"user=(?P<name>\w+)( id=(\d+))?".to_string()],
+        only_matching: true,
+        group: Some("3".to_string()),
+        color: ColorChoice::Never,
+        ..Default::default()
+    };
+    let data = "user=alice id=42\nuser=bob\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    // A group that did not take part in the match prints nothing
+    assert_eq!(result.output, "1:42\n");
+    assert_eq!(result.status, ExitStatus::MatchFound);
+
+    cfg.group = Some("name".to_string());
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "1:alice\n2:bob\n");
+
+    cfg.group = Some("4".to_string());
+    let err = run_on_reader(&cfg, Cursor::new(data), None).unwrap_err();
+    assert!(err.contains("3 capture group(s)"), "{}", err);
+}
+
+// ============ MULTI-FILE TESTS ============
+
+#[test]
+fn test_format_lists_files_in_input_order_without_tags() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, b"2025-01-01 10:00:02 hit\n").unwrap();
+    fs::write(&b, b"2025-01-01 10:00:01 hit\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("hit", "{line}:{text}");
+    cfg.with_filename = Some(false);
+    let result = run(&cfg, &inputs).unwrap();
+
+    assert_eq!(
+        result.output,
+        "1:2025-01-01 10:00:02 hit\n1:2025-01-01 10:00:01 hit\n"
+    );
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "cli")]
+
+use rgrep::{ColorChoice, Compat, Config, ExitStatus, run};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// ============ DIFFERENTIAL TESTS AGAINST GNU GREP ============
+
+/// Whether the system `grep` is GNU grep; the differential tests are skipped otherwise.
+fn has_gnu_grep() -> bool {
+    Command::new("grep")
+        .arg("--version")
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).contains("GNU grep"))
+}
+
+/// Stdout, stderr (with the program name removed), and exit code of running `program`
+/// in `dir`.
+fn output_of(program: &str, args: &[&str], dir: &Path) -> (String, String, Option<i32>) {
+    let out = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .env("LC_ALL", "C")
+        .env_remove("GREP_COLORS")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr)
+        .replace("rgrep: ", "")
+        .replace("grep: ", "");
+    (
+        String::from_utf8_lossy(&out.stdout).into_owned(),
+        stderr,
+        out.status.code(),
+    )
+}
+
+/// Run `grep -e PATTERN ARGS` and `rgrep --compat=gnu --regexp PATTERN ARGS` in `dir`
+/// and check that their output and exit codes agree.
+fn assert_same_as_grep(dir: &Path, pattern: &str, args: &[&str]) {
+    let mut grep_args = vec!["-e", pattern];
+    grep_args.extend(args);
+    let mut rgrep_args = vec!["--compat=gnu", "--regexp", pattern];
+    rgrep_args.extend(args);
+    let expected = output_of("grep", &grep_args, dir);
+    let actual = output_of(env!("CARGO_BIN_EXE_rgrep"), &rgrep_args, dir);
+    assert_eq!(actual, expected, "pattern {:?} with {:?}", pattern, args);
+}
+
+#[test]
+fn test_output_matches_gnu_grep() {
+    if !has_gnu_grep() {
+        eprintln!("skipped: the system grep is not GNU grep");
+        return;
+    }
+    let td = tempfile::tempdir().unwrap();
+    let dir = td.path();
+    fs::write(
+        dir.join("a.txt"),
+        "alpha\nbeta\ngamma\ndelta\nepsilon\nzeta\neta\ntheta\nalpha two\n",
+    )
+    .unwrap();
+    fs::write(dir.join("b.txt"), "one\nAlpha\nthree\n").unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/c.txt"), "x alpha y\n").unwrap();
+
+    let cases: &[(&str, &[&str])] = &[
+        ("alpha", &["a.txt"]),
+        ("alpha", &["a.txt", "b.txt"]),
+        ("alpha", &["-i", "a.txt", "b.txt"]),
+        ("alpha", &["-n", "a.txt", "b.txt"]),
+        ("alpha", &["-C1", "a.txt", "b.txt"]),
+        ("alpha", &["-n", "-C1", "-i", "a.txt", "b.txt"]),
+        ("eta", &["-B2", "a.txt"]),
+        ("eta", &["-A1", "-n", "a.txt"]),
+        ("alpha", &["-c", "a.txt", "b.txt"]),
+        ("a", &["-v", "-n", "a.txt"]),
+        ("alpha", &["-h", "a.txt", "b.txt"]),
+        ("alpha", &["-H", "a.txt"]),
+        ("alpha", &["-w", "-x", "a.txt"]),
+        ("a.p", &["a.txt"]),
+        ("a.p", &["-F", "a.txt"]),
+        (r"\(al\)pha t", &["a.txt"]),
+        ("alpha", &["-R", "sub"]),
+        ("nothing", &["a.txt"]),
+        ("alpha", &["a.txt", "missing.txt"]),
+        ("alpha", &["missing.txt"]),
+        ("alpha", &["sub"]),
+        ("alpha", &["-q", "missing.txt", "a.txt"]),
+        ("nothing", &["-q", "missing.txt", "a.txt"]),
+    ];
+    for (pattern, args) in cases {
+        assert_same_as_grep(dir, pattern, args);
+    }
+}
+
+// ============ LIBRARY TESTS ============
+
+fn gnu_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        line_number: false,
+        compat: Compat::Gnu,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_binary_file_match_is_reported() {
+    let td = tempfile::tempdir().unwrap();
+    let bin = td.path().join("data.bin");
+    fs::write(&bin, b"header\0alpha\n").unwrap();
+    let txt = td.path().join("notes.txt");
+    fs::write(&txt, b"alpha\n").unwrap();
+    let inputs = vec![
+        bin.to_string_lossy().to_string(),
+        txt.to_string_lossy().to_string(),
+    ];
+
+    let result = run(&gnu_config("alpha"), &inputs).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(
+        result.output,
+        format!("Binary file {} matches\n{}:alpha\n", inputs[0], inputs[1])
+    );
+}
+
+#[test]
+fn test_unreadable_input_does_not_stop_search() {
+    let td = tempfile::tempdir().unwrap();
+    let txt = td.path().join("notes.txt");
+    fs::write(&txt, b"alpha\n").unwrap();
+    let missing = td.path().join("missing.txt");
+    let inputs = vec![
+        missing.to_string_lossy().to_string(),
+        txt.to_string_lossy().to_string(),
+    ];
+
+    let result = run(&gnu_config("alpha"), &inputs).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.output, format!("{}:alpha\n", inputs[1]));
+    assert_eq!(
+        result.errors,
+        vec![format!("{}: No such file or directory", inputs[0])]
+    );
+
+    // Without compatibility mode the run fails
+    let mut cfg = gnu_config("alpha");
+    cfg.compat = Compat::Rgrep;
+    assert!(run(&cfg, &inputs).is_err());
+}
+
+#[test]
+fn test_quiet_stops_at_first_matching_file() {
+    let td = tempfile::tempdir().unwrap();
+    let first = td.path().join("first.txt");
+    fs::write(&first, b"alpha\nalpha\n").unwrap();
+    let second = td.path().join("second.txt");
+    fs::write(&second, b"alpha\n").unwrap();
+    let inputs = vec![
+        first.to_string_lossy().to_string(),
+        second.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = gnu_config("alpha");
+    cfg.quiet = true;
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.files.len(), 1);
+    assert_eq!(result.files[0].matched_lines, 1);
+}
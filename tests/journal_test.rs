@@ -0,0 +1,70 @@
+#![cfg(feature = "journald")]
+
+use rgrep::journal::{Entry, parse_syslog, search_entries};
+use rgrep::{ColorChoice, Config};
+use std::io;
+
+fn create_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        line_number: false,
+        ..Default::default()
+    }
+}
+
+/// Search `datagrams` as received by a syslog listener, returning the output and count.
+fn search(cfg: &Config, datagrams: &[&str]) -> (String, usize) {
+    let mut entries: Vec<Entry> = datagrams.iter().map(|d| parse_syslog(d)).collect();
+    entries.reverse();
+    let mut out = Vec::new();
+    let count = search_entries(cfg, || Ok::<_, io::Error>(entries.pop()), &mut out).unwrap();
+    (String::from_utf8(out).unwrap(), count)
+}
+
+const MESSAGES: [&str; 3] = [
+    "<34>Oct 16 09:05:01 web1 sshd[812]: Failed password for root",
+    "<13>Oct 16 09:05:02 cron[9]: job done",
+    "<165>1 2026-10-16T09:05:03Z host app 42 - - access denied",
+];
+
+// ============ TEXT OUTPUT TESTS ============
+
+#[test]
+fn test_entries_print_after_their_identifier() {
+    let (out, count) = search(&create_config("Failed|denied"), &MESSAGES);
+
+    assert_eq!(count, 2);
+    assert_eq!(
+        out,
+        "sshd[812]:Failed password for root\napp[42]:access denied\n"
+    );
+}
+
+#[test]
+fn test_entries_invert_and_count() {
+    let mut cfg = create_config("denied");
+    cfg.invert = true;
+    cfg.count = true;
+    let (out, count) = search(&cfg, &MESSAGES);
+
+    assert_eq!(count, 2);
+    assert_eq!(out, "2\n");
+}
+
+// ============ JSON OUTPUT TESTS ============
+
+#[test]
+fn test_json_match_events_carry_fields() {
+    let mut cfg = create_config("job");
+    cfg.json = true;
+    let (out, _) = search(&cfg, &MESSAGES);
+
+    assert_eq!(
+        out,
+        "{\"type\":\"match\",\"path\":null,\"line_number\":2,\"byte_offset\":0,\"text\":\"job done\",\
+         \"submatches\":[{\"match\":\"job\",\"start\":0,\"end\":3}],\"fields\":{\"PRIORITY\":\"5\",\
+         \"SYSLOG_FACILITY\":\"1\",\"SYSLOG_TIMESTAMP\":\"Oct 16 09:05:02\",\
+         \"SYSLOG_IDENTIFIER\":\"cron\",\"SYSLOG_PID\":\"9\"}}\n"
+    );
+}
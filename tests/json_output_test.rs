@@ -0,0 +1,396 @@
+#![cfg(feature = "files")]
+
+use rgrep::output::json;
+use rgrep::{Config, Context, ExitStatus, JsonSchema, run, run_json_lines, run_on_reader};
+use std::fs;
+use std::io::Cursor;
+
+fn create_json_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        json: true,
+        ..Default::default()
+    }
+}
+
+// ============ JSON EVENT TESTS ============
+
+#[test]
+fn test_json_reader_events() {
+    let cfg = create_json_config("error");
+    let data = "ok\nan error here\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            r#"{"type":"begin","path":null}"#,
+            r#"{"type":"match","path":null,"line_number":2,"byte_offset":3,"text":"an error here","submatches":[{"match":"error","start":3,"end":8}]}"#,
+            r#"{"type":"end","path":null,"matched_lines":1,"matches":1,"timeouts":0}"#,
+        ]
+    );
+}
+
+#[test]
+fn test_json_context_events() {
+    let mut cfg = create_json_config("b");
+    cfg.context = Context {
+        before: 1,
+        after: 0,
+        ..Default::default()
+    };
+    let data = "a\nb\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), Some("f.txt")).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with(r#"{"type":"context","path":"f.txt","line_number":1"#));
+    assert!(lines[2].starts_with(r#"{"type":"match","path":"f.txt","line_number":2"#));
+}
+
+#[test]
+fn test_json_escapes_text() {
+    let cfg = create_json_config("quote");
+    let data = "a \"quote\"\tand \\ slash\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    assert!(
+        result
+            .output
+            .contains(r#""text":"a \"quote\"\tand \\ slash""#)
+    );
+}
+
+#[test]
+fn test_json_multi_file_summary() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("b.txt");
+    fs::write(&a, b"hit\nhit\n").unwrap();
+    fs::write(&b, b"miss\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let result = run(&create_json_config("hit"), &inputs).unwrap();
+
+    let last = result.output.lines().last().unwrap();
+    assert_eq!(
+        last,
+        r#"{"type":"summary","files_searched":2,"files_with_matches":1,"matched_lines":2,"matches":2,"timeouts":0}"#
+    );
+    assert_eq!(
+        result
+            .output
+            .lines()
+            .filter(|l| l.contains(r#""type":"begin""#))
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn test_json_count_mode_reports_end_only() {
+    let mut cfg = create_json_config("x");
+    cfg.count = true;
+    let data = "x\nx\ny\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    assert!(!result.output.contains(r#""type":"match""#));
+    assert!(result.output.contains(r#""matched_lines":2"#));
+}
+
+#[test]
+fn test_json_counts_matches_of_lines_not_printed() {
+    let data = "x x\ny\nx\n";
+
+    // Count mode prints no lines, but counts their matches
+    let mut cfg = create_json_config("x");
+    cfg.count = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert!(result.output.ends_with(
+        r#""matched_lines":2,"matches":3,"timeouts":0}
+"#
+    ));
+
+    // So does a preview, for the lines beyond it
+    let mut cfg = create_json_config("x");
+    cfg.preview = Some(1);
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output.matches(r#""type":"match""#).count(), 1);
+    assert!(result.output.ends_with(
+        r#""matched_lines":2,"matches":3,"timeouts":0}
+"#
+    ));
+
+    // A line selected with -v counts once, as with --count-matches
+    let mut cfg = create_json_config("x");
+    cfg.invert = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert!(result.output.ends_with(
+        r#""matched_lines":1,"matches":1,"timeouts":0}
+"#
+    ));
+    cfg.json = false;
+    cfg.count = true;
+    cfg.count_matches = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "1\n");
+
+    // The streamed summary agrees
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    fs::write(&a, data).unwrap();
+    let mut cfg = create_json_config("x");
+    cfg.count = true;
+    let mut streamed = Vec::new();
+    run_json_lines(&cfg, &[a.to_string_lossy().to_string()], &mut streamed).unwrap();
+    let streamed = String::from_utf8(streamed).unwrap();
+    assert!(
+        streamed.ends_with(
+            r#""files_with_matches":1,"matched_lines":2,"matches":3,"timeouts":0}
+"#
+        ),
+        "{}",
+        streamed
+    );
+}
+
+#[test]
+fn test_json_match_lists_matched_terms() {
+    let cfg = create_json_config("(error|panic)&!retry");
+    let data = "panic: boom\nerror, then panic\nerror, retry\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].ends_with(r#""terms":["panic"]}"#), "{}", lines[1]);
+    assert!(
+        lines[2].ends_with(r#""terms":["error","panic"]}"#),
+        "{}",
+        lines[2]
+    );
+
+    // Plain patterns carry no terms
+    let result = run_on_reader(&create_json_config("panic"), Cursor::new(data), None).unwrap();
+    assert!(!result.output.contains("\"terms\""));
+}
+
+#[test]
+fn test_json_no_match_event() {
+    let mut out = String::new();
+    json::append_no_match(&mut out, "no \"error\" lines\tfound");
+    assert_eq!(
+        out,
+        "{\"type\":\"no_match\",\"message\":\"no \\\"error\\\" lines\\tfound\"}\n"
+    );
+}
+
+#[test]
+fn test_json_context_hash_survives_line_shift() {
+    let cfg = Config {
+        context_hashes: true,
+        ..create_json_config("needle")
+    };
+    let hash_of = |data: &str| {
+        let result = run_on_reader(&cfg, Cursor::new(data.to_string()), None).unwrap();
+        result.files[0].matches[0].context_hash.unwrap()
+    };
+
+    let original = hash_of("a\nb\nneedle\nc\nd\ne\n");
+    // Lines added above and reindentation leave the fingerprint alone
+    assert_eq!(hash_of("new\nnew\na\nb\n    needle\nc\nd\ne\n"), original);
+    // A change within two lines of the match is a different finding
+    assert_ne!(hash_of("a\nB\nneedle\nc\nd\ne\n"), original);
+    assert_eq!(hash_of("x\na\nb\nneedle\nc\nd\n"), original);
+
+    let result = run_on_reader(&cfg, Cursor::new("needle\n"), None).unwrap();
+    assert!(result.output.contains(&format!(
+        ",\"context_hash\":\"{:016x}\"}}",
+        rgrep::search::context_hash(["needle"])
+    )));
+}
+
+// ============ RIPGREP SCHEMA TESTS ============
+
+#[test]
+fn test_ripgrep_schema_events() {
+    let cfg = Config {
+        json_schema: JsonSchema::Ripgrep,
+        context: Context {
+            before: 1,
+            after: 0,
+            ..Default::default()
+        },
+        ..create_json_config("error")
+    };
+    let result = run_on_reader(&cfg, Cursor::new("ok\nerror, error\n"), None).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[0],
+        r#"{"type":"begin","data":{"path":{"text":"<stdin>"}}}"#
+    );
+    assert_eq!(
+        lines[1],
+        r#"{"type":"context","data":{"path":{"text":"<stdin>"},"lines":{"text":"ok\n"},"line_number":1,"absolute_offset":0,"submatches":[]}}"#
+    );
+    assert_eq!(
+        lines[2],
+        r#"{"type":"match","data":{"path":{"text":"<stdin>"},"lines":{"text":"error, error\n"},"line_number":2,"absolute_offset":3,"submatches":[{"match":{"text":"error"},"start":0,"end":5},{"match":{"text":"error"},"start":7,"end":12}]}}"#
+    );
+    assert!(lines[3].starts_with(
+        r#"{"type":"end","data":{"path":{"text":"<stdin>"},"binary_offset":null,"stats":{"elapsed":"#
+    ));
+    // The events before `end` are the bytes printed for the input
+    let printed: usize = lines[..3].iter().map(|l| l.len() + 1).sum();
+    assert!(lines[3].ends_with(&format!(
+        r#""searches":1,"searches_with_match":1,"bytes_searched":16,"bytes_printed":{},"matched_lines":1,"matches":2,"timeouts":0}}}}}}"#,
+        printed
+    )));
+}
+
+#[test]
+fn test_ripgrep_schema_summary_and_streaming() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("b.txt");
+    fs::write(&a, b"one hit\ntwo\n").unwrap();
+    fs::write(&b, b"nothing\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+    let cfg = Config {
+        json_schema: JsonSchema::Ripgrep,
+        ..create_json_config("hit")
+    };
+
+    let buffered = run(&cfg, &inputs).unwrap();
+    let mut streamed = Vec::new();
+    run_json_lines(&cfg, &inputs, &mut streamed).unwrap();
+    for output in [buffered.output, String::from_utf8(streamed).unwrap()] {
+        let summary = output.lines().last().unwrap();
+        assert!(summary.starts_with(r#"{"type":"summary","data":{"elapsed_total":{"secs":"#));
+        assert!(summary.contains(r#""searches":2,"searches_with_match":1,"bytes_searched":20,"#));
+        assert!(summary.ends_with(r#""matched_lines":1,"matches":1,"timeouts":0}}}"#));
+    }
+
+    // ripgrep's schema has no path-only or no-match events
+    let cfg = Config {
+        path_or_content: true,
+        ..cfg
+    };
+    let err = run(&cfg, &inputs).unwrap_err();
+    assert!(err.contains("--path-or-content is not supported with --json=rg"));
+}
+
+// ============ STREAMING EMITTER TESTS ============
+
+#[test]
+fn test_streaming_matches_buffered_output() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("b.txt");
+    fs::write(&a, b"one hit\ntwo\n").unwrap();
+    fs::write(&b, b"hit again\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+    let cfg = create_json_config("hit");
+
+    let mut streamed = Vec::new();
+    let status = run_json_lines(&cfg, &inputs, &mut streamed).unwrap();
+    let buffered = run(&cfg, &inputs).unwrap();
+
+    assert_eq!(status, ExitStatus::MatchFound);
+    assert_eq!(String::from_utf8(streamed).unwrap(), buffered.output);
+}
+
+#[test]
+fn test_streaming_reports_unreadable_files_and_goes_on() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    fs::write(&a, b"hit\n").unwrap();
+    let missing = td.path().join("missing.txt");
+    let inputs = vec![
+        missing.to_string_lossy().to_string(),
+        a.to_string_lossy().to_string(),
+    ];
+
+    let mut streamed = Vec::new();
+    let status = run_json_lines(&create_json_config("hit"), &inputs, &mut streamed).unwrap();
+    let streamed = String::from_utf8(streamed).unwrap();
+    let lines: Vec<&str> = streamed.lines().collect();
+
+    assert_eq!(status, ExitStatus::MatchFound);
+    assert_eq!(
+        lines[0],
+        format!(
+            r#"{{"type":"error","path":"{}","message":"No such file or directory"}}"#,
+            inputs[0]
+        )
+    );
+    assert!(lines[1].starts_with(r#"{"type":"begin""#));
+    assert_eq!(
+        lines[4],
+        r#"{"type":"summary","files_searched":1,"files_with_matches":1,"matched_lines":1,"matches":1,"timeouts":0}"#
+    );
+}
+
+#[test]
+fn test_streaming_quiet_writes_nothing() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    fs::write(&a, b"hit\n").unwrap();
+    let mut cfg = create_json_config("hit");
+    cfg.quiet = true;
+
+    let mut streamed = Vec::new();
+    let status = run_json_lines(&cfg, &[a.to_string_lossy().to_string()], &mut streamed).unwrap();
+
+    assert_eq!(status, ExitStatus::MatchFound);
+    assert!(streamed.is_empty());
+}
+
+/// Writer whose reader goes away after `remaining` successful writes.
+struct ClosingPipe {
+    remaining: usize,
+    failed_writes: usize,
+}
+
+impl std::io::Write for ClosingPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            self.failed_writes += 1;
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        self.remaining -= 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_streaming_stops_when_pipe_closes() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    fs::write(&a, "hit\n".repeat(1000)).unwrap();
+    let cfg = create_json_config("hit");
+
+    let mut pipe = ClosingPipe {
+        remaining: 3,
+        failed_writes: 0,
+    };
+    let res = run_json_lines(&cfg, &[a.to_string_lossy().to_string()], &mut pipe);
+
+    assert!(res.is_err());
+    // The search stops at the first failed write instead of emitting the remaining events
+    assert_eq!(pipe.failed_writes, 1);
+}
@@ -0,0 +1,279 @@
+#![cfg(feature = "files")]
+
+use rgrep::{ColorChoice, Config, MergeTies, run};
+use std::fs;
+
+fn create_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        // Source tags are only added when lines carry no filename prefix
+        with_filename: Some(false),
+        ..Default::default()
+    }
+}
+
+fn write_logs(root: &std::path::Path, files: &[(&str, &str)]) -> Vec<String> {
+    files
+        .iter()
+        .map(|(name, content)| {
+            let p = root.join(name);
+            fs::write(&p, content).unwrap();
+            p.to_string_lossy().to_string()
+        })
+        .collect()
+}
+
+// ============ CHRONOLOGICAL MERGE TESTS ============
+
+#[test]
+fn test_merge_interleaves_by_timestamp_with_source_tags() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            (
+                "api.log",
+                "2025-01-01 10:00:00 api start\n2025-01-01 10:00:02 api done\n",
+            ),
+            ("web.log", "2025-01-01 10:00:01 web start\n"),
+        ],
+    );
+
+    let result = run(&create_config("start|done"), &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "[api.log] 1:2025-01-01 10:00:00 api start",
+            "[web.log] 1:2025-01-01 10:00:01 web start",
+            "[api.log] 2:2025-01-01 10:00:02 api done",
+        ]
+    );
+}
+
+#[test]
+fn test_merge_with_filename_prefixes_skips_source_tags() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            ("api.log", "2025-01-01 10:00:02 api done\n"),
+            ("web.log", "2025-01-01 10:00:01 web start\n"),
+        ],
+    );
+
+    let mut cfg = create_config("start|done");
+    cfg.with_filename = None;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            format!("{}:1:2025-01-01 10:00:01 web start", inputs[1]),
+            format!("{}:1:2025-01-01 10:00:02 api done", inputs[0]),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_ties_input_order() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            ("b.log", "2025-01-01 10:00:00 from b\n"),
+            ("a.log", "2025-01-01 10:00:00 from a\n"),
+        ],
+    );
+
+    let result = run(&create_config("from"), &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert!(lines[0].starts_with("[b.log]"));
+    assert!(lines[1].starts_with("[a.log]"));
+}
+
+#[test]
+fn test_merge_ties_by_path() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            ("b.log", "2025-01-01 10:00:00 from b\n"),
+            ("a.log", "2025-01-01 10:00:00 from a\n"),
+        ],
+    );
+
+    let mut cfg = create_config("from");
+    cfg.merge_ties = MergeTies::Path;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert!(lines[0].starts_with("[a.log]"));
+    assert!(lines[1].starts_with("[b.log]"));
+}
+
+#[test]
+fn test_merge_ties_stable_keeps_current_source() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            ("a.log", "2025-01-01 10:00:01 a tie\n"),
+            (
+                "b.log",
+                "2025-01-01 10:00:00 b first\n2025-01-01 10:00:01 b tie\n",
+            ),
+        ],
+    );
+
+    let mut cfg = create_config("tie|first");
+    cfg.merge_ties = MergeTies::Stable;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert!(lines[0].contains("b first"));
+    assert!(lines[1].contains("b tie"));
+    assert!(lines[2].contains("a tie"));
+}
+
+// ============ PARTIAL MERGE TESTS ============
+
+#[test]
+fn test_merge_keeps_untimestamped_lines_with_predecessor() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            (
+                "api.log",
+                "2025-01-01 10:00:00 api error\n  at frame one\n2025-01-01 10:00:02 api error again\n",
+            ),
+            ("web.log", "2025-01-01 10:00:01 web error\n"),
+        ],
+    );
+
+    let result = run(&create_config("error|frame"), &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "[api.log] 1:2025-01-01 10:00:00 api error",
+            "[api.log] 2:  at frame one",
+            "[web.log] 1:2025-01-01 10:00:01 web error",
+            "[api.log] 3:2025-01-01 10:00:02 api error again",
+        ]
+    );
+    assert!(result.warnings.is_empty());
+}
+
+#[test]
+fn test_merge_appends_untimestamped_files_with_warning() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            ("plain.txt", "an error without time\n"),
+            ("api.log", "2025-01-01 10:00:00 api error\n"),
+        ],
+    );
+
+    let result = run(&create_config("error"), &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "[api.log] 1:2025-01-01 10:00:00 api error",
+            "[plain.txt] 1:an error without time",
+        ]
+    );
+    assert_eq!(result.warnings.len(), 1);
+    assert!(result.warnings[0].contains("plain.txt"));
+}
+
+#[test]
+fn test_no_timestamps_anywhere_concatenates() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path(), &[("a.txt", "x error\n"), ("b.txt", "y error\n")]);
+
+    let result = run(&create_config("error"), &inputs).unwrap();
+
+    assert_eq!(result.output, "1:x error\n1:y error\n");
+    assert!(result.warnings.is_empty());
+}
+
+// ============ TIMEZONE TESTS ============
+
+#[test]
+fn test_merge_honors_utc_offsets() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            // 10:30 at +02:00 is 08:30 UTC
+            ("berlin.log", "2025-01-01 10:30:00 +02:00 berlin event\n"),
+            // 09:00Z is later than 08:30 UTC despite the smaller wall-clock time
+            ("utc.log", "2025-01-01T09:00:00Z utc event\n"),
+        ],
+    );
+
+    let result = run(&create_config("event"), &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert!(lines[0].contains("berlin event"));
+    assert!(lines[1].contains("utc event"));
+}
+
+#[test]
+fn test_merge_assume_tz_for_naive_timestamps() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(
+        td.path(),
+        &[
+            ("naive.log", "2025-01-01 10:00:00 naive event\n"),
+            ("offset.log", "2025-01-01 09:30:00+0000 offset event\n"),
+        ],
+    );
+
+    // Without an assumption, naive timestamps are UTC and sort after 09:30Z
+    let result = run(&create_config("event"), &inputs).unwrap();
+    assert!(
+        result
+            .output
+            .lines()
+            .next()
+            .unwrap()
+            .contains("offset event")
+    );
+
+    // At +02:00, 10:00 local is 08:00 UTC and sorts first
+    let mut cfg = create_config("event");
+    cfg.assume_tz = rgrep::parse_utc_offset("+02:00");
+    let result = run(&cfg, &inputs).unwrap();
+    assert!(
+        result
+            .output
+            .lines()
+            .next()
+            .unwrap()
+            .contains("naive event")
+    );
+}
+
+#[test]
+fn test_parse_utc_offset_forms() {
+    assert_eq!(rgrep::parse_utc_offset("Z"), Some(0));
+    assert_eq!(rgrep::parse_utc_offset("utc"), Some(0));
+    assert_eq!(rgrep::parse_utc_offset("+02:00"), Some(7200));
+    assert_eq!(
+        rgrep::parse_utc_offset("-0530"),
+        Some(-(5 * 3600 + 30 * 60))
+    );
+    assert_eq!(rgrep::parse_utc_offset("+05"), Some(5 * 3600));
+    assert_eq!(rgrep::parse_utc_offset("Europe/Berlin"), None);
+}
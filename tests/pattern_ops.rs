@@ -1,11 +1,11 @@
-use rgrep::{Config, ExitStatus, run_on_reader};
+use rgrep::{ColorChoice, Config, ExitStatus, run_on_reader};
 use std::io::Cursor;
 
 #[test]
 fn or_operator_matches_either() {
     let mut cfg = Config::default();
     cfg.patterns = vec!["foo|bar".to_string()];
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
     let data = "one\nbar baz\nfizz\nfoo qux\n";
     let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
     assert_eq!(res.status, ExitStatus::MatchFound);
@@ -18,7 +18,7 @@ fn or_operator_matches_either() {
 fn and_operator_requires_both() {
     let mut cfg = Config::default();
     cfg.patterns = vec!["foo&bar".to_string()];
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
     let data = "foo only\nbar only\nfoo and bar\nfoobar together\n";
     let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
     assert_eq!(res.status, ExitStatus::MatchFound);
@@ -29,3 +29,311 @@ fn and_operator_requires_both() {
     assert!(!res.output.contains("foo only"));
     assert!(!res.output.contains("bar only"));
 }
+
+#[test]
+fn not_operator_excludes_lines() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["error&!(timeout|retry)".to_string()];
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "error timeout\nerror disk\nerror retry\nok\n";
+    let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    assert_eq!(res.output, "error disk\n");
+}
+
+#[test]
+fn not_operator_binds_tighter_than_and_and_or() {
+    let mut cfg = Config::default();
+    // disk|((!error)&(!ok))
+    cfg.patterns = vec!["disk|!error&!ok".to_string()];
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "error disk\nerror\nok\nother\n";
+    let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    assert_eq!(res.output, "error disk\nother\n");
+}
+
+#[test]
+fn not_operator_is_literal_inside_a_pattern_or_escaped() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["wow!".to_string()];
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let res = run_on_reader(&cfg, Cursor::new("wow!\nwow\n"), None).unwrap();
+    assert_eq!(res.output, "wow!\n");
+
+    cfg.patterns = vec![r"\!bang".to_string()];
+    let res = run_on_reader(&cfg, Cursor::new("!bang\nbang\n"), None).unwrap();
+    assert_eq!(res.output, "!bang\n");
+}
+
+#[test]
+fn negated_terms_are_not_highlighted() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["error&!(disk&full)".to_string()];
+    cfg.color = ColorChoice::Always;
+    cfg.line_number = false;
+    let res = run_on_reader(&cfg, Cursor::new("error disk\n"), None).unwrap();
+    assert_eq!(res.output, "\x1b[1;31merror\x1b[0m disk\n");
+}
+
+#[test]
+fn near_operator_matches_within_distance() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["connect~2~timeout".to_string()];
+    cfg.color = ColorChoice::Never;
+    let data = "connect a\nx\ntimeout 1\ny\nz\nw\ntimeout 2\n";
+    let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    // The second timeout is too far from the connect line
+    assert_eq!(res.output, "1:connect a\n3:timeout 1\n");
+
+    cfg.patterns = vec!["connect~1~timeout".to_string()];
+    let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    assert_eq!(res.status, ExitStatus::NoMatch);
+}
+
+#[test]
+fn near_operator_combines_with_other_operators() {
+    let mut cfg = Config::default();
+    // (a~1~b)|c, with !d applied to the NEAR side
+    cfg.patterns = vec!["a~1~!d|c".to_string()];
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "a\nd\nq\nc\n";
+    let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    // "a" is next to "d" only (and is itself not "d"), "q" is not next to "a"
+    assert_eq!(res.output, "a\nc\n");
+}
+
+#[test]
+fn escaped_operators_are_literal() {
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "a&b\na|b\nf(x)\nab\n";
+    for (pattern, expected) in [
+        (r"a\&b", "a&b\n"),
+        (r"a\|b", "a|b\n"),
+        (r"f\(x\)", "f(x)\n"),
+        (r"a\&b|f\(x\)", "a&b\nf(x)\n"),
+    ] {
+        cfg.patterns = vec![pattern.to_string()];
+        let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+        assert_eq!(res.output, expected, "pattern {}", pattern);
+    }
+}
+
+#[test]
+fn quoted_terms_are_literal_text() {
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "a&b\na|b\nf(\nab\n";
+    for (pattern, expected) in [
+        ("\"a&b\"", "a&b\n"),
+        ("\"a&b\" | \"f(\"", "a&b\nf(\n"),
+        ("\"a|b\"&!\"a&b\"", "a|b\n"),
+    ] {
+        cfg.patterns = vec![pattern.to_string()];
+        let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+        assert_eq!(res.output, expected, "pattern {}", pattern);
+    }
+}
+
+#[test]
+fn quotes_without_operators_are_part_of_the_pattern() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["\"level\":\"error\"".to_string()];
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "{\"level\":\"error\"}\nlevel:error\n";
+    let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    assert_eq!(res.output, "{\"level\":\"error\"}\n");
+}
+
+#[test]
+fn groups_followed_by_text_are_regex_groups() {
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "red car\nred bike\nblue car\ngreen car\n";
+    for (pattern, expected) in [
+        ("(red|blue) car", "red car\nblue car\n"),
+        ("x|(red|blue) car&!blue", "red car\n"),
+        ("car&(red|green)", "red car\ngreen car\n"),
+    ] {
+        cfg.patterns = vec![pattern.to_string()];
+        let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+        assert_eq!(res.output, expected, "pattern {}", pattern);
+    }
+}
+
+#[test]
+fn count_thresholds_count_occurrences_per_line() {
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "retry retry retry\nretry once\nretry retry\nabc aa\n";
+    for (pattern, expected) in [
+        ("retry{>=3}", "retry retry retry\n"),
+        ("retry{==2}|abc", "retry retry\nabc aa\n"),
+        ("retry{ > 1 }&!once", "retry retry retry\nretry retry\n"),
+        ("!retry{<2}&retry", "retry retry retry\nretry retry\n"),
+        ("\"c a\"{<1}&a{2}", "abc aa\n"),
+        // Regex repetitions are not thresholds
+        ("a{2}", "abc aa\n"),
+    ] {
+        cfg.patterns = vec![pattern.to_string()];
+        let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+        assert_eq!(res.output, expected, "pattern {}", pattern);
+    }
+}
+
+#[cfg(feature = "fancy")]
+#[test]
+fn fancy_engine_supports_backreferences_and_lookaround() {
+    use rgrep::Engine;
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    cfg.engine = Engine::Fancy;
+    let data = "testtest\ntest\nfoobar\nfoobaz\n";
+    for (pattern, expected) in [
+        (r"(test)\1", "testtest\n"),
+        ("foo(?!bar)", "foobaz\n"),
+        // Operators are regex syntax with the fancy engine
+        ("(?<=foo)baz|^test$", "test\nfoobaz\n"),
+    ] {
+        cfg.patterns = vec![pattern.to_string()];
+        let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+        assert_eq!(res.output, expected, "pattern {}", pattern);
+    }
+}
+
+#[cfg(not(feature = "fancy"))]
+#[test]
+fn fancy_engine_needs_its_feature() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec![r"(test)\1".to_string()];
+    cfg.engine = rgrep::Engine::Fancy;
+    let err = run_on_reader(&cfg, Cursor::new("testtest\n"), None).unwrap_err();
+    assert!(err.contains("`fancy` feature"), "{}", err);
+}
+
+#[cfg(feature = "script")]
+#[test]
+fn filter_script_keeps_lines_it_returns_true_for() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("slow.rhai");
+    std::fs::write(
+        &script,
+        "captures[1].parse_int() > 500 && !line.contains(\"/health\") && line_number > 1",
+    )
+    .unwrap();
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.patterns = vec![r"took (\d+)ms".to_string()];
+    cfg.filter_script = Some(script.to_string_lossy().to_string());
+    let data = "GET /a took 900ms\nGET /b took 700ms\nGET /health took 800ms\nGET /c took 20ms\n";
+    let res = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(res.output, "2:GET /b took 700ms\n");
+
+    cfg.filter_script = Some(
+        dir.path()
+            .join("missing.rhai")
+            .to_string_lossy()
+            .to_string(),
+    );
+    let err = run_on_reader(&cfg, Cursor::new(data), None).unwrap_err();
+    assert!(err.contains("cannot read filter script"), "{}", err);
+}
+
+#[cfg(not(feature = "script"))]
+#[test]
+fn filter_script_needs_its_feature() {
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["x".to_string()];
+    cfg.filter_script = Some("keep.rhai".to_string());
+    let err = run_on_reader(&cfg, Cursor::new("x\n"), None).unwrap_err();
+    assert!(err.contains("`script` feature"), "{}", err);
+}
+
+#[test]
+fn literal_and_basic_syntax_disable_operators() {
+    use rgrep::Syntax;
+    let mut cfg = Config::default();
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let data = "a&b (x)\nab\nfoo.bar\nfooxbar\nf(x)|g\n";
+    for (syntax, pattern, expected) in [
+        (Syntax::Literal, "a&b (x)", "a&b (x)\n"),
+        (Syntax::Literal, "foo.bar", "foo.bar\n"),
+        (Syntax::Basic, r"\(a\|x\)b", "ab\nfooxbar\n"),
+        (Syntax::Basic, "f(x)|g", "f(x)|g\n"),
+        (Syntax::Basic, r"o\{2\}[.]", "foo.bar\n"),
+        (Syntax::Extended, "b&x", "a&b (x)\nfooxbar\n"),
+    ] {
+        cfg.syntax = syntax;
+        cfg.patterns = vec![pattern.to_string()];
+        let res = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+        assert_eq!(res.output, expected, "{:?} pattern {}", syntax, pattern);
+    }
+}
+
+#[test]
+fn plain_patterns_are_matched_as_literals() {
+    use rgrep::{Matcher, Matching, Syntax};
+    let matching = |cfg: &Config| Matcher::new(cfg).unwrap().matching().chosen;
+    let mut cfg = Config::default();
+    cfg.patterns = vec![r"v1\.2 beta?".to_string()];
+    assert_eq!(matching(&cfg), Matching::Regex);
+    cfg.patterns = vec![r"v1\.2 beta".to_string()];
+    assert_eq!(matching(&cfg), Matching::Literal);
+    cfg.syntax = Syntax::Literal;
+    cfg.patterns = vec!["v1.2 (beta)".to_string()];
+    assert_eq!(matching(&cfg), Matching::Literal);
+    cfg.case_insensitive = true;
+    assert_eq!(matching(&cfg), Matching::Literal);
+    cfg.patterns = vec!["café".to_string()];
+    assert_eq!(matching(&cfg), Matching::Regex);
+
+    // Both strategies find and highlight the same text
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["ab".to_string()];
+    cfg.color = ColorChoice::Always;
+    let data = "abab xab\nba\nab\n";
+    let literal = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    cfg.matching = Some(Matching::Regex);
+    let regex = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    assert_eq!(literal.output, regex.output);
+    assert_eq!(literal.files, regex.files);
+}
+
+#[test]
+fn case_insensitive_literals_match_like_the_regex_engine() {
+    use rgrep::Matching;
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["Disk Full".to_string()];
+    cfg.case_insensitive = true;
+    cfg.color = ColorChoice::Always;
+    // The Kelvin sign folds to `k`, and the long s to `s`
+    let data = "DISK FULL\ndisk full: disk FULL\ndis\u{212A} full\n\u{17F}disk full\ndisk ful\n";
+    let literal = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    cfg.matching = Some(Matching::Regex);
+    let regex = run_on_reader(&cfg, Cursor::new(data.as_bytes()), None).unwrap();
+    assert_eq!(literal.output, regex.output);
+    assert_eq!(literal.files[0].matches.len(), 4);
+}
+
+#[test]
+fn literal_strategy_rejects_regex_patterns() {
+    use rgrep::{Matcher, Matching};
+    let mut cfg = Config::default();
+    cfg.patterns = vec!["err.r".to_string()];
+    cfg.matching = Some(Matching::Literal);
+    let err = Matcher::new(&cfg).err().unwrap();
+    assert!(err.contains("--strategy literal"), "{}", err);
+    cfg.patterns = vec!["error".to_string()];
+    let matcher = Matcher::new(&cfg).unwrap();
+    assert!(matcher.matching().forced);
+}
@@ -0,0 +1,251 @@
+#![cfg(feature = "files")]
+
+use rgrep::batch::{Query, QueryFormat, parse_queries};
+use rgrep::{ColorChoice, Config, ExitStatus, run};
+use std::fs;
+
+fn create_config(queries: &[&str]) -> Config {
+    Config {
+        queries: queries.iter().map(|q| Query::new(*q)).collect(),
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+fn write_logs(dir: &std::path::Path) -> Vec<String> {
+    let a = dir.join("a.log");
+    let b = dir.join("b.log");
+    fs::write(&a, "GET /a took 5ms\nPOST /b error\ntimeout here\n").unwrap();
+    fs::write(&b, "error again\nnothing\n").unwrap();
+    vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ]
+}
+
+// ============ QUERIES FILE PARSING TESTS ============
+
+#[test]
+fn test_parse_queries_skips_blank_lines_and_comments() {
+    let queries = parse_queries("# comment\nerror\n\n   \ntimeout|slow\r\nGET&ms\n").unwrap();
+    let patterns: Vec<&str> = queries.iter().map(|q| q.pattern.as_str()).collect();
+    assert_eq!(patterns, vec!["error", "timeout|slow", "GET&ms"]);
+}
+
+#[test]
+fn test_parse_job_specs() {
+    let text = "name=errors; pattern=ERROR&retry; out=errors.jsonl\n\
+                pattern=took (\\d+)ms;out=slow.log;format=json;\n\
+                user=alice\n";
+    let queries = parse_queries(text).unwrap();
+    assert_eq!(
+        queries[0],
+        Query {
+            pattern: "ERROR&retry".into(),
+            name: Some("errors".into()),
+            out: Some("errors.jsonl".into()),
+            format: QueryFormat::Json,
+        }
+    );
+    assert_eq!(queries[1].pattern, r"took (\d+)ms");
+    assert_eq!(queries[1].format, QueryFormat::Json);
+    assert_eq!(queries[1].label(), r"took (\d+)ms");
+    // Unknown keys make the line a plain pattern
+    assert_eq!(queries[2], Query::new("user=alice"));
+
+    let err = parse_queries("error\nname=x; out=x.txt\n").unwrap_err();
+    assert!(err.starts_with("line 2:"), "{}", err);
+    assert!(parse_queries("pattern=x; format=xml\n").is_err());
+}
+
+// ============ BATCH SEARCH TESTS ============
+
+#[test]
+fn test_results_grouped_per_query() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+    let (a, b) = (&inputs[0], &inputs[1]);
+
+    let cfg = create_config(&["error", "missing", "timeout"]);
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(res.status, ExitStatus::MatchFound);
+    assert_eq!(
+        res.output,
+        format!(
+            "query: error\n{a}:2:POST /b error\n{b}:1:error again\n\nquery: timeout\n{a}:3:timeout here\n"
+        )
+    );
+}
+
+#[test]
+fn test_and_queries_combined_with_plain_ones() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+
+    let mut cfg = create_config(&["GET&ms", "ERROR"]);
+    cfg.case_insensitive = true;
+    cfg.with_filename = Some(false);
+    cfg.line_number = false;
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(
+        res.output,
+        "query: GET&ms\nGET /a took 5ms\n\nquery: ERROR\nPOST /b error\nerror again\n"
+    );
+}
+
+#[test]
+fn test_count_per_query() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+
+    let mut cfg = create_config(&["error", "missing"]);
+    cfg.count = true;
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(
+        res.output,
+        "QUERY    COUNT\nerror        2\nmissing      0\n"
+    );
+
+    cfg.table = false;
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(res.output, "error:2\nmissing:0\n");
+}
+
+#[test]
+fn test_invert_and_no_match_status() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+
+    let mut cfg = create_config(&["missing"]);
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(res.status, ExitStatus::NoMatch);
+    assert!(res.output.is_empty());
+
+    cfg.invert = true;
+    cfg.count = true;
+    cfg.table = false;
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(res.status, ExitStatus::MatchFound);
+    assert_eq!(res.output, "missing:5\n");
+}
+
+#[test]
+fn test_invalid_query_is_reported() {
+    let cfg = create_config(&["ok", "bad["]);
+    let err = run(&cfg, &["-".to_string()]).expect_err("invalid query");
+    assert!(err.contains("query 'bad['"), "{}", err);
+}
+
+#[test]
+fn test_near_queries_see_neighboring_lines() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+    let a = &inputs[0];
+
+    let cfg = create_config(&["GET~1~error", "GET~2~timeout"]);
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(
+        res.output,
+        format!(
+            "query: GET~1~error\n{a}:1:GET /a took 5ms\n{a}:2:POST /b error\n\n\
+             query: GET~2~timeout\n{a}:1:GET /a took 5ms\n{a}:3:timeout here\n"
+        )
+    );
+}
+
+// ============ PER-QUERY OUTPUT TESTS ============
+
+#[test]
+fn test_queries_written_to_their_own_files() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+    let (a, b) = (&inputs[0], &inputs[1]);
+    let text_out = td.path().join("errors.txt");
+    let json_out = td.path().join("timeouts.jsonl");
+
+    let text = format!(
+        "name=errors; pattern=error; out={}\nname=timeouts; pattern=timeout; out={}\nGET\n",
+        text_out.display(),
+        json_out.display()
+    );
+    let mut cfg = create_config(&[]);
+    cfg.queries = parse_queries(&text).unwrap();
+    cfg.color = ColorChoice::Always;
+
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(res.status, ExitStatus::MatchFound);
+    // Only the query without a file is printed
+    assert!(res.output.starts_with("query: GET\n"), "{}", res.output);
+    assert!(!res.output.contains("error"));
+
+    // Files never contain color codes
+    assert_eq!(
+        fs::read_to_string(&text_out).unwrap(),
+        format!("{a}:2:POST /b error\n{b}:1:error again\n")
+    );
+    let json = fs::read_to_string(&json_out).unwrap();
+    let lines: Vec<&str> = json.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(r#"{"type":"match","#));
+    assert!(lines[0].contains(r#""text":"timeout here""#));
+    assert_eq!(
+        lines[1],
+        r#"{"type":"summary","files_searched":2,"files_with_matches":1,"matched_lines":1,"matches":1,"timeouts":0}"#
+    );
+}
+
+#[test]
+fn test_query_json_summaries_count_matches_when_inverted_or_counting() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("a.log");
+    fs::write(&log, "abc abc\nxyz\nnothing\n").unwrap();
+    let inputs = vec![log.to_string_lossy().to_string()];
+    let out = td.path().join("a.jsonl");
+    let summary = |cfg: &Config| {
+        run(cfg, &inputs).unwrap();
+        let json = fs::read_to_string(&out).unwrap();
+        json.lines().last().unwrap().to_string()
+    };
+
+    let mut cfg = create_config(&[]);
+    cfg.queries = parse_queries(&format!(
+        "pattern=abc; out={}; format=json\n",
+        out.display()
+    ))
+    .unwrap();
+    // Every match of a line counts, in count mode too
+    assert!(summary(&cfg).ends_with(r#""matched_lines":1,"matches":2,"timeouts":0}"#));
+    cfg.count = true;
+    assert!(summary(&cfg).ends_with(r#""matched_lines":1,"matches":2,"timeouts":0}"#));
+    // Lines selected with -v have no matched text, and count once each
+    cfg.invert = true;
+    assert!(summary(&cfg).ends_with(r#""matched_lines":2,"matches":2,"timeouts":0}"#));
+    cfg.count = false;
+    assert!(summary(&cfg).ends_with(r#""matched_lines":2,"matches":2,"timeouts":0}"#));
+}
+
+#[test]
+fn test_query_files_with_count_and_quiet() {
+    let td = tempfile::tempdir().unwrap();
+    let inputs = write_logs(td.path());
+    let out = td.path().join("errors.txt");
+
+    let mut cfg = create_config(&["GET"]);
+    let mut errors = Query::new("error");
+    errors.name = Some("errors".into());
+    errors.out = Some(out.to_string_lossy().to_string());
+    cfg.queries.push(errors);
+
+    cfg.count = true;
+    cfg.table = false;
+    let res = run(&cfg, &inputs).unwrap();
+    assert_eq!(res.output, "GET:1\n");
+    assert_eq!(fs::read_to_string(&out).unwrap(), "2\n");
+
+    // Quiet only silences stdout
+    cfg.count = false;
+    cfg.quiet = true;
+    let res = run(&cfg, &inputs).unwrap();
+    assert!(res.output.is_empty());
+    assert_eq!(fs::read_to_string(&out).unwrap().lines().count(), 2);
+}
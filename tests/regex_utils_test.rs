@@ -1,4 +1,4 @@
-use rgrep::{Config, run_on_reader};
+use rgrep::{ColorChoice, Config, run_on_reader};
 use std::io::Cursor;
 
 fn create_config(patterns: Vec<&str>) -> Config {
@@ -11,7 +11,7 @@ fn create_config(patterns: Vec<&str>) -> Config {
 #[test]
 fn test_simple_or_pattern() {
     let mut cfg = create_config(vec!["foo|bar"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "foo\nbaz\nbar\nqux";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -25,7 +25,7 @@ fn test_simple_or_pattern() {
 #[test]
 fn test_simple_and_pattern() {
     let mut cfg = create_config(vec!["foo&bar"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "foo bar\nfoo\nbar\nfoo bar baz";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -41,7 +41,7 @@ fn test_simple_and_pattern() {
 #[test]
 fn test_complex_boolean_with_parentheses() {
     let mut cfg = create_config(vec!["(foo)|(bar&baz)"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "foo\nbar\nbaz\nbar baz\nfoo bar";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -54,7 +54,7 @@ fn test_complex_boolean_with_parentheses() {
 #[test]
 fn test_nested_parentheses() {
     let mut cfg = create_config(vec!["(a&(b|c))"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "a b\na c\na d\nb c";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -67,7 +67,7 @@ fn test_nested_parentheses() {
 fn test_word_boundary_flag() {
     let mut cfg = create_config(vec!["test"]);
     cfg.word = true;
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "test\ntesting\ntest word\ncontest";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -82,7 +82,7 @@ fn test_word_boundary_flag() {
 fn test_line_match_flag() {
     let mut cfg = create_config(vec!["test"]);
     cfg.line = true;
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "test\ntest word\nword test\ntest";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -95,7 +95,7 @@ fn test_line_match_flag() {
 fn test_case_insensitive_with_or() {
     let mut cfg = create_config(vec!["FOO|bar"]);
     cfg.case_insensitive = true;
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "foo\nBAR\nFOO\nbar\nbaz";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -112,7 +112,7 @@ fn test_case_insensitive_with_or() {
 fn test_case_insensitive_with_and() {
     let mut cfg = create_config(vec!["HELLO&WORLD"]);
     cfg.case_insensitive = true;
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "hello world\nHELLO WORLD\nHello World\nhello\nworld";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -129,7 +129,7 @@ fn test_case_insensitive_with_and() {
 fn test_dotall_flag() {
     let mut cfg = create_config(vec!["a.b"]);
     cfg.dotall = true;
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     // With dotall, . can match any character including special ones
     // Since we read line-by-line, test that dotall flag is accepted and works within a line
@@ -142,7 +142,7 @@ fn test_dotall_flag() {
 #[test]
 fn test_escaped_special_chars_in_pattern() {
     let mut cfg = create_config(vec![r"test\(value\)"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "test(value)\ntest value\ntest";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -154,7 +154,7 @@ fn test_escaped_special_chars_in_pattern() {
 #[test]
 fn test_regex_special_chars() {
     let mut cfg = create_config(vec![r"\d+"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "abc\n123\ntest456\nno numbers";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -180,7 +180,7 @@ fn test_empty_pattern_error() {
 #[test]
 fn test_multiple_and_operators() {
     let mut cfg = create_config(vec!["foo&bar&baz"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "foo bar baz\nfoo bar\nbar baz\nfoo baz";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -196,7 +196,7 @@ fn test_multiple_and_operators() {
 #[test]
 fn test_multiple_or_operators() {
     let mut cfg = create_config(vec!["foo|bar|baz"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "foo\nbar\nbaz\nqux";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -212,7 +212,7 @@ fn test_multiple_or_operators() {
 #[test]
 fn test_pattern_with_spaces() {
     let mut cfg = create_config(vec!["hello world"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "hello world\nhello\nworld\nhello  world";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -224,7 +224,7 @@ fn test_pattern_with_spaces() {
 #[test]
 fn test_boolean_with_spaces_in_patterns() {
     let mut cfg = create_config(vec!["(hello world)|(foo bar)"]);
-    cfg.color = false;
+    cfg.color = ColorChoice::Never;
 
     let data = "hello world\nfoo bar\nhello\nfoo\ntest";
     let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
@@ -236,3 +236,54 @@ fn test_boolean_with_spaces_in_patterns() {
         "Should match lines with 'hello world' or 'foo bar'"
     );
 }
+
+#[test]
+fn test_ident_pattern_matches_whole_identifiers() {
+    let pattern = rgrep::regex_utils::ident_pattern("foo", false);
+    let mut cfg = create_config(vec![&pattern]);
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    cfg.case_insensitive = true;
+
+    let data = "foo(1)\nfoo_bar\nfoo2\nFoo\nx.foo\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "foo(1)\nx.foo\n");
+}
+
+#[test]
+fn test_ident_pattern_with_punctuation_and_qualified_paths() {
+    let pattern = rgrep::regex_utils::ident_pattern("$var", false);
+    let mut cfg = create_config(vec![&pattern]);
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+    let result = run_on_reader(&cfg, Cursor::new("$var = 1\na$var\n$vars\n"), None).unwrap();
+    assert_eq!(result.output, "$var = 1\n");
+
+    let pattern = rgrep::regex_utils::ident_pattern("take", true);
+    let mut cfg = create_config(vec![&pattern]);
+    cfg.color = ColorChoice::Always;
+    cfg.line_number = false;
+    let result = run_on_reader(&cfg, Cursor::new("std::mem::take(x)\nmistake\n"), None).unwrap();
+    assert_eq!(result.output, "\x1b[1;31mstd::mem::take\x1b[0m(x)\n");
+
+    // Without `qualified` the same line matches, with only the name highlighted
+    let pattern = rgrep::regex_utils::ident_pattern("take", false);
+    cfg.patterns = vec![pattern];
+    let result = run_on_reader(&cfg, Cursor::new("std::mem::take(x)\nmistake\n"), None).unwrap();
+    assert_eq!(result.output, "std::mem::\x1b[1;31mtake\x1b[0m(x)\n");
+}
+
+#[test]
+fn test_regex_groups_are_not_boolean_grouping() {
+    let mut cfg = create_config(vec!["(?:warn|err)or&disk"]);
+    cfg.color = ColorChoice::Never;
+    cfg.line_number = false;
+
+    let data = "error disk\nwarnor disk\nerror\ninfo disk\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "error disk\nwarnor disk\n");
+
+    cfg.patterns = vec!["(?i:ERR)&(x|y)".to_string()];
+    let result = run_on_reader(&cfg, Cursor::new("err x\nerr z\n"), None).unwrap();
+    assert_eq!(result.output, "err x\n");
+}
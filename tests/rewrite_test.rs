@@ -0,0 +1,124 @@
+#![cfg(feature = "files")]
+
+use rgrep::rewrite::{FileRewrite, rewrite};
+use rgrep::{Config, ExitStatus};
+use std::fs;
+
+fn create_config(pattern: &str, template: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        replace: Some(template.to_string()),
+        write: true,
+        ..Default::default()
+    }
+}
+
+// ============ REWRITE TESTS ============
+
+#[test]
+fn test_rewrite_replaces_in_place_with_backup() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.conf");
+    let b = td.path().join("b.conf");
+    fs::write(&a, "port=80\r\nhost=a\nport=8080").unwrap();
+    fs::write(&b, "nothing\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config(r"port=(\d+)", "port=${1}0");
+    cfg.backup_suffix = Some(".bak".to_string());
+    let result = rewrite(&cfg, &inputs).unwrap();
+
+    assert_eq!(result.status(), ExitStatus::MatchFound);
+    assert!(result.errors.is_empty());
+    assert_eq!(
+        result.files,
+        vec![FileRewrite {
+            path: inputs[0].clone(),
+            lines: 2,
+            replacements: 2,
+            backup: Some(format!("{}.bak", inputs[0])),
+            diff: None,
+        }]
+    );
+    // Line endings are kept, and unchanged files are not touched or backed up
+    assert_eq!(
+        fs::read_to_string(&a).unwrap(),
+        "port=800\r\nhost=a\nport=80800"
+    );
+    assert_eq!(
+        fs::read_to_string(td.path().join("a.conf.bak")).unwrap(),
+        "port=80\r\nhost=a\nport=8080"
+    );
+    assert!(!td.path().join("b.conf.bak").exists());
+    // No temporary files are left behind
+    assert_eq!(fs::read_dir(td.path()).unwrap().count(), 3);
+}
+
+#[test]
+fn test_rewrite_dry_run_writes_nothing() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    fs::write(&a, "foo foo\nbar\n").unwrap();
+
+    let mut cfg = create_config("foo", "baz");
+    cfg.dry_run = true;
+    cfg.backup_suffix = Some(".orig".to_string());
+    let result = rewrite(&cfg, &[a.to_string_lossy().to_string()]).unwrap();
+
+    assert_eq!(result.files[0].replacements, 2);
+    assert_eq!(result.files[0].lines, 1);
+    assert_eq!(fs::read_to_string(&a).unwrap(), "foo foo\nbar\n");
+    assert_eq!(fs::read_dir(td.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_rewrite_reports_files_it_cannot_edit() {
+    let td = tempfile::tempdir().unwrap();
+    let latin1 = td.path().join("latin1.txt");
+    let ok = td.path().join("ok.txt");
+    fs::write(&latin1, b"caf\xe9 foo\n").unwrap();
+    fs::write(&ok, "foo\n").unwrap();
+    let inputs = vec![
+        latin1.to_string_lossy().to_string(),
+        ok.to_string_lossy().to_string(),
+    ];
+
+    let result = rewrite(&create_config("foo", "bar"), &inputs).unwrap();
+
+    assert_eq!(result.errors.len(), 1);
+    assert!(result.errors[0].starts_with(&inputs[0]));
+    assert_eq!(fs::read(&latin1).unwrap(), b"caf\xe9 foo\n");
+    assert_eq!(fs::read_to_string(&ok).unwrap(), "bar\n");
+
+    let err = rewrite(&create_config("foo", "bar"), &[]).unwrap_err();
+    assert!(err.contains("standard input"));
+}
+
+// ============ DIFF TESTS ============
+
+#[test]
+fn test_diff_previews_changes_without_writing() {
+    let td = tempfile::tempdir().unwrap();
+    let path = td.path().join("app.conf");
+    let text = "port=80\na\nb\nc\nd\ne\nf\ng\nh\nport=81";
+    fs::write(&path, text).unwrap();
+    let inputs = vec![path.to_string_lossy().to_string()];
+
+    let mut cfg = create_config(r"port=(\d+)", "port=${1}0");
+    cfg.write = false;
+    cfg.diff = true;
+    let result = rewrite(&cfg, &inputs).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    let name = &inputs[0];
+    let expected = format!(
+        "--- {name}\n+++ {name}\n\
+         @@ -1,4 +1,4 @@\n-port=80\n+port=800\n a\n b\n c\n\
+         @@ -7,4 +7,4 @@\n f\n g\n h\n-port=81\n\\ No newline at end of file\n+port=810\n\\ No newline at end of file\n"
+    );
+    assert_eq!(result.files[0].diff.as_deref(), Some(expected.as_str()));
+    assert_eq!(result.files[0].lines, 2);
+}
@@ -0,0 +1,435 @@
+#![cfg(feature = "files")]
+
+use rgrep::self_check::self_check;
+use rgrep::{
+    ColorChoice, Config, ExitStatus, JsonSchema, Match, RunResult, Sink, run, run_detailed,
+    run_on_reader, run_with_sink, search_iter,
+};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::time::Duration;
+
+fn create_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+// ============ STRUCTURED MATCH TESTS ============
+
+#[test]
+fn test_run_result_lists_matches_per_file() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "error one\nok\n").unwrap();
+    fs::write(&b, "ok\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let result = run(&create_config("error"), &inputs).unwrap();
+    assert_eq!(result.files.len(), 2);
+    assert_eq!(result.files[0].path.as_deref(), Some(inputs[0].as_str()));
+    assert_eq!(result.files[0].matched_lines, 1);
+    let m = &result.files[0].matches[0];
+    assert_eq!((m.line_number, m.line.as_str()), (1, "error one"));
+    assert_eq!(m.spans, vec![0..5]);
+    assert!(result.files[1].matches.is_empty());
+}
+
+#[test]
+fn test_run_result_matches_exclude_context_lines() {
+    let mut cfg = create_config("b");
+    cfg.context.before = 1;
+
+    let result = run_on_reader(&cfg, Cursor::new("a\nb\n"), None).unwrap();
+    assert_eq!(result.files.len(), 1);
+    assert_eq!(result.files[0].path, None);
+    let lines: Vec<&str> = result.files[0]
+        .matches
+        .iter()
+        .map(|m| m.line.as_str())
+        .collect();
+    assert_eq!(lines, vec!["b"]);
+}
+
+#[test]
+fn test_matches_attribute_and_terms() {
+    let result = run_on_reader(
+        &create_config("GET&ms"),
+        Cursor::new("GET /a took 5ms\n"),
+        None,
+    )
+    .unwrap();
+    assert_eq!(result.files[0].matches[0].terms, vec!["GET", "ms"]);
+}
+
+#[test]
+fn test_match_timeout_skips_slow_lines() {
+    let mut cfg = create_config(r"(\w+\s*){3}x&y");
+    cfg.line_number = false;
+    // No line can be matched within a zero budget
+    cfg.match_timeout_ms = Some(0);
+    let data = format!("a b c x y\n{}\n", "word ".repeat(10_000));
+
+    let result = run_on_reader(&cfg, Cursor::new(data.as_str()), None).unwrap();
+    assert_eq!(result.status, ExitStatus::NoMatch);
+    let skipped: Vec<usize> = result.files[0]
+        .timeouts
+        .iter()
+        .map(|t| t.line_number)
+        .collect();
+    assert_eq!(skipped, vec![1, 2]);
+    assert!(result.warnings[1].starts_with("(standard input):2: match timed out after"));
+
+    // Skipped lines are not inverted matches either
+    cfg.invert = true;
+    let result = run_on_reader(&cfg, Cursor::new(data.as_str()), None).unwrap();
+    assert_eq!(result.files[0].matched_lines, 0);
+
+    cfg.invert = false;
+    assert_eq!(result.stats.timeouts, 2);
+    cfg.match_timeout_ms = Some(60_000);
+    let result = run_on_reader(&cfg, Cursor::new(data.as_str()), None).unwrap();
+    assert!(result.files[0].timeouts.is_empty());
+    assert_eq!(result.output, "a b c x y\n");
+}
+
+#[test]
+fn test_timed_out_lines_are_counted_in_json_and_merged_stats() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "x y\nx y\n").unwrap();
+    fs::write(&b, "x y\n").unwrap();
+    let mut cfg = create_config("x&y");
+    cfg.json = true;
+    cfg.match_timeout_ms = Some(0);
+
+    let shards = [&a, &b].map(|p| run(&cfg, &[p.to_string_lossy().to_string()]).unwrap());
+    assert!(
+        shards[0]
+            .output
+            .contains(r#""matched_lines":0,"matches":0,"timeouts":2}"#)
+    );
+    let merged = RunResult::merge(shards);
+    assert_eq!(merged.stats.timeouts, 3);
+    assert!(merged.output.ends_with(
+        r#""matched_lines":0,"matches":0,"timeouts":3}
+"#
+    ));
+
+    cfg.json_schema = JsonSchema::Ripgrep;
+    let inputs = [&a, &b].map(|p| p.to_string_lossy().to_string());
+    let result = run(&cfg, &inputs).unwrap();
+    assert!(result.output.ends_with(
+        r#""matched_lines":0,"matches":0,"timeouts":3}}}
+"#
+    ));
+}
+
+#[test]
+fn test_run_detailed_reports_each_file() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "error one\nok\nerror two\n").unwrap();
+    fs::write(&b, "ok\n").unwrap();
+    let missing = td.path().join("missing.log");
+    let inputs: Vec<String> = [&a, &missing, &b]
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    // A file that cannot be read is reported with the others instead of failing the run
+    assert!(run(&create_config("error"), &inputs).is_err());
+    let files = run_detailed(&create_config("error"), &inputs).unwrap();
+    let paths: Vec<_> = files.iter().map(|f| f.path.as_deref().unwrap()).collect();
+    assert_eq!(paths, inputs);
+    assert_eq!(files[0].matched_lines, 2);
+    let lines: Vec<_> = files[0].matches.iter().map(|m| m.line_number).collect();
+    assert_eq!(lines, vec![1, 3]);
+    assert_eq!(files[0].error, None);
+    assert!(files[1].error.is_some());
+    assert!(files[1].matches.is_empty());
+    assert_eq!((files[2].matched_lines, files[2].error.as_ref()), (0, None));
+
+    assert!(run_detailed(&create_config("("), &inputs).is_err());
+}
+
+#[test]
+fn test_search_iter_yields_matches_lazily() {
+    let cfg = create_config("error");
+    // Reading on after the second match would never end
+    let endless = Cursor::new("ok\nerror one\nan error\n").chain(io::repeat(b'\n'));
+    let found: Vec<Match> = search_iter(&cfg, endless)
+        .unwrap()
+        .take(2)
+        .collect::<io::Result<_>>()
+        .unwrap();
+    let lines: Vec<_> = found
+        .iter()
+        .map(|m| (m.line_number, m.byte_offset, m.line.as_str()))
+        .collect();
+    assert_eq!(lines, vec![(2, 3, "error one"), (3, 13, "an error")]);
+    assert_eq!(found[0].spans, vec![0..5]);
+    assert_eq!(found[1].spans, vec![3..8]);
+
+    let mut cfg = create_config("error");
+    cfg.invert = true;
+    cfg.context.after = 1;
+    let found: Vec<Match> = search_iter(&cfg, Cursor::new("ok\nerror\nfine\n"))
+        .unwrap()
+        .collect::<io::Result<_>>()
+        .unwrap();
+    let lines: Vec<&str> = found.iter().map(|m| m.line.as_str()).collect();
+    assert_eq!(lines, vec!["ok", "fine"]);
+    assert!(found.iter().all(|m| m.spans.is_empty()));
+
+    // A read error is the last item
+    let mut iter = search_iter(&cfg, Cursor::new(b"ok\n\xff\nfine\n".to_vec())).unwrap();
+    assert_eq!(iter.next().unwrap().unwrap().line, "ok");
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+
+    assert!(search_iter(&create_config("("), Cursor::new("")).is_err());
+}
+
+// ============ SINK TESTS ============
+
+/// Records the events of a search, stopping at the match after `stop_after` if set.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+    stop_after: Option<usize>,
+}
+
+impl Sink for Recorder {
+    fn on_file_start(&mut self, path: Option<&str>) -> io::Result<()> {
+        self.events.push(format!("start {}", path.unwrap_or("-")));
+        Ok(())
+    }
+
+    fn on_match(&mut self, _path: Option<&str>, line: &Match) -> io::Result<()> {
+        if self.stop_after == Some(0) {
+            return Err(io::Error::other("enough"));
+        }
+        self.stop_after = self.stop_after.map(|n| n - 1);
+        self.events
+            .push(format!("match {} {:?}", line.line_number, line.spans));
+        Ok(())
+    }
+
+    fn on_context(&mut self, _path: Option<&str>, line: &Match) -> io::Result<()> {
+        self.events.push(format!("context {}", line.line_number));
+        Ok(())
+    }
+
+    fn on_file_end(&mut self, path: Option<&str>, matched_lines: usize) -> io::Result<()> {
+        self.events
+            .push(format!("end {} {}", path.unwrap_or("-"), matched_lines));
+        Ok(())
+    }
+
+    fn on_error(&mut self, path: Option<&str>, _error: &str) -> io::Result<()> {
+        self.events.push(format!("error {}", path.unwrap_or("-")));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_sink_receives_events_in_input_order() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "ok\nerror one\nok\nerror two\n").unwrap();
+    fs::write(&b, "ok\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+    let (a, b) = (&inputs[0], &inputs[1]);
+    let mut cfg = create_config("error");
+    cfg.context.before = 1;
+
+    let mut sink = Recorder::default();
+    let status = run_with_sink(&cfg, &inputs, &mut sink).unwrap();
+    assert_eq!(status, ExitStatus::MatchFound);
+    assert_eq!(
+        sink.events,
+        vec![
+            format!("start {a}"),
+            "context 1".to_string(),
+            "match 2 [0..5]".to_string(),
+            "context 3".to_string(),
+            "match 4 [0..5]".to_string(),
+            format!("end {a} 2"),
+            format!("start {b}"),
+            format!("end {b} 0"),
+        ]
+    );
+
+    let mut sink = Recorder::default();
+    let status = run_with_sink(&create_config("nothing"), &inputs[1..], &mut sink).unwrap();
+    assert_eq!(status, ExitStatus::NoMatch);
+    assert_eq!(
+        sink.events,
+        vec![format!("start {b}"), format!("end {b} 0")]
+    );
+
+    // A file that fails to read is reported, and the search goes on
+    let bad = td.path().join("bad.log");
+    fs::write(&bad, b"error one\n\xff\n").unwrap();
+    let inputs = vec![bad.to_string_lossy().to_string(), b.clone()];
+    let mut sink = Recorder::default();
+    run_with_sink(&cfg, &inputs, &mut sink).unwrap();
+    assert_eq!(
+        sink.events,
+        vec![
+            format!("start {}", inputs[0]),
+            "match 1 [0..5]".to_string(),
+            format!("error {}", inputs[0]),
+            format!("start {b}"),
+            format!("end {b} 0"),
+        ]
+    );
+}
+
+#[test]
+fn test_sink_errors_stop_the_search() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "error one\nerror two\n").unwrap();
+    fs::write(&b, "error three\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut sink = Recorder {
+        stop_after: Some(1),
+        ..Default::default()
+    };
+    let err = run_with_sink(&create_config("error"), &inputs, &mut sink).unwrap_err();
+    assert!(err.contains("enough"), "{}", err);
+    assert_eq!(
+        sink.events,
+        vec![format!("start {}", inputs[0]), "match 1 [0..5]".to_string()]
+    );
+}
+
+// ============ MERGE TESTS ============
+
+#[test]
+fn test_merge_concatenates_in_order_and_combines_status() {
+    let mut cfg = create_config("x");
+    cfg.with_filename = Some(true);
+    let first = run_on_reader(&cfg, Cursor::new("x1\n"), Some("a")).unwrap();
+    let second = run_on_reader(&cfg, Cursor::new("y\n"), Some("b")).unwrap();
+    let third = run_on_reader(&cfg, Cursor::new("x2\n"), Some("c")).unwrap();
+
+    let merged = RunResult::merge([first, second.clone(), third]);
+    assert_eq!(merged.output, "a:1:x1\nc:1:x2\n");
+    assert_eq!(merged.status, ExitStatus::MatchFound);
+    let paths: Vec<_> = merged.files.iter().map(|f| f.path.as_deref()).collect();
+    assert_eq!(paths, vec![Some("a"), Some("b"), Some("c")]);
+
+    assert_eq!(RunResult::merge([second]).status, ExitStatus::NoMatch);
+    assert_eq!(RunResult::merge([]).status, ExitStatus::NoMatch);
+}
+
+#[test]
+fn test_merge_combines_json_summaries() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "x\nx\n").unwrap();
+    fs::write(&b, "y\n").unwrap();
+    let mut cfg = create_config("x");
+    cfg.json = true;
+
+    let shards = [&a, &b].map(|p| run(&cfg, &[p.to_string_lossy().to_string()]).unwrap());
+    let merged = RunResult::merge(shards);
+    assert_eq!(merged.output.matches("\"type\":\"summary\"").count(), 1);
+    assert!(merged.output.ends_with(
+        "{\"type\":\"summary\",\"files_searched\":2,\"files_with_matches\":1,\"matched_lines\":2,\"matches\":2,\"timeouts\":0}\n"
+    ));
+    assert_eq!(merged.output.matches("\"type\":\"end\"").count(), 2);
+}
+
+#[test]
+fn test_merge_combines_ripgrep_json_summaries() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "x\nx\n").unwrap();
+    fs::write(&b, "y\n").unwrap();
+    let mut cfg = create_config("x");
+    cfg.json = true;
+    cfg.json_schema = JsonSchema::Ripgrep;
+
+    let shards = [&a, &b].map(|p| run(&cfg, &[p.to_string_lossy().to_string()]).unwrap());
+    let merged = RunResult::merge(shards);
+    assert_eq!(merged.output.matches("\"type\":\"summary\"").count(), 1);
+    assert_eq!(merged.output.matches("\"type\":\"end\"").count(), 2);
+    let summary = merged.output.lines().last().unwrap();
+    assert!(
+        summary.starts_with("{\"type\":\"summary\",\"data\":{\"elapsed_total\":"),
+        "{}",
+        summary
+    );
+    assert!(
+        summary.contains(
+            "\"searches\":2,\"searches_with_match\":1,\"bytes_searched\":6,\"bytes_printed\":"
+        ),
+        "{}",
+        summary
+    );
+    assert!(
+        summary.ends_with(",\"matched_lines\":2,\"matches\":2,\"timeouts\":0}}}"),
+        "{}",
+        summary
+    );
+}
+
+#[test]
+fn test_merge_takes_the_longest_elapsed_time() {
+    let cfg = create_config("x");
+    let parts = [3, 5, 1].map(|secs| {
+        let mut part = run_on_reader(&cfg, Cursor::new("x\n"), None).unwrap();
+        part.stats.elapsed = Duration::from_secs(secs);
+        part
+    });
+    let merged = RunResult::merge(parts);
+    assert_eq!(merged.stats.elapsed, Duration::from_secs(5));
+    assert_eq!(merged.stats.matched_lines, 3);
+}
+
+// ============ SELF-CHECK TESTS ============
+
+#[test]
+fn test_self_check_parallel_and_sequential_runs_agree() {
+    let td = tempfile::tempdir().unwrap();
+    for i in 0..20 {
+        let lines: String = (0..50)
+            .map(|n| format!("2025-01-01 10:00:{:02} hit {} {}\n", n % 60, i, n))
+            .collect();
+        fs::write(td.path().join(format!("{:02}.log", i)), lines).unwrap();
+    }
+    let mut cfg = create_config("hit");
+    cfg.recursive = true;
+    let inputs = vec![td.path().to_string_lossy().to_string()];
+
+    let check = self_check(&cfg, &inputs).unwrap();
+    assert!(check.divergences.is_empty(), "{:?}", check.divergences);
+    assert!(check.threads >= 4);
+    assert_eq!(check.result.files.len(), 20);
+
+    let err = self_check(&create_config("hit"), &[]).unwrap_err();
+    assert!(err.contains("standard input"));
+}
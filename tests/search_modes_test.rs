@@ -1,12 +1,21 @@
-use rgrep::{Config, ExitStatus, run, run_on_reader};
+#![cfg(feature = "files")]
+
+use rgrep::{ColorChoice, Config, ExitStatus, run, run_on_reader};
 use std::fs;
 use std::io::Cursor;
-use tempfile;
 
 fn create_config(pattern: &str) -> Config {
     Config {
         patterns: vec![pattern.to_string()],
-        color: false,
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+fn create_definition_config(name: &str) -> Config {
+    Config {
+        definition: Some(name.to_string()),
+        color: ColorChoice::Never,
         ..Default::default()
     }
 }
@@ -87,6 +96,116 @@ fn test_count_mode_ignores_context() {
     assert!(result.output.trim().ends_with("1"));
 }
 
+#[test]
+fn test_count_matches_counts_every_match() {
+    let mut cfg = create_config("foo|bar");
+    cfg.count = true;
+    cfg.count_matches = true;
+
+    let data = "foo foo bar
+baz
+foo
+";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "4\n");
+    assert_eq!(result.stats.matched_lines, 2);
+    assert_eq!(result.stats.matches, 4);
+
+    // Inverted lines have no matches of their own and count once each
+    cfg.invert = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "1\n");
+}
+
+#[test]
+fn test_count_matches_in_table_and_json() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, "x x x\n").unwrap();
+    fs::write(&b, "x\nx\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+    let mut cfg = create_config("x");
+    cfg.count = true;
+    cfg.count_matches = true;
+    cfg.table = false;
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(result.output, format!("{}:3\n{}:2\n", inputs[0], inputs[1]));
+
+    cfg.json = true;
+    let result = run(&cfg, &inputs).unwrap();
+    assert!(
+        result.output.ends_with(
+            r#""files_with_matches":2,"matched_lines":3,"matches":5,"timeouts":0}
+"#
+        ),
+        "{}",
+        result.output
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_count_matches_cli() {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["--count-matches", "-r", "ab"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            use std::io::Write;
+            child.stdin.take().unwrap().write_all(b"abab\nab\nx\n")?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "3\n");
+}
+
+// ============ FILES WITH MATCHES TESTS ============
+
+#[test]
+fn test_files_with_matches_lists_matching_files() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), "error\nok\nerror\n").unwrap();
+    fs::write(td.path().join("b.log"), "ok\n").unwrap();
+    fs::write(td.path().join("c.log"), "ok\nerror\n").unwrap();
+    let mut cfg = create_config("error");
+    cfg.files_with_matches = true;
+    cfg.recursive = true;
+
+    let dir = td.path().to_string_lossy().to_string();
+    let result = run(&cfg, &[dir]).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    let a = td.path().join("a.log").to_string_lossy().to_string();
+    let c = td.path().join("c.log").to_string_lossy().to_string();
+    let mut listed: Vec<&str> = result.output.lines().collect();
+    listed.sort();
+    assert_eq!(listed, [a.as_str(), c.as_str()]);
+}
+
+#[test]
+fn test_files_with_matches_stops_at_first_match() {
+    // Fails once read past its first line
+    struct FirstLineOnly(bool);
+    impl std::io::Read for FirstLineOnly {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.0, true) {
+                return Err(std::io::Error::other("read past the first match"));
+            }
+            buf[..6].copy_from_slice(b"error\n");
+            Ok(6)
+        }
+    }
+    let mut cfg = create_config("error");
+    cfg.files_with_matches = true;
+    let result = run_on_reader(&cfg, FirstLineOnly(false), None).unwrap();
+    assert_eq!(result.output, "(standard input)\n");
+    assert_eq!(result.files[0].matched_lines, 1);
+}
+
 // ============ QUIET MODE TESTS ============
 
 #[test]
@@ -229,6 +348,125 @@ fn test_stdin_no_filename_prefix() {
     assert!(result.output.starts_with("1:"));
 }
 
+#[cfg(feature = "cli")]
+#[test]
+fn test_follow_filters_stdin_until_it_ends() {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["-f", "-A", "1", "-r", "hit", "-"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            use std::io::Write;
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(b"miss\na hit\nafter\nmiss\nlast hit")?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "a hit\nafter\nlast hit\n"
+    );
+    assert_eq!(out.status.code(), Some(0));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_follow_stdin_writes_metrics_before_waiting_for_input() {
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    let td = tempfile::tempdir().unwrap();
+    let prom = td.path().join("rgrep.prom");
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["-f", "-r", "hit", "--metrics-file"])
+        .arg(&prom)
+        .arg("-")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let counted = |line: &str| {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            let metrics = fs::read_to_string(&prom).unwrap_or_default();
+            if metrics.lines().any(|l| l.starts_with(line)) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        false
+    };
+    assert!(counted("rgrep_lines_total{path=\"(standard input)\"} 0"));
+    // Written within the second after the first write, and then nothing more comes in
+    stdin.write_all(b"a hit\n").unwrap();
+    stdin.flush().unwrap();
+    let seen = counted("rgrep_matched_lines_total{path=\"(standard input)\",pattern=\"hit\"} 1");
+    drop(stdin);
+    child.wait().unwrap();
+    assert!(seen);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_follow_tail_lines_prints_the_last_matches_first() {
+    use std::io::BufRead;
+
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    fs::write(&log, "a hit\nmiss\nb hit\nc hit\n").unwrap();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+        .args(["-f", "--tail-lines", "2", "-r", "hit"])
+        .arg(&log)
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    let mut lines = Vec::new();
+    for _ in 0..2 {
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        lines.push(line);
+    }
+    // Following goes on until it is stopped
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(lines, vec!["b hit\n", "c hit\n"]);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_follow_start_flags_need_follow_and_exclude_each_other() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    fs::write(&log, "a hit\n").unwrap();
+    for args in [
+        &["--tail-lines", "2", "-r", "hit"][..],
+        &["--follow-from-start", "-r", "hit"],
+        &[
+            "-f",
+            "--follow-from-start",
+            "--tail-lines",
+            "2",
+            "-r",
+            "hit",
+        ],
+        &["-f", "--tail-lines", "two", "-r", "hit"],
+    ] {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"))
+            .args(args)
+            .arg(&log)
+            .output()
+            .unwrap();
+        assert_eq!(out.status.code(), Some(2), "{:?}", args);
+        assert!(out.stdout.is_empty(), "{:?}", args);
+    }
+}
+
 // ============ MULTI-FILE TESTS ============
 
 #[test]
@@ -317,7 +555,7 @@ fn test_single_line_file() {
 #[test]
 fn test_very_long_line() {
     let cfg = create_config("needle");
-    let long_line = "a".repeat(10000) + "needle" + &"b".repeat(10000);
+    let long_line = "a".repeat(10000) + "needle" + "b".repeat(10000).as_str();
     let result = run_on_reader(&cfg, Cursor::new(&long_line), None).unwrap();
 
     assert_eq!(result.status, ExitStatus::MatchFound);
@@ -441,3 +679,678 @@ fn test_line_and_case_insensitive_together() {
         "Should match exact lines case-insensitively"
     );
 }
+
+// ============ COLUMN TESTS ============
+
+#[test]
+fn test_column_reports_first_match() {
+    let mut cfg = create_config("needle");
+    cfg.column = true;
+
+    let data = "no match\nhay needle needle\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines, vec!["2:5:hay needle needle"]);
+}
+
+#[test]
+fn test_column_omitted_on_context_lines() {
+    let mut cfg = create_config("b");
+    cfg.column = true;
+    cfg.context.before = 1;
+
+    let data = "a\nxb\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines, vec!["1:a", "2:2:xb"]);
+}
+
+// ============ FILENAME PREFIX TESTS ============
+
+#[test]
+fn test_filename_prefix_default_for_multiple_files() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("b.txt");
+    fs::write(&a, b"hit\n").unwrap();
+    fs::write(&b, b"miss\nhit\n").unwrap();
+    let a = a.to_string_lossy().to_string();
+    let b = b.to_string_lossy().to_string();
+
+    let result = run(&create_config("hit"), &[a.clone(), b.clone()]).unwrap();
+    let expected = format!("{}:1:hit\n{}:2:hit\n", a, b);
+    assert_eq!(result.output, expected);
+
+    // A single file has no prefix unless -H is given
+    let result = run(&create_config("hit"), std::slice::from_ref(&a)).unwrap();
+    assert_eq!(result.output, "1:hit\n");
+
+    let mut cfg = create_config("hit");
+    cfg.with_filename = Some(true);
+    let result = run(&cfg, std::slice::from_ref(&a)).unwrap();
+    assert_eq!(result.output, format!("{}:1:hit\n", a));
+}
+
+#[test]
+fn test_no_filename_suppresses_prefix() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("b.txt");
+    fs::write(&a, b"hit\n").unwrap();
+    fs::write(&b, b"hit\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("hit");
+    cfg.with_filename = Some(false);
+    cfg.count = true;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines, vec!["1", "1"]);
+}
+
+#[test]
+fn test_with_filename_on_reader_labels_stdin() {
+    let mut cfg = create_config("hit");
+    cfg.with_filename = Some(true);
+
+    let result = run_on_reader(&cfg, Cursor::new("hit\n"), None).unwrap();
+    assert_eq!(result.output, "(standard input):1:hit\n");
+}
+
+// ============ WHERE PREDICATE TESTS ============
+
+#[test]
+fn test_where_filters_by_numeric_capture() {
+    let mut cfg = create_config(r"took (\d+)ms");
+    cfg.where_expr = Some("$1 > 500".to_string());
+
+    let data = "GET /a took 90ms\nGET /b took 750ms\nGET /c took 1200ms\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines, vec!["2:GET /b took 750ms", "3:GET /c took 1200ms"]);
+}
+
+#[test]
+fn test_where_with_invert_and_count() {
+    let mut cfg = create_config(r"(?P<method>[A-Z]+) \S+ (?P<ms>\d+)");
+    cfg.where_expr = Some(r#"$method == "POST" && $ms >= 100"#.to_string());
+    cfg.count = true;
+
+    let data = "POST /a 150\nGET /b 900\nPOST /c 20\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output.trim(), "1");
+
+    cfg.invert = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output.trim(), "2");
+}
+
+#[test]
+fn test_where_rejects_unknown_group() {
+    let mut cfg = create_config(r"took (\d+)ms");
+    cfg.where_expr = Some("$2 > 1".to_string());
+
+    let err = run_on_reader(&cfg, Cursor::new("took 1ms\n"), None)
+        .expect_err("unknown group is rejected");
+    assert!(err.contains("$2"));
+}
+
+// ============ LINE NUMBER TESTS ============
+
+#[test]
+fn test_no_line_number_prints_bare_lines() {
+    let mut cfg = create_config("b");
+    cfg.line_number = false;
+    cfg.context.before = 1;
+    cfg.column = true;
+
+    let data = "a\nxb\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "a\n2:xb\n");
+}
+
+#[test]
+fn test_no_line_number_keeps_filename_and_merge_order() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, b"2025-01-01 10:00:02 late\n").unwrap();
+    fs::write(&b, b"2025-01-01 10:00:01 early\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("early|late");
+    cfg.line_number = false;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let expected = format!(
+        "{}:2025-01-01 10:00:01 early\n{}:2025-01-01 10:00:02 late\n",
+        inputs[1], inputs[0]
+    );
+    assert_eq!(result.output, expected);
+}
+
+// ============ HEADING TESTS ============
+
+#[test]
+fn test_heading_groups_lines_per_file() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    let c = td.path().join("c.log");
+    fs::write(&a, b"2025-01-01 10:00:02 hit a\nmiss\n").unwrap();
+    fs::write(&b, b"miss\n").unwrap();
+    fs::write(&c, b"2025-01-01 10:00:01 hit c\nhit c again\n").unwrap();
+    let inputs: Vec<String> = [&a, &b, &c]
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    let mut cfg = create_config("hit");
+    cfg.heading = true;
+    let result = run(&cfg, &inputs).unwrap();
+
+    // Files stay grouped in input order; no chronological merge or source tags
+    let expected = format!(
+        "{}\n1:2025-01-01 10:00:02 hit a\n\n{}\n1:2025-01-01 10:00:01 hit c\n2:hit c again\n",
+        inputs[0], inputs[2]
+    );
+    assert_eq!(result.output, expected);
+}
+
+#[test]
+fn test_heading_needs_shown_filenames() {
+    let mut cfg = create_config("hit");
+    cfg.heading = true;
+
+    let result = run_on_reader(&cfg, Cursor::new("hit\n"), Some("f.txt")).unwrap();
+    assert_eq!(result.output, "1:hit\n");
+
+    cfg.with_filename = Some(true);
+    let result = run_on_reader(&cfg, Cursor::new("hit\n"), Some("f.txt")).unwrap();
+    assert_eq!(result.output, "f.txt\n1:hit\n");
+}
+
+// ============ VIMGREP TESTS ============
+
+#[test]
+fn test_vimgrep_one_entry_per_match() {
+    let mut cfg = create_config("ab");
+    cfg.vimgrep = true;
+    cfg.color = ColorChoice::Always;
+    cfg.context.before = 1;
+
+    let data = "ctx\nab x ab\nno\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), Some("f.txt")).unwrap();
+
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert_eq!(lines, vec!["f.txt:2:1:ab x ab", "f.txt:2:6:ab x ab"]);
+}
+
+#[test]
+fn test_vimgrep_lists_files_in_input_order() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, b"2025-01-01 10:00:02 hit\n").unwrap();
+    fs::write(&b, b"2025-01-01 10:00:01 hit\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("hit");
+    cfg.vimgrep = true;
+    cfg.line_number = false;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let expected = format!(
+        "{}:1:21:2025-01-01 10:00:02 hit\n{}:1:21:2025-01-01 10:00:01 hit\n",
+        inputs[0], inputs[1]
+    );
+    assert_eq!(result.output, expected);
+}
+
+// ============ PATH OR CONTENT TESTS ============
+
+#[test]
+fn test_path_or_content_reports_path_only_hits() {
+    let td = tempfile::tempdir().unwrap();
+    let by_content = td.path().join("notes.txt");
+    let by_path = td.path().join("kafka.conf");
+    let neither = td.path().join("other.txt");
+    fs::write(&by_content, b"uses kafka\n").unwrap();
+    fs::write(&by_path, b"brokers=3\n").unwrap();
+    fs::write(&neither, b"nothing\n").unwrap();
+    let inputs: Vec<String> = [&by_content, &by_path, &neither]
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    let mut cfg = create_config("kafka");
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(result.output, format!("{}:1:uses kafka\n", inputs[0]));
+
+    cfg.path_or_content = true;
+    let result = run(&cfg, &inputs).unwrap();
+    let expected = format!("{}:1:uses kafka\n{} (path match)\n", inputs[0], inputs[1]);
+    assert_eq!(result.output, expected);
+}
+
+#[test]
+fn test_path_or_content_path_hit_sets_status() {
+    let td = tempfile::tempdir().unwrap();
+    let by_path = td.path().join("kafka.conf");
+    fs::write(&by_path, b"brokers=3\n").unwrap();
+    let inputs = vec![by_path.to_string_lossy().to_string()];
+
+    let mut cfg = create_config("kafka");
+    assert_eq!(run(&cfg, &inputs).unwrap().status, ExitStatus::NoMatch);
+
+    cfg.path_or_content = true;
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.output, format!("{} (path match)\n", inputs[0]));
+
+    cfg.json = true;
+    let result = run(&cfg, &inputs).unwrap();
+    assert!(result.output.contains(r#"{"type":"path_match","path":"#));
+    assert!(result.output.contains(r#""files_with_matches":1"#));
+}
+
+// ============ ENVIRONMENT EXPANSION TESTS ============
+
+#[test]
+fn test_expand_env_in_pattern() {
+    // SAFETY: the variable name is unique to this test
+    unsafe { std::env::set_var("RGREP_TEST_EXPAND_USER", "alice") };
+
+    let mut cfg = create_config("user=${RGREP_TEST_EXPAND_USER}");
+    cfg.expand_env = true;
+    let data = "user=bob\nuser=alice\n";
+
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "2:user=alice\n");
+}
+
+#[test]
+fn test_expand_env_missing_variable_is_error() {
+    let mut cfg = create_config("${RGREP_TEST_SURELY_UNSET}");
+    cfg.expand_env = true;
+
+    let err =
+        run_on_reader(&cfg, Cursor::new("x\n"), None).expect_err("unset variable is rejected");
+    assert!(err.contains("RGREP_TEST_SURELY_UNSET"));
+}
+
+// ============ COLOR TESTS ============
+
+#[test]
+fn test_default_colors_highlight_only_matches() {
+    let mut cfg = create_config("err");
+    cfg.color = ColorChoice::Always;
+    cfg.with_filename = Some(true);
+
+    let result = run_on_reader(&cfg, Cursor::new("an err\n"), Some("a.log")).unwrap();
+    assert_eq!(result.output, "a.log:1:an \x1b[1;31merr\x1b[0m\n");
+}
+
+#[test]
+fn test_custom_colors_for_all_elements() {
+    let mut cfg = create_config("err");
+    cfg.color = ColorChoice::Always;
+    cfg.with_filename = Some(true);
+    cfg.column = true;
+    cfg.colors = rgrep::Colors::parse("ms=4:fn=35:ln=32:se=36").unwrap();
+
+    let result = run_on_reader(&cfg, Cursor::new("an err\n"), Some("a.log")).unwrap();
+    assert_eq!(
+        result.output,
+        "\x1b[35ma.log\x1b[0m\x1b[36m:\x1b[0m\x1b[32m1\x1b[0m\x1b[36m:\x1b[0m\
+         \x1b[32m4\x1b[0m\x1b[36m:\x1b[0man \x1b[4merr\x1b[0m\n"
+    );
+
+    // Prefix colors are not used when coloring is off
+    cfg.color = ColorChoice::Never;
+    let result = run_on_reader(&cfg, Cursor::new("an err\n"), Some("a.log")).unwrap();
+    assert_eq!(result.output, "a.log:1:4:an err\n");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_colors_option_rejects_what_grep_colors_ignores() {
+    let td = tempfile::tempdir().unwrap();
+    fs::write(td.path().join("a.log"), "hit\n").unwrap();
+    let run = |colors: Option<&str>, grep_colors: &str| {
+        let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_rgrep"));
+        cmd.args(["--color=always", "-r", "hit", "a.log"])
+            .env("GREP_COLORS", grep_colors)
+            .current_dir(td.path());
+        if let Some(spec) = colors {
+            cmd.args(["--colors", spec]);
+        }
+        cmd.output().unwrap()
+    };
+    for spec in ["match:fg:blue", "ms=4:sl=1", "ne"] {
+        let out = run(Some(spec), "");
+        assert_eq!(out.status.code(), Some(2), "{}", spec);
+        assert!(out.stdout.is_empty(), "{}", spec);
+        assert!(
+            String::from_utf8_lossy(&out.stderr).contains("invalid --colors"),
+            "{}",
+            spec
+        );
+    }
+    // GNU grep's other capabilities in GREP_COLORS are ignored
+    let out = run(None, "ms=4:sl=1:ne");
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "1:\x1b[4mhit\x1b[0m\n"
+    );
+}
+
+#[test]
+fn test_highlight_whole_line_with_line_regexp() {
+    let mut cfg = create_config("err.*");
+    cfg.color = ColorChoice::Always;
+    cfg.line = true;
+    cfg.line_number = false;
+
+    let result = run_on_reader(&cfg, Cursor::new("err here\nno err\n"), None).unwrap();
+    assert_eq!(result.output, "\x1b[1;31merr here\x1b[0m\n");
+}
+
+#[test]
+fn test_highlight_boolean_sub_patterns() {
+    let mut cfg = create_config("warn&(disk|net)");
+    cfg.color = ColorChoice::Always;
+    cfg.word = true;
+    cfg.line_number = false;
+
+    let data = "warn: disk full\nwarning: disk\nwarn: netdisk\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(
+        result.output,
+        "\x1b[1;31mwarn\x1b[0m: \x1b[1;31mdisk\x1b[0m full\n"
+    );
+}
+
+// ============ MAX COLUMNS TESTS ============
+
+#[test]
+fn test_max_columns_omits_long_lines() {
+    let mut cfg = create_config("err");
+    cfg.max_columns = Some(10);
+    cfg.context.before = 1;
+    let long = format!("{}err", "x".repeat(20));
+    let data = format!("{}\n{}\nshort err\n", "y".repeat(12), long);
+
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(
+        result.output,
+        "1:[... omitted: line too long (12 bytes)]\n\
+         2:[... omitted: line too long (23 bytes)]\n\
+         3:short err\n"
+    );
+    // The omitted line still matched
+    assert_eq!(result.files[0].matches[0].line, long);
+}
+
+#[test]
+fn test_max_columns_preview_shows_the_match() {
+    let mut cfg = create_config("err");
+    cfg.max_columns = Some(9);
+    cfg.max_columns_preview = true;
+    cfg.color = ColorChoice::Always;
+    cfg.line_number = false;
+    let data = format!("{}an err here{}\n", "x".repeat(30), "y".repeat(30));
+
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "[...]an \x1b[1;31merr\x1b[0m he[...]\n");
+}
+
+#[test]
+fn test_match_window_prints_the_text_around_matches() {
+    let mut cfg = create_config("error");
+    cfg.match_window = Some(6);
+    cfg.line_number = false;
+    let blob = r#"{"ts":"2024-01-01T00:00:00Z","msg":"disk error","host":"db1","retries":3}"#;
+    let data = format!("{}\nshort error\n", blob);
+
+    let result = run_on_reader(&cfg, Cursor::new(data.as_str()), None).unwrap();
+    assert_eq!(
+        result.output,
+        "\u{2026}\"disk error\",\"hos\u{2026}\nshort error\n"
+    );
+
+    // Only lines longer than --max-columns are cut
+    cfg.max_columns = Some(20);
+    let result = run_on_reader(&cfg, Cursor::new(data.as_str()), None).unwrap();
+    assert_eq!(
+        result.output,
+        "\u{2026}\"disk error\",\"hos\u{2026}\nshort error\n"
+    );
+    cfg.max_columns = Some(100);
+    let result = run_on_reader(&cfg, Cursor::new(data.as_str()), None).unwrap();
+    assert_eq!(result.output, data);
+}
+
+// ============ PREVIEW TESTS ============
+
+#[test]
+fn test_preview_limits_printed_matches() {
+    let mut cfg = create_config("a");
+    cfg.preview = Some(2);
+    cfg.with_filename = Some(true);
+
+    let result = run_on_reader(&cfg, Cursor::new("a1\nb\na2\na3\nc\na4\n"), Some("p.log")).unwrap();
+    assert_eq!(
+        result.output,
+        "p.log:1:a1\np.log:3:a2\np.log:\u{2026} (+2 more matches)\n"
+    );
+}
+
+#[test]
+fn test_preview_keeps_context_of_shown_matches_only() {
+    let mut cfg = create_config("a");
+    cfg.preview = Some(1);
+    cfg.line_number = false;
+    cfg.context.after = 1;
+    cfg.context.before = 1;
+
+    let result = run_on_reader(&cfg, Cursor::new("x\na1\nb\nc\na2\nd\n"), None).unwrap();
+    assert_eq!(result.output, "x\na1\nb\n\u{2026} (+1 more matches)\n");
+}
+
+#[test]
+fn test_preview_without_hidden_matches_adds_no_note() {
+    let mut cfg = create_config("a");
+    cfg.preview = Some(2);
+    cfg.line_number = false;
+
+    let result = run_on_reader(&cfg, Cursor::new("a1\nb\na2\n"), None).unwrap();
+    assert_eq!(result.output, "a1\na2\n");
+}
+
+#[test]
+fn test_preview_json_reports_full_counts() {
+    let mut cfg = create_config("a");
+    cfg.preview = Some(1);
+    cfg.json = true;
+
+    let result = run_on_reader(&cfg, Cursor::new("a1\na2\na3\n"), Some("p.log")).unwrap();
+    assert_eq!(result.output.matches("\"type\":\"match\"").count(), 1);
+    assert!(!result.output.contains('\u{2026}'));
+    assert!(result.output.contains("\"matched_lines\":3"));
+}
+
+#[test]
+fn test_preview_does_not_affect_count() {
+    let mut cfg = create_config("a");
+    cfg.preview = Some(1);
+    cfg.count = true;
+
+    let result = run_on_reader(&cfg, Cursor::new("a1\na2\na3\n"), None).unwrap();
+    assert_eq!(result.output, "3\n");
+}
+
+// ============ DEFINITION TESTS ============
+
+#[test]
+fn test_definition_mode_skips_uses() {
+    let mut cfg = create_definition_config("parse");
+    cfg.line_number = false;
+
+    let data = "use crate::parse;\npub fn parse(s: &str) -> Ast {\n    let ast = parse(s);\n}\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.output, "pub fn parse(s: &str) -> Ast {\n");
+    // Only the name is reported as matched text
+    assert_eq!(result.files[0].matches[0].spans, vec![7..12]);
+}
+
+#[test]
+fn test_definition_kind_in_json() {
+    let mut cfg = create_definition_config("Parser");
+    cfg.json = true;
+    cfg.context.after = 1;
+
+    let data = "struct Parser {\n    pos: usize,\n}\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    let lines: Vec<&str> = result.output.lines().collect();
+    assert!(lines[1].starts_with(r#"{"type":"match","#), "{}", lines[1]);
+    assert!(lines[1].ends_with(r#""def":"struct"}"#), "{}", lines[1]);
+    // Context lines carry no tag
+    assert!(!lines[2].contains("\"def\""), "{}", lines[2]);
+}
+
+// ============ CRLF TESTS ============
+
+#[test]
+fn test_crlf_line_endings_are_stripped() {
+    let mut cfg = create_config("abc$");
+    cfg.line_number = false;
+    let data = "abc\r\nxabc\r\nabcd\r\n";
+
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "abc\nxabc\n");
+
+    cfg.patterns = vec!["abc".to_string()];
+    cfg.line = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "abc\n");
+}
+
+#[test]
+fn test_crlf_matches_before_a_lone_carriage_return() {
+    let mut cfg = create_config("last$");
+    let data = "first\r\nlast\r";
+
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.status, ExitStatus::NoMatch);
+
+    cfg.crlf = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.files[0].matches[0].spans[0], 0..4);
+
+    cfg.patterns = vec!["first|last".to_string()];
+    cfg.line = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.files[0].matches.len(), 2);
+}
+
+#[test]
+fn test_crlf_dot_stops_at_carriage_return() {
+    let mut cfg = create_config("a.*");
+    cfg.only_matching = true;
+    cfg.line_number = false;
+    cfg.crlf = true;
+
+    let result = run_on_reader(&cfg, Cursor::new("ab\rcd\n"), None).unwrap();
+    assert_eq!(result.output, "ab\n");
+}
+
+// ============ MULTILINE TESTS ============
+
+#[test]
+fn test_multiline_match_spans_lines() {
+    let mut cfg = create_config(r"fn foo\(\s*\w+: \w+,\s*\)\s*\{");
+    cfg.multiline = true;
+
+    let data = "fn bar() {}\nfn foo(\n    a: u32,\n)\n{\n    a\n}\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.status, ExitStatus::MatchFound);
+    assert_eq!(result.output, "2:fn foo(\n3:    a: u32,\n4:)\n5:{\n");
+    // Each line reports the part of the match it holds
+    let spans: Vec<_> = result.files[0]
+        .matches
+        .iter()
+        .map(|m| m.spans.clone())
+        .collect();
+    assert_eq!(spans, vec![vec![0..7], vec![0..11], vec![0..1], vec![0..1]]);
+
+    // Without -U the pattern cannot cross a line boundary
+    cfg.multiline = false;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.status, ExitStatus::NoMatch);
+}
+
+#[test]
+fn test_multiline_with_context_and_count() {
+    let mut cfg = create_config(r"begin\nend");
+    cfg.multiline = true;
+    cfg.line_number = false;
+    cfg.context.before = 1;
+
+    let data = "x\nbegin\nend\nbegin\nmiddle\nend\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "x\nbegin\nend\n");
+
+    cfg.context.before = 0;
+    cfg.count = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "2\n");
+}
+
+#[test]
+fn test_multiline_operators_are_regex_syntax() {
+    // '&' and parentheses have no Boolean meaning with -U
+    let mut cfg = create_config(r"a&(b|c)\nd");
+    cfg.multiline = true;
+    cfg.line_number = false;
+    let result = run_on_reader(&cfg, Cursor::new("a&c\nd\na\nd\n"), None).unwrap();
+    assert_eq!(result.output, "a&c\nd\n");
+}
+
+// ============ LINE MAP TESTS ============
+
+#[test]
+fn test_map_matches_transformed_line_and_prints_original() {
+    let mut cfg = create_config("error: disk");
+    cfg.maps = vec!["strip-ansi".to_string(), "lowercase".to_string()];
+    let data = "\x1b[31mERROR\x1b[0m: disk full\nerror: net down\n";
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert_eq!(result.output, "1:\x1b[31mERROR\x1b[0m: disk full\n");
+
+    // Spans point into the original line, skipping the removed escape codes
+    cfg.json = true;
+    let result = run_on_reader(&cfg, Cursor::new(data), None).unwrap();
+    assert!(result.output.contains(r#""start":5"#), "{}", result.output);
+
+    cfg.json = false;
+    cfg.maps = vec!["s/[0-9]+/N/g".to_string()];
+    cfg.patterns = vec!["id=N,N".to_string()];
+    let result = run_on_reader(&cfg, Cursor::new("id=12,345\nid=1\n"), None).unwrap();
+    assert_eq!(result.output, "1:id=12,345\n");
+}
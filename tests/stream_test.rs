@@ -0,0 +1,302 @@
+#![cfg(feature = "tokio")]
+
+use rgrep::follow::FollowEvent;
+use rgrep::stream::{SearchEvent, follow_async, run_async};
+use rgrep::{ColorChoice, Config};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+fn create_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// ============ RUN ASYNC TESTS ============
+
+#[test]
+fn test_run_async_streams_sink_events() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    fs::write(&a, "ok\nerror one\n").unwrap();
+    let path = a.to_string_lossy().to_string();
+    let mut cfg = create_config("error");
+    cfg.context.before = 1;
+
+    let events: Vec<_> = block_on(async { run_async(cfg, vec![path.clone()]).collect().await });
+    let kinds: Vec<String> = events
+        .into_iter()
+        .map(|event| match event.unwrap() {
+            SearchEvent::FileStart { path } => format!("start {}", path.unwrap()),
+            SearchEvent::Match { line, .. } => format!("match {}", line.line),
+            SearchEvent::Context { line, .. } => format!("context {}", line.line),
+            SearchEvent::FileEnd { matched_lines, .. } => format!("end {}", matched_lines),
+            SearchEvent::Error { error, .. } => format!("error {}", error),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            format!("start {}", path),
+            "context ok".to_string(),
+            "match error one".to_string(),
+            "end 1".to_string(),
+        ]
+    );
+
+    let events: Vec<_> =
+        block_on(async { run_async(create_config("("), vec![path]).collect().await });
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_err());
+}
+
+// ============ FOLLOW ASYNC TESTS ============
+
+#[test]
+fn test_follow_async_streams_new_lines_until_dropped() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    fs::write(&log, "old hit\n").unwrap();
+    let mut cfg = create_config("hit");
+    cfg.follow = true;
+    cfg.context.before = 1;
+
+    // Keep appending until the follower has seen a batch; lines written before it found
+    // the end of the file are not followed
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let (log, done) = (log.clone(), done.clone());
+        std::thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                let mut file = OpenOptions::new().append(true).open(&log).unwrap();
+                file.write_all(b"quiet\nnew hit\n").unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        })
+    };
+
+    let inputs_path = log.to_string_lossy().to_string();
+    let inputs = vec![inputs_path.clone()];
+    let events: Vec<_> = block_on(async {
+        let events = follow_async(cfg, inputs).take(2).collect::<Vec<_>>().await;
+        // The stream is dropped here, which ends following
+        events
+    });
+    done.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+
+    let events: Vec<FollowEvent> = events.into_iter().map(Result::unwrap).collect();
+    let FollowEvent::Context {
+        path,
+        line_number: context_number,
+        line,
+    } = &events[0]
+    else {
+        panic!("{:?}", events);
+    };
+    assert_eq!(line, "quiet");
+    assert_eq!(path.as_deref(), Some(inputs_path.as_str()));
+    let FollowEvent::Match {
+        path,
+        line_number,
+        line,
+        spans,
+    } = &events[1]
+    else {
+        panic!("{:?}", events);
+    };
+    assert_eq!(line, "new hit");
+    assert_eq!(*spans, vec![4..7]);
+    assert_eq!(path.as_deref(), Some(inputs_path.as_str()));
+    // Lines already in the file when following started are counted: "old hit", then
+    // pairs of "quiet" and "new hit"
+    assert_eq!(*line_number, context_number + 1);
+    assert_eq!(*line_number % 2, 1);
+
+    let mut cfg = create_config("hit");
+    cfg.follow = true;
+    let path = log.to_string_lossy().to_string();
+    let inputs = vec![path.clone(), path];
+    let events: Vec<_> = block_on(async { follow_async(cfg, inputs).collect().await });
+    assert_eq!(events.len(), 1);
+    assert!(
+        events[0]
+            .as_ref()
+            .unwrap_err()
+            .contains("exactly one regular file or standard input")
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_follow_async_reads_across_log_rotation() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    let rotated = td.path().join("app.log.1");
+    fs::write(&log, "").unwrap();
+    let append = |path: &std::path::Path, text: &str| {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    };
+    let mut cfg = create_config("hit");
+    cfg.follow = true;
+
+    let inputs = vec![log.to_string_lossy().to_string()];
+    let lines: Vec<String> = block_on(async {
+        let mut events = Box::pin(follow_async(cfg, inputs));
+        // Keep appending until the follower has found the end of the file
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (log, done) = (log.clone(), done.clone());
+            std::thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    append(&log, "early hit\n");
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            })
+        };
+        events.next().await.unwrap().unwrap();
+        done.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+
+        // Rotate: the old file is renamed and still written to, a line is left
+        // unfinished, and a new file takes its place
+        append(&log, "before hit\nunfinished ");
+        fs::rename(&log, &rotated).unwrap();
+        append(&rotated, "late hit, still unfinished");
+        fs::write(&log, "new hit\n").unwrap();
+
+        let mut lines = Vec::new();
+        let mut truncated = false;
+        while let Some(event) = events.next().await {
+            let FollowEvent::Match {
+                line_number, line, ..
+            } = event.unwrap()
+            else {
+                continue;
+            };
+            if line == "early hit" {
+                continue;
+            }
+            if line == "new hit" || line == "s hit" {
+                // Numbered again in the new contents
+                assert_eq!(line_number, 1, "{}", line);
+            }
+            let last = line == "s hit";
+            lines.push(line);
+            if last {
+                break;
+            }
+            if !truncated && lines.last().unwrap() == "new hit" {
+                // Truncated in place, as by `copytruncate`, and written again with less
+                // than was read, so the truncation shows
+                truncated = true;
+                fs::write(&log, "s hit\n").unwrap();
+            }
+        }
+        lines
+    });
+    assert_eq!(
+        lines,
+        vec![
+            "before hit",
+            "unfinished late hit, still unfinished",
+            "new hit",
+            "s hit"
+        ]
+    );
+}
+
+#[test]
+fn test_follow_async_starts_with_the_last_matching_lines() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    fs::write(
+        &log,
+        "a hit\nafter a\nb hit\nafter b\nskipped\nbefore c\nc hit\n",
+    )
+    .unwrap();
+    let mut cfg = create_config("hit");
+    cfg.follow = true;
+    cfg.tail_lines = Some(2);
+    cfg.context.before = 1;
+    cfg.context.after = 1;
+
+    let inputs = vec![log.to_string_lossy().to_string()];
+    let lines: Vec<String> = block_on(async {
+        let mut events = Box::pin(follow_async(cfg, inputs));
+        let mut lines = Vec::new();
+        while let Some(event) = events.next().await {
+            let line = match event.unwrap() {
+                FollowEvent::Match {
+                    line_number, line, ..
+                } => format!("match {}:{}", line_number, line),
+                FollowEvent::Context {
+                    line_number, line, ..
+                } => format!("context {}:{}", line_number, line),
+            };
+            if line == "match 7:c hit" {
+                // Everything after the lines already there is new
+                let mut file = OpenOptions::new().append(true).open(&log).unwrap();
+                file.write_all(b"d hit\n").unwrap();
+            }
+            let last = line == "match 8:d hit";
+            lines.push(line);
+            if last {
+                break;
+            }
+        }
+        lines
+    });
+    assert_eq!(
+        lines,
+        vec![
+            "match 3:b hit",
+            "context 4:after b",
+            "context 6:before c",
+            "match 7:c hit",
+            "match 8:d hit",
+        ]
+    );
+}
+
+#[test]
+fn test_follow_async_from_start_reads_existing_lines() {
+    let td = tempfile::tempdir().unwrap();
+    let log = td.path().join("app.log");
+    fs::write(&log, "old hit\nold miss\n").unwrap();
+    let mut cfg = create_config("hit");
+    cfg.follow = true;
+    cfg.follow_from_start = true;
+
+    let inputs = vec![log.to_string_lossy().to_string()];
+    let first = block_on(async {
+        let mut events = Box::pin(follow_async(cfg, inputs));
+        events.next().await.unwrap().unwrap()
+    });
+    let FollowEvent::Match {
+        line_number,
+        line,
+        spans,
+        ..
+    } = first
+    else {
+        panic!("expected a match, got {:?}", first);
+    };
+    assert_eq!(line_number, 1);
+    assert_eq!(line, "old hit");
+    assert_eq!(spans[0], 4..7);
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "files")]
+
+use rgrep::output::table::{Align, Table};
+use rgrep::{ColorChoice, Config, run};
+use std::fs;
+
+fn create_config(pattern: &str) -> Config {
+    Config {
+        patterns: vec![pattern.to_string()],
+        color: ColorChoice::Never,
+        ..Default::default()
+    }
+}
+
+// ============ TABLE RENDERING TESTS ============
+
+#[test]
+fn test_table_pads_and_aligns_columns() {
+    let mut table = Table::new(&[("NAME", Align::Left), ("N", Align::Right)]);
+    table.push_row(vec!["a".into(), "1".into()]);
+    table.push_row(vec!["longer".into(), "120".into()]);
+
+    let mut out = String::new();
+    table.render(&mut out);
+    assert_eq!(out, "NAME      N\na         1\nlonger  120\n");
+}
+
+// ============ SUMMARY TABLE TESTS ============
+
+#[test]
+fn test_multi_file_count_table() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    let b = td.path().join("bb.txt");
+    fs::write(&a, b"hit\nhit\n").unwrap();
+    fs::write(&b, "hit\n".repeat(10)).unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("hit");
+    cfg.count = true;
+    let result = run(&cfg, &inputs).unwrap();
+
+    let width = inputs[1].len();
+    let expected = format!(
+        "{:<w$}  COUNT\n{:<w$}      2\n{:<w$}     10\n",
+        "FILE",
+        inputs[0],
+        inputs[1],
+        w = width
+    );
+    assert_eq!(result.output, expected);
+
+    // --no-table keeps the raw name:count lines
+    cfg.table = false;
+    let result = run(&cfg, &inputs).unwrap();
+    assert_eq!(
+        result.output,
+        format!("{}:2\n{}:10\n", inputs[0], inputs[1])
+    );
+}
+
+#[test]
+fn test_duration_stats_table_with_total() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.log");
+    let b = td.path().join("b.log");
+    fs::write(&a, b"took 10ms\ntook 30ms\n").unwrap();
+    fs::write(&b, b"nothing\n").unwrap();
+    let inputs = vec![
+        a.to_string_lossy().to_string(),
+        b.to_string_lossy().to_string(),
+    ];
+
+    let mut cfg = create_config("took|nothing");
+    cfg.duration_stats = Some(r"took (\d+)ms".to_string());
+    let result = run(&cfg, &inputs).unwrap();
+
+    let lines: Vec<Vec<&str>> = result
+        .output
+        .lines()
+        .map(|l| l.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        lines[0],
+        vec!["FILE", "COUNT", "MIN", "AVG", "P50", "P95", "MAX"]
+    );
+    assert_eq!(lines[1][1..], ["2", "10", "20", "10", "30", "30"]);
+    assert_eq!(lines[2][1..], ["0", "-", "-", "-", "-", "-"]);
+    assert_eq!(lines[3], vec!["total", "2", "10", "20", "10", "30", "30"]);
+}
+
+#[test]
+fn test_single_file_count_is_not_a_table() {
+    let td = tempfile::tempdir().unwrap();
+    let a = td.path().join("a.txt");
+    fs::write(&a, b"hit\n").unwrap();
+
+    let mut cfg = create_config("hit");
+    cfg.count = true;
+    let result = run(&cfg, &[a.to_string_lossy().to_string()]).unwrap();
+    assert_eq!(result.output, "1\n");
+}